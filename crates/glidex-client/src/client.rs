@@ -0,0 +1,349 @@
+use crate::console::Console;
+use crate::models::{
+    ApiError, ConsoleInfo, ConsoleMode, ConsoleSession, CreateSnapshotRequest, CreateVmRequest,
+    DeviceRequest, ExpectRequest, ExpectResponse, IncomingMigrationRequest,
+    IncomingMigrationResponse, LogsQuery, MigrateVmRequest, MigrateVmResponse, PciDeviceInfo,
+    Replay, RestoreVmRequest, SnapshotResponse, StartQuery, StopVmRequest, UpdateVmRequest,
+    VmResponse,
+};
+use crate::Error;
+use reqwest::{RequestBuilder, Response};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// The version of the API the client speaks.
+const API_PREFIX: &str = "/v1";
+
+/// A connection to one control plane. Cheap to clone; clones share
+/// their connection pool.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+}
+
+/// What a console client asks for when it attaches.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConsoleOptions {
+    pub replay: Replay,
+    pub mode: ConsoleMode,
+    /// Who is connecting, for the recording and the session list; the
+    /// server says `browser` if unset. A single word.
+    pub client: Option<String>,
+}
+
+/// The body of `GET /vms/{id}/logs`, read as it arrives. With `follow`
+/// it keeps coming until the VM stops.
+pub struct Logs(Response);
+
+impl Logs {
+    /// The next part of the log, or `None` at its end.
+    pub async fn chunk(&mut self) -> Result<Option<Vec<u8>>, Error> {
+        Ok(self.0.chunk().await?.map(|chunk| chunk.to_vec()))
+    }
+}
+
+impl Client {
+    /// A client of the control plane at `base_url`, e.g.
+    /// `http://127.0.0.1:8080`.
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self::with_http_client(base_url, reqwest::Client::new())
+    }
+
+    /// Like [`Client::new`], sending requests with `http`, e.g. one with
+    /// timeouts or TLS settings of its own.
+    pub fn with_http_client(base_url: impl Into<String>, http: reqwest::Client) -> Self {
+        let base_url = base_url.into().trim_end_matches('/').to_string();
+        Self { http, base_url }
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}{}", self.base_url, API_PREFIX, path)
+    }
+
+    /// Send `request`, turning an error answer into [`Error::Api`].
+    async fn send(request: RequestBuilder) -> Result<Response, Error> {
+        let resp = request.send().await?;
+        let status = resp.status();
+        if status.is_success() {
+            return Ok(resp);
+        }
+        let body = resp.bytes().await?;
+        // Not every error is the API's JSON, e.g. a rejected WebSocket
+        // query or a proxy in between.
+        let error = serde_json::from_slice(&body).unwrap_or_else(|_| {
+            ApiError::new(
+                status.canonical_reason().unwrap_or("error").to_lowercase().replace(' ', "_"),
+                String::from_utf8_lossy(&body).trim().to_string(),
+            )
+        });
+        Err(Error::Api { status, error })
+    }
+
+    async fn json<T: DeserializeOwned>(request: RequestBuilder) -> Result<T, Error> {
+        let body = Self::send(request).await?.bytes().await?;
+        Ok(serde_json::from_slice(&body)?)
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        self.http.get(self.url(path))
+    }
+
+    fn post(&self, path: &str, body: &impl Serialize) -> RequestBuilder {
+        self.http.post(self.url(path)).json(body)
+    }
+
+    /// `GET /health`: whether the control plane is up.
+    pub async fn health(&self) -> Result<(), Error> {
+        Self::send(self.get("/health")).await.map(drop)
+    }
+
+    pub async fn list_vms(&self) -> Result<Vec<VmResponse>, Error> {
+        Self::json(self.get("/vms")).await
+    }
+
+    pub async fn get_vm(&self, id: &str) -> Result<VmResponse, Error> {
+        Self::json(self.get(&format!("/vms/{}", id))).await
+    }
+
+    pub async fn create_vm(&self, request: &CreateVmRequest) -> Result<VmResponse, Error> {
+        Self::json(self.post("/vms", request)).await
+    }
+
+    /// Change the settings of a created, stopped, crashed or failed VM;
+    /// they take effect on its next start. Other states are refused.
+    pub async fn update_vm(&self, id: &str, update: &UpdateVmRequest) -> Result<VmResponse, Error> {
+        Self::json(self.http.patch(self.url(&format!("/vms/{}", id))).json(update)).await
+    }
+
+    pub async fn delete_vm(&self, id: &str) -> Result<(), Error> {
+        Self::send(self.http.delete(self.url(&format!("/vms/{}", id))))
+            .await
+            .map(drop)
+    }
+
+    /// Start a VM, or resume a paused one. With `StartWait::Ready`, the
+    /// answer comes once the guest has passed its readiness probe.
+    pub async fn start_vm(&self, id: &str, query: &StartQuery) -> Result<VmResponse, Error> {
+        let request = self.http.post(self.url(&format!("/vms/{}/start", id)));
+        Self::json(request.query(query)).await
+    }
+
+    pub async fn stop_vm(&self, id: &str, request: &StopVmRequest) -> Result<VmResponse, Error> {
+        Self::json(self.post(&format!("/vms/{}/stop", id), request)).await
+    }
+
+    pub async fn pause_vm(&self, id: &str) -> Result<VmResponse, Error> {
+        Self::json(self.http.post(self.url(&format!("/vms/{}/pause", id)))).await
+    }
+
+    pub async fn reboot_vm(&self, id: &str) -> Result<VmResponse, Error> {
+        Self::json(self.http.post(self.url(&format!("/vms/{}/reboot", id)))).await
+    }
+
+    pub async fn reset_vm(&self, id: &str) -> Result<VmResponse, Error> {
+        Self::json(self.http.post(self.url(&format!("/vms/{}/reset", id)))).await
+    }
+
+    pub async fn create_snapshot(
+        &self,
+        id: &str,
+        request: &CreateSnapshotRequest,
+    ) -> Result<SnapshotResponse, Error> {
+        Self::json(self.post(&format!("/vms/{}/snapshots", id), request)).await
+    }
+
+    pub async fn list_snapshots(&self, id: &str) -> Result<Vec<SnapshotResponse>, Error> {
+        Self::json(self.get(&format!("/vms/{}/snapshots", id))).await
+    }
+
+    pub async fn delete_snapshot(&self, id: &str, snapshot_id: &str) -> Result<(), Error> {
+        let url = self.url(&format!("/vms/{}/snapshots/{}", id, snapshot_id));
+        Self::send(self.http.delete(url)).await.map(drop)
+    }
+
+    pub async fn restore_vm(&self, id: &str, request: &RestoreVmRequest) -> Result<VmResponse, Error> {
+        Self::json(self.post(&format!("/vms/{}/restore", id), request)).await
+    }
+
+    /// Live-migrate a running VM to another control plane.
+    pub async fn migrate_vm(
+        &self,
+        id: &str,
+        request: &MigrateVmRequest,
+    ) -> Result<MigrateVmResponse, Error> {
+        Self::json(self.post(&format!("/vms/{}/migrate", id), request)).await
+    }
+
+    /// `POST /migrations/incoming`, as the source control plane of a
+    /// live migration: have this one get ready to receive the VM. The
+    /// answer says where to send the hypervisor's migration stream.
+    pub async fn prepare_incoming_migration(
+        &self,
+        request: &IncomingMigrationRequest,
+    ) -> Result<IncomingMigrationResponse, Error> {
+        Self::json(self.post("/migrations/incoming", request)).await
+    }
+
+    /// Once the stream is sent: wait for this control plane to resume the
+    /// guest.
+    pub async fn complete_incoming_migration(&self, id: &str) -> Result<VmResponse, Error> {
+        let url = self.url(&format!("/migrations/incoming/{}/complete", id));
+        Self::json(self.http.post(url)).await
+    }
+
+    /// Give up on an incoming migration; this control plane kills its
+    /// hypervisor and forgets the VM.
+    pub async fn abort_incoming_migration(&self, id: &str) -> Result<(), Error> {
+        let url = self.url(&format!("/migrations/incoming/{}", id));
+        Self::send(self.http.delete(url)).await.map(drop)
+    }
+
+    pub async fn console_info(&self, id: &str) -> Result<ConsoleInfo, Error> {
+        Self::json(self.get(&format!("/vms/{}/console", id))).await
+    }
+
+    /// The console WebSocket of a VM: `ws://` for an `http://` server,
+    /// `wss://` for `https://`.
+    pub fn console_url(&self, id: &str, options: &ConsoleOptions) -> String {
+        let base = match self.base_url.split_once("://") {
+            Some(("https", rest)) => format!("wss://{}", rest),
+            Some((_, rest)) => format!("ws://{}", rest),
+            None => format!("ws://{}", self.base_url),
+        };
+        let mut url = format!(
+            "{}{}/vms/{}/console/ws?replay={}&mode={}",
+            base, API_PREFIX, id, options.replay, options.mode
+        );
+        if let Some(client) = &options.client {
+            url.push_str(&format!("&client={}", client));
+        }
+        url
+    }
+
+    /// Attach to a VM's console through the control plane.
+    pub async fn console(&self, id: &str, options: &ConsoleOptions) -> Result<Console, Error> {
+        Console::connect(&self.console_url(id, options)).await
+    }
+
+    /// Type into a VM's console and wait for a pattern in what it prints
+    /// back.
+    pub async fn expect(&self, id: &str, request: &ExpectRequest) -> Result<ExpectResponse, Error> {
+        Self::json(self.post(&format!("/vms/{}/console/expect", id), request)).await
+    }
+
+    pub async fn list_console_sessions(&self, id: &str) -> Result<Vec<ConsoleSession>, Error> {
+        Self::json(self.get(&format!("/vms/{}/console/sessions", id))).await
+    }
+
+    /// The asciicast file of one recorded console session.
+    pub async fn download_console_session(
+        &self,
+        id: &str,
+        session_id: &str,
+    ) -> Result<Vec<u8>, Error> {
+        let request = self.get(&format!("/vms/{}/console/sessions/{}", id, session_id));
+        Ok(Self::send(request).await?.bytes().await?.to_vec())
+    }
+
+    /// A VM's console log; see [`LogsQuery`] for which part.
+    pub async fn logs(&self, id: &str, query: &LogsQuery) -> Result<Logs, Error> {
+        let request = self.get(&format!("/vms/{}/logs", id)).query(query);
+        Self::send(request).await.map(Logs)
+    }
+
+    pub async fn list_pci_devices(&self) -> Result<Vec<PciDeviceInfo>, Error> {
+        Self::json(self.get("/pci-devices")).await
+    }
+
+    /// Pass a host PCI device through to a VM: hot-plugged into a running
+    /// one, or added to the config of a created, stopped, crashed or
+    /// failed one for its next start. A starting, paused or stopping VM
+    /// is refused.
+    pub async fn attach_device(&self, id: &str, device_path: &str) -> Result<VmResponse, Error> {
+        let request = DeviceRequest {
+            device_path: device_path.to_string(),
+        };
+        Self::json(self.post(&format!("/vms/{}/devices", id), &request)).await
+    }
+
+    /// Take a passed-through device from a VM: hot-unplugged from a
+    /// running one, or removed from the config of one that is not
+    /// running, in the same states as [`Client::attach_device`].
+    pub async fn detach_device(&self, id: &str, device_path: &str) -> Result<VmResponse, Error> {
+        let request = DeviceRequest {
+            device_path: device_path.to_string(),
+        };
+        let url = self.url(&format!("/vms/{}/devices", id));
+        Self::json(self.http.delete(url).json(&request)).await
+    }
+
+    /// Resolve a VM identifier (name or ID) to an ID.
+    /// First tries to use it as an ID, then searches by name.
+    pub async fn resolve_vm(&self, name_or_id: &str) -> Result<String, Error> {
+        if let Ok(vm) = self.get_vm(name_or_id).await {
+            return Ok(vm.id);
+        }
+
+        let vms = self.list_vms().await?;
+        let mut ids: Vec<String> = vms
+            .into_iter()
+            .filter(|vm| vm.name == name_or_id)
+            .map(|vm| vm.id)
+            .collect();
+        match ids.len() {
+            0 => Err(Error::VmNotFound(name_or_id.to_string())),
+            1 => Ok(ids.remove(0)),
+            _ => Err(Error::AmbiguousName {
+                name: name_or_id.to_string(),
+                ids,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ConsoleMode;
+
+    #[test]
+    fn console_url_follows_server_scheme() {
+        let options = ConsoleOptions {
+            replay: Replay::Tail(20),
+            mode: ConsoleMode::Exclusive,
+            client: Some("gxctl".to_string()),
+        };
+        assert_eq!(
+            Client::new("https://cp.example:8443/").console_url("ab", &options),
+            "wss://cp.example:8443/v1/vms/ab/console/ws?replay=tail:20&mode=exclusive&client=gxctl"
+        );
+        assert_eq!(
+            Client::new("http://127.0.0.1:8080").console_url("ab", &ConsoleOptions::default()),
+            "ws://127.0.0.1:8080/v1/vms/ab/console/ws?replay=all&mode=shared"
+        );
+    }
+
+    #[test]
+    fn api_errors_read_like_the_server_wrote_them() {
+        let error = Error::Api {
+            status: reqwest::StatusCode::NOT_FOUND,
+            error: ApiError::new("not_found", "VM not found: ab"),
+        };
+        assert_eq!(error.to_string(), "not_found: VM not found: ab");
+        assert_eq!(error.status(), Some(reqwest::StatusCode::NOT_FOUND));
+
+        let ambiguous = Error::AmbiguousName {
+            name: "web".to_string(),
+            ids: vec!["a".to_string(), "b".to_string()],
+        };
+        assert_eq!(
+            ambiguous.to_string(),
+            "Multiple VMs found with name 'web'. Use ID instead: a, b"
+        );
+    }
+}
@@ -0,0 +1,380 @@
+//! The framed console protocol, and a client of the console WebSocket.
+//!
+//! A console connection that asked for frames carries [`Frame`]s both
+//! ways: terminal data, and [`Control`] messages such as resizes next to
+//! it. Over the WebSocket, binary messages are data frames and text
+//! messages control frames; over a VM's console socket, frames are
+//! encoded as bytes and split again with a [`FrameDecoder`]. See
+//! `spec/console.md`.
+
+use crate::models::{ConsoleMode, Replay};
+use crate::Error;
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use std::io;
+use tokio_tungstenite::tungstenite::client::IntoClientRequest;
+use tokio_tungstenite::tungstenite::Message;
+
+/// WebSocket subprotocol of the framed console protocol. Clients that do
+/// not offer it get raw bytes both ways.
+pub const CONSOLE_WS_PROTOCOL: &str = "glidex.console.v1";
+
+/// Largest frame payload accepted from a peer, and sent to one.
+pub const FRAME_MAX_LEN: usize = 64 * 1024;
+
+/// Prefix of the optional handshake line a client may send first on a
+/// VM's console socket: `GLIDEX-CONSOLE replay=<mode> [frames=v1]\n`.
+pub const HANDSHAKE_PREFIX: &[u8] = b"GLIDEX-CONSOLE ";
+
+const FRAME_DATA: u8 = 0;
+const FRAME_CONTROL: u8 = 1;
+
+/// A message of the framed console protocol: one byte of kind (0 data,
+/// 1 control), the payload length as a big-endian `u32`, the payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Frame {
+    /// Console output, or keyboard input.
+    Data(Vec<u8>),
+    /// A [`Control`] message as JSON. Kept as received, so that a bridge
+    /// can relay messages it does not know.
+    Control(Vec<u8>),
+}
+
+impl Frame {
+    pub fn encode(&self) -> Vec<u8> {
+        let (kind, payload) = match self {
+            Frame::Data(data) => (FRAME_DATA, data),
+            Frame::Control(json) => (FRAME_CONTROL, json),
+        };
+        let mut frame = Vec::with_capacity(5 + payload.len());
+        frame.push(kind);
+        frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// `data` as data frames no larger than the protocol allows.
+    pub fn encode_data(data: &[u8]) -> Vec<u8> {
+        data.chunks(FRAME_MAX_LEN)
+            .flat_map(|chunk| Frame::Data(chunk.to_vec()).encode())
+            .collect()
+    }
+
+    /// The control message of a control frame, if it is one this
+    /// version knows.
+    pub fn control(&self) -> Option<Control> {
+        match self {
+            Frame::Control(json) => serde_json::from_slice(json).ok(),
+            Frame::Data(_) => None,
+        }
+    }
+}
+
+/// Splits a byte stream into [`Frame`]s as it arrives.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn push(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+    }
+
+    /// The next complete frame, if one has arrived. Fails on a frame of
+    /// unknown kind or over the size limit, after which the stream cannot
+    /// be resynchronised.
+    pub fn next_frame(&mut self) -> io::Result<Option<Frame>> {
+        let Some(header) = self.buf.get(..5) else {
+            return Ok(None);
+        };
+        let len = u32::from_be_bytes([header[1], header[2], header[3], header[4]]) as usize;
+        if len > FRAME_MAX_LEN {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "console frame too large"));
+        }
+        if self.buf.len() < 5 + len {
+            return Ok(None);
+        }
+        let kind = self.buf[0];
+        let payload = self.buf[5..5 + len].to_vec();
+        self.buf.drain(..5 + len);
+        match kind {
+            FRAME_DATA => Ok(Some(Frame::Data(payload))),
+            FRAME_CONTROL => Ok(Some(Frame::Control(payload))),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, "unknown console frame kind")),
+        }
+    }
+}
+
+/// Control messages of the framed protocol. Clients send `resize` and
+/// `break`; the proxy sends `status`, `notice` and `error`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Control {
+    /// Set the console's window size (`TIOCSWINSZ`).
+    Resize { cols: u16, rows: u16 },
+    /// Send a break condition on the serial line.
+    Break,
+    Status { event: ConsoleEvent },
+    /// A control message from the client could not be applied.
+    Error { message: String },
+    /// Something about the other clients, such as the console being
+    /// locked, for the user to see.
+    Notice { message: String },
+}
+
+impl Control {
+    pub fn frame(&self) -> Frame {
+        Frame::Control(serde_json::to_vec(self).unwrap_or_default())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConsoleEvent {
+    /// The requested scrollback has been sent; live output follows.
+    ReplayFinished,
+    /// The guest's serial console closed: the hypervisor exited.
+    GuestExited,
+}
+
+type WebSocket =
+    tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+/// A VM's console, attached through the control plane's WebSocket with
+/// the framed protocol. Opened with [`Client::console`](crate::Client::console).
+pub struct Console {
+    ws: Box<WebSocket>,
+}
+
+impl Console {
+    /// Connect to the console WebSocket at `url`. Fails unless the
+    /// server agrees to the framed protocol.
+    pub async fn connect(url: &str) -> Result<Self, Error> {
+        let mut request = url.into_client_request()?;
+        request.headers_mut().insert(
+            "Sec-WebSocket-Protocol",
+            CONSOLE_WS_PROTOCOL.parse().expect("valid header value"),
+        );
+        let (ws, response) = tokio_tungstenite::connect_async(request).await?;
+        let framed = response
+            .headers()
+            .get("Sec-WebSocket-Protocol")
+            .is_some_and(|protocol| protocol == CONSOLE_WS_PROTOCOL);
+        if !framed {
+            return Err(Error::Protocol(
+                "the control plane does not speak the framed console protocol".to_string(),
+            ));
+        }
+        Ok(Self { ws: Box::new(ws) })
+    }
+
+    pub async fn send(&mut self, frame: Frame) -> Result<(), Error> {
+        let message = match frame {
+            Frame::Data(data) => Message::Binary(data.into()),
+            Frame::Control(json) => Message::Text(String::from_utf8_lossy(&json).into_owned().into()),
+        };
+        Ok(self.ws.send(message).await?)
+    }
+
+    /// The next frame from the console, or `None` once it closed.
+    pub async fn recv(&mut self) -> Option<Result<Frame, Error>> {
+        loop {
+            match self.ws.next().await? {
+                Ok(Message::Binary(data)) => return Some(Ok(Frame::Data(data.to_vec()))),
+                Ok(Message::Text(json)) => {
+                    return Some(Ok(Frame::Control(json.as_bytes().to_vec())))
+                }
+                Ok(Message::Close(_)) => return None,
+                Ok(_) => {}
+                Err(e) => return Some(Err(e.into())),
+            }
+        }
+    }
+
+    /// Close the connection, detaching from the console.
+    pub async fn close(mut self) -> Result<(), Error> {
+        Ok(self.ws.close().await?)
+    }
+}
+
+/// What a client asks for when it connects.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Handshake {
+    pub replay: Replay,
+    /// Exchange [`Frame`]s rather than raw bytes after the handshake.
+    pub framed: bool,
+    /// Record the session on the server. Internal clients such as
+    /// readiness probes turn it off.
+    pub record: bool,
+    /// Who is connecting, e.g. `gxctl`, for the recording and the
+    /// session list. A single word without spaces.
+    pub client: Option<String>,
+    pub mode: ConsoleMode,
+    /// Instead of attaching, ask for the list of attached sessions, which
+    /// the proxy answers with one JSON line before closing.
+    pub query: bool,
+}
+
+impl Default for Handshake {
+    fn default() -> Self {
+        Self {
+            replay: Replay::default(),
+            framed: false,
+            record: true,
+            client: None,
+            mode: ConsoleMode::default(),
+            query: false,
+        }
+    }
+}
+
+impl Handshake {
+    /// The line to open a console socket connection with.
+    pub fn line(&self) -> Vec<u8> {
+        let mut line = HANDSHAKE_PREFIX.to_vec();
+        line.extend_from_slice(format!("replay={}", self.replay).as_bytes());
+        if self.framed {
+            line.extend_from_slice(b" frames=v1");
+        }
+        if !self.record {
+            line.extend_from_slice(b" record=off");
+        }
+        if let Some(client) = &self.client {
+            line.extend_from_slice(format!(" client={}", client).as_bytes());
+        }
+        if self.mode != ConsoleMode::default() {
+            line.extend_from_slice(format!(" mode={}", self.mode).as_bytes());
+        }
+        if self.query {
+            line.extend_from_slice(b" query=sessions");
+        }
+        line.push(b'\n');
+        line
+    }
+
+    /// Parse what follows the prefix. Unknown options are skipped, so
+    /// newer clients can still talk to an older proxy; what was skipped
+    /// is returned next to the handshake, for the proxy to log.
+    pub fn parse(options: &str) -> (Self, Vec<String>) {
+        let mut handshake = Handshake::default();
+        let mut skipped = Vec::new();
+        for option in options.split_whitespace() {
+            let parsed = match option.split_once('=') {
+                Some(("replay", mode)) => mode.parse().map(|replay| handshake.replay = replay),
+                Some(("frames", "v1")) => {
+                    handshake.framed = true;
+                    Ok(())
+                }
+                Some(("record", "off")) => {
+                    handshake.record = false;
+                    Ok(())
+                }
+                Some(("client", name)) => {
+                    handshake.client = Some(name.to_string());
+                    Ok(())
+                }
+                Some(("mode", mode)) => mode.parse().map(|mode| handshake.mode = mode),
+                Some(("query", "sessions")) => {
+                    handshake.query = true;
+                    Ok(())
+                }
+                _ => Err(format!("Unknown console handshake option '{}'", option)),
+            };
+            if let Err(e) = parsed {
+                skipped.push(e);
+            }
+        }
+        (handshake, skipped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn handshake_options_parse_and_skip_unknown_ones() {
+        let framed = Handshake {
+            replay: Replay::Tail(3),
+            framed: true,
+            ..Handshake::default()
+        };
+        assert_eq!(framed.line(), b"GLIDEX-CONSOLE replay=tail:3 frames=v1\n");
+        assert_eq!(Handshake::parse("replay=tail:3 frames=v1"), (framed, vec![]));
+        assert_eq!(
+            Handshake::parse("colour=yes replay=none"),
+            (
+                Handshake {
+                    replay: Replay::None,
+                    ..Handshake::default()
+                },
+                vec!["Unknown console handshake option 'colour=yes'".to_string()]
+            )
+        );
+
+        let unrecorded = Handshake {
+            record: false,
+            client: Some("gxctl".to_string()),
+            ..Handshake::default()
+        };
+        assert_eq!(
+            unrecorded.line(),
+            b"GLIDEX-CONSOLE replay=all record=off client=gxctl\n"
+        );
+        assert_eq!(Handshake::parse("record=off client=gxctl").0, unrecorded);
+
+        let (exclusive, _) = Handshake::parse("replay=none mode=exclusive");
+        assert_eq!(exclusive.mode, ConsoleMode::Exclusive);
+        assert_eq!(
+            exclusive.line(),
+            b"GLIDEX-CONSOLE replay=none mode=exclusive\n"
+        );
+        let (solo, skipped) = Handshake::parse("mode=solo");
+        assert_eq!(solo.mode, ConsoleMode::Shared);
+        assert_eq!(skipped.len(), 1);
+    }
+
+    #[test]
+    fn frames_are_decoded_as_they_complete() {
+        let resize = Control::Resize { cols: 120, rows: 40 };
+        let mut stream = Frame::Data(b"ls\r".to_vec()).encode();
+        stream.extend(resize.frame().encode());
+
+        let mut decoder = FrameDecoder::default();
+        let (first, rest) = stream.split_at(6);
+        decoder.push(first);
+        assert_eq!(decoder.next_frame().unwrap(), None);
+        decoder.push(rest);
+        assert_eq!(decoder.next_frame().unwrap(), Some(Frame::Data(b"ls\r".to_vec())));
+        let Some(Frame::Control(json)) = decoder.next_frame().unwrap() else {
+            panic!("expected a control frame");
+        };
+        assert_eq!(
+            String::from_utf8(json).unwrap(),
+            r#"{"type":"resize","cols":120,"rows":40}"#
+        );
+        assert_eq!(decoder.next_frame().unwrap(), None);
+
+        decoder.push(&[FRAME_DATA, 0xff, 0xff, 0xff, 0xff]);
+        assert!(decoder.next_frame().is_err());
+    }
+
+    #[test]
+    fn control_messages_use_the_documented_json() {
+        let status = Control::Status {
+            event: ConsoleEvent::GuestExited,
+        };
+        assert_eq!(
+            serde_json::to_value(&status).unwrap(),
+            serde_json::json!({"type": "status", "event": "guest_exited"})
+        );
+        let json = br#"{"type": "notice", "message": "locked"}"#;
+        assert_eq!(
+            Frame::Control(json.to_vec()).control(),
+            Some(Control::Notice {
+                message: "locked".to_string()
+            })
+        );
+    }
+}
@@ -0,0 +1,58 @@
+//! Typed client of the glidex control plane's REST API.
+//!
+//! [`models`] holds the request and response bodies, which the control
+//! plane itself serves, and [`Client`] has a method for every endpoint,
+//! including the console WebSocket ([`console`]). `gxctl` is built on it.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), glidex_client::Error> {
+//! let client = glidex_client::Client::new("http://127.0.0.1:8080");
+//! for vm in client.list_vms().await? {
+//!     println!("{} {}", vm.name, vm.state);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+pub mod console;
+pub mod models;
+
+pub use client::{Client, ConsoleOptions, Logs};
+
+use models::ApiError;
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// The control plane could not be reached, or the response not read.
+    #[error("Request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    /// The control plane answered with an error.
+    #[error("{}: {}", .error.error, .error.message)]
+    Api {
+        status: reqwest::StatusCode,
+        error: ApiError,
+    },
+    #[error("Failed to parse response: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("Console connection failed: {0}")]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    /// The console connection broke the framed protocol.
+    #[error("Console protocol error: {0}")]
+    Protocol(String),
+    /// No VM has the name or id.
+    #[error("VM '{0}' not found")]
+    VmNotFound(String),
+    #[error("Multiple VMs found with name '{name}'. Use ID instead: {}", .ids.join(", "))]
+    AmbiguousName { name: String, ids: Vec<String> },
+}
+
+impl Error {
+    /// The HTTP status of an error answer.
+    pub fn status(&self) -> Option<reqwest::StatusCode> {
+        match self {
+            Error::Api { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+}
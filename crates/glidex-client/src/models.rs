@@ -0,0 +1,728 @@
+//! The request and response bodies of the REST API. The control plane
+//! serves these same types, so they cannot drift apart.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum VmState {
+    Created,
+    /// Hypervisor is being spawned/configured/booted, or restored from a
+    /// snapshot (which ends in `Paused`).
+    Starting,
+    Running,
+    Paused,
+    /// Hypervisor is being shut down on request.
+    Stopping,
+    Stopped,
+    /// Hypervisor exited on its own abnormally; see `VmResponse::last_exit`.
+    Crashed,
+    /// The last start, reboot or reset failed to bring the guest up; see
+    /// `VmResponse::last_error`.
+    Failed,
+}
+
+impl VmState {
+    /// The lifecycle state machine. Every state change `VmManager` makes
+    /// is checked against this table.
+    pub fn can_transition_to(&self, next: &VmState) -> bool {
+        use VmState::*;
+        matches!(
+            (self, next),
+            (Created | Stopped | Crashed | Failed, Starting)
+                | (Starting, Running | Paused | Failed)
+                | (Running, Paused | Stopping | Stopped | Crashed | Failed)
+                | (Paused, Running | Stopping | Stopped | Crashed)
+                | (Stopping, Stopped | Crashed)
+        )
+    }
+}
+
+impl fmt::Display for VmState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            VmState::Created => "created",
+            VmState::Starting => "starting",
+            VmState::Running => "running",
+            VmState::Paused => "paused",
+            VmState::Stopping => "stopping",
+            VmState::Stopped => "stopped",
+            VmState::Crashed => "crashed",
+            VmState::Failed => "failed",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Supported hypervisor types
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum HypervisorType {
+    Firecracker,
+    CloudHypervisor,
+    #[default]
+    Qemu,
+}
+
+impl HypervisorType {
+    /// Get the binary name for this hypervisor
+    pub fn binary_name(&self) -> &'static str {
+        match self {
+            HypervisorType::Firecracker => "firecracker",
+            HypervisorType::CloudHypervisor => "cloud-hypervisor",
+            HypervisorType::Qemu => "qemu-system-x86_64",
+        }
+    }
+
+    /// Get the socket path prefix for this hypervisor
+    pub fn socket_prefix(&self) -> &'static str {
+        match self {
+            HypervisorType::Firecracker => "firecracker",
+            HypervisorType::CloudHypervisor => "cloud-hypervisor",
+            HypervisorType::Qemu => "qemu",
+        }
+    }
+
+    /// Get the default kernel boot arguments for this hypervisor
+    pub fn default_kernel_args(&self) -> &'static str {
+        match self {
+            HypervisorType::Firecracker => "console=ttyS0 reboot=k panic=1 pci=off",
+            HypervisorType::CloudHypervisor => "console=hvc0 root=/dev/vda reboot=k panic=1",
+            HypervisorType::Qemu => "console=ttyS0 root=/dev/vda reboot=k panic=1",
+        }
+    }
+
+    /// Whether running guests can be live-migrated to another control
+    /// plane. Firecracker has no live migration, only snapshots.
+    pub fn supports_live_migration(&self) -> bool {
+        !matches!(self, HypervisorType::Firecracker)
+    }
+}
+
+impl fmt::Display for HypervisorType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            HypervisorType::Firecracker => write!(f, "firecracker"),
+            HypervisorType::CloudHypervisor => write!(f, "cloudhypervisor"),
+            HypervisorType::Qemu => write!(f, "qemu"),
+        }
+    }
+}
+
+/// How a hypervisor process ended without being asked to, as observed by
+/// the `VmManager` supervisor. At most one of `code` / `signal` is set;
+/// neither is for a process adopted after a control-plane restart, whose
+/// exit status cannot be collected.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VmExit {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signal: Option<i32>,
+    /// Last lines of the serial console before the exit.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub console_tail: Vec<String>,
+}
+
+#[cfg(unix)]
+impl From<std::process::ExitStatus> for VmExit {
+    fn from(status: std::process::ExitStatus) -> Self {
+        use std::os::unix::process::ExitStatusExt;
+        VmExit {
+            code: status.code(),
+            signal: status.signal(),
+            console_tail: Vec::new(),
+        }
+    }
+}
+
+impl VmExit {
+    /// Whether this exit should be reported as a crash rather than a clean
+    /// power-off. QEMU (`-no-reboot`) and Firecracker exit 0 when the guest
+    /// reboots, which with `panic=1` is also how a kernel panic ends, so
+    /// the console is checked for a panic banner too. That is all there is
+    /// to go on when the status is unknown.
+    pub fn is_crash(&self) -> bool {
+        self.code.is_some_and(|code| code != 0)
+            || self.signal.is_some()
+            || self
+                .console_tail
+                .iter()
+                .any(|line| line.contains("Kernel panic"))
+    }
+}
+
+impl fmt::Display for VmExit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.code, self.signal) {
+            (Some(code), _) => write!(f, "exited with code {}", code),
+            (None, Some(signal)) => write!(f, "killed by signal {}", signal),
+            (None, None) => write!(f, "exited with unknown status"),
+        }
+    }
+}
+
+/// How a VM's console log is capped. See `logs.rs`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(default)]
+pub struct LogPolicy {
+    /// Size at which the current log is rotated. 0 never rotates.
+    pub max_bytes: u64,
+    /// Rotated segments kept per boot; older output is dropped.
+    pub max_files: u32,
+    /// Logs of previous boots kept in addition to the current one.
+    pub keep_boots: u32,
+    /// Recorded console sessions kept. 0 records none.
+    pub keep_sessions: u32,
+}
+
+impl Default for LogPolicy {
+    fn default() -> Self {
+        Self {
+            max_bytes: 8 * 1024 * 1024,
+            max_files: 3,
+            keep_boots: 3,
+            keep_sessions: 20,
+        }
+    }
+}
+
+/// What a guest must do to count as booted. See `readiness.rs`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReadinessProbe {
+    /// The console prints something matching `pattern`, e.g. a login
+    /// prompt.
+    Console { pattern: String },
+    /// `host:port` accepts TCP connections.
+    Tcp { host: String, port: u16 },
+    /// The QEMU guest agent answers a ping. QEMU only.
+    GuestAgent,
+}
+
+/// Body of `POST /vms`. What is left out gets the server's default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateVmRequest {
+    pub name: String,
+    pub vcpu_count: u8,
+    pub mem_size_mib: u32,
+    pub kernel_image_path: String,
+    pub rootfs_path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_args: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hypervisor: Option<HypervisorType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vfio_devices: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_reboot: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub log_policy: Option<LogPolicy>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ReadinessProbe>,
+}
+
+/// `PATCH /vms/{id}`: the settings to change; absent ones are kept.
+/// The hypervisor cannot change.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UpdateVmRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcpu_count: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_size_mib: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_image_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rootfs_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_args: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vfio_devices: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_reboot: Option<bool>,
+}
+
+/// What a hypervisor process uses of the host. CPU time is cumulative;
+/// clients sample it twice for a utilisation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ProcessUsage {
+    pub pid: u32,
+    /// User plus system time, in milliseconds.
+    pub cpu_time_ms: u64,
+    pub rss_bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VmResponse {
+    pub id: String,
+    pub name: String,
+    pub state: VmState,
+    pub vcpu_count: u8,
+    pub mem_size_mib: u32,
+    #[serde(default)]
+    pub kernel_image_path: String,
+    #[serde(default)]
+    pub rootfs_path: String,
+    #[serde(default)]
+    pub kernel_args: String,
+    #[serde(default)]
+    pub no_reboot: bool,
+    pub console_socket_path: String,
+    pub log_path: String,
+    pub hypervisor: HypervisorType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vfio_devices: Vec<String>,
+    #[serde(default)]
+    pub log_policy: LogPolicy,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ReadinessProbe>,
+    #[serde(default)]
+    pub ready: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_duration_ms: Option<u64>,
+    /// Seconds since the current boot began, while running or paused.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uptime_secs: Option<u64>,
+    /// The hypervisor process's host usage, read when the response is
+    /// built.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub process: Option<ProcessUsage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_exit: Option<VmExit>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+}
+
+/// The settings of a VM, as the control plane keeps them, and as one
+/// control plane hands a VM to another in a live migration.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct VmConfig {
+    pub vcpu_count: u8,
+    pub mem_size_mib: u32,
+    pub kernel_image_path: String,
+    pub rootfs_path: String,
+    pub kernel_args: String,
+    #[serde(default)]
+    pub hypervisor: HypervisorType,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub vfio_devices: Vec<String>,
+    /// Make a guest-initiated reboot end the hypervisor process (QEMU's
+    /// `-no-reboot`) instead of resetting the guest in place. Keeps a
+    /// `panic=1` guest from reboot-looping unnoticed. QEMU only; Firecracker
+    /// always exits on guest reboot and Cloud-Hypervisor never does.
+    #[serde(default = "default_no_reboot")]
+    pub no_reboot: bool,
+    #[serde(default)]
+    pub log_policy: LogPolicy,
+    /// How to tell that the guest has booted. Without one, a running VM
+    /// counts as ready.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub readiness: Option<ReadinessProbe>,
+}
+
+/// `no_reboot` of a VM created without one.
+pub fn default_no_reboot() -> bool {
+    true
+}
+
+/// What `POST /vms/{id}/start` waits for before answering.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum StartWait {
+    /// The hypervisor runs the guest (the default).
+    Running,
+    /// The guest passed its readiness probe.
+    Ready,
+}
+
+/// Query of `POST /vms/{id}/start`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema, utoipa::IntoParams))]
+#[cfg_attr(feature = "openapi", into_params(parameter_in = Query))]
+pub struct StartQuery {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub wait: Option<StartWait>,
+    /// How long to wait for readiness; the server's default if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+/// Body of `POST /vms/{id}/stop`. Optional; an empty body means a
+/// graceful shutdown with the default timeout.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct StopVmRequest {
+    /// Skip the guest power-off and kill the hypervisor immediately.
+    #[serde(default)]
+    pub force: bool,
+    /// How long to wait for the guest to power off before killing it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SnapshotResponse {
+    pub id: String,
+    pub vm_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub created_at: u64,
+    pub path: String,
+    pub hypervisor: HypervisorType,
+}
+
+/// Body of `POST /vms/{id}/snapshots`. Optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreateSnapshotRequest {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// Body of `POST /vms/{id}/restore`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct RestoreVmRequest {
+    pub snapshot_id: String,
+}
+
+/// Body of `POST /vms/{id}/migrate`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MigrateVmRequest {
+    /// Base URL of the receiving control plane, e.g. `http://127.0.0.1:8081`.
+    pub target: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct MigrateVmResponse {
+    pub vm_id: String,
+    pub target: String,
+}
+
+/// Body of `POST /migrations/incoming`, sent by the source control plane.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IncomingMigrationRequest {
+    pub id: String,
+    pub name: String,
+    pub config: VmConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct IncomingMigrationResponse {
+    pub vm_id: String,
+    /// Where the source hypervisor should send the migration stream, in
+    /// the hypervisor's own URI syntax (`unix:<path>`).
+    pub uri: String,
+}
+
+/// Body of `POST /vms/{id}/console/expect`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExpectRequest {
+    /// Regular expression to wait for in the console output.
+    pub pattern: String,
+    /// Typed into the console first, as is; e.g. `"root\r"`.
+    #[serde(default)]
+    pub send: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timeout_secs: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExpectResponse {
+    /// The output that matched the pattern.
+    pub matched: String,
+    /// The output between the input being sent and the match.
+    pub before: String,
+}
+
+/// Query of `GET /vms/{id}/logs`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LogsQuery {
+    /// 0 for the current boot, -N for the Nth previous one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boot: Option<i64>,
+    /// Only the last lines.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tail: Option<usize>,
+    /// A Unix timestamp in seconds, or a duration back from now such as
+    /// `10m`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub since: Option<String>,
+    /// Keep streaming new output until the VM stops.
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub follow: bool,
+}
+
+/// A recorded console session, as listed by
+/// `GET /vms/{id}/console/sessions`. See `recording.rs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConsoleSession {
+    pub id: String,
+    /// Seconds since the Unix epoch.
+    pub started_at: u64,
+    /// Seconds from connecting to the last recorded event.
+    pub duration_secs: f64,
+    pub size_bytes: u64,
+    /// Who connected, as named in the console handshake, e.g. `gxctl`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+}
+
+/// How much scrollback a console client is sent before live output.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Replay {
+    /// Live output only.
+    None,
+    /// The last `n` lines.
+    Tail(usize),
+    /// Everything still in the scrollback buffer.
+    #[default]
+    All,
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Replay::None => write!(f, "none"),
+            Replay::Tail(lines) => write!(f, "tail:{}", lines),
+            Replay::All => write!(f, "all"),
+        }
+    }
+}
+
+impl FromStr for Replay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Replay::None),
+            "all" => Ok(Replay::All),
+            _ => s
+                .strip_prefix("tail:")
+                .and_then(|lines| lines.parse().ok())
+                .map(Replay::Tail)
+                .ok_or_else(|| {
+                    format!("Invalid replay mode '{}': expected none, tail:<lines> or all", s)
+                }),
+        }
+    }
+}
+
+/// Whether a console client may type, as it asks in its handshake.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "kebab-case")]
+pub enum ConsoleMode {
+    /// Output only; input is ignored.
+    ReadOnly,
+    /// Types alongside the other shared clients.
+    #[default]
+    Shared,
+    /// Holds the write lock: only this client's input reaches the guest.
+    Exclusive,
+}
+
+impl fmt::Display for ConsoleMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConsoleMode::ReadOnly => write!(f, "read-only"),
+            ConsoleMode::Shared => write!(f, "shared"),
+            ConsoleMode::Exclusive => write!(f, "exclusive"),
+        }
+    }
+}
+
+impl FromStr for ConsoleMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "read-only" => Ok(ConsoleMode::ReadOnly),
+            "shared" => Ok(ConsoleMode::Shared),
+            "exclusive" => Ok(ConsoleMode::Exclusive),
+            _ => Err(format!(
+                "Invalid console mode '{}': expected read-only, shared or exclusive",
+                s
+            )),
+        }
+    }
+}
+
+/// A client attached to a console.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct AttachedSession {
+    /// Counts up from 1 for each proxy.
+    pub id: u64,
+    /// What the session may do; an `exclusive` request made while
+    /// another session held the lock is listed as `read-only`.
+    pub mode: ConsoleMode,
+    /// As named in the handshake, e.g. `gxctl` or `browser`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub client: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub connected_at: u64,
+    /// Credentials of the connecting process. Browser sessions are
+    /// connected by the control plane itself.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pid: Option<i32>,
+}
+
+/// How the session is named in notices, e.g. `gxctl (session 3, uid 1000)`.
+impl fmt::Display for AttachedSession {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (session {}", self.client.as_deref().unwrap_or("client"), self.id)?;
+        if let Some(uid) = self.uid {
+            write!(f, ", uid {}", uid)?;
+        }
+        write!(f, ")")
+    }
+}
+
+/// The clients attached to a console and who holds its write lock.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Attached {
+    #[serde(default)]
+    pub sessions: Vec<AttachedSession>,
+    /// Id of the exclusive session holding the write lock.
+    #[serde(default)]
+    pub write_lock: Option<u64>,
+}
+
+/// `GET /vms/{id}/console`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ConsoleInfo {
+    pub vm_id: String,
+    pub console_socket_path: String,
+    pub log_path: String,
+    /// Whether the VM is running.
+    pub available: bool,
+    /// The attached clients and who holds the write lock.
+    #[serde(flatten)]
+    pub attached: Attached,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct DeviceRequest {
+    pub device_path: String,
+}
+
+/// A host PCI device, as listed by `GET /pci-devices`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct PciDeviceInfo {
+    pub address: String,
+    pub vendor_id: String,
+    pub device_id: String,
+    pub class_id: String,
+    pub driver: Option<String>,
+    pub iommu_group: Option<String>,
+    pub sysfs_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiError {
+    pub error: String,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(error: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            error: error.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pci_device_info_deserializes_sysfs_path() {
+        let json = r#"{
+            "address": "0000:00:1f.0",
+            "vendor_id": "8086",
+            "device_id": "9d4e",
+            "class_id": "060100",
+            "driver": "lpc_ich",
+            "iommu_group": "5",
+            "sysfs_path": "/sys/bus/pci/devices/0000:00:1f.0"
+        }"#;
+
+        let info: PciDeviceInfo = serde_json::from_str(json).unwrap();
+        assert_eq!(info.sysfs_path, "/sys/bus/pci/devices/0000:00:1f.0");
+        assert_eq!(info.address, "0000:00:1f.0");
+        assert_eq!(info.driver.as_deref(), Some("lpc_ich"));
+        assert_eq!(info.iommu_group.as_deref(), Some("5"));
+    }
+
+    #[test]
+    fn vm_response_deserializes_crash_details() {
+        let json = r#"{
+            "id": "abc",
+            "name": "vm",
+            "state": "crashed",
+            "vcpu_count": 1,
+            "mem_size_mib": 256,
+            "kernel_image_path": "/k",
+            "rootfs_path": "/r",
+            "kernel_args": "console=ttyS0",
+            "no_reboot": true,
+            "console_socket_path": "/tmp/abc.sock",
+            "log_path": "/tmp/abc.log",
+            "hypervisor": "qemu",
+            "last_exit": { "signal": 9, "console_tail": ["Kernel panic"] }
+        }"#;
+
+        let vm: VmResponse = serde_json::from_str(json).unwrap();
+        assert_eq!(vm.state, VmState::Crashed);
+        let exit = vm.last_exit.unwrap();
+        assert_eq!(exit.to_string(), "killed by signal 9");
+        assert_eq!(exit.console_tail, vec!["Kernel panic".to_string()]);
+        assert!(vm.last_error.is_none());
+    }
+
+    #[test]
+    fn logs_query_leaves_out_what_is_unset() {
+        let query = LogsQuery {
+            tail: Some(50),
+            follow: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&query).unwrap(),
+            serde_json::json!({"tail": 50, "follow": true})
+        );
+        assert_eq!(serde_json::to_value(LogsQuery::default()).unwrap(), serde_json::json!({}));
+    }
+}
@@ -1,57 +1,214 @@
 use axum::{
+    body::Body,
     extract::{
         ws::{Message, WebSocket, WebSocketUpgrade},
-        Path, State,
+        Path, Query, Request, State,
     },
-    http::StatusCode,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::{self, Next},
     response::{IntoResponse, Response},
-    routing::{delete, get, post},
+    routing::{delete, get, patch, post},
     Json, Router,
 };
+use futures_util::stream::{self, Stream, StreamExt};
+use std::ops::Range;
 use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
 
-use crate::models::{ApiError, CreateVmRequest, DeviceRequest, VmConfig, VmResponse, VmState};
-use crate::state::{VmManager, VmManagerError};
-use serde::Serialize;
+use crate::console::{Control, Frame, FrameDecoder, Handshake};
+use crate::logs::LogFollower;
+use crate::models::{
+    new_config, ApiError, ConsoleInfo, ConsoleSession, CreateSnapshotRequest, CreateVmRequest,
+    DeviceRequest, ExpectRequest, ExpectResponse, IncomingMigrationRequest,
+    IncomingMigrationResponse, MigrateVmRequest, MigrateVmResponse, RestoreVmRequest,
+    SnapshotResponse, StartQuery, StartWait, StopVmRequest, UpdateVmRequest, VmResponse, VmState,
+};
+use crate::pci::PciDeviceInfo;
+use crate::state::{
+    VmManager, VmManagerError, DEFAULT_EXPECT_TIMEOUT, DEFAULT_READY_TIMEOUT, DEFAULT_STOP_TIMEOUT,
+};
+use regex_automata::meta::Regex;
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, OpenApi, ToSchema};
 
 pub type AppState = Arc<VmManager>;
 
+/// Prefix of the current version of the API. The same routes are also
+/// served without it, as deprecated aliases.
+pub const API_PREFIX: &str = "/v1";
+
+/// The `/v1` API, as documented by the handlers' annotations.
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        health_check,
+        list_vms,
+        create_vm,
+        get_vm,
+        update_vm,
+        delete_vm,
+        start_vm,
+        stop_vm,
+        pause_vm,
+        reboot_vm,
+        reset_vm,
+        list_snapshots,
+        create_snapshot,
+        delete_snapshot,
+        restore_vm,
+        migrate_vm,
+        get_console_info,
+        console_ws,
+        expect_console,
+        list_console_sessions,
+        download_console_session,
+        get_logs,
+        attach_device,
+        detach_device,
+        prepare_incoming_migration,
+        complete_incoming_migration,
+        abort_incoming_migration,
+        list_pci_devices,
+    ),
+    tags(
+        (name = "vms", description = "VM lifecycle and settings"),
+        (name = "snapshots", description = "Snapshots and restore"),
+        (name = "console", description = "Serial console, logs and recordings"),
+        (name = "devices", description = "VFIO PCI passthrough"),
+        (name = "migrations", description = "Live migration between control planes"),
+        (name = "system", description = "The control plane itself"),
+    )
+)]
+struct V1Api;
+
+/// The OpenAPI document served at `/openapi.json`.
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "glidex",
+        description = "REST API of the glidex control plane. Only the `/v1` routes are \
+            described; the same routes without the prefix are deprecated aliases.",
+        license(name = "MIT")
+    ),
+    nest((path = "/v1", api = V1Api))
+)]
+pub struct ApiDoc;
+
 pub fn create_router(state: AppState) -> Router {
+    let routes = api_routes();
+    Router::new()
+        .nest(API_PREFIX, routes.clone())
+        .merge(routes.route_layer(middleware::from_fn(deprecated_alias)))
+        .route("/openapi.json", get(openapi))
+        .with_state(state)
+}
+
+fn api_routes() -> Router<AppState> {
     Router::new()
         .route("/vms", get(list_vms))
         .route("/vms", post(create_vm))
         .route("/vms/{id}", get(get_vm))
+        .route("/vms/{id}", patch(update_vm))
         .route("/vms/{id}", delete(delete_vm))
         .route("/vms/{id}/start", post(start_vm))
         .route("/vms/{id}/stop", post(stop_vm))
         .route("/vms/{id}/pause", post(pause_vm))
+        .route("/vms/{id}/reboot", post(reboot_vm))
+        .route("/vms/{id}/reset", post(reset_vm))
+        .route("/vms/{id}/snapshots", get(list_snapshots))
+        .route("/vms/{id}/snapshots", post(create_snapshot))
+        .route("/vms/{id}/snapshots/{snapshot_id}", delete(delete_snapshot))
+        .route("/vms/{id}/restore", post(restore_vm))
+        .route("/vms/{id}/migrate", post(migrate_vm))
         .route("/vms/{id}/console", get(get_console_info))
         .route("/vms/{id}/console/ws", get(console_ws))
+        .route("/vms/{id}/console/expect", post(expect_console))
+        .route("/vms/{id}/console/sessions", get(list_console_sessions))
+        .route(
+            "/vms/{id}/console/sessions/{session_id}",
+            get(download_console_session),
+        )
+        .route("/vms/{id}/logs", get(get_logs))
         .route("/vms/{id}/devices", post(attach_device))
         .route("/vms/{id}/devices", delete(detach_device))
+        .route("/migrations/incoming", post(prepare_incoming_migration))
+        .route(
+            "/migrations/incoming/{id}/complete",
+            post(complete_incoming_migration),
+        )
+        .route("/migrations/incoming/{id}", delete(abort_incoming_migration))
         .route("/pci-devices", get(list_pci_devices))
         .route("/health", get(health_check))
-        .with_state(state)
 }
 
+async fn openapi() -> impl IntoResponse {
+    Json(ApiDoc::openapi())
+}
+
+/// Mark a response of an unversioned route as deprecated, pointing at
+/// its `/v1` successor.
+async fn deprecated_alias(request: Request, next: Next) -> Response {
+    let successor = format!("<{}{}>; rel=\"successor-version\"", API_PREFIX, request.uri().path());
+    let mut response = next.run(request).await;
+    let headers = response.headers_mut();
+    headers.insert("deprecation", HeaderValue::from_static("true"));
+    if let Ok(link) = HeaderValue::from_str(&successor) {
+        headers.insert(header::LINK, link);
+    }
+    response
+}
+
+#[derive(Serialize, ToSchema)]
+struct Health {
+    /// Always `ok`.
+    status: &'static str,
+}
+
+#[utoipa::path(
+    get,
+    path = "/health",
+    tag = "system",
+    responses(
+        (status = 200, description = "The control plane is up", body = Health),
+    )
+)]
 async fn health_check() -> impl IntoResponse {
-    Json(serde_json::json!({ "status": "ok" }))
+    Json(Health { status: "ok" })
 }
 
+#[utoipa::path(
+    get,
+    path = "/vms",
+    tag = "vms",
+    responses(
+        (status = 200, description = "All VMs", body = [VmResponse]),
+    )
+)]
 async fn list_vms(State(manager): State<AppState>) -> impl IntoResponse {
     let vms = manager.list_vms().await;
     let response: Vec<VmResponse> = vms.iter().map(VmResponse::from).collect();
     Json(response)
 }
 
+#[utoipa::path(
+    post,
+    path = "/vms",
+    tag = "vms",
+    request_body = CreateVmRequest,
+    responses(
+        (status = 201, description = "The VM was created", body = VmResponse),
+        (status = 409, description = "A VM of that name exists", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
 async fn create_vm(
     State(manager): State<AppState>,
     Json(request): Json<CreateVmRequest>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
     let name = request.name.clone();
-    let config = VmConfig::from(request);
+    let config = new_config(request);
 
     match manager.create_vm(name, config).await {
         Ok(vm) => Ok((StatusCode::CREATED, Json(VmResponse::from(&vm)))),
@@ -59,6 +216,16 @@ async fn create_vm(
     }
 }
 
+#[utoipa::path(
+    get,
+    path = "/vms/{id}",
+    tag = "vms",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+    )
+)]
 async fn get_vm(
     State(manager): State<AppState>,
     Path(id): Path<String>,
@@ -69,6 +236,39 @@ async fn get_vm(
     }
 }
 
+#[utoipa::path(
+    patch,
+    path = "/vms/{id}",
+    tag = "vms",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = UpdateVmRequest,
+    responses(
+        (status = 200, description = "The VM, changed", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state", body = ApiError),
+    )
+)]
+async fn update_vm(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<UpdateVmRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.update_vm(&id, request).await {
+        Ok(vm) => Ok(Json(VmResponse::from(&vm))),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/vms/{id}",
+    tag = "vms",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 204, description = "The VM was deleted"),
+        (status = 404, description = "No such VM", body = ApiError),
+    )
+)]
 async fn delete_vm(
     State(manager): State<AppState>,
     Path(id): Path<String>,
@@ -79,26 +279,75 @@ async fn delete_vm(
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/start",
+    tag = "vms",
+    params(("id" = String, Path, description = "VM id"), StartQuery),
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state, or `timeout_secs` is too long", body = ApiError),
+        (status = 504, description = "The guest did not pass its readiness probe in time", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
 async fn start_vm(
     State(manager): State<AppState>,
     Path(id): Path<String>,
+    Query(query): Query<StartQuery>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
-    match manager.start_vm(&id).await {
+    let timeout = request_timeout(query.timeout_secs, DEFAULT_READY_TIMEOUT)?;
+    let mut result = manager.start_vm(&id).await;
+    if result.is_ok() && query.wait == Some(StartWait::Ready) {
+        result = manager.wait_ready(&id, timeout).await;
+    }
+
+    match result {
         Ok(vm) => Ok(Json(VmResponse::from(&vm))),
         Err(e) => Err(error_to_response(e)),
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/stop",
+    tag = "vms",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = Option<StopVmRequest>,
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state, or `timeout_secs` is too long", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
 async fn stop_vm(
     State(manager): State<AppState>,
     Path(id): Path<String>,
+    request: Option<Json<StopVmRequest>>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
-    match manager.stop_vm(&id).await {
+    let Json(request) = request.unwrap_or_default();
+    let timeout = request_timeout(request.timeout_secs, DEFAULT_STOP_TIMEOUT)?;
+
+    match manager.stop_vm(&id, request.force, timeout).await {
         Ok(vm) => Ok(Json(VmResponse::from(&vm))),
         Err(e) => Err(error_to_response(e)),
     }
 }
 
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/pause",
+    tag = "vms",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
 async fn pause_vm(
     State(manager): State<AppState>,
     Path(id): Path<String>,
@@ -109,37 +358,585 @@ async fn pause_vm(
     }
 }
 
-#[derive(Debug, Serialize)]
-struct ConsoleInfo {
-    vm_id: String,
-    console_socket_path: String,
-    log_path: String,
-    available: bool,
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/reboot",
+    tag = "vms",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
+async fn reboot_vm(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.reboot_vm(&id).await {
+        Ok(vm) => Ok(Json(VmResponse::from(&vm))),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/reset",
+    tag = "vms",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
+async fn reset_vm(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.reset_vm(&id).await {
+        Ok(vm) => Ok(Json(VmResponse::from(&vm))),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/vms/{id}/snapshots",
+    tag = "snapshots",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "The VM's snapshots", body = [SnapshotResponse]),
+        (status = 404, description = "No such VM", body = ApiError),
+    )
+)]
+async fn list_snapshots(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.list_snapshots(&id).await {
+        Ok(snapshots) => Ok(Json(
+            snapshots
+                .iter()
+                .map(SnapshotResponse::from)
+                .collect::<Vec<_>>(),
+        )),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/snapshots",
+    tag = "snapshots",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = Option<CreateSnapshotRequest>,
+    responses(
+        (status = 201, description = "The snapshot was taken", body = SnapshotResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
+async fn create_snapshot(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+    request: Option<Json<CreateSnapshotRequest>>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let Json(request) = request.unwrap_or_default();
+
+    match manager.snapshot_vm(&id, request.name).await {
+        Ok(snapshot) => Ok((StatusCode::CREATED, Json(SnapshotResponse::from(&snapshot)))),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/vms/{id}/snapshots/{snapshot_id}",
+    tag = "snapshots",
+    params(("id" = String, Path, description = "VM id"), ("snapshot_id" = String, Path, description = "Snapshot id")),
+    responses(
+        (status = 204, description = "The snapshot was deleted"),
+        (status = 404, description = "No such VM or snapshot", body = ApiError),
+    )
+)]
+async fn delete_snapshot(
+    State(manager): State<AppState>,
+    Path((id, snapshot_id)): Path<(String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.delete_snapshot(&id, &snapshot_id).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/restore",
+    tag = "snapshots",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = RestoreVmRequest,
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM or snapshot", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
+async fn restore_vm(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<RestoreVmRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.restore_vm(&id, &request.snapshot_id).await {
+        Ok(vm) => Ok(Json(VmResponse::from(&vm))),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/migrate",
+    tag = "migrations",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = MigrateVmRequest,
+    responses(
+        (status = 200, description = "The VM now runs on the target", body = MigrateVmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state", body = ApiError),
+        (status = 502, description = "The target control plane failed", body = ApiError),
+    )
+)]
+async fn migrate_vm(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<MigrateVmRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.migrate_vm(&id, &request.target).await {
+        Ok(()) => Ok(Json(MigrateVmResponse {
+            vm_id: id,
+            target: request.target,
+        })),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/migrations/incoming",
+    tag = "migrations",
+    request_body = IncomingMigrationRequest,
+    responses(
+        (status = 201, description = "The VM waits for the migration stream", body = IncomingMigrationResponse),
+        (status = 409, description = "A VM of that id or name exists", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
+async fn prepare_incoming_migration(
+    State(manager): State<AppState>,
+    Json(request): Json<IncomingMigrationRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager
+        .prepare_incoming(request.id, request.name, request.config)
+        .await
+    {
+        Ok((vm, uri)) => Ok((
+            StatusCode::CREATED,
+            Json(IncomingMigrationResponse { vm_id: vm.id, uri }),
+        )),
+        Err(e) => Err(error_to_response(e)),
+    }
 }
 
+#[utoipa::path(
+    post,
+    path = "/migrations/incoming/{id}/complete",
+    tag = "migrations",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
+async fn complete_incoming_migration(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.complete_incoming(&id).await {
+        Ok(vm) => Ok(Json(VmResponse::from(&vm))),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    delete,
+    path = "/migrations/incoming/{id}",
+    tag = "migrations",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 204, description = "The incoming VM was removed"),
+        (status = 404, description = "No such VM", body = ApiError),
+    )
+)]
+async fn abort_incoming_migration(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.abort_incoming(&id).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+#[utoipa::path(
+    get,
+    path = "/vms/{id}/console",
+    tag = "console",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "How to reach the console, and who is attached", body = ConsoleInfo),
+        (status = 404, description = "No such VM", body = ApiError),
+    )
+)]
 async fn get_console_info(
     State(manager): State<AppState>,
     Path(id): Path<String>,
 ) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
-    match manager.get_vm(&id).await {
-        Ok(vm) => {
-            let available = vm.state == VmState::Running;
-            Ok(Json(ConsoleInfo {
-                vm_id: vm.id,
-                console_socket_path: vm.console_socket_path,
-                log_path: vm.log_path,
-                available,
-            }))
+    let vm = manager.get_vm(&id).await.map_err(error_to_response)?;
+    let attached = manager
+        .console_sessions(&id)
+        .await
+        .map_err(error_to_response)?;
+    Ok(Json(ConsoleInfo {
+        vm_id: vm.id,
+        console_socket_path: vm.console_socket_path,
+        log_path: vm.log_path,
+        available: vm.state == VmState::Running,
+        attached,
+    }))
+}
+
+/// Type into a VM's console and wait for a pattern in what it prints
+/// back.
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/console/expect",
+    tag = "console",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = ExpectRequest,
+    responses(
+        (status = 200, description = "The pattern matched", body = ExpectResponse),
+        (status = 400, description = "Invalid pattern, `timeout_secs` is too long, or the VM is not running", body = ApiError),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 409, description = "The guest exited before the pattern matched", body = ApiError),
+        (status = 502, description = "The VM's console could not be reached", body = ApiError),
+        (status = 504, description = "The pattern did not match in time", body = ApiError),
+    )
+)]
+async fn expect_console(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+    Json(request): Json<ExpectRequest>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    let pattern = Regex::new(&request.pattern).map_err(|e| {
+        (
+            StatusCode::BAD_REQUEST,
+            Json(ApiError::new(
+                "invalid_request",
+                format!("Invalid pattern '{}': {}", request.pattern, e),
+            )),
+        )
+    })?;
+    let timeout = request_timeout(request.timeout_secs, DEFAULT_EXPECT_TIMEOUT)?;
+
+    match manager
+        .expect_console(&id, request.send.as_bytes(), &pattern, timeout)
+        .await
+    {
+        Ok(expected) => Ok(Json(ExpectResponse {
+            matched: String::from_utf8_lossy(&expected.matched).into_owned(),
+            before: String::from_utf8_lossy(&expected.before).into_owned(),
+        })),
+        Err(e) => Err(error_to_response(e)),
+    }
+}
+
+/// How often a followed log is checked for new output.
+const FOLLOW_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+const TEXT_PLAIN: &str = "text/plain; charset=utf-8";
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct LogsQuery {
+    /// `0` for the current boot, `-N` for the Nth previous one.
+    #[serde(default)]
+    boot: i64,
+    /// Only the last this many lines.
+    tail: Option<usize>,
+    /// Only what was written since: Unix seconds, or a duration back
+    /// such as `10m`.
+    #[serde(default, deserialize_with = "deserialize_since")]
+    #[param(value_type = Option<String>)]
+    since: Option<u64>,
+    /// Keep the response open and stream new output until the VM stops.
+    #[serde(default)]
+    follow: bool,
+}
+
+fn deserialize_since<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let value = String::deserialize(deserializer)?;
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64;
+    parse_since(&value, now)
+        .map(Some)
+        .ok_or_else(|| serde::de::Error::custom(format!("invalid since: {}", value)))
+}
+
+/// `since` is a Unix timestamp in seconds, fractions allowed, or a
+/// duration back from `now` such as `90s`, `10m`, `2h` or `1d`. Both are
+/// resolved to Unix milliseconds.
+fn parse_since(value: &str, now: u64) -> Option<u64> {
+    let unit = match value.chars().last()? {
+        's' => 1_000,
+        'm' => 60_000,
+        'h' => 3_600_000,
+        'd' => 86_400_000,
+        _ => {
+            let seconds: f64 = value.parse().ok()?;
+            return (seconds.is_finite() && seconds >= 0.0).then_some((seconds * 1000.0) as u64);
+        }
+    };
+    let ago: u64 = value[..value.len() - 1].parse().ok()?;
+    Some(now.saturating_sub(ago.checked_mul(unit)?))
+}
+
+/// The console log of the current boot, or with `?boot=-N` of the Nth
+/// previous one, as plain text. `since` and `tail` narrow it down to its
+/// last lines, a `Range` header to part of what is left; `follow` keeps
+/// the response open and streams new output until the VM stops.
+#[utoipa::path(
+    get,
+    path = "/vms/{id}/logs",
+    tag = "console",
+    params(("id" = String, Path, description = "VM id"), LogsQuery),
+    responses(
+        (status = 200, description = "The console log", body = String, content_type = "text/plain"),
+        (status = 206, description = "Part of the console log, for a `Range` header", body = String, content_type = "text/plain"),
+        (status = 416, description = "The `Range` is outside the log"),
+        (status = 404, description = "No such VM or boot", body = ApiError),
+    )
+)]
+async fn get_logs(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+    Query(query): Query<LogsQuery>,
+    headers: HeaderMap,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let log = manager
+        .read_log(&id, query.boot)
+        .await
+        .map_err(error_to_response)?;
+    let selected = log.select(query.since, query.tail).to_vec();
+
+    // Previous boots are complete, so there is nothing to follow.
+    if query.follow && query.boot == 0 {
+        let body = Body::from_stream(follow_log(manager, id, selected, log.follow()));
+        return Ok(([(header::CONTENT_TYPE, TEXT_PLAIN)], body).into_response());
+    }
+
+    let len = selected.len();
+    let range = headers
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_range(value, len));
+    let response = match range {
+        None => (
+            [
+                (header::CONTENT_TYPE, TEXT_PLAIN.to_string()),
+                (header::ACCEPT_RANGES, "bytes".to_string()),
+            ],
+            selected,
+        )
+            .into_response(),
+        Some(Some(range)) => (
+            StatusCode::PARTIAL_CONTENT,
+            [
+                (header::CONTENT_TYPE, TEXT_PLAIN.to_string()),
+                (
+                    header::CONTENT_RANGE,
+                    format!("bytes {}-{}/{}", range.start, range.end - 1, len),
+                ),
+            ],
+            selected[range].to_vec(),
+        )
+            .into_response(),
+        Some(None) => (
+            StatusCode::RANGE_NOT_SATISFIABLE,
+            [(header::CONTENT_RANGE, format!("bytes */{}", len))],
+        )
+            .into_response(),
+    };
+    Ok(response)
+}
+
+/// Resolve a `Range` header against a body of `len` bytes. `None` means
+/// ignore it and send the whole body — anything but a single byte range
+/// is not supported — and `Some(None)` that it cannot be satisfied.
+fn parse_range(value: &str, len: usize) -> Option<Option<Range<usize>>> {
+    let spec = value.trim().strip_prefix("bytes=")?;
+    let (first, last) = spec.split_once('-')?;
+    let (first, last) = (first.trim(), last.trim());
+    let range = if first.is_empty() {
+        let suffix: usize = last.parse().ok()?;
+        len.saturating_sub(suffix)..len
+    } else {
+        let first: usize = first.parse().ok()?;
+        let end = match last {
+            "" => len,
+            last => {
+                let last: usize = last.parse().ok()?;
+                if last < first {
+                    return None;
+                }
+                len.min(last.saturating_add(1))
+            }
+        };
+        first..end
+    };
+    Some((range.start < range.end).then_some(range))
+}
+
+/// `head`, then whatever the VM's console appends to its log until the VM
+/// is no longer running, or is deleted.
+fn follow_log(
+    manager: AppState,
+    id: String,
+    head: Vec<u8>,
+    follower: LogFollower,
+) -> impl Stream<Item = std::io::Result<Vec<u8>>> {
+    let new_output = stream::unfold(Some(follower), move |follower| {
+        let manager = manager.clone();
+        let id = id.clone();
+        async move {
+            let mut follower = follower?;
+            loop {
+                // Checked before reading, so the output that came in just
+                // before the VM stopped is still sent.
+                let running = manager.get_vm(&id).await.is_ok_and(|vm| {
+                    matches!(
+                        vm.state,
+                        VmState::Starting | VmState::Running | VmState::Paused | VmState::Stopping
+                    )
+                });
+                let (returned, new) = tokio::task::spawn_blocking(move || {
+                    let new = follower.read_new();
+                    (follower, new)
+                })
+                .await
+                .ok()?;
+                follower = returned;
+                match new {
+                    Ok(new) if !new.is_empty() => {
+                        return Some((Ok(new), running.then_some(follower)));
+                    }
+                    Ok(_) if running => tokio::time::sleep(FOLLOW_POLL_INTERVAL).await,
+                    Ok(_) => return None,
+                    Err(e) => return Some((Err(e), None)),
+                }
+            }
         }
+    });
+    stream::once(async move { Ok(head) }).chain(new_output)
+}
+
+#[utoipa::path(
+    get,
+    path = "/vms/{id}/console/sessions",
+    tag = "console",
+    params(("id" = String, Path, description = "VM id")),
+    responses(
+        (status = 200, description = "The recorded console sessions", body = [ConsoleSession]),
+        (status = 404, description = "No such VM", body = ApiError),
+    )
+)]
+async fn list_console_sessions(
+    State(manager): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, Json<ApiError>)> {
+    match manager.list_console_sessions(&id).await {
+        Ok(sessions) => Ok(Json(sessions)),
         Err(e) => Err(error_to_response(e)),
     }
 }
 
+/// One recorded console session, as an asciicast v2 file.
+#[utoipa::path(
+    get,
+    path = "/vms/{id}/console/sessions/{session_id}",
+    tag = "console",
+    params(("id" = String, Path, description = "VM id"), ("session_id" = String, Path, description = "Session id")),
+    responses(
+        (status = 200, description = "The session as an asciicast v2 file", body = String, content_type = "application/x-asciicast"),
+        (status = 404, description = "No such VM or session", body = ApiError),
+    )
+)]
+async fn download_console_session(
+    State(manager): State<AppState>,
+    Path((id, session_id)): Path<(String, String)>,
+) -> Result<Response, (StatusCode, Json<ApiError>)> {
+    let cast = manager
+        .read_console_session(&id, &session_id)
+        .await
+        .map_err(error_to_response)?;
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/x-asciicast".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.cast\"", session_id),
+            ),
+        ],
+        cast,
+    )
+        .into_response())
+}
+
+#[utoipa::path(
+    get,
+    path = "/pci-devices",
+    tag = "devices",
+    responses(
+        (status = 200, description = "The host's PCI devices", body = [PciDeviceInfo]),
+    )
+)]
 async fn list_pci_devices() -> impl IntoResponse {
     let devices = crate::pci::scan_pci_devices();
     Json(devices)
 }
 
+#[utoipa::path(
+    post,
+    path = "/vms/{id}/devices",
+    tag = "devices",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = DeviceRequest,
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
 async fn attach_device(
     State(manager): State<AppState>,
     Path(id): Path<String>,
@@ -151,6 +948,19 @@ async fn attach_device(
     }
 }
 
+#[utoipa::path(
+    delete,
+    path = "/vms/{id}/devices",
+    tag = "devices",
+    params(("id" = String, Path, description = "VM id")),
+    request_body = DeviceRequest,
+    responses(
+        (status = 200, description = "The VM", body = VmResponse),
+        (status = 404, description = "No such VM", body = ApiError),
+        (status = 400, description = "Not possible in the VM's current state", body = ApiError),
+        (status = 500, description = "The hypervisor or the database failed", body = ApiError),
+    )
+)]
 async fn detach_device(
     State(manager): State<AppState>,
     Path(id): Path<String>,
@@ -162,11 +972,57 @@ async fn detach_device(
     }
 }
 
+pub use glidex_client::console::CONSOLE_WS_PROTOCOL;
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+struct ConsoleWsQuery {
+    /// `none`, `tail:<lines>` or `all` (the default) of the scrollback.
+    replay: Option<String>,
+    /// `read-only`, `shared` (the default) or `exclusive`.
+    mode: Option<String>,
+    /// Who is connecting, for the recording and the session list;
+    /// `browser` by default.
+    client: Option<String>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/vms/{id}/console/ws",
+    tag = "console",
+    params(("id" = String, Path, description = "VM id"), ConsoleWsQuery),
+    responses(
+        (status = 101, description = "Switched to a WebSocket; with the `glidex.console.v1` subprotocol, binary messages carry console data and text messages JSON control messages"),
+        (status = 400, description = "Invalid query", body = String, content_type = "text/plain"),
+        (status = 404, description = "No such VM", body = String, content_type = "text/plain"),
+    )
+)]
 async fn console_ws(
     State(manager): State<AppState>,
     Path(id): Path<String>,
+    Query(query): Query<ConsoleWsQuery>,
     ws: WebSocketUpgrade,
 ) -> Response {
+    let replay = match query.replay.as_deref().map(str::parse).transpose() {
+        Ok(replay) => replay.unwrap_or_default(),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    let mode = match query.mode.as_deref().map(str::parse).transpose() {
+        Ok(mode) => mode.unwrap_or_default(),
+        Err(e) => return (StatusCode::BAD_REQUEST, e).into_response(),
+    };
+    // It becomes a word of the handshake line.
+    let client = query.client.unwrap_or_else(|| "browser".to_string());
+    let valid_client = !client.is_empty()
+        && client.len() <= 32
+        && client
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+    if !valid_client {
+        return (StatusCode::BAD_REQUEST, format!("Invalid client name '{}'", client))
+            .into_response();
+    }
+
     let vm = match manager.get_vm(&id).await {
         Ok(vm) => vm,
         Err(VmManagerError::VmNotFound(_)) => {
@@ -177,68 +1033,127 @@ async fn console_ws(
         }
     };
 
+    let ws = ws.protocols([CONSOLE_WS_PROTOCOL]);
+    let handshake = Handshake {
+        replay,
+        framed: ws.selected_protocol().is_some(),
+        record: true,
+        client: Some(client),
+        mode,
+        query: false,
+    };
     let console_path = vm.console_socket_path.clone();
-    ws.on_upgrade(move |socket| bridge_console(socket, console_path))
+    ws.on_upgrade(move |socket| bridge_console(socket, console_path, handshake))
 }
 
 /// Pump bytes in both directions between a browser WebSocket and the VM's
-/// console Unix socket until either side closes. The console proxy thread in
-/// the hypervisor backend keeps the listener alive even after the guest
-/// exits, so connecting to a dead VM still succeeds and replays the log.
-async fn bridge_console(mut ws: WebSocket, console_path: String) {
-    let unix = match UnixStream::connect(&console_path).await {
+/// console Unix socket until either side closes. The console proxy keeps
+/// the listener alive even after the guest exits, so connecting to a dead
+/// VM still succeeds and replays its scrollback.
+///
+/// With the framed protocol, binary messages carry console data and text
+/// messages JSON control messages, mapped one to one onto the proxy's
+/// frames. Otherwise both kinds of message are typed into the console.
+async fn bridge_console(mut ws: WebSocket, console_path: String, handshake: Handshake) {
+    let connected = match UnixStream::connect(&console_path).await {
+        Ok(mut unix) => unix.write_all(&handshake.line()).await.map(|()| unix),
+        Err(e) => Err(e),
+    };
+    let unix = match connected {
         Ok(s) => s,
         Err(e) => {
-            let _ = ws
-                .send(Message::Text(
-                    format!("Failed to connect to console socket {}: {}", console_path, e)
-                        .into(),
-                ))
-                .await;
+            let message = format!("Failed to connect to console socket {}: {}", console_path, e);
+            let message = if handshake.framed {
+                serde_json::to_string(&Control::Error { message }).unwrap_or_default()
+            } else {
+                message
+            };
+            let _ = ws.send(Message::Text(message.into())).await;
             let _ = ws.send(Message::Close(None)).await;
             return;
         }
     };
     let (mut unix_rx, mut unix_tx) = unix.into_split();
     let mut buf = [0u8; 4096];
+    let mut frames = FrameDecoder::default();
 
     loop {
         tokio::select! {
             read = unix_rx.read(&mut buf) => {
-                match read {
-                    Ok(0) => break,
-                    Ok(n) => {
-                        let chunk: Vec<u8> = buf[..n].to_vec();
-                        if ws.send(Message::Binary(chunk.into())).await.is_err() {
-                            break;
-                        }
+                let n = match read {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => n,
+                };
+                let mut messages = Vec::new();
+                if handshake.framed {
+                    frames.push(&buf[..n]);
+                    while let Ok(Some(frame)) = frames.next_frame() {
+                        messages.push(match frame {
+                            Frame::Data(data) => Message::Binary(data.into()),
+                            Frame::Control(json) => {
+                                Message::Text(String::from_utf8_lossy(&json).into_owned().into())
+                            }
+                        });
+                    }
+                } else {
+                    messages.push(Message::Binary(buf[..n].to_vec().into()));
+                }
+                for message in messages {
+                    if ws.send(message).await.is_err() {
+                        return;
                     }
-                    Err(_) => break,
                 }
             }
             msg = ws.recv() => {
-                match msg {
-                    Some(Ok(Message::Binary(data))) => {
-                        if unix_tx.write_all(&data).await.is_err() {
-                            break;
-                        }
+                let data = match msg {
+                    Some(Ok(Message::Binary(data))) if handshake.framed => {
+                        Frame::encode_data(&data)
                     }
-                    Some(Ok(Message::Text(text))) => {
-                        if unix_tx.write_all(text.as_bytes()).await.is_err() {
-                            break;
-                        }
+                    Some(Ok(Message::Text(text))) if handshake.framed => {
+                        Frame::Control(text.as_bytes().to_vec()).encode()
                     }
+                    Some(Ok(Message::Binary(data))) => data.to_vec(),
+                    Some(Ok(Message::Text(text))) => text.as_bytes().to_vec(),
                     Some(Ok(Message::Close(_))) | None | Some(Err(_)) => break,
-                    Some(Ok(_)) => {}
+                    Some(Ok(_)) => continue,
+                };
+                if unix_tx.write_all(&data).await.is_err() {
+                    break;
                 }
             }
         }
     }
 }
 
+/// Longest `timeout_secs` a request may ask for.
+const MAX_TIMEOUT_SECS: u64 = 24 * 60 * 60;
+
+/// The timeout a request asked for, or `default`; 400 past `MAX_TIMEOUT_SECS`
+fn request_timeout(
+    secs: Option<u64>,
+    default: Duration,
+) -> Result<Duration, (StatusCode, Json<ApiError>)> {
+    match secs {
+        None => Ok(default),
+        Some(secs) if secs <= MAX_TIMEOUT_SECS => Ok(Duration::from_secs(secs)),
+        Some(secs) => Err((
+            StatusCode::BAD_REQUEST,
+            Json(ApiError::new(
+                "invalid_request",
+                format!(
+                    "timeout_secs {} exceeds the maximum of {}",
+                    secs, MAX_TIMEOUT_SECS
+                ),
+            )),
+        )),
+    }
+}
+
 fn error_to_response(error: VmManagerError) -> (StatusCode, Json<ApiError>) {
     match &error {
-        VmManagerError::VmNotFound(_) => (
+        VmManagerError::VmNotFound(_)
+        | VmManagerError::SnapshotNotFound(_)
+        | VmManagerError::LogNotFound(_) => (
             StatusCode::NOT_FOUND,
             Json(ApiError::new("not_found", error.to_string())),
         ),
@@ -262,5 +1177,25 @@ fn error_to_response(error: VmManagerError) -> (StatusCode, Json<ApiError>) {
             StatusCode::SERVICE_UNAVAILABLE,
             Json(ApiError::new("hypervisor_unavailable", error.to_string())),
         ),
+        VmManagerError::MigrationFailed(_) => (
+            StatusCode::BAD_GATEWAY,
+            Json(ApiError::new("migration_failed", error.to_string())),
+        ),
+        VmManagerError::ExpectFailed(_) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            Json(ApiError::new("expect_failed", error.to_string())),
+        ),
+        VmManagerError::GuestExited(_) => (
+            StatusCode::CONFLICT,
+            Json(ApiError::new("guest_exited", error.to_string())),
+        ),
+        VmManagerError::ConsoleFailed(_) => (
+            StatusCode::BAD_GATEWAY,
+            Json(ApiError::new("console_failed", error.to_string())),
+        ),
+        VmManagerError::NotReady(_) => (
+            StatusCode::GATEWAY_TIMEOUT,
+            Json(ApiError::new("not_ready", error.to_string())),
+        ),
     }
 }
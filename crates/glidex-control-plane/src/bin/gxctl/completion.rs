@@ -0,0 +1,281 @@
+//! Tab completion and hints for the REPL: command names, VM names and
+//! ids, PCI devices for `attach-device`, and file paths.
+
+use super::{Client, COMMANDS};
+use colored::Colorize;
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Context, Helper};
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex};
+
+/// Commands whose first argument is a VM.
+const VM_COMMANDS: &[&str] = &[
+    "get",
+    "start",
+    "stop",
+    "pause",
+    "reboot",
+    "reset",
+    "snapshot",
+    "snapshots",
+    "restore",
+    "delete-snapshot",
+    "migrate",
+    "connect",
+    "console",
+    "attach",
+    "expect",
+    "log",
+    "logs",
+    "sessions",
+    "download-session",
+    "delete",
+    "rm",
+    "attach-device",
+    "detach-device",
+    "export",
+];
+
+/// Flags whose value is a file path.
+const PATH_FLAGS: &[&str] = &["-f", "--filename", "--kernel", "--rootfs"];
+
+/// What completion knows of the control plane.
+#[derive(Default)]
+struct Cache {
+    /// Id, name and attached devices of each VM.
+    vms: Vec<(String, String, Vec<String>)>,
+    /// Address and sysfs path of each host PCI device.
+    pci_devices: Vec<(String, String)>,
+}
+
+/// What to complete the word under the cursor with.
+enum Candidates {
+    Words(Vec<Pair>),
+    Files,
+}
+
+fn pair(display: String, replacement: &str) -> Pair {
+    Pair {
+        display,
+        replacement: replacement.to_string(),
+    }
+}
+
+/// The REPL's rustyline helper.
+#[derive(Default)]
+pub struct ReplHelper {
+    cache: Arc<Mutex<Cache>>,
+    files: FilenameCompleter,
+}
+
+impl ReplHelper {
+    /// Update the cached VMs, and the PCI devices until there are some,
+    /// in the background so as not to hold up the prompt.
+    pub fn refresh(&self, client: &Client) {
+        let cache = self.cache.clone();
+        let client = client.clone();
+        tokio::spawn(async move {
+            if let Ok(vms) = client.list_vms().await {
+                cache.lock().unwrap().vms = vms
+                    .into_iter()
+                    .map(|vm| (vm.id, vm.name, vm.vfio_devices))
+                    .collect();
+            }
+            if !cache.lock().unwrap().pci_devices.is_empty() {
+                return;
+            }
+            if let Ok(devices) = client.list_pci_devices().await {
+                cache.lock().unwrap().pci_devices = devices
+                    .into_iter()
+                    .map(|device| (device.address, device.sysfs_path))
+                    .collect();
+            }
+        });
+    }
+
+    /// Where the word that ends `line` starts, and its candidates.
+    fn candidates(&self, line: &str) -> (usize, Candidates) {
+        let start = line.rfind(char::is_whitespace).map_or(0, |at| at + 1);
+        let word = &line[start..];
+        let before: Vec<&str> = line[..start].split_whitespace().collect();
+        let cache = self.cache.lock().unwrap();
+
+        let pairs: Vec<Pair> = match before[..] {
+            [] => COMMANDS
+                .iter()
+                .map(|command| pair(command.to_string(), command))
+                .collect(),
+            [_, .., flag] if PATH_FLAGS.contains(&flag) => return (start, Candidates::Files),
+            [command] if VM_COMMANDS.contains(&command) => cache
+                .vms
+                .iter()
+                .flat_map(|(id, name, _)| {
+                    [
+                        pair(name.clone(), name),
+                        pair(format!("{} ({})", id, name), id),
+                    ]
+                })
+                .collect(),
+            ["attach-device", _] => cache
+                .pci_devices
+                .iter()
+                .map(|(address, path)| pair(address.clone(), path))
+                .collect(),
+            ["detach-device", vm] => cache
+                .vms
+                .iter()
+                .filter(|(id, name, _)| id == vm || name == vm)
+                .flat_map(|(_, _, devices)| devices)
+                .map(|device| pair(device.clone(), device))
+                .collect(),
+            _ => Vec::new(),
+        };
+        let pairs = pairs
+            .into_iter()
+            .filter(|pair| pair.replacement.starts_with(word) || pair.display.starts_with(word))
+            .collect();
+        (start, Candidates::Words(pairs))
+    }
+}
+
+impl Completer for ReplHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        match self.candidates(&line[..pos]) {
+            (start, Candidates::Words(pairs)) => Ok((start, pairs)),
+            (_, Candidates::Files) => self.files.complete_path(line, pos),
+        }
+    }
+}
+
+impl Hinter for ReplHelper {
+    type Hint = String;
+
+    /// The rest of the word being typed at the end of the line, when
+    /// only one candidate fits it.
+    fn hint(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> Option<String> {
+        if pos < line.len() || line.ends_with(char::is_whitespace) {
+            return None;
+        }
+        let (start, Candidates::Words(pairs)) = self.candidates(line) else {
+            return None;
+        };
+        let word = &line[start..];
+        match &pairs[..] {
+            [only] => only.replacement.strip_prefix(word).map(str::to_string),
+            _ => None,
+        }
+    }
+}
+
+impl Highlighter for ReplHelper {
+    fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
+        Cow::Owned(hint.dimmed().to_string())
+    }
+}
+
+impl Validator for ReplHelper {}
+
+impl Helper for ReplHelper {}
+
+/// Completes file paths, for the prompts that ask for one.
+#[derive(Default)]
+pub struct PathHelper(FilenameCompleter);
+
+impl Completer for PathHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        self.0.complete_path(line, pos)
+    }
+}
+
+impl Hinter for PathHelper {
+    type Hint = String;
+}
+
+impl Highlighter for PathHelper {}
+
+impl Validator for PathHelper {}
+
+impl Helper for PathHelper {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper() -> ReplHelper {
+        let helper = ReplHelper::default();
+        {
+            let mut cache = helper.cache.lock().unwrap();
+            cache.vms = vec![
+                ("4f1c-aa".to_string(), "web-1".to_string(), Vec::new()),
+                (
+                    "9b2e-bb".to_string(),
+                    "db".to_string(),
+                    vec!["/sys/bus/pci/devices/0000:41:00.0".to_string()],
+                ),
+            ];
+            cache.pci_devices = vec![(
+                "0000:41:00.0".to_string(),
+                "/sys/bus/pci/devices/0000:41:00.0".to_string(),
+            )];
+        }
+        helper
+    }
+
+    fn words(helper: &ReplHelper, line: &str) -> (usize, Vec<String>) {
+        match helper.candidates(line) {
+            (start, Candidates::Words(pairs)) => {
+                (start, pairs.into_iter().map(|pair| pair.replacement).collect())
+            }
+            (_, Candidates::Files) => panic!("{} completes files", line),
+        }
+    }
+
+    #[test]
+    fn commands_vms_and_devices_are_completed() {
+        let helper = helper();
+        assert_eq!(words(&helper, "snap"), (0, vec!["snapshot".into(), "snapshots".into()]));
+        assert_eq!(words(&helper, "start w"), (6, vec!["web-1".into()]));
+        assert_eq!(words(&helper, "get 9b"), (4, vec!["9b2e-bb".into()]));
+        assert_eq!(
+            words(&helper, "attach-device web-1 0000:41").1,
+            ["/sys/bus/pci/devices/0000:41:00.0"]
+        );
+        assert_eq!(words(&helper, "detach-device db ").1, ["/sys/bus/pci/devices/0000:41:00.0"]);
+        assert_eq!(words(&helper, "detach-device web-1 ").1, Vec::<String>::new());
+        assert_eq!(words(&helper, "health now").1, Vec::<String>::new());
+        assert!(matches!(helper.candidates("apply -f vm"), (9, Candidates::Files)));
+        assert!(matches!(
+            helper.candidates("create web --rootfs ~/"),
+            (20, Candidates::Files)
+        ));
+    }
+
+    #[test]
+    fn hints_show_the_rest_of_a_unique_candidate() {
+        let helper = helper();
+        let history = rustyline::history::DefaultHistory::new();
+        let ctx = Context::new(&history);
+        assert_eq!(helper.hint("hea", 3, &ctx), Some("lth".to_string()));
+        assert_eq!(helper.hint("connect we", 10, &ctx), Some("b-1".to_string()));
+        assert_eq!(helper.hint("s", 1, &ctx), None);
+        assert_eq!(helper.hint("health ", 7, &ctx), None);
+        assert_eq!(helper.hint("hea", 1, &ctx), None);
+    }
+}
@@ -0,0 +1,2498 @@
+use clap::{Args, Parser, Subcommand};
+use colored::Colorize;
+use glidex_client::console::{Console, ConsoleEvent, Control, Frame, FrameDecoder, Handshake};
+use glidex_client::models::{
+    AttachedSession, ConsoleMode, ConsoleSession, CreateSnapshotRequest, CreateVmRequest,
+    ExpectRequest, HypervisorType, LogsQuery, MigrateVmRequest, PciDeviceInfo, Replay,
+    RestoreVmRequest, SnapshotResponse, StartQuery, StartWait, StopVmRequest, UpdateVmRequest,
+    VmResponse, VmState,
+};
+use glidex_client::{Client, ConsoleOptions};
+use nix::sys::termios::{self, LocalFlags, SetArg, Termios};
+use rustyline::error::ReadlineError;
+use rustyline::history::{DefaultHistory, FileHistory};
+use rustyline::{Config, Editor};
+use serde::Serialize;
+use std::io::{self, Write};
+use std::os::fd::{AsFd, BorrowedFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use tabled::{Table, Tabled};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod completion;
+mod manifest;
+mod top;
+
+#[derive(Parser)]
+#[command(name = "gxctl")]
+#[command(about = "Interactive CLI for Glidex Control Plane")]
+#[command(after_help = "Without a command, gxctl starts an interactive shell.")]
+struct Cli {
+    /// API server URL
+    #[arg(
+        short,
+        long,
+        alias = "api-url",
+        global = true,
+        default_value = "http://localhost:8080"
+    )]
+    server: String,
+
+    /// Output format of commands that print VMs, snapshots, sessions or
+    /// devices
+    #[arg(short, long, global = true, value_enum, default_value_t = Output::Table)]
+    output: Output,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Output {
+    Table,
+    Json,
+    Yaml,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Manage VMs
+    #[command(subcommand)]
+    Vm(VmCommand),
+    /// Create, update or delete VMs to match manifests
+    Apply {
+        /// A manifest file, or a directory of them
+        #[arg(short = 'f', long = "filename", required = true)]
+        files: Vec<String>,
+        /// Only print the plan
+        #[arg(long)]
+        dry_run: bool,
+        /// Delete VMs that no manifest names
+        #[arg(long)]
+        prune: bool,
+    },
+    /// Print a manifest for an existing VM
+    Export {
+        vm: String,
+        /// yaml or toml
+        #[arg(long)]
+        format: Option<String>,
+    },
+    /// Watch the VMs live, with keys to start, stop, pause and attach
+    Top {
+        /// Seconds between refreshes
+        #[arg(long, short = 'n')]
+        interval: Option<u64>,
+    },
+    /// List host PCI devices, or show one
+    Pci { address: Option<String> },
+    /// Check API server health
+    Health,
+}
+
+#[derive(Debug, Subcommand)]
+enum VmCommand {
+    /// List all VMs
+    #[command(alias = "ls")]
+    List,
+    /// Show VM details
+    Get { vm: String },
+    /// Create a new VM
+    Create(CreateArgs),
+    /// Start a VM
+    Start {
+        vm: String,
+        /// Wait until the guest is ready
+        #[arg(short, long)]
+        wait: bool,
+    },
+    /// Stop a VM (graceful unless --force)
+    Stop {
+        vm: String,
+        #[arg(short, long)]
+        force: bool,
+        /// Seconds to wait for a graceful shutdown
+        #[arg(short, long)]
+        timeout: Option<u64>,
+    },
+    /// Pause a VM
+    Pause { vm: String },
+    /// Reboot a VM (guest-cooperative)
+    Reboot { vm: String },
+    /// Hard-reset a VM
+    Reset { vm: String },
+    /// Delete a VM
+    #[command(alias = "rm")]
+    Delete {
+        vm: String,
+        /// Do not ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Connect to the VM's console (interactive)
+    Connect {
+        vm: String,
+        /// none, tail:<lines> or all
+        #[arg(short, long)]
+        replay: Option<String>,
+        /// read-only, shared or exclusive
+        #[arg(short, long)]
+        mode: Option<String>,
+        /// Use the console socket on this host instead of the WebSocket
+        #[arg(short, long)]
+        local: bool,
+    },
+    /// Show the VM's serial console log
+    Log {
+        vm: String,
+        /// Only the last <lines> lines
+        #[arg(short = 'n', long)]
+        tail: Option<usize>,
+        /// Only output since <time> (Unix seconds, or e.g. 10m ago)
+        #[arg(long)]
+        since: Option<String>,
+        /// A previous boot, e.g. -1
+        #[arg(short, long, allow_hyphen_values = true)]
+        boot: Option<String>,
+        /// Keep printing new output
+        #[arg(short, long)]
+        follow: bool,
+    },
+    /// Type into the console and wait for a pattern
+    Expect {
+        vm: String,
+        pattern: String,
+        /// Text to type first; \r presses Enter
+        #[arg(long)]
+        send: Option<String>,
+        #[arg(short, long)]
+        timeout: Option<u64>,
+    },
+    /// List the VM's recorded console sessions
+    Sessions { vm: String },
+    /// Save a console session as an asciicast file
+    DownloadSession {
+        vm: String,
+        session_id: String,
+        file: Option<String>,
+    },
+    /// Snapshot a paused VM
+    Snapshot { vm: String, label: Option<String> },
+    /// List the VM's snapshots
+    Snapshots { vm: String },
+    /// Boot a stopped VM from a snapshot
+    Restore { vm: String, snapshot_id: String },
+    /// Delete a snapshot
+    DeleteSnapshot { vm: String, snapshot_id: String },
+    /// Live-migrate a running VM to another control plane
+    Migrate { vm: String, target: String },
+    /// Attach a PCI device to the VM
+    AttachDevice { vm: String, path: String },
+    /// Detach a PCI device from the VM
+    DetachDevice { vm: String, path: String },
+}
+
+/// `vm create`: the flags of the REPL's `create <name> ...`.
+#[derive(Debug, Args)]
+struct CreateArgs {
+    name: String,
+    #[arg(long)]
+    vcpus: Option<u8>,
+    /// Memory in MiB
+    #[arg(long)]
+    memory: Option<u32>,
+    #[arg(long)]
+    kernel: Option<String>,
+    #[arg(long)]
+    rootfs: Option<String>,
+    #[arg(long, allow_hyphen_values = true)]
+    kernel_args: Option<String>,
+    /// firecracker, cloudhypervisor or qemu
+    #[arg(long)]
+    hypervisor: Option<String>,
+    /// VFIO PCI devices, comma-separated
+    #[arg(long)]
+    vfio: Option<String>,
+    /// Keep QEMU running when the guest reboots
+    #[arg(long)]
+    reboot: bool,
+}
+
+/// Append `--<name> <value>` if there is a value.
+fn push_flag(words: &mut Vec<String>, name: &str, value: Option<impl ToString>) {
+    if let Some(value) = value {
+        words.push(format!("--{}", name));
+        words.push(value.to_string());
+    }
+}
+
+impl Command {
+    /// The REPL command this stands for, as words for `handle_command`.
+    fn into_words(self) -> Vec<String> {
+        let mut words: Vec<String> = Vec::new();
+        let vm = match self {
+            Command::Health => {
+                words.push("health".to_string());
+                return words;
+            }
+            Command::Top { interval } => {
+                words.push("top".to_string());
+                push_flag(&mut words, "interval", interval);
+                return words;
+            }
+            Command::Pci { address } => {
+                words.push("pci".to_string());
+                words.extend(address);
+                return words;
+            }
+            Command::Apply {
+                files,
+                dry_run,
+                prune,
+            } => {
+                words.push("apply".to_string());
+                for file in files {
+                    words.extend(["-f".to_string(), file]);
+                }
+                if dry_run {
+                    words.push("--dry-run".to_string());
+                }
+                if prune {
+                    words.push("--prune".to_string());
+                }
+                return words;
+            }
+            Command::Export { vm, format } => {
+                words.extend(["export".to_string(), vm]);
+                push_flag(&mut words, "format", format);
+                return words;
+            }
+            Command::Vm(vm) => vm,
+        };
+        match vm {
+            VmCommand::List => words.push("list".to_string()),
+            VmCommand::Get { vm } => words.extend(["get".to_string(), vm]),
+            VmCommand::Create(args) => {
+                words.extend(["create".to_string(), args.name]);
+                push_flag(&mut words, "vcpus", args.vcpus);
+                push_flag(&mut words, "memory", args.memory);
+                push_flag(&mut words, "kernel", args.kernel);
+                push_flag(&mut words, "rootfs", args.rootfs);
+                push_flag(&mut words, "kernel-args", args.kernel_args);
+                push_flag(&mut words, "hypervisor", args.hypervisor);
+                push_flag(&mut words, "vfio", args.vfio);
+                if args.reboot {
+                    words.push("--reboot".to_string());
+                }
+            }
+            VmCommand::Start { vm, wait } => {
+                words.extend(["start".to_string(), vm]);
+                if wait {
+                    words.push("--wait".to_string());
+                }
+            }
+            VmCommand::Stop { vm, force, timeout } => {
+                words.extend(["stop".to_string(), vm]);
+                if force {
+                    words.push("--force".to_string());
+                }
+                push_flag(&mut words, "timeout", timeout);
+            }
+            VmCommand::Pause { vm } => words.extend(["pause".to_string(), vm]),
+            VmCommand::Reboot { vm } => words.extend(["reboot".to_string(), vm]),
+            VmCommand::Reset { vm } => words.extend(["reset".to_string(), vm]),
+            VmCommand::Delete { vm, yes } => {
+                words.extend(["delete".to_string(), vm]);
+                if yes {
+                    words.push("--yes".to_string());
+                }
+            }
+            VmCommand::Connect {
+                vm,
+                replay,
+                mode,
+                local,
+            } => {
+                words.extend(["connect".to_string(), vm]);
+                push_flag(&mut words, "replay", replay);
+                push_flag(&mut words, "mode", mode);
+                if local {
+                    words.push("--local".to_string());
+                }
+            }
+            VmCommand::Log {
+                vm,
+                tail,
+                since,
+                boot,
+                follow,
+            } => {
+                words.extend(["log".to_string(), vm]);
+                push_flag(&mut words, "tail", tail);
+                push_flag(&mut words, "since", since);
+                push_flag(&mut words, "boot", boot);
+                if follow {
+                    words.push("--follow".to_string());
+                }
+            }
+            VmCommand::Expect {
+                vm,
+                pattern,
+                send,
+                timeout,
+            } => {
+                words.extend(["expect".to_string(), vm, pattern]);
+                push_flag(&mut words, "send", send);
+                push_flag(&mut words, "timeout", timeout);
+            }
+            VmCommand::Sessions { vm } => words.extend(["sessions".to_string(), vm]),
+            VmCommand::DownloadSession {
+                vm,
+                session_id,
+                file,
+            } => {
+                words.extend(["download-session".to_string(), vm, session_id]);
+                words.extend(file);
+            }
+            VmCommand::Snapshot { vm, label } => {
+                words.extend(["snapshot".to_string(), vm]);
+                words.extend(label);
+            }
+            VmCommand::Snapshots { vm } => words.extend(["snapshots".to_string(), vm]),
+            VmCommand::Restore { vm, snapshot_id } => {
+                words.extend(["restore".to_string(), vm, snapshot_id])
+            }
+            VmCommand::DeleteSnapshot { vm, snapshot_id } => {
+                words.extend(["delete-snapshot".to_string(), vm, snapshot_id])
+            }
+            VmCommand::Migrate { vm, target } => {
+                words.extend(["migrate".to_string(), vm, target])
+            }
+            VmCommand::AttachDevice { vm, path } => {
+                words.extend(["attach-device".to_string(), vm, path])
+            }
+            VmCommand::DetachDevice { vm, path } => {
+                words.extend(["detach-device".to_string(), vm, path])
+            }
+        }
+        words
+    }
+}
+
+/// Why a command did not succeed. Run from the command line, this is
+/// gxctl's exit status.
+#[derive(Debug, PartialEq)]
+enum CommandError {
+    /// The arguments were wrong; the usage was printed.
+    Usage,
+    /// The command failed; the error was printed.
+    Failed,
+}
+
+impl CommandError {
+    /// 2 like clap's own usage errors, 1 for anything else.
+    fn exit_code(&self) -> i32 {
+        match self {
+            CommandError::Usage => 2,
+            CommandError::Failed => 1,
+        }
+    }
+}
+
+type CommandResult = Result<(), CommandError>;
+
+fn usage(text: &str) -> CommandError {
+    eprintln!("{}", text.yellow());
+    CommandError::Usage
+}
+
+fn failed(e: impl std::fmt::Display) -> CommandError {
+    eprintln!("{} {}", "Error:".red(), e);
+    CommandError::Failed
+}
+
+/// Print `value` for `--output json|yaml`.
+fn print_as<T: Serialize + ?Sized>(output: Output, value: &T) -> CommandResult {
+    let text = match output {
+        Output::Json => serde_json::to_string_pretty(value).map_err(failed)?,
+        Output::Yaml => serde_yaml::to_string(value).map_err(failed)?,
+        Output::Table => unreachable!("tables are printed by each command"),
+    };
+    println!("{}", text.trim_end());
+    Ok(())
+}
+
+/// What `--output json|yaml` prints for a command whose API answer has
+/// no body, e.g. `{"status": "deleted", "id": "…"}`.
+#[derive(Serialize)]
+struct Status<'a> {
+    status: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<&'a str>,
+}
+
+/// Commands that print something of their own, a manifest, a log or a
+/// console, which `--output` cannot change.
+const OWN_FORMAT_COMMANDS: &[&str] = &[
+    "export",
+    "apply",
+    "log",
+    "logs",
+    "connect",
+    "console",
+    "attach",
+    "download-session",
+    "top",
+];
+
+fn print_new_state(vm: &VmResponse) {
+    println!(
+        "{} VM {} is now {}",
+        "Success:".green(),
+        vm.name,
+        format_state(&vm.state)
+    );
+}
+
+/// Take `-o`/`--output <format>` out of a REPL command line.
+fn take_output_flag(words: &mut Vec<String>) -> Option<Output> {
+    let mut output = Output::Table;
+    while let Some(at) = words.iter().position(|w| w == "-o" || w == "--output") {
+        let value = words.get(at + 1)?;
+        output = clap::ValueEnum::from_str(value, true).ok()?;
+        words.drain(at..at + 2);
+    }
+    Some(output)
+}
+
+/// A row of `list`.
+#[derive(Tabled)]
+struct VmRow {
+    id: String,
+    name: String,
+    state: VmState,
+    vcpu_count: u8,
+    mem_size_mib: u32,
+    hypervisor: HypervisorType,
+}
+
+impl From<&VmResponse> for VmRow {
+    fn from(vm: &VmResponse) -> Self {
+        VmRow {
+            id: vm.id.clone(),
+            name: vm.name.clone(),
+            state: vm.state.clone(),
+            vcpu_count: vm.vcpu_count,
+            mem_size_mib: vm.mem_size_mib,
+            hypervisor: vm.hypervisor,
+        }
+    }
+}
+
+/// A row of `pci`; `pci <address>` shows the sysfs path too.
+#[derive(Tabled)]
+struct PciRow {
+    address: String,
+    vendor_id: String,
+    device_id: String,
+    class_id: String,
+    #[tabled(display_with = "display_option")]
+    driver: Option<String>,
+    #[tabled(display_with = "display_option")]
+    iommu_group: Option<String>,
+}
+
+impl From<&PciDeviceInfo> for PciRow {
+    fn from(device: &PciDeviceInfo) -> Self {
+        PciRow {
+            address: device.address.clone(),
+            vendor_id: device.vendor_id.clone(),
+            device_id: device.device_id.clone(),
+            class_id: device.class_id.clone(),
+            driver: device.driver.clone(),
+            iommu_group: device.iommu_group.clone(),
+        }
+    }
+}
+
+/// A row of `snapshots`.
+#[derive(Tabled)]
+struct SnapshotRow {
+    id: String,
+    #[tabled(display_with = "display_option")]
+    name: Option<String>,
+    #[tabled(rename = "created", display_with = "display_age")]
+    created_at: u64,
+}
+
+impl From<&SnapshotResponse> for SnapshotRow {
+    fn from(snapshot: &SnapshotResponse) -> Self {
+        SnapshotRow {
+            id: snapshot.id.clone(),
+            name: snapshot.name.clone(),
+            created_at: snapshot.created_at,
+        }
+    }
+}
+
+/// Render a Unix timestamp as a coarse age, e.g. "5m ago".
+fn display_age(timestamp: &u64) -> String {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let secs = now.saturating_sub(*timestamp);
+    match secs {
+        0..=59 => format!("{}s ago", secs),
+        60..=3599 => format!("{}m ago", secs / 60),
+        3600..=86399 => format!("{}h ago", secs / 3600),
+        _ => format!("{}d ago", secs / 86400),
+    }
+}
+
+fn display_option(o: &Option<String>) -> String {
+    match o {
+        Some(s) => s.clone(),
+        None => "-".to_string(),
+    }
+}
+
+/// A row of `sessions`.
+#[derive(Tabled)]
+struct SessionRow {
+    id: String,
+    #[tabled(rename = "started", display_with = "display_age")]
+    started_at: u64,
+    #[tabled(rename = "duration", display_with = "display_duration")]
+    duration_secs: f64,
+    #[tabled(rename = "size")]
+    size_bytes: u64,
+    #[tabled(display_with = "display_option")]
+    client: Option<String>,
+}
+
+impl From<&ConsoleSession> for SessionRow {
+    fn from(session: &ConsoleSession) -> Self {
+        SessionRow {
+            id: session.id.clone(),
+            started_at: session.started_at,
+            duration_secs: session.duration_secs,
+            size_bytes: session.size_bytes,
+            client: session.client.clone(),
+        }
+    }
+}
+
+fn display_duration(secs: &f64) -> String {
+    format!("{:.1}s", secs)
+}
+
+/// How another session attached to a console is listed, e.g.
+/// `gxctl (session 3, uid 1000), shared`.
+fn describe_session(session: &AttachedSession) -> String {
+    format!("{}, {}", session, session.mode)
+}
+
+/// The commands `print_help` lists, for tab completion.
+const COMMANDS: &[&str] = &[
+    "list",
+    "get",
+    "create",
+    "start",
+    "stop",
+    "pause",
+    "reboot",
+    "reset",
+    "snapshot",
+    "snapshots",
+    "restore",
+    "delete-snapshot",
+    "migrate",
+    "connect",
+    "log",
+    "expect",
+    "sessions",
+    "download-session",
+    "delete",
+    "apply",
+    "export",
+    "pci",
+    "attach-device",
+    "detach-device",
+    "top",
+    "health",
+    "help",
+    "exit",
+];
+
+fn print_help() {
+    println!("{}", "Available commands:".bold());
+    println!("  {}              - List all VMs", "list".cyan());
+    println!("  {}    - Show VM details", "get <name|id>".cyan());
+    println!(
+        "  {}           - Create a new VM (interactive)",
+        "create".cyan()
+    );
+    println!(
+        "  {} - Create a new VM without prompting",
+        "create <name> [--vcpus <n>] [--memory <MiB>] [--kernel <path>] [--rootfs <path>] [--kernel-args <args>] [--hypervisor <name>] [--vfio <path>,...] [--reboot]".cyan()
+    );
+    println!(
+        "  {} - Start a VM (--wait: until the guest is ready)",
+        "start <name|id> [--wait]".cyan()
+    );
+    println!(
+        "  {} - Stop a VM (graceful unless --force)",
+        "stop <name|id> [--force] [--timeout <secs>]".cyan()
+    );
+    println!("  {}  - Pause a VM", "pause <name|id>".cyan());
+    println!("  {} - Reboot a VM (guest-cooperative)", "reboot <name|id>".cyan());
+    println!("  {}  - Hard-reset a VM", "reset <name|id>".cyan());
+    println!(
+        "  {} - Snapshot a paused VM",
+        "snapshot <name|id> [label]".cyan()
+    );
+    println!("  {} - List a VM's snapshots", "snapshots <name|id>".cyan());
+    println!(
+        "  {} - Boot a stopped VM from a snapshot",
+        "restore <name|id> <snapshot-id>".cyan()
+    );
+    println!(
+        "  {} - Delete a snapshot",
+        "delete-snapshot <name|id> <snapshot-id>".cyan()
+    );
+    println!(
+        "  {} - Live-migrate a running VM to another control plane",
+        "migrate <name|id> <target-url>".cyan()
+    );
+    println!(
+        "  {} - Connect to VM console (interactive)",
+        "connect <name|id> [--replay none|tail:<lines>|all] [--mode read-only|shared|exclusive] [--local]".cyan()
+    );
+    println!(
+        "  {} - Show VM serial console log",
+        "log <name|id> [--tail <lines>] [--since <time>] [--boot -<n>] [--follow]".cyan()
+    );
+    println!(
+        "  {} - Type into the console and wait for a pattern",
+        "expect <name|id> <regex> [--send <text>] [--timeout <secs>]".cyan()
+    );
+    println!(
+        "  {} - List a VM's recorded console sessions",
+        "sessions <name|id>".cyan()
+    );
+    println!(
+        "  {} - Save a console session as an asciicast file",
+        "download-session <name|id> <session-id> [file]".cyan()
+    );
+    println!("  {} - Delete a VM", "delete <name|id> [--yes]".cyan());
+    println!(
+        "  {} - Create, update or delete VMs to match manifests",
+        "apply -f <file|dir> [--dry-run] [--prune]".cyan()
+    );
+    println!(
+        "  {} - Print a manifest for a VM",
+        "export <name|id> [--format yaml|toml]".cyan()
+    );
+    println!("  {}               - List host PCI devices", "pci".cyan());
+    println!(
+        "  {}     - Show detailed info (incl. sysfs path) for one device",
+        "pci <address>".cyan()
+    );
+    println!(
+        "  {} - Attach PCI device to VM",
+        "attach-device <name|id> <path>".cyan()
+    );
+    println!(
+        "  {} - Detach PCI device from VM",
+        "detach-device <name|id> <path>".cyan()
+    );
+    println!(
+        "  {} - Watch VMs live; s/x/p start, stop, pause, c attaches",
+        "top [--interval <secs>]".cyan()
+    );
+    println!("  {}            - Check API server health", "health".cyan());
+    println!("  {}              - Show this help", "help".cyan());
+    println!("  {}              - Exit the CLI", "exit".cyan());
+    println!();
+    println!(
+        "{} You can use either VM name or ID for commands.",
+        "Note:".dimmed()
+    );
+    println!(
+        "{} Add {} to print VMs, snapshots, sessions or devices as JSON or YAML.",
+        "Note:".dimmed(),
+        "-o json|yaml".bold()
+    );
+    println!(
+        "{} In a console, type {} after Enter for the escape menu.",
+        "Note:".dimmed(),
+        "~?".bold()
+    );
+}
+
+fn prompt(msg: &str) -> String {
+    print!("{}", msg);
+    io::stdout().flush().unwrap();
+    let mut input = String::new();
+    io::stdin().read_line(&mut input).unwrap();
+    input.trim().to_string()
+}
+
+/// Like `prompt`, with tab completion of file paths. `Ctrl+C` cancels.
+fn prompt_path(msg: &str) -> Result<String, CommandError> {
+    let mut editor = Editor::<completion::PathHelper, DefaultHistory>::new().map_err(failed)?;
+    editor.set_helper(Some(completion::PathHelper::default()));
+    match editor.readline(msg) {
+        Ok(input) => Ok(input.trim().to_string()),
+        Err(ReadlineError::Eof) => Ok(String::new()),
+        Err(ReadlineError::Interrupted) => Err(failed("Cancelled")),
+        Err(e) => Err(failed(e)),
+    }
+}
+
+fn prompt_optional(msg: &str) -> Option<String> {
+    let input = prompt(msg);
+    if input.is_empty() {
+        None
+    } else {
+        Some(input)
+    }
+}
+
+/// The hypervisor a name or shorthand stands for.
+fn parse_hypervisor(name: &str) -> Option<HypervisorType> {
+    match name.to_lowercase().as_str() {
+        "firecracker" | "fc" => Some(HypervisorType::Firecracker),
+        "cloudhypervisor" | "cloud-hypervisor" | "ch" => Some(HypervisorType::CloudHypervisor),
+        "qemu" | "q" => Some(HypervisorType::Qemu),
+        _ => None,
+    }
+}
+
+/// Split a comma-separated list of VFIO devices.
+fn parse_vfio_devices(list: &str) -> Option<Vec<String>> {
+    let devices: Vec<String> = list
+        .split(',')
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .collect();
+    (!devices.is_empty()).then_some(devices)
+}
+
+/// The kernel and rootfs `create` uses unless told otherwise.
+fn default_images() -> (String, String) {
+    let home_dir = std::env::var("HOME").unwrap_or_else(|_| ".".to_string());
+    (
+        format!("{}/.glidex/vmlinux.bin", home_dir),
+        format!("{}/.glidex/rootfs.ext4", home_dir),
+    )
+}
+
+/// Parse `create <name> [flags]`, which creates a VM without prompting.
+/// What is not given is left to the same defaults as the prompts, or
+/// the server's. Returns `None` on anything unrecognised.
+fn parse_create_flags(name: &str, args: &[String]) -> Option<CreateVmRequest> {
+    if name.starts_with('-') {
+        return None;
+    }
+    let (kernel_image_path, rootfs_path) = default_images();
+    let mut request = CreateVmRequest {
+        name: name.to_string(),
+        vcpu_count: 1,
+        mem_size_mib: 512,
+        kernel_image_path,
+        rootfs_path,
+        kernel_args: None,
+        hypervisor: None,
+        vfio_devices: None,
+        no_reboot: None,
+        log_policy: None,
+        readiness: None,
+    };
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--vcpus" => request.vcpu_count = args.next()?.parse().ok()?,
+            "--memory" => request.mem_size_mib = args.next()?.parse().ok()?,
+            "--kernel" => request.kernel_image_path = args.next()?.clone(),
+            "--rootfs" => request.rootfs_path = args.next()?.clone(),
+            "--kernel-args" => request.kernel_args = Some(args.next()?.clone()),
+            "--hypervisor" => request.hypervisor = Some(parse_hypervisor(args.next()?)?),
+            "--vfio" => request.vfio_devices = Some(parse_vfio_devices(args.next()?)?),
+            "--reboot" => request.no_reboot = Some(false),
+            _ => return None,
+        }
+    }
+    Some(request)
+}
+
+/// Ask for everything `create` needs.
+fn prompt_create_request() -> Result<CreateVmRequest, CommandError> {
+    println!("{}", "Create new VM".bold());
+    println!("{}", "-".repeat(40));
+
+    let name = prompt("VM name: ");
+    if name.is_empty() {
+        return Err(failed("VM name is required"));
+    }
+
+    let vcpu_count: u8 = prompt("vCPU count [1]: ").parse().unwrap_or(1);
+
+    let mem_size_mib: u32 = prompt("Memory (MiB) [512]: ").parse().unwrap_or(512);
+
+    let (default_kernel, default_rootfs) = default_images();
+
+    let kernel_image_path = match prompt_path(&format!("Kernel image path [{}]: ", default_kernel))?.as_str() {
+        "" => default_kernel,
+        s => s.to_string(),
+    };
+
+    let rootfs_path = match prompt_path(&format!("Root filesystem path [{}]: ", default_rootfs))?.as_str() {
+        "" => default_rootfs,
+        s => s.to_string(),
+    };
+
+    let kernel_args = prompt_optional("Kernel arguments (optional, default: root=/dev/vda reboot=k panic=1): ");
+
+    let hypervisor = match prompt("Hypervisor [firecracker/cloudhypervisor/qemu] (default: qemu): ").as_str() {
+        "" => HypervisorType::Qemu,
+        other => parse_hypervisor(other).unwrap_or_else(|| {
+            println!(
+                "{} Unknown hypervisor '{}', using qemu",
+                "Warning:".yellow(),
+                other
+            );
+            HypervisorType::Qemu
+        }),
+    };
+
+    let vfio_devices = if hypervisor != HypervisorType::Firecracker {
+        prompt_optional("VFIO PCI devices (comma-separated, e.g. /sys/bus/pci/devices/0000:41:00.0): ")
+            .and_then(|list| parse_vfio_devices(&list))
+    } else {
+        None
+    };
+
+    // Only QEMU can choose; the server default keeps `-no-reboot`.
+    let no_reboot = if hypervisor == HypervisorType::Qemu {
+        let answer = prompt("Exit QEMU when the guest reboots (-no-reboot)? [Y/n]: ");
+        matches!(answer.to_lowercase().as_str(), "n" | "no").then_some(false)
+    } else {
+        None
+    };
+
+    Ok(CreateVmRequest {
+        name,
+        vcpu_count,
+        mem_size_mib,
+        kernel_image_path,
+        rootfs_path,
+        kernel_args,
+        hypervisor: Some(hypervisor),
+        vfio_devices,
+        no_reboot,
+        log_policy: None,
+        readiness: None,
+    })
+}
+
+/// `create` prompts for the VM's settings; `create <name> [flags]`
+/// takes them from `args` instead.
+async fn handle_create(client: &Client, args: &[String], output: Output) -> CommandResult {
+    let request = match args {
+        [] => prompt_create_request()?,
+        [name, flags @ ..] => parse_create_flags(name, flags).ok_or_else(|| {
+            usage(
+                "Usage: create [<name> [--vcpus <n>] [--memory <MiB>] [--kernel <path>] [--rootfs <path>] \
+                 [--kernel-args <args>] [--hypervisor <name>] [--vfio <path>,...] [--reboot]]",
+            )
+        })?,
+    };
+
+    let vm = client.create_vm(&request).await.map_err(failed)?;
+    if output != Output::Table {
+        return print_as(output, &vm);
+    }
+    println!("{}", "VM created successfully!".green());
+    println!("  ID: {}", vm.id.yellow());
+    println!("  Name: {}", vm.name);
+    println!("  State: {}", vm.state);
+    println!("  Hypervisor: {}", vm.hypervisor);
+    Ok(())
+}
+
+/// Parse `apply` flags: the manifest paths, `--dry-run` and `--prune`.
+/// Returns `None` on anything unrecognised or without a path.
+fn parse_apply_flags<'a>(args: &[&'a str]) -> Option<(Vec<&'a str>, bool, bool)> {
+    let mut files = Vec::new();
+    let mut dry_run = false;
+    let mut prune = false;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "-f" | "--filename" => files.push(*args.next()?),
+            "--dry-run" => dry_run = true,
+            "--prune" => prune = true,
+            _ => return None,
+        }
+    }
+    (!files.is_empty()).then_some((files, dry_run, prune))
+}
+
+/// Run the steps of one `apply` action, stopping at the first failure.
+async fn apply_action(client: &Client, action: &manifest::Action) -> Result<(), String> {
+    use manifest::Step;
+
+    let mut vm_id = action.vm_id.clone().unwrap_or_default();
+    for step in &action.steps {
+        let result = match step {
+            Step::Create(request) => client.create_vm(request).await.map(|vm| vm_id = vm.id),
+            Step::Stop => client
+                .stop_vm(&vm_id, &StopVmRequest::default())
+                .await
+                .map(drop),
+            Step::Update(update) => client.update_vm(&vm_id, update).await.map(drop),
+            Step::AttachDevice(path) => client.attach_device(&vm_id, path).await.map(drop),
+            Step::DetachDevice(path) => client.detach_device(&vm_id, path).await.map(drop),
+            Step::Start => client
+                .start_vm(&vm_id, &StartQuery::default())
+                .await
+                .map(drop),
+            Step::Delete => client.delete_vm(&vm_id).await,
+        };
+        result.map_err(|e| format!("{} failed: {}", step, e))?;
+    }
+    Ok(())
+}
+
+/// Bring the VMs in line with the manifests in `files`, printing the
+/// plan first.
+async fn handle_apply(
+    client: &Client,
+    files: &[&str],
+    dry_run: bool,
+    prune: bool,
+) -> CommandResult {
+    let manifests = manifest::load(files).map_err(failed)?;
+    let vms = client.list_vms().await.map_err(failed)?;
+    let actions = manifest::plan(&manifests, &vms, prune);
+
+    println!("{}", "Plan:".bold());
+    for action in &actions {
+        println!("  {}", action);
+    }
+    let unmanaged = vms
+        .iter()
+        .filter(|vm| !manifests.iter().any(|m| m.name == vm.name))
+        .count();
+    if !prune && unmanaged > 0 {
+        println!(
+            "  {}",
+            format!("{} VM(s) without a manifest kept; --prune deletes them", unmanaged).dimmed()
+        );
+    }
+
+    let mut result = match actions.iter().any(|action| action.refused.is_some()) {
+        true => Err(CommandError::Failed),
+        false => Ok(()),
+    };
+    if dry_run {
+        return result;
+    }
+    if actions.iter().all(manifest::Action::is_noop) {
+        println!("Nothing to do");
+        return result;
+    }
+    for action in actions.iter().filter(|action| !action.steps.is_empty()) {
+        match apply_action(client, action).await {
+            Ok(()) => println!("{} {}", "Applied:".green(), action.name),
+            Err(e) => result = Err(failed(format!("{}: {}", action.name, e))),
+        }
+    }
+    result
+}
+
+/// Parse the flags after `stop <vm>`: `--force` and `--timeout <secs>`.
+/// Returns `None` on anything unrecognised.
+fn parse_stop_flags(args: &[&str]) -> Option<(bool, Option<u64>)> {
+    let mut force = false;
+    let mut timeout_secs = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--force" | "-f" => force = true,
+            "--timeout" | "-t" => timeout_secs = Some(args.next()?.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some((force, timeout_secs))
+}
+
+/// Split a command line into words, where '...' and "..." group words
+/// with their spaces. Nothing else is interpreted, so regular expressions
+/// keep their backslashes. Returns `None` on an unterminated quote.
+fn split_words(line: &str) -> Option<Vec<String>> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.get_or_insert_with(String::new).push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            (None, c) if c.is_whitespace() => words.extend(word.take()),
+            (None, c) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return None;
+    }
+    words.extend(word);
+    Some(words)
+}
+
+/// Turn the escapes `\r`, `\n`, `\t` and `\\` into the characters, so
+/// `--send` can press Enter.
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('r') => out.push('\r'),
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(other) => {
+                if other != '\\' {
+                    out.push('\\');
+                }
+                out.push(other);
+            }
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Parse the flags after `expect <vm> <regex>`: `--send <text>` and
+/// `--timeout <secs>`. Returns `None` on anything unrecognised.
+fn parse_expect_flags(args: &[String]) -> Option<(String, Option<u64>)> {
+    let mut send = String::new();
+    let mut timeout_secs = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--send" | "-s" => send = unescape(args.next()?),
+            "--timeout" | "-t" => timeout_secs = Some(args.next()?.parse().ok()?),
+            _ => return None,
+        }
+    }
+    Some((send, timeout_secs))
+}
+
+/// Parse `log` flags into the query of `GET /vms/{id}/logs`.
+/// Returns `None` on anything unrecognised.
+fn parse_log_flags(args: &[&str]) -> Option<LogsQuery> {
+    let mut query = LogsQuery::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--follow" | "-f" => query.follow = true,
+            "--tail" | "-n" => query.tail = Some(args.next()?.parse().ok()?),
+            "--boot" | "-b" => query.boot = Some(args.next()?.parse().ok()?),
+            "--since" => {
+                let since = args.next()?;
+                if !since.chars().all(|c| c.is_ascii_alphanumeric() || c == '.') {
+                    return None;
+                }
+                query.since = Some(since.to_string());
+            }
+            _ => return None,
+        }
+    }
+    Some(query)
+}
+
+/// What `connect` asks for. By default, the whole scrollback in a
+/// shared session over the WebSocket.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct ConnectFlags {
+    replay: Replay,
+    mode: ConsoleMode,
+    /// Use the console socket rather than the WebSocket.
+    local: bool,
+}
+
+/// Parse `connect` flags, starting from the defaults.
+fn parse_connect_flags(args: &[&str]) -> Option<ConnectFlags> {
+    let mut flags = ConnectFlags::default();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match *arg {
+            "--replay" | "-r" => flags.replay = args.next()?.parse().ok()?,
+            "--mode" | "-m" => flags.mode = args.next()?.parse().ok()?,
+            "--read-only" => flags.mode = ConsoleMode::ReadOnly,
+            "--exclusive" | "-x" => flags.mode = ConsoleMode::Exclusive,
+            "--local" | "-l" => flags.local = true,
+            _ => return None,
+        }
+    }
+    Some(flags)
+}
+
+fn format_state(state: &VmState) -> String {
+    let text = state.to_string();
+    match state {
+        VmState::Running => text.green().to_string(),
+        VmState::Stopped => text.red().to_string(),
+        VmState::Paused => text.yellow().to_string(),
+        VmState::Created => text.blue().to_string(),
+        VmState::Starting | VmState::Stopping => text.cyan().to_string(),
+        VmState::Crashed | VmState::Failed => text.red().bold().to_string(),
+    }
+}
+
+/// Set terminal to raw mode for interactive console
+fn set_raw_mode(fd: BorrowedFd<'_>) -> Option<Termios> {
+    let orig_termios = termios::tcgetattr(fd).ok()?;
+    let mut raw = orig_termios.clone();
+
+    // Disable canonical mode and echo
+    raw.local_flags.remove(LocalFlags::ICANON);
+    raw.local_flags.remove(LocalFlags::ECHO);
+    raw.local_flags.remove(LocalFlags::ISIG);
+
+    termios::tcsetattr(fd, SetArg::TCSANOW, &raw).ok()?;
+    Some(orig_termios)
+}
+
+/// Restore terminal to original mode
+fn restore_terminal(fd: BorrowedFd<'_>, termios: &Termios) {
+    let _ = termios::tcsetattr(fd, SetArg::TCSANOW, termios);
+}
+
+async fn handle_log(client: &Client, vm_id: &str, query: &LogsQuery) -> CommandResult {
+    let mut logs = match client.logs(vm_id, query).await {
+        Ok(logs) => logs,
+        Err(e) if e.status() == Some(reqwest::StatusCode::NOT_FOUND) => {
+            eprintln!("{} No log for that boot. Start the VM first.", "Info:".yellow());
+            return Err(CommandError::Failed);
+        }
+        Err(e) => return Err(failed(e)),
+    };
+
+    // With --follow this runs until the VM stops or the user hits Ctrl+C.
+    let mut stdout = io::stdout();
+    let mut has_content = false;
+    loop {
+        let chunk = tokio::select! {
+            chunk = logs.chunk() => chunk,
+            _ = tokio::signal::ctrl_c() => break,
+        };
+        match chunk {
+            Ok(Some(data)) => {
+                has_content |= !data.is_empty();
+                let _ = stdout.write_all(&data);
+                let _ = stdout.flush();
+            }
+            Ok(None) => break,
+            Err(e) => {
+                println!();
+                return Err(failed(format!("Error reading log: {}", e)));
+            }
+        }
+    }
+
+    if !has_content {
+        eprintln!("{} Log is empty. Start the VM to see console output.", "Info:".yellow());
+    } else {
+        println!();
+    }
+    Ok(())
+}
+
+/// `Ctrl+]`, which detaches from the console.
+const DETACH_KEY: u8 = 0x1d;
+
+/// An attached console: through the control plane's WebSocket, or with
+/// `--local` through the VM's console socket, which only works on the
+/// control plane's host but skips a hop.
+enum ConsoleConnection {
+    WebSocket(Box<Console>),
+    Local {
+        stream: tokio::net::UnixStream,
+        frames: FrameDecoder,
+    },
+}
+
+impl ConsoleConnection {
+    async fn local(socket_path: &str, flags: ConnectFlags) -> Result<Self, String> {
+        let mut stream = tokio::net::UnixStream::connect(socket_path)
+            .await
+            .map_err(|e| format!("Failed to connect to console socket {}: {}", socket_path, e))?;
+        let handshake = Handshake {
+            replay: flags.replay,
+            framed: true,
+            client: Some("gxctl".into()),
+            mode: flags.mode,
+            ..Default::default()
+        };
+        stream
+            .write_all(&handshake.line())
+            .await
+            .map_err(|e| format!("Failed to write to console socket: {}", e))?;
+        Ok(ConsoleConnection::Local {
+            stream,
+            frames: FrameDecoder::default(),
+        })
+    }
+
+    async fn send(&mut self, frame: Frame) -> Result<(), String> {
+        match self {
+            ConsoleConnection::WebSocket(console) => {
+                console.send(frame).await.map_err(|e| e.to_string())
+            }
+            ConsoleConnection::Local { stream, .. } => stream
+                .write_all(&frame.encode())
+                .await
+                .map_err(|e| e.to_string()),
+        }
+    }
+
+    /// The next frame from the console, or `None` once it closed.
+    async fn recv(&mut self) -> Option<Result<Frame, String>> {
+        match self {
+            ConsoleConnection::WebSocket(console) => {
+                Some(console.recv().await?.map_err(|e| e.to_string()))
+            }
+            ConsoleConnection::Local { stream, frames } => loop {
+                match frames.next_frame() {
+                    Ok(Some(frame)) => return Some(Ok(frame)),
+                    Ok(None) => {}
+                    Err(e) => return Some(Err(e.to_string())),
+                }
+                let mut chunk = [0u8; 4096];
+                match stream.read(&mut chunk).await {
+                    Ok(0) => return None,
+                    Ok(n) => frames.push(&chunk[..n]),
+                    Err(e) => return Some(Err(e.to_string())),
+                }
+            },
+        }
+    }
+}
+
+/// The terminal's size, to pass on to the console.
+fn window_size() -> Option<(u16, u16)> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ok = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    (ok && size.ws_col > 0).then_some((size.ws_col, size.ws_row))
+}
+
+/// Read stdin on a thread and pass what is typed on, until `running`
+/// is cleared. Stdin is polled, so that a console that closes by itself
+/// does not leave the thread waiting for a keystroke meant for the REPL.
+fn spawn_stdin_reader(
+    running: Arc<AtomicBool>,
+    typed: tokio::sync::mpsc::Sender<Vec<u8>>,
+) -> thread::JoinHandle<()> {
+    thread::spawn(move || {
+        let mut buf = [0u8; 1024];
+        while running.load(Ordering::SeqCst) {
+            let mut stdin = libc::pollfd {
+                fd: libc::STDIN_FILENO,
+                events: libc::POLLIN,
+                revents: 0,
+            };
+            if unsafe { libc::poll(&mut stdin, 1, 100) } <= 0 {
+                continue;
+            }
+            // Not `io::stdin()`: its buffer would hide input from `poll`.
+            let n = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+            if n <= 0 || typed.blocking_send(buf[..n as usize].to_vec()).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// The escape prefix: typed at the start of a line, the next key is a
+/// command for gxctl rather than input for the guest.
+const ESCAPE_KEY: u8 = b'~';
+
+/// The escape menu, as shown by `~?`.
+const ESCAPES: &[(u8, &str)] = &[
+    (b'.', "detach from the console"),
+    (b'p', "pause the VM"),
+    (b'r', "resume the VM"),
+    (b'R', "hard reset the VM"),
+    (b'b', "send a serial break"),
+    (b'l', "start or stop capturing output to a local file"),
+    (b's', "show the VM's status"),
+    (b'?', "show this menu"),
+    (b'~', "type a ~"),
+];
+
+/// Keystrokes, after picking out escapes.
+#[derive(Debug, PartialEq)]
+enum Typed {
+    Input(Vec<u8>),
+    Escape(u8),
+    Detach,
+}
+
+/// Picks escapes out of what is typed, like ssh: `~` right after Enter
+/// (or first thing) is held back, and the key after it is a command
+/// from [`ESCAPES`]. `~~` types a `~`; `~` before any other key is
+/// passed on together with it.
+struct EscapeParser {
+    line_start: bool,
+    escaping: bool,
+}
+
+impl Default for EscapeParser {
+    fn default() -> Self {
+        Self {
+            line_start: true,
+            escaping: false,
+        }
+    }
+}
+
+impl EscapeParser {
+    fn feed(&mut self, data: &[u8]) -> Vec<Typed> {
+        let mut typed = Vec::new();
+        let mut input = Vec::new();
+        for &b in data {
+            if std::mem::take(&mut self.escaping) {
+                if b == ESCAPE_KEY {
+                    input.push(b);
+                    self.line_start = false;
+                    continue;
+                }
+                if ESCAPES.iter().any(|&(key, _)| key == b) {
+                    if !input.is_empty() {
+                        typed.push(Typed::Input(std::mem::take(&mut input)));
+                    }
+                    if b == b'.' {
+                        typed.push(Typed::Detach);
+                        return typed;
+                    }
+                    typed.push(Typed::Escape(b));
+                    continue;
+                }
+                input.push(ESCAPE_KEY);
+            } else if b == DETACH_KEY {
+                if !input.is_empty() {
+                    typed.push(Typed::Input(input));
+                }
+                typed.push(Typed::Detach);
+                return typed;
+            } else if b == ESCAPE_KEY && self.line_start {
+                self.escaping = true;
+                continue;
+            }
+            input.push(b);
+            self.line_start = b == b'\r' || b == b'\n';
+        }
+        if !input.is_empty() {
+            typed.push(Typed::Input(input));
+        }
+        typed
+    }
+}
+
+/// Console output being captured to a local file, toggled by `~l`.
+struct Capture {
+    file: std::fs::File,
+    path: String,
+    bytes: usize,
+}
+
+/// Show a line from gxctl itself in the raw-mode terminal.
+fn show_line(text: &str) {
+    print!("\r\n{}\r\n", format!("[glidex] {}", text).dimmed());
+    let _ = io::stdout().flush();
+}
+
+/// Show a control message from the console in the terminal.
+fn show_control(frame: &Frame) {
+    let text = match frame.control() {
+        Some(Control::Notice { message }) => message,
+        Some(Control::Error { message }) => format!("error: {}", message),
+        Some(Control::Status {
+            event: ConsoleEvent::GuestExited,
+        }) => "guest exited".to_string(),
+        _ => return,
+    };
+    show_line(&text);
+}
+
+/// Run an escape command. Errors of the action itself are shown in the
+/// terminal; only a failure to reach the console is returned.
+async fn run_escape(
+    command: u8,
+    connection: &mut ConsoleConnection,
+    client: &Client,
+    vm_id: &str,
+    capture: &mut Option<Capture>,
+) -> Result<(), String> {
+    let show_vm = |result: Result<VmResponse, glidex_client::Error>| match result {
+        Ok(vm) => show_line(&format!("VM {} is now {}", vm.name, vm.state)),
+        Err(e) => show_line(&format!("error: {}", e)),
+    };
+    match command {
+        b'p' => show_vm(client.pause_vm(vm_id).await),
+        // Starting a paused VM resumes it.
+        b'r' => show_vm(client.start_vm(vm_id, &StartQuery::default()).await),
+        b'R' => show_vm(client.reset_vm(vm_id).await),
+        b'b' => {
+            connection.send(Control::Break.frame()).await?;
+            show_line("sent a serial break");
+        }
+        b'l' => match capture.take() {
+            Some(capture) => show_line(&format!(
+                "stopped capturing output to {} ({} bytes)",
+                capture.path, capture.bytes
+            )),
+            None => {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_secs();
+                let path = format!("{}-console-{}.log", vm_id, now);
+                match std::fs::File::create(&path) {
+                    Ok(file) => {
+                        show_line(&format!("capturing output to {}", path));
+                        *capture = Some(Capture {
+                            file,
+                            path,
+                            bytes: 0,
+                        });
+                    }
+                    Err(e) => show_line(&format!("error: failed to create {}: {}", path, e)),
+                }
+            }
+        },
+        b's' => match client.get_vm(vm_id).await {
+            Ok(vm) => {
+                let mut status = format!(
+                    "VM {} is {}, {} vCPU, {} MiB",
+                    vm.name, vm.state, vm.vcpu_count, vm.mem_size_mib
+                );
+                if vm.state == VmState::Running {
+                    status.push_str(if vm.ready { ", ready" } else { ", not ready" });
+                }
+                if let Some(path) = capture.as_ref().map(|capture| &capture.path) {
+                    status.push_str(&format!(", capturing output to {}", path));
+                }
+                show_line(&status);
+            }
+            Err(e) => show_line(&format!("error: {}", e)),
+        },
+        _ => {
+            let menu: Vec<String> = ESCAPES
+                .iter()
+                .map(|&(key, action)| format!("  ~{}  {}", key as char, action))
+                .collect();
+            show_line(&format!(
+                "escapes, typed after Enter:\r\n{}",
+                menu.join("\r\n")
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Relay between the terminal and `connection` until the user detaches
+/// or the console closes. Returns how it ended.
+async fn relay_console(
+    connection: &mut ConsoleConnection,
+    client: &Client,
+    vm_id: &str,
+) -> Result<&'static str, String> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let resize = |(cols, rows): (u16, u16)| Control::Resize { cols, rows }.frame();
+    if let Some(size) = window_size() {
+        connection.send(resize(size)).await?;
+    }
+    let mut resized = signal(SignalKind::window_change()).ok();
+
+    let running = Arc::new(AtomicBool::new(true));
+    let (typed_tx, mut typed) = tokio::sync::mpsc::channel(64);
+    let stdin_reader = spawn_stdin_reader(running.clone(), typed_tx);
+    let mut escapes = EscapeParser::default();
+    let mut capture = None;
+    let mut stdout = io::stdout();
+
+    let result = 'relay: loop {
+        tokio::select! {
+            input = typed.recv() => {
+                let Some(input) = input else {
+                    break Ok("Detached from console");
+                };
+                for typed in escapes.feed(&input) {
+                    let sent = match typed {
+                        Typed::Input(data) => connection.send(Frame::Data(data)).await,
+                        Typed::Escape(command) => {
+                            run_escape(command, connection, client, vm_id, &mut capture).await
+                        }
+                        Typed::Detach => break 'relay Ok("Detached from console"),
+                    };
+                    if let Err(e) = sent {
+                        break 'relay Err(e);
+                    }
+                }
+            }
+            Some(()) = async {
+                match &mut resized {
+                    Some(signal) => signal.recv().await,
+                    None => std::future::pending().await,
+                }
+            } => {
+                if let Some(size) = window_size() {
+                    if let Err(e) = connection.send(resize(size)).await {
+                        break Err(e);
+                    }
+                }
+            }
+            message = connection.recv() => match message {
+                Some(Ok(Frame::Data(data))) => {
+                    let _ = stdout.write_all(&data);
+                    let _ = stdout.flush();
+                    if let Some(active) = &mut capture {
+                        match active.file.write_all(&data) {
+                            Ok(()) => active.bytes += data.len(),
+                            Err(e) => {
+                                show_line(&format!("error: stopped capturing to {}: {}", active.path, e));
+                                capture = None;
+                            }
+                        }
+                    }
+                }
+                Some(Ok(control)) => show_control(&control),
+                Some(Err(e)) => break Err(e),
+                None => break Ok("Console connection closed"),
+            },
+        }
+    };
+
+    running.store(false, Ordering::SeqCst);
+    let _ = stdin_reader.join();
+    if let Some(capture) = capture {
+        println!("\r\n{} Captured output to {}", "Info:".cyan(), capture.path);
+    }
+    result
+}
+
+async fn handle_connect(client: &Client, vm_id: &str, flags: ConnectFlags) -> CommandResult {
+    // Get console info from API
+    let console_info = client.console_info(vm_id).await.map_err(failed)?;
+
+    if !console_info.available {
+        return Err(failed(format!(
+            "VM is not running. Start the VM first with: start {}",
+            vm_id
+        )));
+    }
+
+    let attached = &console_info.attached;
+    for session in &attached.sessions {
+        let locked = attached.write_lock == Some(session.id);
+        println!(
+            "{} Also attached: {}{}",
+            "Info:".cyan(),
+            describe_session(session),
+            if locked { ", holds the write lock" } else { "" }
+        );
+    }
+
+    let (via, connected) = if flags.local {
+        let socket_path = &console_info.console_socket_path;
+        let connected = ConsoleConnection::local(socket_path, flags).await;
+        (socket_path.clone(), connected)
+    } else {
+        let options = ConsoleOptions {
+            replay: flags.replay,
+            mode: flags.mode,
+            client: Some("gxctl".to_string()),
+        };
+        let connected = client
+            .console(vm_id, &options)
+            .await
+            .map(|console| ConsoleConnection::WebSocket(Box::new(console)))
+            .map_err(|e| e.to_string());
+        (client.console_url(vm_id, &options), connected)
+    };
+    let mut connection = connected.map_err(failed)?;
+    println!("{} Connected to VM console via {}", "Info:".cyan(), via);
+    println!(
+        "{} Press {} to detach from console, or {} after Enter for the escape menu\n",
+        "Tip:".yellow(),
+        "Ctrl+]".bold(),
+        "~?".bold()
+    );
+
+    // Save original terminal settings and set raw mode
+    let stdin = io::stdin();
+    let orig_termios = set_raw_mode(stdin.as_fd())
+        .ok_or_else(|| failed("Failed to set terminal to raw mode"))?;
+
+    let result = relay_console(&mut connection, client, vm_id).await;
+
+    // Restore terminal
+    restore_terminal(stdin.as_fd(), &orig_termios);
+
+    match result {
+        Ok(how) => {
+            println!("\n{} {}", "Info:".cyan(), how);
+            Ok(())
+        }
+        Err(e) => {
+            println!();
+            Err(failed(format!("Console connection failed: {}", e)))
+        }
+    }
+}
+
+async fn handle_command(words: &[String], client: &Client, output: Output) -> CommandResult {
+    let parts: Vec<&str> = words.iter().map(String::as_str).collect();
+    let Some(&command) = parts.first() else {
+        return Ok(());
+    };
+
+    if output != Output::Table && OWN_FORMAT_COMMANDS.contains(&command) {
+        return Err(usage(&format!("{} does not take -o/--output", command)));
+    }
+
+    match command {
+        "help" | "?" => print_help(),
+
+        "list" | "ls" => {
+            let vms = client.list_vms().await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vms);
+            }
+            if vms.is_empty() {
+                println!("{}", "No VMs found".yellow());
+            } else {
+                let table = Table::new(vms.iter().map(VmRow::from)).to_string();
+                println!("{}", table);
+            }
+        }
+
+        "get" => {
+            let [_, vm] = parts[..] else {
+                return Err(usage("Usage: get <name|id>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let vm = client.get_vm(&vm_id).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vm);
+            }
+            println!("{}", "VM Details".bold());
+            println!("{}", "-".repeat(40));
+            println!("  ID:         {}", vm.id.yellow());
+            println!("  Name:       {}", vm.name);
+            println!("  State:      {}", format_state(&vm.state));
+            println!("  Hypervisor: {}", vm.hypervisor);
+            println!("  vCPUs:      {}", vm.vcpu_count);
+            println!("  Memory:     {} MiB", vm.mem_size_mib);
+            if vm.ready {
+                match vm.boot_duration_ms {
+                    Some(ms) => println!(
+                        "  Ready:      {} (booted in {:.1}s)",
+                        "yes".green(),
+                        ms as f64 / 1000.0
+                    ),
+                    None => println!("  Ready:      {}", "yes".green()),
+                }
+            } else {
+                println!("  Ready:      {}", "no".yellow());
+            }
+            if !vm.vfio_devices.is_empty() {
+                println!("  VFIO:       {}", vm.vfio_devices.join(", "));
+            }
+            if let Some(error) = &vm.last_error {
+                println!("  Error:      {}", error.red());
+            }
+            if let Some(exit) = &vm.last_exit {
+                println!("  Last exit:  {}", exit);
+                if !exit.console_tail.is_empty() {
+                    println!("  Console before exit:");
+                    for line in &exit.console_tail {
+                        println!("    {}", line.dimmed());
+                    }
+                }
+            }
+        }
+
+        "create" => handle_create(client, &words[1..], output).await?,
+
+        "start" => {
+            let usage_line = "Usage: start <name|id> [--wait]";
+            let (vm, wait_ready) = match parts[1..] {
+                [vm] => (vm, false),
+                [vm, "--wait" | "-w"] => (vm, true),
+                _ => return Err(usage(usage_line)),
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            if wait_ready && output == Output::Table {
+                eprintln!("{}", "Waiting for the guest to become ready...".dimmed());
+            }
+            let query = StartQuery {
+                wait: wait_ready.then_some(StartWait::Ready),
+                timeout_secs: None,
+            };
+            let vm = client.start_vm(&vm_id, &query).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vm);
+            }
+            if wait_ready {
+                let took = vm
+                    .boot_duration_ms
+                    .map(|ms| format!(" after {:.1}s", ms as f64 / 1000.0))
+                    .unwrap_or_default();
+                println!("{} VM {} is ready{}", "Success:".green(), vm.name, took);
+            } else {
+                print_new_state(&vm);
+            }
+        }
+
+        "stop" => {
+            let usage_line = "Usage: stop <name|id> [--force] [--timeout <secs>]";
+            let [_, vm, ref flags @ ..] = parts[..] else {
+                return Err(usage(usage_line));
+            };
+            let Some((force, timeout_secs)) = parse_stop_flags(flags) else {
+                return Err(usage(usage_line));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            if !force && output == Output::Table {
+                eprintln!("Waiting for {} to shut down...", vm);
+            }
+            let vm = client
+                .stop_vm(&vm_id, &StopVmRequest { force, timeout_secs })
+                .await
+                .map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vm);
+            }
+            print_new_state(&vm);
+        }
+
+        "pause" => {
+            let [_, vm] = parts[..] else {
+                return Err(usage("Usage: pause <name|id>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let vm = client.pause_vm(&vm_id).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vm);
+            }
+            print_new_state(&vm);
+        }
+
+        "reboot" => {
+            let [_, vm] = parts[..] else {
+                return Err(usage("Usage: reboot <name|id>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let vm = client.reboot_vm(&vm_id).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vm);
+            }
+            println!("{} VM {} rebooted", "Success:".green(), vm.name);
+        }
+
+        "reset" => {
+            let [_, vm] = parts[..] else {
+                return Err(usage("Usage: reset <name|id>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let vm = client.reset_vm(&vm_id).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vm);
+            }
+            println!("{} VM {} was reset", "Success:".green(), vm.name);
+        }
+
+        "snapshot" => {
+            let (vm, label) = match parts[1..] {
+                [vm] => (vm, None),
+                [vm, label] => (vm, Some(label)),
+                _ => return Err(usage("Usage: snapshot <name|id> [label]")),
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let request = CreateSnapshotRequest {
+                name: label.map(str::to_string),
+            };
+            let snapshot = client
+                .create_snapshot(&vm_id, &request)
+                .await
+                .map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &snapshot);
+            }
+            println!(
+                "{} Snapshot {} written to {}",
+                "Success:".green(),
+                snapshot.id.yellow(),
+                snapshot.path
+            );
+        }
+
+        "snapshots" => {
+            let [_, vm] = parts[..] else {
+                return Err(usage("Usage: snapshots <name|id>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let snapshots = client.list_snapshots(&vm_id).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &snapshots);
+            }
+            if snapshots.is_empty() {
+                println!("{}", "No snapshots found".yellow());
+            } else {
+                let table = Table::new(snapshots.iter().map(SnapshotRow::from)).to_string();
+                println!("{}", table);
+            }
+        }
+
+        "restore" => {
+            let [_, vm, snapshot_id] = parts[..] else {
+                return Err(usage("Usage: restore <name|id> <snapshot-id>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let request = RestoreVmRequest {
+                snapshot_id: snapshot_id.to_string(),
+            };
+            let vm = client
+                .restore_vm(&vm_id, &request)
+                .await
+                .map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vm);
+            }
+            println!(
+                "{} VM {} restored and {} (use 'start' to resume)",
+                "Success:".green(),
+                vm.name,
+                format_state(&vm.state)
+            );
+        }
+
+        "delete-snapshot" => {
+            let [_, vm, snapshot_id] = parts[..] else {
+                return Err(usage("Usage: delete-snapshot <name|id> <snapshot-id>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            client
+                .delete_snapshot(&vm_id, snapshot_id)
+                .await
+                .map_err(failed)?;
+            if output != Output::Table {
+                let status = Status {
+                    status: "deleted",
+                    id: Some(snapshot_id),
+                };
+                return print_as(output, &status);
+            }
+            println!("{} Snapshot deleted", "Success:".green());
+        }
+
+        "migrate" => {
+            let [_, vm, target] = parts[..] else {
+                return Err(usage("Usage: migrate <name|id> <target-url>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            if output == Output::Table {
+                eprintln!("Migrating VM to {}...", target);
+            }
+            let request = MigrateVmRequest {
+                target: target.to_string(),
+            };
+            let migrated = client.migrate_vm(&vm_id, &request).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &migrated);
+            }
+            println!("{} VM {} now runs on {}", "Success:".green(), vm, target);
+        }
+
+        "connect" | "console" | "attach" => {
+            let usage_line = "Usage: connect <name|id> [--replay none|tail:<lines>|all] [--mode read-only|shared|exclusive] [--local]";
+            let [_, vm, ref flags @ ..] = parts[..] else {
+                return Err(usage(usage_line));
+            };
+            let Some(flags) = parse_connect_flags(flags) else {
+                return Err(usage(usage_line));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            handle_connect(client, &vm_id, flags).await?;
+        }
+
+        "expect" => {
+            let usage_line =
+                "Usage: expect <name|id> <regex> [--send <text>] [--timeout <secs>] (quote with spaces)";
+            let [_, vm, pattern, flags @ ..] = words else {
+                return Err(usage(usage_line));
+            };
+            let Some((send, timeout_secs)) = parse_expect_flags(flags) else {
+                return Err(usage(usage_line));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let request = ExpectRequest {
+                pattern: pattern.clone(),
+                send,
+                timeout_secs,
+            };
+            let expected = client
+                .expect(&vm_id, &request)
+                .await
+                .map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &expected);
+            }
+            print!("{}{}", expected.before, expected.matched);
+            println!("\n{} Matched {:?}", "Success:".green(), expected.matched);
+        }
+
+        "log" | "logs" => {
+            let usage_line =
+                "Usage: log <name|id> [--tail <lines>] [--since <time>] [--boot -<n>] [--follow]";
+            let [_, vm, ref flags @ ..] = parts[..] else {
+                return Err(usage(usage_line));
+            };
+            let Some(query) = parse_log_flags(flags) else {
+                return Err(usage(usage_line));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            handle_log(client, &vm_id, &query).await?;
+        }
+
+        "sessions" => {
+            let [_, vm] = parts[..] else {
+                return Err(usage("Usage: sessions <name|id>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let sessions = client.list_console_sessions(&vm_id).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &sessions);
+            }
+            if sessions.is_empty() {
+                println!("{}", "No console sessions recorded".yellow());
+            } else {
+                let table = Table::new(sessions.iter().map(SessionRow::from)).to_string();
+                println!("{}", table);
+            }
+        }
+
+        "download-session" => {
+            let (vm, session_id, file) = match parts[1..] {
+                [vm, session_id] => (vm, session_id, format!("{}.cast", session_id)),
+                [vm, session_id, file] => (vm, session_id, file.to_string()),
+                _ => {
+                    return Err(usage(
+                        "Usage: download-session <name|id> <session-id> [file]",
+                    ))
+                }
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let cast = client
+                .download_console_session(&vm_id, session_id)
+                .await
+                .map_err(failed)?;
+            std::fs::write(&file, cast)
+                .map_err(|e| failed(format!("Failed to write {}: {}", file, e)))?;
+            println!(
+                "{} Session saved to {} (play it with `asciinema play`)",
+                "Success:".green(),
+                file
+            );
+        }
+
+        "delete" | "rm" => {
+            let (vm, confirmed) = match parts[1..] {
+                [vm] => (vm, false),
+                [vm, "--yes" | "-y"] => (vm, true),
+                _ => return Err(usage("Usage: delete <name|id> [--yes]")),
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let confirmed = confirmed
+                || prompt(&format!(
+                    "Are you sure you want to delete VM {}? [y/N]: ",
+                    vm
+                ))
+                .eq_ignore_ascii_case("y");
+            if !confirmed {
+                eprintln!("Cancelled");
+                return Err(CommandError::Failed);
+            }
+            client.delete_vm(&vm_id).await.map_err(failed)?;
+            if output != Output::Table {
+                let status = Status {
+                    status: "deleted",
+                    id: Some(&vm_id),
+                };
+                return print_as(output, &status);
+            }
+            println!("{} VM deleted", "Success:".green());
+        }
+
+        "pci" | "pci-devices" => {
+            let devices = client.list_pci_devices().await.map_err(failed)?;
+            match parts[1..] {
+                // `pci <address>` shows full details for a single device,
+                // including the sysfs path needed for VFIO attachment.
+                [address] => {
+                    let Some(dev) = devices.iter().find(|d| d.address == address) else {
+                        return Err(failed(format!("no PCI device with address {}", address)));
+                    };
+                    if output != Output::Table {
+                        return print_as(output, dev);
+                    }
+                    println!("Address:    {}", dev.address);
+                    println!("Vendor:     {}", dev.vendor_id);
+                    println!("Device:     {}", dev.device_id);
+                    println!("Class:      {}", dev.class_id);
+                    println!("Driver:     {}", display_option(&dev.driver));
+                    println!("IOMMU:      {}", display_option(&dev.iommu_group));
+                    println!("Sysfs path: {}", dev.sysfs_path);
+                }
+                [] if output != Output::Table => return print_as(output, &devices),
+                [] if devices.is_empty() => println!("{}", "No PCI devices found".yellow()),
+                [] => {
+                    let table = Table::new(devices.iter().map(PciRow::from)).to_string();
+                    println!("{}", table);
+                }
+                _ => return Err(usage("Usage: pci [address]")),
+            }
+        }
+
+        "attach-device" => {
+            let [_, vm, path] = parts[..] else {
+                return Err(usage("Usage: attach-device <name|id> <device-path>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let vm = client.attach_device(&vm_id, path).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vm);
+            }
+            println!(
+                "{} Device {} attached to VM {}",
+                "Success:".green(),
+                path,
+                vm.name
+            );
+            if !vm.vfio_devices.is_empty() {
+                println!("  VFIO devices: {}", vm.vfio_devices.join(", "));
+            }
+        }
+
+        "detach-device" => {
+            let [_, vm, path] = parts[..] else {
+                return Err(usage("Usage: detach-device <name|id> <device-path>"));
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let vm = client.detach_device(&vm_id, path).await.map_err(failed)?;
+            if output != Output::Table {
+                return print_as(output, &vm);
+            }
+            println!(
+                "{} Device {} detached from VM {}",
+                "Success:".green(),
+                path,
+                vm.name
+            );
+            if !vm.vfio_devices.is_empty() {
+                println!("  VFIO devices: {}", vm.vfio_devices.join(", "));
+            }
+        }
+
+        "apply" => {
+            let Some((files, dry_run, prune)) = parse_apply_flags(&parts[1..]) else {
+                return Err(usage("Usage: apply -f <file|dir> [-f ...] [--dry-run] [--prune]"));
+            };
+            handle_apply(client, &files, dry_run, prune).await?;
+        }
+
+        "export" => {
+            let usage_line = "Usage: export <name|id> [--format yaml|toml]";
+            let (vm, format) = match parts[1..] {
+                [vm] => (vm, manifest::Format::Yaml),
+                [vm, "--format", "yaml"] => (vm, manifest::Format::Yaml),
+                [vm, "--format", "toml"] => (vm, manifest::Format::Toml),
+                _ => return Err(usage(usage_line)),
+            };
+            let vm_id = client.resolve_vm(vm).await.map_err(failed)?;
+            let vm = client.get_vm(&vm_id).await.map_err(failed)?;
+            let text = manifest::Manifest::export(&vm)
+                .render(format)
+                .map_err(failed)?;
+            print!("{}", text);
+        }
+
+        "top" => {
+            let interval = match parts[1..] {
+                [] => 2,
+                ["--interval" | "-n", secs] => match secs.parse() {
+                    Ok(secs) if secs > 0 => secs,
+                    _ => return Err(usage("Usage: top [--interval <secs>]")),
+                },
+                _ => return Err(usage("Usage: top [--interval <secs>]")),
+            };
+            top::run(client, std::time::Duration::from_secs(interval)).await?;
+        }
+
+        "health" => {
+            client.health().await.map_err(failed)?;
+            if output != Output::Table {
+                let status = Status {
+                    status: "ok",
+                    id: None,
+                };
+                return print_as(output, &status);
+            }
+            println!("{} API server is healthy", "OK:".green());
+        }
+
+        _ => {
+            return Err(usage(&format!(
+                "Unknown command: {}. Type 'help' for available commands.",
+                command
+            )))
+        }
+    }
+
+    Ok(())
+}
+
+/// How many REPL lines the history keeps.
+const HISTORY_SIZE: usize = 1000;
+
+/// `~/.glidex/gxctl_history`, creating the directory if need be.
+fn history_path() -> Option<std::path::PathBuf> {
+    let dir = dirs::home_dir()?.join(".glidex");
+    std::fs::create_dir_all(&dir).ok()?;
+    Some(dir.join("gxctl_history"))
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    let client = Client::new(cli.server.clone());
+
+    if let Some(command) = cli.command {
+        let words = command.into_words();
+        let code = match handle_command(&words, &client, cli.output).await {
+            Ok(()) => 0,
+            Err(e) => e.exit_code(),
+        };
+        std::process::exit(code);
+    }
+
+    println!(
+        "{}",
+        r#"
+   _____ _ _     _
+  / ____| (_)   | |
+ | |  __| |_  __| | _____  __
+ | | |_ | | |/ _` |/ _ \ \/ /
+ | |__| | | | (_| |  __/>  <
+  \_____|_|_|\__,_|\___/_/\_\
+          Control Plane CLI
+"#
+        .cyan()
+    );
+
+    println!("Connected to: {}", cli.server.yellow());
+    println!("Type {} for available commands\n", "help".cyan());
+
+    let config = Config::builder()
+        .max_history_size(HISTORY_SIZE)
+        .expect("Invalid history size")
+        .build();
+    let mut rl: Editor<completion::ReplHelper, FileHistory> =
+        Editor::with_config(config).expect("Failed to initialize readline");
+    rl.set_helper(Some(completion::ReplHelper::default()));
+    let history = history_path();
+    if let Some(path) = &history {
+        let _ = rl.load_history(path);
+    }
+
+    loop {
+        if let Some(helper) = rl.helper() {
+            helper.refresh(&client);
+        }
+        match rl.readline("gxctl> ") {
+            Ok(line) => {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                let _ = rl.add_history_entry(&line);
+                // Saved as it goes, so that it survives a crash or a kill.
+                if let Some(path) = &history {
+                    let _ = rl.append_history(path);
+                }
+                if matches!(line.trim(), "exit" | "quit" | "q") {
+                    println!("Goodbye!");
+                    break;
+                }
+                let Some(mut words) = split_words(&line) else {
+                    usage("Unterminated quote");
+                    continue;
+                };
+                let Some(output) = take_output_flag(&mut words) else {
+                    usage("Usage: -o|--output table|json|yaml");
+                    continue;
+                };
+                let _ = handle_command(&words, &client, output).await;
+            }
+            Err(ReadlineError::Interrupted) => {
+                println!("Use 'exit' to quit");
+            }
+            Err(ReadlineError::Eof) => {
+                println!("Goodbye!");
+                break;
+            }
+            Err(err) => {
+                println!("Error: {:?}", err);
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn output_flag_is_refused_by_commands_with_their_own_format() {
+        // Refused before anything is sent, so nothing needs to listen.
+        let client = Client::new("http://127.0.0.1:1");
+        for line in ["export web", "log web --tail 5", "connect web", "top"] {
+            let words = split_words(line).unwrap();
+            assert_eq!(
+                handle_command(&words, &client, Output::Json).await,
+                Err(CommandError::Usage),
+                "{}",
+                line
+            );
+        }
+
+        let status = Status {
+            status: "deleted",
+            id: Some("snap-1"),
+        };
+        assert_eq!(
+            serde_json::to_string(&status).unwrap(),
+            r#"{"status":"deleted","id":"snap-1"}"#
+        );
+    }
+
+    #[test]
+    fn expect_arguments_keep_quoted_spaces_and_backslashes() {
+        assert_eq!(
+            split_words(r#"expect web "login: *$" --send 'root\r'"#).unwrap(),
+            vec!["expect", "web", "login: *$", "--send", "root\\r"]
+        );
+        assert_eq!(split_words(r"expect web \d+ ''").unwrap(), vec!["expect", "web", r"\d+", ""]);
+        assert_eq!(split_words("expect web 'oops"), None);
+
+        let args: Vec<String> = ["--send", r"ls -l\r\n", "-t", "5"].map(String::from).to_vec();
+        assert_eq!(
+            parse_expect_flags(&args),
+            Some(("ls -l\r\n".to_string(), Some(5)))
+        );
+        assert_eq!(unescape(r"a\\b\x"), r"a\b\x");
+        assert_eq!(parse_expect_flags(&["--wait".to_string()]), None);
+    }
+
+    #[test]
+    fn parse_log_flags_builds_query() {
+        assert_eq!(parse_log_flags(&[]), Some(LogsQuery::default()));
+        assert_eq!(
+            parse_log_flags(&["-n", "50", "--since", "10m", "-f"]),
+            Some(LogsQuery {
+                tail: Some(50),
+                since: Some("10m".to_string()),
+                follow: true,
+                ..Default::default()
+            })
+        );
+        assert_eq!(
+            parse_log_flags(&["--boot", "-1"]),
+            Some(LogsQuery {
+                boot: Some(-1),
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_log_flags(&["--tail", "lots"]), None);
+        assert_eq!(parse_log_flags(&["--since", "1h&boot=-1"]), None);
+        assert_eq!(parse_log_flags(&["--replay", "all"]), None);
+    }
+
+    #[test]
+    fn parse_stop_flags_accepts_force_and_timeout() {
+        assert_eq!(parse_stop_flags(&[]), Some((false, None)));
+        assert_eq!(parse_stop_flags(&["--force"]), Some((true, None)));
+        assert_eq!(
+            parse_stop_flags(&["--timeout", "90", "-f"]),
+            Some((true, Some(90)))
+        );
+        assert_eq!(parse_stop_flags(&["--timeout"]), None);
+        assert_eq!(parse_stop_flags(&["--timeout", "soon"]), None);
+        assert_eq!(parse_stop_flags(&["--now"]), None);
+    }
+
+    #[test]
+    fn parse_apply_flags_needs_a_path() {
+        assert_eq!(
+            parse_apply_flags(&["-f", "vms/", "--filename", "db.toml", "--dry-run"]),
+            Some((vec!["vms/", "db.toml"], true, false))
+        );
+        assert_eq!(
+            parse_apply_flags(&["--prune", "-f", "vms/"]),
+            Some((vec!["vms/"], false, true))
+        );
+        assert_eq!(parse_apply_flags(&["--dry-run"]), None);
+        assert_eq!(parse_apply_flags(&["-f"]), None);
+        assert_eq!(parse_apply_flags(&["vms/"]), None);
+    }
+
+    #[test]
+    fn parse_connect_flags_validates_replay_mode() {
+        let flags = |replay, mode, local| {
+            Some(ConnectFlags {
+                replay,
+                mode,
+                local,
+            })
+        };
+        assert_eq!(
+            parse_connect_flags(&[]),
+            flags(Replay::All, ConsoleMode::Shared, false)
+        );
+        assert_eq!(
+            parse_connect_flags(&["--replay", "none"]),
+            flags(Replay::None, ConsoleMode::Shared, false)
+        );
+        assert_eq!(
+            parse_connect_flags(&["-r", "tail:50", "-x", "--local"]),
+            flags(Replay::Tail(50), ConsoleMode::Exclusive, true)
+        );
+        assert_eq!(
+            parse_connect_flags(&["--mode", "read-only"]),
+            flags(Replay::All, ConsoleMode::ReadOnly, false)
+        );
+        assert_eq!(parse_connect_flags(&["--mode", "solo"]), None);
+        assert_eq!(parse_connect_flags(&["--replay", "tail:many"]), None);
+        assert_eq!(parse_connect_flags(&["--replay"]), None);
+        assert_eq!(parse_connect_flags(&["--follow"]), None);
+    }
+
+    #[test]
+    fn escapes_are_picked_out_at_line_start() {
+        let mut escapes = EscapeParser::default();
+        assert_eq!(
+            escapes.feed(b"~sls ~/\r~"),
+            [Typed::Escape(b's'), Typed::Input(b"ls ~/\r".to_vec())]
+        );
+        // The held back `~` is completed by the next read.
+        assert_eq!(
+            escapes.feed(b"~x\r~q"),
+            [Typed::Input(b"~x\r~q".to_vec())]
+        );
+        assert_eq!(
+            escapes.feed(b"\r~?\r~.ignored"),
+            [
+                Typed::Input(b"\r".to_vec()),
+                Typed::Escape(b'?'),
+                Typed::Input(b"\r".to_vec()),
+                Typed::Detach,
+            ]
+        );
+        assert_eq!(
+            EscapeParser::default().feed(b"top\x1d"),
+            [Typed::Input(b"top".to_vec()), Typed::Detach]
+        );
+    }
+
+    #[test]
+    fn subcommands_become_repl_words() {
+        let words = |args: &[&str]| {
+            let cli = Cli::try_parse_from(args).unwrap();
+            cli.command.unwrap().into_words()
+        };
+        assert_eq!(words(&["gxctl", "vm", "start", "web-1", "--wait"]), ["start", "web-1", "--wait"]);
+        assert_eq!(
+            words(&["gxctl", "vm", "log", "web-1", "-b", "-1", "-f"]),
+            ["log", "web-1", "--boot", "-1", "--follow"]
+        );
+        assert_eq!(
+            words(&["gxctl", "vm", "expect", "web-1", "login: ", "--send", "root\\r"]),
+            ["expect", "web-1", "login: ", "--send", "root\\r"]
+        );
+        assert_eq!(words(&["gxctl", "pci"]), ["pci"]);
+
+        let cli = Cli::try_parse_from(["gxctl", "vm", "list", "-o", "yaml"]).unwrap();
+        assert_eq!(cli.output, Output::Yaml);
+        assert!(Cli::try_parse_from(["gxctl", "vm", "list", "-o", "xml"]).is_err());
+    }
+
+    #[test]
+    fn output_flag_is_taken_out_of_repl_words() {
+        let mut words: Vec<String> = ["list", "-o", "JSON"].map(String::from).to_vec();
+        assert_eq!(take_output_flag(&mut words), Some(Output::Json));
+        assert_eq!(words, ["list"]);
+        let mut words: Vec<String> = ["get", "web-1", "--output"].map(String::from).to_vec();
+        assert_eq!(take_output_flag(&mut words), None);
+    }
+
+    #[test]
+    fn parse_create_flags_fills_in_defaults() {
+        let flags = |args: &[&str]| args.iter().map(|a| a.to_string()).collect::<Vec<_>>();
+        let request = parse_create_flags(
+            "web-1",
+            &flags(&["--vcpus", "2", "--hypervisor", "ch", "--vfio", "a, b", "--reboot"]),
+        )
+        .unwrap();
+        assert_eq!(request.vcpu_count, 2);
+        assert_eq!(request.mem_size_mib, 512);
+        assert!(request.rootfs_path.ends_with("/.glidex/rootfs.ext4"));
+        assert_eq!(request.hypervisor, Some(HypervisorType::CloudHypervisor));
+        assert_eq!(request.vfio_devices, Some(vec!["a".to_string(), "b".to_string()]));
+        assert_eq!(request.no_reboot, Some(false));
+        assert!(parse_create_flags("web-1", &flags(&["--hypervisor", "xen"])).is_none());
+        assert!(parse_create_flags("--vcpus", &flags(&["2"])).is_none());
+    }
+
+    #[test]
+    fn display_option_renders_dash_for_none() {
+        assert_eq!(display_option(&None), "-");
+        assert_eq!(display_option(&Some("vfio-pci".to_string())), "vfio-pci");
+    }
+
+    #[test]
+    fn display_age_uses_coarsest_unit() {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        assert_eq!(display_age(&(now - 90)), "1m ago");
+        assert_eq!(display_age(&(now - 3 * 86400)), "3d ago");
+        // Clock skew must not underflow.
+        assert_eq!(display_age(&(now + 60)), "0s ago");
+    }
+}
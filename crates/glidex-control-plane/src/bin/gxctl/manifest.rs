@@ -0,0 +1,497 @@
+//! Declarative VM manifests, for `apply` and `export`.
+//!
+//! A manifest describes one VM as it should be. `apply` compares the
+//! manifests with `GET /vms` and plans, per VM, the steps that make it
+//! so: create it, change its settings, start or stop it, or (with
+//! `--prune`) delete a VM no manifest names.
+
+use super::{parse_hypervisor, CreateVmRequest, UpdateVmRequest, VmResponse, VmState};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+/// One VM as it should be.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub name: String,
+    pub vcpus: u8,
+    pub memory_mib: u32,
+    pub kernel: String,
+    pub rootfs: String,
+    /// Left alone when absent, like the fields below.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel_args: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hypervisor: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vfio_devices: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub no_reboot: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<DesiredState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DesiredState {
+    Running,
+    Stopped,
+}
+
+/// The format of a manifest file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Yaml,
+    Toml,
+}
+
+impl Format {
+    fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            "yaml" | "yml" => Some(Format::Yaml),
+            "toml" => Some(Format::Toml),
+            _ => None,
+        }
+    }
+}
+
+impl Manifest {
+    /// The manifest that describes `vm` as it is.
+    pub fn export(vm: &VmResponse) -> Self {
+        Manifest {
+            name: vm.name.clone(),
+            vcpus: vm.vcpu_count,
+            memory_mib: vm.mem_size_mib,
+            kernel: vm.kernel_image_path.clone(),
+            rootfs: vm.rootfs_path.clone(),
+            kernel_args: Some(vm.kernel_args.clone()),
+            hypervisor: Some(vm.hypervisor.to_string()),
+            vfio_devices: Some(vm.vfio_devices.clone()),
+            no_reboot: Some(vm.no_reboot),
+            state: Some(if is_up(&vm.state) {
+                DesiredState::Running
+            } else {
+                DesiredState::Stopped
+            }),
+        }
+    }
+
+    pub fn render(&self, format: Format) -> Result<String, String> {
+        match format {
+            Format::Yaml => serde_yaml::to_string(self).map_err(|e| e.to_string()),
+            Format::Toml => toml::to_string(self).map_err(|e| e.to_string()),
+        }
+    }
+
+    fn create_request(&self) -> CreateVmRequest {
+        CreateVmRequest {
+            name: self.name.clone(),
+            vcpu_count: self.vcpus,
+            mem_size_mib: self.memory_mib,
+            kernel_image_path: self.kernel.clone(),
+            rootfs_path: self.rootfs.clone(),
+            kernel_args: self.kernel_args.clone(),
+            hypervisor: self.hypervisor.as_deref().and_then(parse_hypervisor),
+            vfio_devices: self.vfio_devices.clone().filter(|devices| !devices.is_empty()),
+            no_reboot: self.no_reboot,
+            log_policy: None,
+            readiness: None,
+        }
+    }
+}
+
+/// Parse the manifests in `text`. A YAML file may hold several, as
+/// `---`-separated documents; a TOML file holds one.
+fn parse(text: &str, format: Format) -> Result<Vec<Manifest>, String> {
+    let mut manifests: Vec<Manifest> = match format {
+        Format::Yaml => serde_yaml::Deserializer::from_str(text)
+            .map(Manifest::deserialize)
+            .collect::<Result<_, _>>()
+            .map_err(|e| e.to_string())?,
+        Format::Toml => vec![toml::from_str(text).map_err(|e| e.to_string())?],
+    };
+    for manifest in &mut manifests {
+        if let Some(hypervisor) = &manifest.hypervisor {
+            let known = parse_hypervisor(hypervisor)
+                .ok_or_else(|| format!("{}: unknown hypervisor {}", manifest.name, hypervisor))?;
+            manifest.hypervisor = Some(known.to_string());
+        }
+    }
+    Ok(manifests)
+}
+
+/// Read the manifests at each of `paths`: a file, or every `.yaml`,
+/// `.yml` and `.toml` file in a directory, in name order.
+pub fn load<P: AsRef<Path>>(paths: &[P]) -> Result<Vec<Manifest>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        let path = path.as_ref();
+        if path.is_dir() {
+            let entries = std::fs::read_dir(path)
+                .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let mut found: Vec<_> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && Format::of(path).is_some())
+                .collect();
+            found.sort();
+            files.extend(found);
+        } else {
+            files.push(path.to_path_buf());
+        }
+    }
+
+    let mut manifests = Vec::new();
+    let mut names = HashSet::new();
+    for file in files {
+        let format = Format::of(&file)
+            .ok_or_else(|| format!("{}: not a .yaml, .yml or .toml file", file.display()))?;
+        let text = std::fs::read_to_string(&file)
+            .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        for manifest in parse(&text, format).map_err(|e| format!("{}: {}", file.display(), e))? {
+            if !names.insert(manifest.name.clone()) {
+                return Err(format!("{}: VM {} is defined twice", file.display(), manifest.name));
+            }
+            manifests.push(manifest);
+        }
+    }
+    Ok(manifests)
+}
+
+/// Whether a VM has a hypervisor that runs or holds its guest.
+fn is_up(state: &VmState) -> bool {
+    matches!(state, VmState::Running | VmState::Paused)
+}
+
+/// Whether a manifest's path names the VM's. The server expands `~/`
+/// with its own home directory, so only the rest has to match.
+fn same_path(manifest: &str, actual: &str) -> bool {
+    manifest == actual
+        || manifest
+            .strip_prefix('~')
+            .is_some_and(|rest| rest.starts_with('/') && actual.ends_with(rest))
+}
+
+/// A setting that differs, for the plan.
+#[derive(Debug, PartialEq)]
+pub struct Change {
+    pub field: &'static str,
+    pub from: String,
+    pub to: String,
+}
+
+/// One request towards a manifest.
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    Create(CreateVmRequest),
+    Stop,
+    Update(UpdateVmRequest),
+    AttachDevice(String),
+    DetachDevice(String),
+    Start,
+    Delete,
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Step::Create(_) => write!(f, "create"),
+            Step::Stop => write!(f, "stop"),
+            Step::Update(_) => write!(f, "update"),
+            Step::AttachDevice(path) => write!(f, "attach {}", path),
+            Step::DetachDevice(path) => write!(f, "detach {}", path),
+            Step::Start => write!(f, "start"),
+            Step::Delete => write!(f, "delete"),
+        }
+    }
+}
+
+/// What `apply` does about one VM.
+#[derive(Debug, PartialEq)]
+pub struct Action {
+    pub name: String,
+    /// The VM's id, unless it is yet to be created.
+    pub vm_id: Option<String>,
+    pub changes: Vec<Change>,
+    pub steps: Vec<Step>,
+    /// Why the VM cannot be brought in line, if it cannot.
+    pub refused: Option<String>,
+}
+
+impl Action {
+    fn new(name: &str, vm_id: Option<&str>) -> Self {
+        Action {
+            name: name.to_string(),
+            vm_id: vm_id.map(str::to_string),
+            changes: Vec::new(),
+            steps: Vec::new(),
+            refused: None,
+        }
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self.steps.is_empty() && self.refused.is_none()
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(reason) = &self.refused {
+            return write!(f, "! {}: {}", self.name, reason);
+        }
+        let sign = match self.steps.first() {
+            None => '=',
+            Some(Step::Create(_)) => '+',
+            Some(Step::Delete) => '-',
+            Some(_) if !self.changes.is_empty() => '~',
+            Some(_) => '>',
+        };
+        write!(f, "{} {}", sign, self.name)?;
+        if self.steps.is_empty() {
+            return write!(f, ": up to date");
+        }
+        let steps: Vec<String> = self.steps.iter().map(Step::to_string).collect();
+        write!(f, ": {}", steps.join(", "))?;
+        for change in &self.changes {
+            write!(f, "\n    {}: {} -> {}", change.field, change.from, change.to)?;
+        }
+        Ok(())
+    }
+}
+
+/// Plan what makes the VMs in `actual` match `manifests`. VMs without
+/// a manifest are deleted only if `prune` is set.
+pub fn plan(manifests: &[Manifest], actual: &[VmResponse], prune: bool) -> Vec<Action> {
+    let mut actions: Vec<Action> = manifests
+        .iter()
+        .map(|manifest| match actual.iter().find(|vm| vm.name == manifest.name) {
+            Some(vm) => plan_existing(manifest, vm),
+            None => {
+                let mut action = Action::new(&manifest.name, None);
+                action.steps.push(Step::Create(manifest.create_request()));
+                if manifest.state == Some(DesiredState::Running) {
+                    action.steps.push(Step::Start);
+                }
+                action
+            }
+        })
+        .collect();
+    if prune {
+        for vm in actual {
+            if !manifests.iter().any(|manifest| manifest.name == vm.name) {
+                let mut action = Action::new(&vm.name, Some(&vm.id));
+                action.steps.push(Step::Delete);
+                actions.push(action);
+            }
+        }
+    }
+    actions
+}
+
+fn plan_existing(manifest: &Manifest, vm: &VmResponse) -> Action {
+    let mut action = Action::new(&vm.name, Some(&vm.id));
+    if let Some(hypervisor) = manifest.hypervisor.as_ref().filter(|h| **h != vm.hypervisor.to_string()) {
+        action.refused = Some(format!(
+            "runs on {}, the manifest wants {}; delete the VM to change its hypervisor",
+            vm.hypervisor, hypervisor
+        ));
+        return action;
+    }
+    if matches!(vm.state, VmState::Starting | VmState::Stopping) {
+        action.refused = Some(format!("is {}; apply again once it settles", vm.state));
+        return action;
+    }
+
+    let mut update = UpdateVmRequest::default();
+    let mut change = |field, from: String, to: String| {
+        action.changes.push(Change { field, from, to });
+    };
+    if manifest.vcpus != vm.vcpu_count {
+        change("vcpus", vm.vcpu_count.to_string(), manifest.vcpus.to_string());
+        update.vcpu_count = Some(manifest.vcpus);
+    }
+    if manifest.memory_mib != vm.mem_size_mib {
+        change("memory_mib", vm.mem_size_mib.to_string(), manifest.memory_mib.to_string());
+        update.mem_size_mib = Some(manifest.memory_mib);
+    }
+    if !same_path(&manifest.kernel, &vm.kernel_image_path) {
+        change("kernel", vm.kernel_image_path.clone(), manifest.kernel.clone());
+        update.kernel_image_path = Some(manifest.kernel.clone());
+    }
+    if !same_path(&manifest.rootfs, &vm.rootfs_path) {
+        change("rootfs", vm.rootfs_path.clone(), manifest.rootfs.clone());
+        update.rootfs_path = Some(manifest.rootfs.clone());
+    }
+    if let Some(kernel_args) = manifest.kernel_args.as_ref().filter(|a| **a != vm.kernel_args) {
+        change("kernel_args", vm.kernel_args.clone(), kernel_args.clone());
+        update.kernel_args = Some(kernel_args.clone());
+    }
+    if let Some(no_reboot) = manifest.no_reboot.filter(|n| *n != vm.no_reboot) {
+        change("no_reboot", vm.no_reboot.to_string(), no_reboot.to_string());
+        update.no_reboot = Some(no_reboot);
+    }
+    let mut devices = None;
+    if let Some(wanted) = &manifest.vfio_devices {
+        let mut sorted = wanted.clone();
+        sorted.sort();
+        let mut current = vm.vfio_devices.clone();
+        current.sort();
+        if sorted != current {
+            change("vfio_devices", vm.vfio_devices.join(","), wanted.join(","));
+            devices = Some(wanted.clone());
+        }
+    }
+
+    let up = is_up(&vm.state);
+    let want_up = match manifest.state {
+        Some(DesiredState::Running) => true,
+        Some(DesiredState::Stopped) => false,
+        None => up,
+    };
+    let settings_changed = update != UpdateVmRequest::default();
+
+    if up && !settings_changed && want_up && vm.state == VmState::Running {
+        // Only devices differ: hot-plug them.
+        if let Some(wanted) = &devices {
+            for path in vm.vfio_devices.iter().filter(|path| !wanted.contains(path)) {
+                action.steps.push(Step::DetachDevice(path.clone()));
+            }
+            for path in wanted.iter().filter(|path| !vm.vfio_devices.contains(path)) {
+                action.steps.push(Step::AttachDevice(path.clone()));
+            }
+        }
+        return action;
+    }
+
+    update.vfio_devices = devices;
+    let needs_update = update != UpdateVmRequest::default();
+    if up && (needs_update || !want_up) {
+        action.steps.push(Step::Stop);
+    }
+    if needs_update {
+        action.steps.push(Step::Update(update));
+    }
+    if want_up && (!up || needs_update || vm.state == VmState::Paused) {
+        action.steps.push(Step::Start);
+    }
+    action
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vm(name: &str, state: &str) -> VmResponse {
+        serde_json::from_value(serde_json::json!({
+            "id": format!("id-{}", name),
+            "name": name,
+            "state": state,
+            "vcpu_count": 1,
+            "mem_size_mib": 512,
+            "hypervisor": "qemu",
+            "kernel_image_path": "/home/ops/.glidex/vmlinux.bin",
+            "rootfs_path": "/home/ops/.glidex/rootfs.ext4",
+            "kernel_args": "console=ttyS0",
+            "no_reboot": true,
+            "console_socket_path": format!("/tmp/{}.sock", name),
+            "log_path": format!("/tmp/{}.log", name),
+        }))
+        .unwrap()
+    }
+
+    fn manifest(name: &str) -> Manifest {
+        Manifest {
+            name: name.to_string(),
+            vcpus: 1,
+            memory_mib: 512,
+            kernel: "~/.glidex/vmlinux.bin".to_string(),
+            rootfs: "~/.glidex/rootfs.ext4".to_string(),
+            kernel_args: None,
+            hypervisor: None,
+            vfio_devices: None,
+            no_reboot: None,
+            state: None,
+        }
+    }
+
+    fn steps(action: &Action) -> Vec<String> {
+        action.steps.iter().map(Step::to_string).collect()
+    }
+
+    #[test]
+    fn manifests_parse_from_yaml_documents_and_toml() {
+        let yaml = "name: web-1\nvcpus: 2\nmemory_mib: 1024\nkernel: /k\nrootfs: /r\nhypervisor: ch\n\
+                    ---\nname: web-2\nvcpus: 1\nmemory_mib: 512\nkernel: /k\nrootfs: /r\nstate: running\n";
+        let manifests = parse(yaml, Format::Yaml).unwrap();
+        assert_eq!(manifests.len(), 2);
+        assert_eq!(manifests[0].hypervisor.as_deref(), Some("cloudhypervisor"));
+        assert_eq!(manifests[1].state, Some(DesiredState::Running));
+
+        let toml = "name = \"db\"\nvcpus = 4\nmemory_mib = 4096\nkernel = \"/k\"\nrootfs = \"/r\"\n";
+        assert_eq!(parse(toml, Format::Toml).unwrap()[0].vcpus, 4);
+        assert!(parse("name: x\nvcpu: 1\n", Format::Yaml).is_err());
+    }
+
+    #[test]
+    fn exported_manifest_round_trips() {
+        let exported = Manifest::export(&vm("web-1", "running"));
+        assert_eq!(exported.state, Some(DesiredState::Running));
+        for format in [Format::Yaml, Format::Toml] {
+            let text = exported.render(format).unwrap();
+            assert_eq!(parse(&text, format).unwrap(), std::slice::from_ref(&exported));
+        }
+        // Applying it back changes nothing.
+        assert!(plan(&[exported], &[vm("web-1", "running")], false)[0].is_noop());
+    }
+
+    #[test]
+    fn plan_converges_state_and_settings() {
+        let mut bigger = manifest("web-1");
+        bigger.vcpus = 2;
+        let mut started = manifest("web-2");
+        started.state = Some(DesiredState::Running);
+        let mut stopped = manifest("web-3");
+        stopped.state = Some(DesiredState::Stopped);
+        let mut new = manifest("web-4");
+        new.state = Some(DesiredState::Running);
+        let actual = [
+            vm("web-1", "running"),
+            vm("web-2", "stopped"),
+            vm("web-3", "running"),
+            vm("old", "stopped"),
+        ];
+
+        let actions = plan(&[bigger, started, stopped, new], &actual, true);
+        let planned: Vec<_> = actions
+            .iter()
+            .map(|a| format!("{}: {}", a.name, steps(a).join(" ")))
+            .collect();
+        assert_eq!(
+            planned,
+            [
+                "web-1: stop update start",
+                "web-2: start",
+                "web-3: stop",
+                "web-4: create start",
+                "old: delete",
+            ]
+        );
+        assert_eq!(actions[0].changes[0].field, "vcpus");
+        assert_eq!(plan(&[], &actual, false), []);
+    }
+
+    #[test]
+    fn plan_hot_plugs_devices_and_refuses_hypervisor_change() {
+        let mut devices = manifest("web-1");
+        devices.vfio_devices = Some(vec!["/sys/bus/pci/devices/0000:41:00.0".to_string()]);
+        let action = &plan(&[devices], &[vm("web-1", "running")], false)[0];
+        assert_eq!(steps(action), ["attach /sys/bus/pci/devices/0000:41:00.0"]);
+
+        let mut firecracker = manifest("web-1");
+        firecracker.hypervisor = Some("firecracker".to_string());
+        let action = &plan(&[firecracker], &[vm("web-1", "stopped")], false)[0];
+        assert!(action.refused.is_some());
+        assert!(action.steps.is_empty());
+    }
+}
@@ -0,0 +1,417 @@
+//! `top`: a full-screen view of the VMs that refreshes itself, with keys
+//! to start, stop, pause and attach to the selected one.
+
+use super::{
+    failed, handle_connect, restore_terminal, set_raw_mode, spawn_stdin_reader, window_size,
+    Client, CommandResult, ConnectFlags, StartQuery, StopVmRequest, VmResponse, VmState,
+};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::os::fd::AsFd;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tabled::settings::Style;
+use tabled::{Table, Tabled};
+use tokio::time::{Duration, Instant};
+
+/// Switch to the alternate screen with the cursor hidden, and back.
+const ENTER_SCREEN: &str = "\x1b[?1049h\x1b[?25l";
+const LEAVE_SCREEN: &str = "\x1b[?25h\x1b[?1049l";
+const CLEAR_SCREEN: &str = "\x1b[H\x1b[2J";
+
+/// Lines around the table: the title, a blank line and the table header
+/// above it, the status and help lines below.
+const CHROME_LINES: usize = 6;
+
+const HELP: &str = "↑/↓ select  s start/resume  x stop  p pause  c attach  r refresh  q quit";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    Start,
+    Stop,
+    Pause,
+    Attach,
+    Refresh,
+    Quit,
+}
+
+/// The keys in what was typed; anything else is ignored.
+fn parse_keys(input: &[u8]) -> Vec<Key> {
+    let mut keys = Vec::new();
+    let mut rest = input;
+    while let Some((&byte, tail)) = rest.split_first() {
+        rest = tail;
+        let key = match byte {
+            // Arrow keys, in both cursor key modes.
+            0x1b => match rest {
+                [b'[' | b'O', code, tail @ ..] => {
+                    rest = tail;
+                    match code {
+                        b'A' => Some(Key::Up),
+                        b'B' => Some(Key::Down),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            b'k' => Some(Key::Up),
+            b'j' => Some(Key::Down),
+            b's' => Some(Key::Start),
+            b'x' => Some(Key::Stop),
+            b'p' => Some(Key::Pause),
+            b'c' | b'\r' | b'\n' => Some(Key::Attach),
+            b'r' => Some(Key::Refresh),
+            // Ctrl+C arrives as a byte: the terminal is raw.
+            b'q' | 0x03 => Some(Key::Quit),
+            _ => None,
+        };
+        keys.extend(key);
+    }
+    keys
+}
+
+/// One VM's line in the table.
+#[derive(Debug, PartialEq, Tabled)]
+struct Row {
+    name: String,
+    state: String,
+    hypervisor: String,
+    vcpus: u8,
+    memory: String,
+    uptime: String,
+    cpu: String,
+    rss: String,
+}
+
+/// The hypervisor CPU time seen at the last refresh, per VM, to turn the
+/// cumulative figure into a utilisation.
+#[derive(Default)]
+struct CpuSamples {
+    last: HashMap<String, (u32, u64, Instant)>,
+}
+
+impl CpuSamples {
+    /// The rows for `vms` at `now`. A VM's CPU shows once it has been
+    /// seen twice with the same hypervisor process, as a percentage of
+    /// one host CPU.
+    fn rows(&mut self, vms: &[VmResponse], now: Instant) -> Vec<Row> {
+        let mut last = HashMap::new();
+        let rows = vms
+            .iter()
+            .map(|vm| {
+                let cpu = vm.process.and_then(|process| {
+                    last.insert(vm.id.clone(), (process.pid, process.cpu_time_ms, now));
+                    let &(pid, cpu_time_ms, at) = self.last.get(&vm.id)?;
+                    let elapsed_ms = now.duration_since(at).as_millis();
+                    (pid == process.pid && elapsed_ms > 0).then(|| {
+                        let used_ms = process.cpu_time_ms.saturating_sub(cpu_time_ms);
+                        used_ms as f64 * 100.0 / elapsed_ms as f64
+                    })
+                });
+                Row {
+                    name: vm.name.clone(),
+                    state: vm.state.to_string(),
+                    hypervisor: vm.hypervisor.to_string(),
+                    vcpus: vm.vcpu_count,
+                    memory: format!("{} MiB", vm.mem_size_mib),
+                    uptime: vm.uptime_secs.map(format_uptime).unwrap_or_default(),
+                    cpu: cpu.map(|cpu| format!("{:.1}%", cpu)).unwrap_or_default(),
+                    rss: vm
+                        .process
+                        .map(|process| format_bytes(process.rss_bytes))
+                        .unwrap_or_default(),
+                }
+            })
+            .collect();
+        self.last = last;
+        rows
+    }
+}
+
+/// The two largest units of `secs`, e.g. `2h 05m`.
+fn format_uptime(secs: u64) -> String {
+    let (days, hours, mins) = (secs / 86400, secs / 3600 % 24, secs / 60 % 60);
+    match (days, hours, mins) {
+        (0, 0, 0) => format!("{}s", secs),
+        (0, 0, _) => format!("{}m {:02}s", mins, secs % 60),
+        (0, _, _) => format!("{}h {:02}m", hours, mins),
+        _ => format!("{}d {:02}h", days, hours),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    const MIB: u64 = 1024 * 1024;
+    if bytes >= 1024 * MIB {
+        format!("{:.1} GiB", bytes as f64 / (1024 * MIB) as f64)
+    } else {
+        format!("{:.1} MiB", bytes as f64 / MIB as f64)
+    }
+}
+
+/// Lay out one screen: the title, the rows that fit in `height` lines
+/// with the `selected` one highlighted, and the status and help lines.
+fn render(title: &str, rows: &[Row], selected: usize, status: &str, height: usize) -> String {
+    let mut screen = format!("{}\n\n", title);
+    if rows.is_empty() {
+        screen.push_str("No VMs\n");
+    } else {
+        let fits = height.saturating_sub(CHROME_LINES).max(1);
+        let first = selected.saturating_sub(fits - 1);
+        let shown = &rows[first..rows.len().min(first + fits)];
+        let table = Table::new(shown).with(Style::psql()).to_string();
+        for (line, text) in table.lines().enumerate() {
+            // The header and its rule come before the first row.
+            if line == selected - first + 2 {
+                screen.push_str(&format!("\x1b[7m{}\x1b[0m\n", text));
+            } else {
+                screen.push_str(&format!("{}\n", text));
+            }
+        }
+    }
+    screen.push_str(&format!("\n{}\n{}", status, HELP));
+    screen
+}
+
+/// The live view, between attaching to consoles.
+struct Top<'a> {
+    client: &'a Client,
+    interval: Duration,
+    vms: Vec<VmResponse>,
+    /// The id of the selected VM, so the selection follows it as VMs
+    /// come and go.
+    selected: Option<String>,
+    samples: CpuSamples,
+    rows: Vec<Row>,
+    status: String,
+}
+
+impl Top<'_> {
+    fn selected_index(&self) -> usize {
+        self.selected
+            .as_ref()
+            .and_then(|id| self.vms.iter().position(|vm| &vm.id == id))
+            .unwrap_or(0)
+    }
+
+    fn select(&mut self, index: usize) {
+        self.selected = self.vms.get(index).map(|vm| vm.id.clone());
+    }
+
+    async fn refresh(&mut self) {
+        match self.client.list_vms().await {
+            Ok(mut vms) => {
+                vms.sort_by(|a, b| a.name.cmp(&b.name));
+                let index = self.selected_index().min(vms.len().saturating_sub(1));
+                let selected = self.selected.clone();
+                self.vms = vms;
+                // Keep the selection where it was if that VM is gone.
+                if !self.vms.iter().any(|vm| Some(&vm.id) == selected.as_ref()) {
+                    self.select(index);
+                }
+                self.rows = self.samples.rows(&self.vms, Instant::now());
+            }
+            Err(e) => self.status = format!("error: {}", e),
+        }
+    }
+
+    fn draw(&self) {
+        let running = self.vms.iter().filter(|vm| vm.state == VmState::Running).count();
+        let title = format!(
+            "glidex top - {} - {} VMs, {} running - every {}s",
+            self.client.base_url(),
+            self.vms.len(),
+            running,
+            self.interval.as_secs()
+        );
+        let height = window_size().map_or(24, |(_, rows)| rows as usize);
+        let screen = render(&title, &self.rows, self.selected_index(), &self.status, height);
+        let mut stdout = io::stdout();
+        let _ = write!(stdout, "{}{}", CLEAR_SCREEN, screen);
+        let _ = stdout.flush();
+    }
+
+    /// Run `key` on the selected VM, reporting on the status line.
+    async fn act(&mut self, key: Key) {
+        let Some(vm) = self.vms.get(self.selected_index()) else {
+            return;
+        };
+        let (id, name) = (vm.id.clone(), vm.name.clone());
+        let result = match key {
+            Key::Start => self.client.start_vm(&id, &StartQuery::default()).await,
+            Key::Stop => self.client.stop_vm(&id, &StopVmRequest::default()).await,
+            Key::Pause => self.client.pause_vm(&id).await,
+            _ => return,
+        };
+        self.status = match result {
+            Ok(vm) => format!("{}: {}", name, vm.state),
+            Err(e) => format!("{}: error: {}", name, e),
+        };
+        self.refresh().await;
+    }
+
+    /// Show the view until the user quits, or picks a VM to attach to.
+    async fn watch(&mut self) -> Option<String> {
+        let running = Arc::new(AtomicBool::new(true));
+        let (typed_tx, mut typed) = tokio::sync::mpsc::channel(16);
+        let stdin_reader = spawn_stdin_reader(running.clone(), typed_tx);
+        let mut ticks = tokio::time::interval(self.interval);
+
+        let attach = 'watch: loop {
+            tokio::select! {
+                _ = ticks.tick() => self.refresh().await,
+                input = typed.recv() => {
+                    let Some(input) = input else {
+                        break None;
+                    };
+                    for key in parse_keys(&input) {
+                        let index = self.selected_index();
+                        match key {
+                            Key::Up => self.select(index.saturating_sub(1)),
+                            Key::Down => self.select((index + 1).min(self.vms.len().saturating_sub(1))),
+                            Key::Refresh => self.refresh().await,
+                            Key::Attach => {
+                                if let Some(vm) = self.vms.get(index) {
+                                    break 'watch Some(vm.id.clone());
+                                }
+                            }
+                            Key::Quit => break 'watch None,
+                            _ => self.act(key).await,
+                        }
+                    }
+                }
+            }
+            self.draw();
+        };
+
+        running.store(false, Ordering::SeqCst);
+        let _ = stdin_reader.join();
+        attach
+    }
+}
+
+/// Run `top`, refreshing every `interval`, until the user quits.
+pub async fn run(client: &Client, interval: Duration) -> CommandResult {
+    let mut top = Top {
+        client,
+        interval,
+        vms: Vec::new(),
+        selected: None,
+        samples: CpuSamples::default(),
+        rows: Vec::new(),
+        status: String::new(),
+    };
+    let stdin = io::stdin();
+    loop {
+        let orig_termios = set_raw_mode(stdin.as_fd())
+            .ok_or_else(|| failed("Failed to set terminal to raw mode"))?;
+        print!("{}", ENTER_SCREEN);
+        let attach = top.watch().await;
+        print!("{}", LEAVE_SCREEN);
+        let _ = io::stdout().flush();
+        restore_terminal(stdin.as_fd(), &orig_termios);
+
+        let Some(vm_id) = attach else {
+            return Ok(());
+        };
+        // Its errors are printed already; back to the view either way.
+        top.status = match handle_connect(client, &vm_id, ConnectFlags::default()).await {
+            Ok(()) => String::new(),
+            Err(_) => "error: could not attach to the console".to_string(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vm(name: &str, state: &str, process: Option<(u32, u64)>) -> VmResponse {
+        let mut vm: VmResponse = serde_json::from_value(serde_json::json!({
+            "id": format!("{}-id", name),
+            "name": name,
+            "state": state,
+            "vcpu_count": 2,
+            "mem_size_mib": 1024,
+            "kernel_image_path": "/k",
+            "rootfs_path": "/r",
+            "kernel_args": "console=ttyS0",
+            "no_reboot": true,
+            "console_socket_path": format!("/tmp/{}.sock", name),
+            "log_path": format!("/tmp/{}.log", name),
+            "hypervisor": "qemu",
+            "uptime_secs": 3725,
+        }))
+        .unwrap();
+        vm.process = process.map(|(pid, cpu_time_ms)| glidex_client::models::ProcessUsage {
+            pid,
+            cpu_time_ms,
+            rss_bytes: 300 * 1024 * 1024,
+        });
+        vm
+    }
+
+    #[test]
+    fn keys_are_read_from_letters_and_arrows() {
+        assert_eq!(
+            parse_keys(b"\x1b[A\x1bOBjks?x\rq"),
+            [
+                Key::Up,
+                Key::Down,
+                Key::Down,
+                Key::Up,
+                Key::Start,
+                Key::Stop,
+                Key::Attach,
+                Key::Quit
+            ]
+        );
+        assert_eq!(parse_keys(b"\x1b[C\x03"), [Key::Quit]);
+    }
+
+    #[test]
+    fn cpu_is_the_share_of_time_since_the_last_refresh() {
+        let mut samples = CpuSamples::default();
+        let start = Instant::now();
+        let rows = samples.rows(&[vm("web", "running", Some((10, 1000)))], start);
+        assert_eq!(rows[0].cpu, "");
+        assert_eq!(rows[0].rss, "300.0 MiB");
+        assert_eq!(rows[0].uptime, "1h 02m");
+
+        let later = start + Duration::from_secs(2);
+        let rows = samples.rows(&[vm("web", "running", Some((10, 2500)))], later);
+        assert_eq!(rows[0].cpu, "75.0%");
+
+        // A new hypervisor process starts over.
+        let rows = samples.rows(&[vm("web", "running", Some((11, 3000)))], later + Duration::from_secs(2));
+        assert_eq!(rows[0].cpu, "");
+        let rows = samples.rows(&[vm("web", "stopped", None)], later + Duration::from_secs(4));
+        assert_eq!((rows[0].cpu.as_str(), rows[0].rss.as_str()), ("", ""));
+    }
+
+    #[test]
+    fn render_highlights_the_selected_row_and_scrolls_to_it() {
+        let mut samples = CpuSamples::default();
+        let vms: Vec<_> = ["a", "b", "c"].iter().map(|name| vm(name, "running", None)).collect();
+        let rows = samples.rows(&vms, Instant::now());
+
+        let screen = render("title", &rows, 1, "", 24);
+        let highlighted: Vec<_> = screen.lines().filter(|line| line.starts_with("\x1b[7m")).collect();
+        assert_eq!(highlighted.len(), 1);
+        assert!(highlighted[0].contains(" b "));
+
+        // Room for one row: only the selected one is shown.
+        let screen = render("title", &rows, 2, "", CHROME_LINES + 1);
+        assert!(screen.contains(" c ") && !screen.contains(" a "));
+        assert!(render("title", &[], 0, "", 24).contains("No VMs"));
+    }
+
+    #[test]
+    fn uptime_shows_its_two_largest_units() {
+        assert_eq!(format_uptime(42), "42s");
+        assert_eq!(format_uptime(125), "2m 05s");
+        assert_eq!(format_uptime(90061), "1d 01h");
+        assert_eq!(format_bytes(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}
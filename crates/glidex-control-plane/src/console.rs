@@ -0,0 +1,1455 @@
+//! Console proxy shared by all hypervisor backends.
+//!
+//! Every running VM has one proxy: a Tokio task that reads the guest's
+//! serial output, appends it to the VM's log file and broadcasts it to the
+//! clients of the VM's console socket, and feeds whatever those clients
+//! type to the serial input. The backends only differ in how they obtain
+//! the serial descriptors; see `spec/console.md`.
+//!
+//! New clients are first sent recent output from an in-memory scrollback
+//! buffer. How much of it is up to the client: it may open the
+//! connection with a [`Handshake`] line naming a [`Replay`] mode. The
+//! handshake can also switch the connection to [`Frame`]s, which carry
+//! [`Control`] messages such as terminal resizes next to the data, and
+//! pick a [`ConsoleMode`]: an `exclusive` client locks the other
+//! clients out of typing while it is attached.
+
+use crate::logs::{self, LogWriter};
+use crate::models::LogPolicy;
+use crate::recording::{Recorder, Recordings};
+use nix::sys::termios;
+use regex_automata::meta::Regex;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::unix::AsyncFd;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, Interest};
+use tokio::net::unix::UCred;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc::{self, error::TrySendError, WeakSender};
+use tokio::task::{JoinHandle, JoinSet};
+
+pub use glidex_client::console::{
+    ConsoleEvent, Control, Frame, FrameDecoder, Handshake, HANDSHAKE_PREFIX,
+};
+pub use glidex_client::models::{Attached, AttachedSession, ConsoleMode, Replay};
+
+/// Largest chunk read from the serial console or a client at once.
+const CHUNK_SIZE: usize = 4096;
+
+/// Output chunks queued for one client. A client that falls this far
+/// behind is disconnected instead of holding up the others.
+const CLIENT_QUEUE_CHUNKS: usize = 256;
+
+/// How long a single write to a client may stall before the client is
+/// disconnected.
+const CLIENT_WRITE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Input chunks queued for the serial console. Keystrokes beyond that,
+/// e.g. typed at a paused guest, are dropped.
+const INPUT_QUEUE_CHUNKS: usize = 64;
+
+/// Console output kept in memory for replay to new clients.
+const SCROLLBACK_BYTES: usize = 1024 * 1024;
+
+/// How long a new client gets to send the handshake. Clients that send
+/// nothing, or something else, get the whole scrollback as before.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// Longest handshake line accepted.
+const HANDSHAKE_MAX_LEN: usize = 128;
+
+/// Output kept by [`expect`] while waiting for its pattern.
+const EXPECT_MAX_BYTES: usize = SCROLLBACK_BYTES;
+
+/// How long [`attached`] waits for the proxy to answer.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Pause after a failed `accept`, so running out of descriptors does not
+/// turn into a busy loop.
+const ACCEPT_RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// The part of `history` that `replay` sends.
+fn select_replay(replay: Replay, history: &[Arc<[u8]>]) -> Vec<u8> {
+    let lines = match replay {
+        Replay::None => return Vec::new(),
+        Replay::All => return history.concat(),
+        Replay::Tail(lines) => lines,
+    };
+    let history = history.concat();
+    if lines == 0 {
+        return Vec::new();
+    }
+    // A trailing partial line, such as a prompt, counts as a line; a
+    // trailing newline does not start one.
+    let end = history.len() - usize::from(history.last() == Some(&b'\n'));
+    let start = history[..end]
+        .iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, &b)| b == b'\n')
+        .nth(lines - 1)
+        .map_or(0, |(i, _)| i + 1);
+    history[start..].to_vec()
+}
+
+/// How a control message is sent to a client: as a frame, or for a raw
+/// client as a line of text if it is a notice, else not at all.
+fn encode_control(control: &Control, framed: bool) -> Option<Vec<u8>> {
+    match control {
+        _ if framed => Some(control.frame().encode()),
+        Control::Notice { message } => Some(notice_text(message)),
+        _ => None,
+    }
+}
+
+/// What the proxy queues for a client.
+#[derive(Debug, Clone)]
+enum Outgoing {
+    Output(Arc<[u8]>),
+    Event(ConsoleEvent),
+    Notice(String),
+}
+
+/// What clients ask of the serial console, in the order they asked.
+#[derive(Debug)]
+enum SerialInput {
+    Data(Vec<u8>),
+    Resize { cols: u16, rows: u16 },
+    Break,
+}
+
+/// What [`expect`] waited for.
+#[derive(Debug)]
+pub struct Expected {
+    pub matched: Vec<u8>,
+    /// Output between connecting (including any replay) and the match.
+    pub before: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum ExpectError {
+    Io(io::Error),
+    /// The pattern did not appear in time; `output` is what did.
+    TimedOut { output: Vec<u8> },
+    /// The guest's console closed before the pattern appeared.
+    GuestExited { output: Vec<u8> },
+}
+
+impl From<io::Error> for ExpectError {
+    fn from(e: io::Error) -> Self {
+        ExpectError::Io(e)
+    }
+}
+
+/// Connect to the console socket at `socket_path` as a client, type
+/// `input` and wait for `pattern` to match the output that follows, for
+/// at most `timeout`. Of the scrollback, only what `replay` asks for is
+/// searched. The client is registered with the proxy before the input
+/// is written, so anything the guest prints in response is seen. The
+/// session is not recorded.
+pub async fn expect(
+    socket_path: &Path,
+    input: &[u8],
+    pattern: &Regex,
+    timeout: Duration,
+    replay: Replay,
+) -> Result<Expected, ExpectError> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    let mut opening = Handshake {
+        replay,
+        framed: true,
+        record: false,
+        client: Some("expect".to_string()),
+        // Waiting without typing needs no write access, e.g. during
+        // another session's exclusive lock.
+        mode: if input.is_empty() {
+            ConsoleMode::ReadOnly
+        } else {
+            ConsoleMode::Shared
+        },
+        query: false,
+    }
+    .line();
+    if !input.is_empty() {
+        opening.extend(Frame::encode_data(input));
+    }
+    stream.write_all(&opening).await?;
+
+    let mut output = Vec::new();
+    let mut frames = FrameDecoder::default();
+    let mut buf = [0u8; CHUNK_SIZE];
+    let waited = tokio::time::timeout(timeout, async {
+        loop {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                return Err(ExpectError::GuestExited {
+                    output: std::mem::take(&mut output),
+                });
+            }
+            frames.push(&buf[..n]);
+            while let Some(frame) = frames.next_frame()? {
+                match frame {
+                    Frame::Data(data) => output.extend_from_slice(&data),
+                    Frame::Control(json) => {
+                        let event = ConsoleEvent::GuestExited;
+                        if serde_json::from_slice(&json).ok() == Some(Control::Status { event }) {
+                            return Err(ExpectError::GuestExited {
+                                output: std::mem::take(&mut output),
+                            });
+                        }
+                    }
+                }
+            }
+            // Searched whole, as a match may span chunks.
+            if let Some(found) = pattern.find(&output) {
+                return Ok(Expected {
+                    matched: output[found.range()].to_vec(),
+                    before: output[..found.start()].to_vec(),
+                });
+            }
+            if output.len() > EXPECT_MAX_BYTES {
+                output.drain(..output.len() - EXPECT_MAX_BYTES);
+            }
+        }
+    })
+    .await;
+    waited.unwrap_or(Err(ExpectError::TimedOut { output }))
+}
+
+/// Connect to the console socket at `socket_path` and return once the
+/// guest's console closes, i.e. its hypervisor exited or was stopped.
+/// Only failing to connect is an error; once connected, losing the
+/// connection means the proxy is gone.
+pub async fn wait_for_exit(socket_path: &Path) -> io::Result<()> {
+    let mut stream = UnixStream::connect(socket_path).await?;
+    let watch = async {
+        let opening = Handshake {
+            replay: Replay::None,
+            framed: true,
+            record: false,
+            client: Some("watch".to_string()),
+            mode: ConsoleMode::ReadOnly,
+            query: false,
+        }
+        .line();
+        stream.write_all(&opening).await?;
+
+        let mut frames = FrameDecoder::default();
+        let mut buf = [0u8; CHUNK_SIZE];
+        loop {
+            let n = stream.read(&mut buf).await?;
+            if n == 0 {
+                return Ok(());
+            }
+            frames.push(&buf[..n]);
+            while let Some(frame) = frames.next_frame()? {
+                let event = ConsoleEvent::GuestExited;
+                if let Frame::Control(json) = frame {
+                    if serde_json::from_slice(&json).ok() == Some(Control::Status { event }) {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    };
+    let _: io::Result<()> = watch.await;
+    Ok(())
+}
+
+/// List the clients attached to the console socket at `socket_path`.
+pub async fn attached(socket_path: &Path) -> io::Result<Attached> {
+    let query = async {
+        let mut stream = UnixStream::connect(socket_path).await?;
+        let opening = Handshake {
+            query: true,
+            ..Handshake::default()
+        };
+        stream.write_all(&opening.line()).await?;
+        let mut answer = Vec::new();
+        stream.read_to_end(&mut answer).await?;
+        serde_json::from_slice(&answer).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    };
+    tokio::time::timeout(QUERY_TIMEOUT, query)
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+}
+
+/// The proxy's registry of attached clients, shared by their tasks.
+#[derive(Default)]
+struct Attachments {
+    next_id: u64,
+    attached: Attached,
+    /// Where to queue notices for each session, by id. Weak, so that the
+    /// proxy dropping a client still closes its queue.
+    notices: Vec<(u64, WeakSender<Outgoing>)>,
+}
+
+impl Attachments {
+    /// Register a client that asked for `handshake`. Returns its session
+    /// and the notice it is to be shown, if any. The lock holder is told
+    /// about the new client; if the new client takes the lock, everybody
+    /// else is told.
+    fn attach(
+        &mut self,
+        handshake: &Handshake,
+        peer: Option<UCred>,
+        notices: WeakSender<Outgoing>,
+    ) -> (AttachedSession, Option<String>) {
+        self.next_id += 1;
+        let mut session = AttachedSession {
+            id: self.next_id,
+            mode: handshake.mode,
+            client: handshake.client.clone(),
+            connected_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+            uid: peer.map(|peer| peer.uid()),
+            pid: peer.and_then(|peer| peer.pid()),
+        };
+        let holder = self.holder().map(AttachedSession::to_string);
+        let notice = match (holder, session.mode) {
+            (Some(holder), requested) => {
+                self.notify(
+                    self.attached.write_lock,
+                    &format!("{} attached ({})", session, requested),
+                );
+                match requested {
+                    ConsoleMode::ReadOnly => None,
+                    ConsoleMode::Shared => Some(format!(
+                        "Console is locked by {}; input is ignored until it detaches",
+                        holder
+                    )),
+                    ConsoleMode::Exclusive => {
+                        session.mode = ConsoleMode::ReadOnly;
+                        Some(format!(
+                            "Console is locked by {}; attached read-only",
+                            holder
+                        ))
+                    }
+                }
+            }
+            (None, ConsoleMode::Exclusive) => {
+                self.attached.write_lock = Some(session.id);
+                self.notify_all(&format!(
+                    "{} locked the console; input from other sessions is ignored until it detaches",
+                    session
+                ));
+                None
+            }
+            (None, _) => None,
+        };
+        self.attached.sessions.push(session.clone());
+        self.notices.push((session.id, notices));
+        (session, notice)
+    }
+
+    /// Unregister session `id`, releasing the lock if it held it.
+    fn detach(&mut self, id: u64) {
+        self.notices.retain(|(session, _)| *session != id);
+        let Some(index) = self.attached.sessions.iter().position(|s| s.id == id) else {
+            return;
+        };
+        let session = self.attached.sessions.remove(index);
+        if self.attached.write_lock == Some(id) {
+            self.attached.write_lock = None;
+            self.notify_all(&format!("{} detached; console unlocked", session));
+        }
+    }
+
+    /// Whether session `id` may type; if not, why.
+    fn may_write(&self, id: u64) -> Result<(), String> {
+        let read_only = self
+            .attached
+            .sessions
+            .iter()
+            .any(|s| s.id == id && s.mode == ConsoleMode::ReadOnly);
+        if read_only {
+            return Err("Read-only session; input is ignored".to_string());
+        }
+        match self.holder() {
+            Some(holder) if holder.id != id => Err(format!(
+                "Console is locked by {}; input is ignored",
+                holder
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn holder(&self) -> Option<&AttachedSession> {
+        let id = self.attached.write_lock?;
+        self.attached.sessions.iter().find(|s| s.id == id)
+    }
+
+    fn notify(&self, id: Option<u64>, message: &str) {
+        for (_, notices) in self.notices.iter().filter(|(session, _)| Some(*session) == id) {
+            if let Some(notices) = notices.upgrade() {
+                let _ = notices.try_send(Outgoing::Notice(message.to_string()));
+            }
+        }
+    }
+
+    /// Tell every registered session; the one the message is about is
+    /// not (or no longer) registered.
+    fn notify_all(&self, message: &str) {
+        for (id, _) in &self.notices {
+            self.notify(Some(*id), message);
+        }
+    }
+}
+
+/// The most recent console output, in the chunks it was read in.
+struct Scrollback {
+    chunks: VecDeque<Arc<[u8]>>,
+    len: usize,
+}
+
+impl Scrollback {
+    /// Seed the buffer with the end of the current boot's log, so output
+    /// from before a control-plane restart or hypervisor respawn is
+    /// replayed.
+    fn from_log(log_path: &Path) -> Self {
+        let mut scrollback = Self {
+            chunks: VecDeque::new(),
+            len: 0,
+        };
+        if let Ok(tail) = logs::read_tail(log_path, SCROLLBACK_BYTES as u64) {
+            if !tail.is_empty() {
+                scrollback.push(Arc::from(tail));
+            }
+        }
+        scrollback
+    }
+
+    fn push(&mut self, chunk: Arc<[u8]>) {
+        self.len += chunk.len();
+        self.chunks.push_back(chunk);
+        while self.len > SCROLLBACK_BYTES {
+            match self.chunks.pop_front() {
+                Some(oldest) => self.len -= oldest.len(),
+                None => break,
+            }
+        }
+    }
+
+    fn snapshot(&self) -> Vec<Arc<[u8]>> {
+        self.chunks.iter().cloned().collect()
+    }
+}
+
+/// The guest side of a console.
+pub struct Serial {
+    output: File,
+    input: File,
+}
+
+impl Serial {
+    /// A PTY, read and written through the same descriptor.
+    pub fn pty(pty: File) -> io::Result<Self> {
+        Ok(Self {
+            input: pty.try_clone()?,
+            output: pty,
+        })
+    }
+
+    /// Separate descriptors for output and input, such as our ends of
+    /// Firecracker's serial FIFOs.
+    pub fn split(output: File, input: File) -> Self {
+        Self { output, input }
+    }
+}
+
+/// A running console proxy. Dropping it stops the proxy too, but without
+/// waiting for it to let go of the socket and the serial descriptors.
+pub struct ConsoleProxy {
+    task: JoinHandle<()>,
+}
+
+impl ConsoleProxy {
+    /// Bind the console socket at `socket_path`, replacing any stale one,
+    /// and start proxying `serial` to it. All output is appended to
+    /// `log_path`, which is rotated per `log_policy`, and client sessions
+    /// are recorded next to it. Must be called from within a Tokio
+    /// runtime.
+    pub fn spawn(
+        serial: Serial,
+        socket_path: &str,
+        log_path: &str,
+        log_policy: LogPolicy,
+    ) -> io::Result<Self> {
+        set_nonblocking(&serial.output)?;
+        set_nonblocking(&serial.input)?;
+        let tty = termios::tcgetattr(&serial.input).is_ok();
+        let output = AsyncFd::with_interest(serial.output, Interest::READABLE)?;
+        let input = AsyncFd::with_interest(serial.input, Interest::WRITABLE)?;
+
+        let _ = std::fs::remove_file(socket_path);
+        let listener = UnixListener::bind(socket_path)?;
+
+        let log_path = PathBuf::from(log_path);
+        let proxy = Proxy {
+            listener,
+            log: LogWriter::open(&log_path, log_policy)?,
+            scrollback: Scrollback::from_log(&log_path),
+            recordings: Recordings::new(&log_path, &log_policy),
+            attachments: Arc::default(),
+            log_path,
+            tty,
+        };
+        Ok(Self {
+            task: tokio::spawn(proxy.run(output, input)),
+        })
+    }
+
+    /// Stop the proxy. Once this returns, the console socket accepts no
+    /// more clients, connected ones are closed and the serial descriptors
+    /// are released.
+    pub async fn stop(mut self) {
+        self.task.abort();
+        let _ = (&mut self.task).await;
+    }
+}
+
+impl Drop for ConsoleProxy {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+struct Proxy {
+    listener: UnixListener,
+    log: LogWriter,
+    log_path: PathBuf,
+    scrollback: Scrollback,
+    recordings: Recordings,
+    attachments: Arc<Mutex<Attachments>>,
+    /// Whether the serial console is a terminal, which can be resized.
+    tty: bool,
+}
+
+impl Proxy {
+    async fn run(mut self, output: AsyncFd<File>, input: AsyncFd<File>) {
+        // Client and serial-input tasks are aborted with the proxy.
+        let mut tasks = JoinSet::new();
+        let (input_tx, input_rx) = mpsc::channel(INPUT_QUEUE_CHUNKS);
+        tasks.spawn(write_serial(input, input_rx));
+
+        let mut clients: Vec<mpsc::Sender<Outgoing>> = Vec::new();
+        let mut buf = [0u8; CHUNK_SIZE];
+        // The serial output reads EOF, or fails, once the hypervisor exits.
+        // The listener stays up regardless: a crashed guest is when clients
+        // most want to connect and replay the log.
+        let mut serial_alive = true;
+
+        loop {
+            tokio::select! {
+                accepted = self.listener.accept() => match accepted {
+                    Ok((stream, _)) => {
+                        clients.retain(|client| !client.is_closed());
+                        let client =
+                            self.add_client(&mut tasks, stream, input_tx.clone(), !serial_alive);
+                        clients.push(client);
+                    }
+                    Err(e) => {
+                        tracing::warn!(
+                            log = %self.log_path.display(),
+                            "Failed to accept console client: {}",
+                            e
+                        );
+                        tokio::time::sleep(ACCEPT_RETRY_DELAY).await;
+                    }
+                },
+                read = read_serial(&output, &mut buf), if serial_alive => match read {
+                    Ok(0) | Err(_) => {
+                        serial_alive = false;
+                        self.send(&mut clients, Outgoing::Event(ConsoleEvent::GuestExited));
+                    }
+                    Ok(n) => self.broadcast(&mut clients, &buf[..n]),
+                },
+                Some(_) = tasks.join_next() => {}
+            }
+        }
+    }
+
+    /// Start serving a new client. It gets the scrollback it asks for
+    /// first, then live output.
+    fn add_client(
+        &self,
+        tasks: &mut JoinSet<()>,
+        stream: UnixStream,
+        input: mpsc::Sender<SerialInput>,
+        exited: bool,
+    ) -> mpsc::Sender<Outgoing> {
+        let history = self.scrollback.snapshot();
+        let (tx, rx) = mpsc::channel(CLIENT_QUEUE_CHUNKS);
+        if exited {
+            let _ = tx.try_send(Outgoing::Event(ConsoleEvent::GuestExited));
+        }
+        let context = ClientContext {
+            input,
+            tty: self.tty,
+            recordings: self.recordings.clone(),
+            attachments: Arc::clone(&self.attachments),
+        };
+        tasks.spawn(serve_client(stream, history, rx, tx.downgrade(), context));
+        tx
+    }
+
+    /// Log a chunk of serial output, add it to the scrollback and queue
+    /// it for every client, dropping clients that are gone or too far
+    /// behind.
+    fn broadcast(&mut self, clients: &mut Vec<mpsc::Sender<Outgoing>>, data: &[u8]) {
+        if let Err(e) = self.log.write(data) {
+            tracing::warn!(log = %self.log_path.display(), "Failed to write console log: {}", e);
+        }
+
+        let chunk: Arc<[u8]> = Arc::from(data);
+        self.scrollback.push(Arc::clone(&chunk));
+        self.send(clients, Outgoing::Output(chunk));
+    }
+
+    fn send(&self, clients: &mut Vec<mpsc::Sender<Outgoing>>, item: Outgoing) {
+        clients.retain(|client| match client.try_send(item.clone()) {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                tracing::warn!(
+                    log = %self.log_path.display(),
+                    "Console client fell behind, disconnecting it"
+                );
+                false
+            }
+            Err(TrySendError::Closed(_)) => false,
+        });
+    }
+}
+
+/// What the client tasks of a proxy share.
+struct ClientContext {
+    input: mpsc::Sender<SerialInput>,
+    /// Whether the serial console is a terminal, which can be resized.
+    tty: bool,
+    recordings: Recordings,
+    attachments: Arc<Mutex<Attachments>>,
+}
+
+/// Copy queued output to one client and its keystrokes to the serial
+/// input, until the client hangs up, stalls, or is dropped by the proxy.
+/// `notices` is the sending side of `output`, for the other clients to
+/// queue notices on. Unless it opted out, the session is recorded.
+async fn serve_client(
+    mut stream: UnixStream,
+    history: Vec<Arc<[u8]>>,
+    mut output: mpsc::Receiver<Outgoing>,
+    notices: WeakSender<Outgoing>,
+    context: ClientContext,
+) {
+    let (handshake, typed) = read_handshake(&mut stream).await;
+    if handshake.query {
+        let attached = context.attachments.lock().unwrap().attached.clone();
+        let answer = serde_json::to_vec(&attached).unwrap_or_default();
+        let _ = write_client(&mut stream, &answer).await;
+        return;
+    }
+    let recorder = if handshake.record {
+        context.recordings.start(handshake.client.as_deref())
+    } else {
+        None
+    };
+    let peer = stream.peer_cred().ok();
+    let (session, notice) = context
+        .attachments
+        .lock()
+        .unwrap()
+        .attach(&handshake, peer, notices);
+    let mut client = Client {
+        frames: handshake.framed.then(FrameDecoder::default),
+        input: context.input,
+        tty: context.tty,
+        recorder,
+        attachments: context.attachments,
+        session: session.id,
+        refused: false,
+    };
+    let mut replies = match client.forward_input(&typed) {
+        Ok(replies) => replies,
+        Err(_) => return,
+    };
+    if let Some(message) = notice {
+        client.record(|recorder| recorder.marker(&message));
+        replies.push(Control::Notice { message });
+    }
+
+    let replay = select_replay(handshake.replay, &history);
+    client.record(|recorder| recorder.output(&replay));
+    let replay = if handshake.framed {
+        let mut frames = Frame::encode_data(&replay);
+        let finished = Control::Status {
+            event: ConsoleEvent::ReplayFinished,
+        };
+        frames.extend(finished.frame().encode());
+        frames
+    } else {
+        replay
+    };
+    if write_client(&mut stream, &replay).await.is_err() {
+        return;
+    }
+
+    let mut buf = [0u8; CHUNK_SIZE];
+    loop {
+        for reply in replies.drain(..) {
+            let Some(data) = encode_control(&reply, handshake.framed) else {
+                continue;
+            };
+            if write_client(&mut stream, &data).await.is_err() {
+                return;
+            }
+        }
+        tokio::select! {
+            item = output.recv() => {
+                match &item {
+                    Some(Outgoing::Output(chunk)) => client.record(|recorder| recorder.output(chunk)),
+                    Some(Outgoing::Event(ConsoleEvent::GuestExited)) => {
+                        client.record(|recorder| recorder.marker("guest exited"))
+                    }
+                    Some(Outgoing::Notice(message)) => {
+                        client.record(|recorder| recorder.marker(message))
+                    }
+                    _ => {}
+                }
+                let data = match (item, handshake.framed) {
+                    // `None` once the proxy has dropped us and the queue is empty.
+                    (None, _) => break,
+                    (Some(Outgoing::Output(chunk)), false) => chunk.to_vec(),
+                    (Some(Outgoing::Output(chunk)), true) => Frame::Data(chunk.to_vec()).encode(),
+                    (Some(Outgoing::Event(event)), true) => Control::Status { event }.frame().encode(),
+                    (Some(Outgoing::Event(_)), false) => continue,
+                    (Some(Outgoing::Notice(message)), framed) => {
+                        match encode_control(&Control::Notice { message }, framed) {
+                            Some(data) => data,
+                            None => continue,
+                        }
+                    }
+                };
+                if write_client(&mut stream, &data).await.is_err() {
+                    break;
+                }
+            }
+            read = stream.read(&mut buf) => match read {
+                Ok(0) | Err(_) => break,
+                Ok(n) => match client.forward_input(&buf[..n]) {
+                    Ok(more) => replies = more,
+                    Err(_) => break,
+                },
+            },
+        }
+    }
+}
+
+/// A notice as shown to a raw client, on a line of its own.
+fn notice_text(message: &str) -> Vec<u8> {
+    format!("\r\n[glidex] {}\r\n", message).into_bytes()
+}
+
+/// The input side of a console client. Dropping it detaches the client.
+struct Client {
+    /// Set for clients that asked for frames.
+    frames: Option<FrameDecoder>,
+    input: mpsc::Sender<SerialInput>,
+    tty: bool,
+    recorder: Option<Recorder>,
+    attachments: Arc<Mutex<Attachments>>,
+    session: u64,
+    /// Whether the client was told its last input was ignored; it is
+    /// told once until input gets through again.
+    refused: bool,
+}
+
+impl Client {
+    fn record(&mut self, event: impl FnOnce(&mut Recorder)) {
+        if let Some(recorder) = &mut self.recorder {
+            event(recorder);
+        }
+    }
+
+    /// Pass what the client sent on to the serial console. Returns the
+    /// replies to control messages that could not be applied, or to
+    /// input the client may not send, or an error if the client broke
+    /// the framing. Input the guest is not consuming is dropped rather
+    /// than allowed to stall this client's output.
+    fn forward_input(&mut self, data: &[u8]) -> io::Result<Vec<Control>> {
+        let mut requests = Vec::new();
+        let mut replies = Vec::new();
+        match &mut self.frames {
+            None if data.is_empty() => {}
+            None => requests.push(SerialInput::Data(data.to_vec())),
+            Some(frames) => {
+                frames.push(data);
+                while let Some(frame) = frames.next_frame()? {
+                    requests.push(match frame {
+                        Frame::Data(data) => SerialInput::Data(data),
+                        Frame::Control(json) => match serde_json::from_slice(&json) {
+                            Ok(Control::Resize { cols, rows }) => SerialInput::Resize { cols, rows },
+                            Ok(Control::Break) => SerialInput::Break,
+                            Ok(other) => {
+                                replies.push(Control::Error {
+                                    message: format!(
+                                        "Unexpected control message from client: {:?}",
+                                        other
+                                    ),
+                                });
+                                continue;
+                            }
+                            Err(e) => {
+                                replies.push(Control::Error {
+                                    message: format!("Invalid control message: {}", e),
+                                });
+                                continue;
+                            }
+                        },
+                    });
+                }
+            }
+        }
+
+        for request in requests {
+            if let Err(message) = self.attachments.lock().unwrap().may_write(self.session) {
+                if !std::mem::replace(&mut self.refused, true) {
+                    replies.push(Control::Notice { message });
+                }
+                continue;
+            }
+            self.refused = false;
+            if !self.tty && !matches!(request, SerialInput::Data(_)) {
+                replies.push(Control::Error {
+                    message: "This VM's serial console is not a terminal".to_string(),
+                });
+                continue;
+            }
+            self.record(|recorder| match &request {
+                SerialInput::Data(data) => recorder.input(data),
+                SerialInput::Resize { cols, rows } => recorder.resize(*cols, *rows),
+                SerialInput::Break => recorder.marker("break"),
+            });
+            let _ = self.input.try_send(request);
+        }
+        Ok(replies)
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        self.attachments.lock().unwrap().detach(self.session);
+    }
+}
+
+/// Read the handshake a client may open with. Returns what it asked for,
+/// and whatever it sent after the handshake or, if it turned out not to
+/// send one, instead of it, which is keyboard input.
+async fn read_handshake(stream: &mut UnixStream) -> (Handshake, Vec<u8>) {
+    let deadline = tokio::time::Instant::now() + HANDSHAKE_TIMEOUT;
+    let mut received = Vec::new();
+    let mut buf = [0u8; HANDSHAKE_MAX_LEN];
+    loop {
+        let n = received.len().min(HANDSHAKE_PREFIX.len());
+        if received[..n] != HANDSHAKE_PREFIX[..n] {
+            return (Handshake::default(), received);
+        }
+        if let Some(end) = received.iter().position(|&b| b == b'\n') {
+            let (handshake, skipped) =
+                Handshake::parse(&String::from_utf8_lossy(&received[n..end]));
+            for problem in skipped {
+                tracing::warn!("{}", problem);
+            }
+            return (handshake, received.split_off(end + 1));
+        }
+        if received.len() >= HANDSHAKE_MAX_LEN {
+            return (Handshake::default(), received);
+        }
+        match tokio::time::timeout_at(deadline, stream.read(&mut buf)).await {
+            Ok(Ok(n)) if n > 0 => received.extend_from_slice(&buf[..n]),
+            _ => return (Handshake::default(), received),
+        }
+    }
+}
+
+async fn write_client(stream: &mut UnixStream, data: &[u8]) -> io::Result<()> {
+    tokio::time::timeout(CLIENT_WRITE_TIMEOUT, stream.write_all(data))
+        .await
+        .map_err(|_| io::Error::from(io::ErrorKind::TimedOut))?
+}
+
+async fn read_serial(serial: &AsyncFd<File>, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        let mut ready = serial.readable().await?;
+        if let Ok(result) = ready.try_io(|fd| fd.get_ref().read(buf)) {
+            return result;
+        }
+    }
+}
+
+/// Feed client input to the serial console. Once the guest side is gone,
+/// input is discarded.
+async fn write_serial(serial: AsyncFd<File>, mut input: mpsc::Receiver<SerialInput>) {
+    while let Some(request) = input.recv().await {
+        let result = match request {
+            SerialInput::Data(data) => {
+                let mut data = &data[..];
+                while !data.is_empty() {
+                    let Ok(mut ready) = serial.writable().await else {
+                        return;
+                    };
+                    match ready.try_io(|fd| fd.get_ref().write(data)) {
+                        Ok(Ok(n)) => data = &data[n..],
+                        Ok(Err(_)) => break,
+                        Err(_would_block) => {}
+                    }
+                }
+                Ok(())
+            }
+            SerialInput::Resize { cols, rows } => set_window_size(serial.get_ref(), cols, rows),
+            SerialInput::Break => match serial.get_ref().try_clone() {
+                // Blocks for the duration of the break, a fraction of a second.
+                Ok(tty) => tokio::task::spawn_blocking(move || termios::tcsendbreak(&tty, 0))
+                    .await
+                    .map_err(io::Error::other)
+                    .and_then(|sent| sent.map_err(io::Error::from)),
+                Err(e) => Err(e),
+            },
+        };
+        if let Err(e) = result {
+            tracing::warn!("Failed to control the serial console: {}", e);
+        }
+    }
+}
+
+fn set_window_size(tty: &File, cols: u16, rows: u16) -> io::Result<()> {
+    let size = libc::winsize {
+        ws_row: rows,
+        ws_col: cols,
+        ws_xpixel: 0,
+        ws_ypixel: 0,
+    };
+    if unsafe { libc::ioctl(tty.as_raw_fd(), libc::TIOCSWINSZ, &size) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn set_nonblocking(file: &File) -> io::Result<()> {
+    let fd = file.as_raw_fd();
+    let flags = unsafe { libc::fcntl(fd, libc::F_GETFL) };
+    if flags < 0 || unsafe { libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nix::pty::openpty;
+    use nix::sys::termios::{cfmakeraw, tcgetattr, tcsetattr, SetArg};
+    use std::path::Path;
+    use tempfile::TempDir;
+
+    const QUICK: Duration = Duration::from_secs(5);
+
+    /// The hypervisor's end of a raw pseudo-terminal, standing in for a
+    /// guest's serial port.
+    struct Guest(AsyncFd<File>);
+
+    impl Guest {
+        async fn write_all(&self, mut data: &[u8]) {
+            while !data.is_empty() {
+                let mut ready = self.0.writable().await.unwrap();
+                if let Ok(result) = ready.try_io(|fd| fd.get_ref().write(data)) {
+                    data = &data[result.unwrap()..];
+                }
+            }
+        }
+
+        async fn read_exact(&self, len: usize) -> Vec<u8> {
+            let mut data = Vec::new();
+            let mut buf = [0u8; CHUNK_SIZE];
+            while data.len() < len {
+                let n = read_serial(&self.0, &mut buf[..len - data.len()])
+                    .await
+                    .unwrap();
+                data.extend_from_slice(&buf[..n]);
+            }
+            data
+        }
+    }
+
+    /// A proxy on a fresh pseudo-terminal, with its socket and log in `dir`.
+    fn spawn_proxy(dir: &TempDir) -> (Guest, ConsoleProxy) {
+        let pty = openpty(None, None).unwrap();
+        let mut termios = tcgetattr(&pty.slave).unwrap();
+        cfmakeraw(&mut termios);
+        tcsetattr(&pty.slave, SetArg::TCSANOW, &termios).unwrap();
+
+        let master = File::from(pty.master);
+        set_nonblocking(&master).unwrap();
+        let guest = Guest(AsyncFd::new(master).unwrap());
+
+        let serial = Serial::pty(File::from(pty.slave)).unwrap();
+        let proxy = ConsoleProxy::spawn(
+            serial,
+            socket_path(dir).to_str().unwrap(),
+            log_path(dir).to_str().unwrap(),
+            LogPolicy::default(),
+        )
+        .unwrap();
+        (guest, proxy)
+    }
+
+    fn handshake(replay: Replay) -> Vec<u8> {
+        Handshake {
+            replay,
+            ..Handshake::default()
+        }
+        .line()
+    }
+
+    fn socket_path(dir: &TempDir) -> PathBuf {
+        dir.path().join("console.sock")
+    }
+
+    fn log_path(dir: &TempDir) -> PathBuf {
+        dir.path().join("console.log")
+    }
+
+    async fn read_exact(client: &mut UnixStream, len: usize) -> Vec<u8> {
+        let mut data = vec![0u8; len];
+        tokio::time::timeout(QUICK, client.read_exact(&mut data))
+            .await
+            .expect("console output did not arrive")
+            .unwrap();
+        data
+    }
+
+    async fn wait_for_log(path: &Path, len: usize) {
+        tokio::time::timeout(QUICK, async {
+            while std::fs::metadata(path).map_or(0, |m| m.len()) < len as u64 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("output never reached the log");
+    }
+
+    #[tokio::test]
+    async fn relays_output_to_every_client_and_input_to_guest() {
+        let dir = TempDir::new().unwrap();
+        let (guest, proxy) = spawn_proxy(&dir);
+
+        let mut first = UnixStream::connect(socket_path(&dir)).await.unwrap();
+        let mut second = UnixStream::connect(socket_path(&dir)).await.unwrap();
+
+        guest.write_all(b"login: ").await;
+        assert_eq!(read_exact(&mut first, 7).await, b"login: ");
+        assert_eq!(read_exact(&mut second, 7).await, b"login: ");
+
+        second.write_all(b"root\r").await.unwrap();
+        let typed = tokio::time::timeout(QUICK, guest.read_exact(5))
+            .await
+            .expect("input did not reach the guest");
+        assert_eq!(typed, b"root\r");
+
+        wait_for_log(&log_path(&dir), 7).await;
+        assert_eq!(std::fs::read(log_path(&dir)).unwrap(), b"login: ");
+
+        proxy.stop().await;
+        assert!(UnixStream::connect(socket_path(&dir)).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn late_client_replays_log_after_guest_is_gone() {
+        let dir = TempDir::new().unwrap();
+        let (guest, proxy) = spawn_proxy(&dir);
+
+        guest.write_all(b"Kernel panic\r\n").await;
+        wait_for_log(&log_path(&dir), 14).await;
+        // The hypervisor exits and its end of the PTY closes.
+        drop(guest);
+
+        let mut late = UnixStream::connect(socket_path(&dir)).await.unwrap();
+        assert_eq!(read_exact(&mut late, 14).await, b"Kernel panic\r\n");
+
+        proxy.stop().await;
+    }
+
+    #[test]
+    fn replay_modes_round_trip() {
+        for replay in [Replay::None, Replay::Tail(20), Replay::All] {
+            assert_eq!(replay.to_string().parse::<Replay>(), Ok(replay));
+        }
+        assert!("tail:".parse::<Replay>().is_err());
+        assert!("everything".parse::<Replay>().is_err());
+        assert_eq!(handshake(Replay::Tail(3)), b"GLIDEX-CONSOLE replay=tail:3\n");
+    }
+
+    #[test]
+    fn tail_replays_last_lines_across_chunks() {
+        let history: Vec<Arc<[u8]>> =
+            vec![Arc::from(&b"one\ntw"[..]), Arc::from(&b"o\nthree\n"[..])];
+        assert_eq!(select_replay(Replay::Tail(2), &history), b"two\nthree\n");
+        assert_eq!(select_replay(Replay::Tail(5), &history), b"one\ntwo\nthree\n");
+        assert_eq!(select_replay(Replay::Tail(0), &history), b"");
+        assert_eq!(select_replay(Replay::None, &history), b"");
+
+        // A prompt without a newline yet is the last line.
+        let history: Vec<Arc<[u8]>> = vec![Arc::from(&b"motd\nlogin: "[..])];
+        assert_eq!(select_replay(Replay::Tail(1), &history), b"login: ");
+    }
+
+    #[tokio::test]
+    async fn handshake_selects_replay() {
+        let dir = TempDir::new().unwrap();
+        let (guest, proxy) = spawn_proxy(&dir);
+
+        guest.write_all(b"one\r\ntwo\r\nthree\r\n").await;
+        wait_for_log(&log_path(&dir), 17).await;
+
+        let mut tail = UnixStream::connect(socket_path(&dir)).await.unwrap();
+        tail.write_all(&handshake(Replay::Tail(2))).await.unwrap();
+        let mut live = UnixStream::connect(socket_path(&dir)).await.unwrap();
+        // Keystrokes sent along with the handshake reach the guest.
+        let mut opening = handshake(Replay::None);
+        opening.extend_from_slice(b"ls\r");
+        live.write_all(&opening).await.unwrap();
+
+        assert_eq!(read_exact(&mut tail, 12).await, b"two\r\nthree\r\n");
+        let typed = tokio::time::timeout(QUICK, guest.read_exact(3))
+            .await
+            .expect("input did not reach the guest");
+        assert_eq!(typed, b"ls\r");
+
+        guest.write_all(b"four\r\n").await;
+        assert_eq!(read_exact(&mut live, 6).await, b"four\r\n");
+        assert_eq!(read_exact(&mut tail, 6).await, b"four\r\n");
+
+        proxy.stop().await;
+    }
+
+    /// Read frames from a framed client until `until` returns true for one.
+    async fn read_frames(client: &mut UnixStream, until: impl Fn(&Frame) -> bool) -> Vec<Frame> {
+        let mut decoder = FrameDecoder::default();
+        let mut frames = Vec::new();
+        let mut buf = [0u8; CHUNK_SIZE];
+        tokio::time::timeout(QUICK, async {
+            loop {
+                while let Some(frame) = decoder.next_frame().unwrap() {
+                    let done = until(&frame);
+                    frames.push(frame);
+                    if done {
+                        return;
+                    }
+                }
+                let n = client.read(&mut buf).await.unwrap();
+                assert!(n > 0, "console closed");
+                decoder.push(&buf[..n]);
+            }
+        })
+        .await
+        .expect("frame did not arrive");
+        frames
+    }
+
+    fn is_status(event: ConsoleEvent) -> impl Fn(&Frame) -> bool {
+        move |frame| *frame == Control::Status { event }.frame()
+    }
+
+    #[tokio::test]
+    async fn framed_client_gets_status_and_controls_the_terminal() {
+        let dir = TempDir::new().unwrap();
+        let (guest, proxy) = spawn_proxy(&dir);
+        guest.write_all(b"login: ").await;
+        wait_for_log(&log_path(&dir), 7).await;
+
+        let mut client = UnixStream::connect(socket_path(&dir)).await.unwrap();
+        let mut opening = Handshake {
+            replay: Replay::All,
+            framed: true,
+            ..Handshake::default()
+        }
+        .line();
+        opening.extend(Control::Resize { cols: 132, rows: 43 }.frame().encode());
+        opening.extend(Frame::Data(b"root\r".to_vec()).encode());
+        client.write_all(&opening).await.unwrap();
+
+        let frames = read_frames(&mut client, is_status(ConsoleEvent::ReplayFinished)).await;
+        assert_eq!(frames[0], Frame::Data(b"login: ".to_vec()));
+        let typed = tokio::time::timeout(QUICK, guest.read_exact(5))
+            .await
+            .expect("input did not reach the guest");
+        assert_eq!(typed, b"root\r");
+        // The resize was queued ahead of the keystrokes, so it is applied.
+        let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+        assert_eq!(
+            unsafe { libc::ioctl(guest.0.as_raw_fd(), libc::TIOCGWINSZ, &mut size) },
+            0
+        );
+        assert_eq!((size.ws_col, size.ws_row), (132, 43));
+
+        client
+            .write_all(&Frame::Control(b"{\"type\":\"dance\"}".to_vec()).encode())
+            .await
+            .unwrap();
+        let frames = read_frames(&mut client, |frame| matches!(frame, Frame::Control(_))).await;
+        let Some(Frame::Control(json)) = frames.last() else {
+            unreachable!();
+        };
+        assert!(matches!(
+            serde_json::from_slice(json).unwrap(),
+            Control::Error { .. }
+        ));
+
+        drop(guest);
+        read_frames(&mut client, is_status(ConsoleEvent::GuestExited)).await;
+
+        proxy.stop().await;
+    }
+
+    /// Read up to the next notice and return its message.
+    async fn read_notice(client: &mut UnixStream) -> String {
+        let is_notice = |frame: &Frame| match frame {
+            Frame::Control(json) => matches!(
+                serde_json::from_slice(json),
+                Ok(Control::Notice { .. })
+            ),
+            Frame::Data(_) => false,
+        };
+        let frames = read_frames(client, is_notice).await;
+        let Some(Frame::Control(json)) = frames.last() else {
+            unreachable!();
+        };
+        match serde_json::from_slice(json).unwrap() {
+            Control::Notice { message } => message,
+            _ => unreachable!(),
+        }
+    }
+
+    async fn attach(dir: &TempDir, client: &str, mode: ConsoleMode) -> UnixStream {
+        let mut stream = UnixStream::connect(socket_path(dir)).await.unwrap();
+        let opening = Handshake {
+            replay: Replay::None,
+            framed: true,
+            record: false,
+            client: Some(client.to_string()),
+            mode,
+            query: false,
+        };
+        stream.write_all(&opening.line()).await.unwrap();
+        stream
+    }
+
+    #[tokio::test]
+    async fn exclusive_session_locks_out_other_writers() {
+        let dir = TempDir::new().unwrap();
+        let (guest, proxy) = spawn_proxy(&dir);
+
+        let mut owner = attach(&dir, "owner", ConsoleMode::Exclusive).await;
+        read_frames(&mut owner, is_status(ConsoleEvent::ReplayFinished)).await;
+
+        let mut other = attach(&dir, "other", ConsoleMode::Shared).await;
+        assert!(read_notice(&mut other).await.contains("locked by owner (session 1"));
+        assert!(read_notice(&mut owner).await.contains("other (session 2"));
+        let mut late = attach(&dir, "late", ConsoleMode::Exclusive).await;
+        assert!(read_notice(&mut late).await.contains("attached read-only"));
+        read_notice(&mut owner).await;
+
+        let listed = attached(&socket_path(&dir)).await.unwrap();
+        assert_eq!(listed.write_lock, Some(1));
+        let modes: Vec<_> = listed.sessions.iter().map(|s| s.mode).collect();
+        assert_eq!(
+            modes,
+            [ConsoleMode::Exclusive, ConsoleMode::Shared, ConsoleMode::ReadOnly]
+        );
+        assert!(listed.sessions[0].pid.is_some());
+
+        // Only the lock holder's keystrokes reach the guest.
+        other.write_all(&Frame::encode_data(b"x")).await.unwrap();
+        assert!(read_notice(&mut other).await.contains("input is ignored"));
+        owner.write_all(&Frame::encode_data(b"y")).await.unwrap();
+        assert_eq!(guest.read_exact(1).await, b"y");
+
+        // Detaching releases the lock.
+        drop(owner);
+        assert!(read_notice(&mut other).await.contains("console unlocked"));
+        other.write_all(&Frame::encode_data(b"z")).await.unwrap();
+        assert_eq!(guest.read_exact(1).await, b"z");
+        // Whoever attached read-only for want of the lock stays so.
+        assert!(read_notice(&mut late).await.contains("console unlocked"));
+        late.write_all(&Frame::encode_data(b"w")).await.unwrap();
+        assert!(read_notice(&mut late).await.contains("Read-only"));
+
+        let listed = attached(&socket_path(&dir)).await.unwrap();
+        assert_eq!(listed.write_lock, None);
+        assert_eq!(listed.sessions.len(), 2);
+        proxy.stop().await;
+    }
+
+    #[tokio::test]
+    async fn sessions_are_recorded_in_both_directions() {
+        let dir = TempDir::new().unwrap();
+        let (guest, proxy) = spawn_proxy(&dir);
+        let recordings = Recordings::new(&log_path(&dir), &LogPolicy::default());
+        guest.write_all(b"login: ").await;
+        wait_for_log(&log_path(&dir), 7).await;
+
+        let mut client = UnixStream::connect(socket_path(&dir)).await.unwrap();
+        let mut opening = Handshake {
+            framed: true,
+            client: Some("test".to_string()),
+            ..Handshake::default()
+        }
+        .line();
+        opening.extend(Control::Resize { cols: 100, rows: 30 }.frame().encode());
+        opening.extend(Frame::Data(b"root\r".to_vec()).encode());
+        client.write_all(&opening).await.unwrap();
+        read_frames(&mut client, is_status(ConsoleEvent::ReplayFinished)).await;
+        guest.read_exact(5).await;
+        guest.write_all(b"Password: ").await;
+        read_frames(&mut client, |frame| *frame == Frame::Data(b"Password: ".to_vec())).await;
+        drop(guest);
+        read_frames(&mut client, is_status(ConsoleEvent::GuestExited)).await;
+
+        // Not recorded: clients that opt out, such as expect.
+        let pattern = Regex::new("never").unwrap();
+        let _ = expect(&socket_path(&dir), b"", &pattern, QUICK, Replay::None).await;
+        drop(client);
+        proxy.stop().await;
+
+        let sessions = recordings.list().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].client.as_deref(), Some("test"));
+        let cast = String::from_utf8(recordings.read(&sessions[0].id).unwrap()).unwrap();
+        let events: Vec<(String, String)> = cast
+            .lines()
+            .skip(1)
+            .map(|line| {
+                let (_, code, data): (f64, String, String) = serde_json::from_str(line).unwrap();
+                (code, data)
+            })
+            .collect();
+        let expected = [
+            ("r", "100x30"),
+            ("i", "root\r"),
+            ("o", "login: "),
+            ("o", "Password: "),
+            ("m", "guest exited"),
+        ];
+        assert_eq!(
+            events,
+            expected.map(|(code, data)| (code.to_string(), data.to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn expect_sends_input_and_waits_for_pattern() {
+        let dir = TempDir::new().unwrap();
+        let (guest, proxy) = spawn_proxy(&dir);
+        guest.write_all(b"password: stale\r\n").await;
+        wait_for_log(&log_path(&dir), 17).await;
+
+        let socket = socket_path(&dir);
+        let pattern = Regex::new(r"pass\w+: ").unwrap();
+        let waiting = expect(&socket, b"root\r", &pattern, QUICK, Replay::None);
+        let answering = async {
+            assert_eq!(guest.read_exact(5).await, b"root\r");
+            // The match spans two chunks of output.
+            guest.write_all(b"root\r\npass").await;
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            guest.write_all(b"word: ").await;
+        };
+        let (expected, ()) = tokio::join!(waiting, answering);
+        let expected = expected.unwrap();
+        assert_eq!(expected.matched, b"password: ");
+        assert_eq!(expected.before, b"root\r\n");
+
+        let pattern = Regex::new("login:").unwrap();
+        let waited = expect(&socket, b"", &pattern, Duration::from_millis(100), Replay::None).await;
+        assert!(matches!(waited, Err(ExpectError::TimedOut { output }) if output.is_empty()));
+
+        // With a replay, earlier output counts.
+        let pattern = Regex::new("stale").unwrap();
+        let expected = expect(&socket, b"", &pattern, QUICK, Replay::All).await.unwrap();
+        assert_eq!(expected.before, b"password: ");
+
+        let exit_socket = socket.clone();
+        let exited = tokio::spawn(async move { wait_for_exit(&exit_socket).await });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(guest);
+        let waited = expect(&socket, b"", &pattern, QUICK, Replay::None).await;
+        assert!(matches!(waited, Err(ExpectError::GuestExited { .. })));
+        tokio::time::timeout(QUICK, exited).await.unwrap().unwrap().unwrap();
+
+        proxy.stop().await;
+    }
+
+    #[tokio::test]
+    async fn scrollback_outlives_the_proxy() {
+        let dir = TempDir::new().unwrap();
+        let (guest, proxy) = spawn_proxy(&dir);
+        guest.write_all(b"before restart\r\n").await;
+        wait_for_log(&log_path(&dir), 16).await;
+        proxy.stop().await;
+
+        // A restarted control plane seeds the scrollback from the log.
+        let (_guest, proxy) = spawn_proxy(&dir);
+        let mut client = UnixStream::connect(socket_path(&dir)).await.unwrap();
+        client.write_all(&handshake(Replay::All)).await.unwrap();
+        assert_eq!(read_exact(&mut client, 16).await, b"before restart\r\n");
+
+        proxy.stop().await;
+    }
+
+    #[tokio::test]
+    async fn stalled_client_is_evicted_without_holding_up_others() {
+        let dir = TempDir::new().unwrap();
+        let (guest, proxy) = spawn_proxy(&dir);
+
+        let mut reader = UnixStream::connect(socket_path(&dir)).await.unwrap();
+        let mut stalled = UnixStream::connect(socket_path(&dir)).await.unwrap();
+        // Output from before the proxy accepts them comes from the scrollback.
+        for client in [&mut reader, &mut stalled] {
+            client.write_all(&handshake(Replay::All)).await.unwrap();
+        }
+
+        // Far more than fits in the stalled client's queue and socket buffer.
+        const TOTAL: usize = 4 * 1024 * 1024;
+        let reading = tokio::spawn(async move {
+            let mut data = vec![0u8; TOTAL];
+            reader.read_exact(&mut data).await.map(|_| data)
+        });
+        let output: Vec<u8> = (0..TOTAL).map(|i| b'a' + (i % 26) as u8).collect();
+        tokio::time::timeout(Duration::from_secs(30), guest.write_all(&output))
+            .await
+            .expect("the stalled client held up the guest");
+
+        let received = tokio::time::timeout(Duration::from_secs(30), reading)
+            .await
+            .expect("the stalled client held up the other one")
+            .unwrap()
+            .unwrap();
+        assert!(received == output, "reading client lost output");
+
+        // Whatever was queued for the stalled client drains, then it is
+        // disconnected.
+        let mut leftover = Vec::new();
+        tokio::time::timeout(Duration::from_secs(30), stalled.read_to_end(&mut leftover))
+            .await
+            .expect("the stalled client was never disconnected")
+            .unwrap();
+        assert!(leftover.len() < TOTAL);
+
+        proxy.stop().await;
+    }
+}
@@ -1,15 +1,26 @@
-use super::{Hypervisor, HypervisorError, HypervisorProcess, HypervisorType};
-use crate::models::VmConfig;
+use super::{
+    stop_console, Hypervisor, HypervisorChild, HypervisorError, HypervisorProcess,
+    HypervisorType, ProcessId, MIGRATION_TIMEOUT,
+};
+use crate::console::{ConsoleProxy, Serial};
+use crate::models::{LogPolicy, VmConfig, VmExit};
+use async_trait::async_trait;
 use serde::Serialize;
-use std::fs::{File, OpenOptions};
-use std::io::{Read, Write};
-use std::os::fd::{AsRawFd, FromRawFd};
-use std::os::unix::net::{UnixListener, UnixStream};
-use std::process::{Child, Command, Stdio};
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::process::Stdio;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::sync::Mutex;
 use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+use tokio::process::Command;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+/// How long most API calls may take. Migration calls block until the
+/// transfer is done and get `MIGRATION_TIMEOUT` instead.
+const API_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Cloud-Hypervisor API request structures
 #[derive(Debug, Serialize)]
@@ -95,19 +106,17 @@ impl CloudHypervisorClient {
     }
 
     /// Parsed HTTP response with status code and optional body.
-    fn send_request(
+    async fn send_request(
         &self,
         method: &str,
         path: &str,
         body: Option<&str>,
+        timeout: Duration,
     ) -> Result<(u16, Option<String>), HypervisorError> {
         let mut stream = UnixStream::connect(&self.socket_path)
+            .await
             .map_err(|e| HypervisorError::SocketConnection(e.to_string()))?;
 
-        stream
-            .set_read_timeout(Some(Duration::from_secs(30)))
-            .map_err(HypervisorError::ProcessStart)?;
-
         // Build request matching the official cloud-hypervisor api_client format:
         //   {METHOD} /api/v1/{path} HTTP/1.1\r\nHost: localhost\r\nAccept: */*\r\n
         // With body: add Content-Type and Content-Length headers
@@ -125,16 +134,19 @@ impl CloudHypervisorClient {
 
         stream
             .write_all(request.as_bytes())
+            .await
             .map_err(HypervisorError::ProcessStart)?;
-        stream.flush().map_err(HypervisorError::ProcessStart)?;
 
-        // Read the full response in chunks (matching official client approach)
+        // Read the full response in chunks (matching official client
+        // approach), giving up on whatever is missing after `timeout`
+        let deadline = Instant::now() + timeout;
         let mut raw = Vec::new();
         let mut buf = [0u8; 256];
         loop {
-            match stream.read(&mut buf) {
-                Ok(0) => break,
-                Ok(n) => {
+            match tokio::time::timeout_at(deadline, stream.read(&mut buf)).await {
+                Err(_) => break,
+                Ok(Ok(0)) => break,
+                Ok(Ok(n)) => {
                     raw.extend_from_slice(&buf[..n]);
                     // Check if we have a complete response (headers + full body)
                     if let Some(header_end) = find_header_end(&raw) {
@@ -144,9 +156,7 @@ impl CloudHypervisorClient {
                         }
                     }
                 }
-                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => break,
-                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => break,
-                Err(e) => return Err(HypervisorError::ProcessStart(e)),
+                Ok(Err(e)) => return Err(HypervisorError::ProcessStart(e)),
             }
         }
 
@@ -172,13 +182,25 @@ impl CloudHypervisorClient {
     }
 
     /// Check that the response status indicates success (2xx).
-    fn expect_success(
+    async fn expect_success(
         &self,
         method: &str,
         path: &str,
         body: Option<&str>,
     ) -> Result<Option<String>, HypervisorError> {
-        let (status, response_body) = self.send_request(method, path, body)?;
+        self.expect_success_within(method, path, body, API_TIMEOUT)
+            .await
+    }
+
+    /// `expect_success` for calls that block longer than usual.
+    async fn expect_success_within(
+        &self,
+        method: &str,
+        path: &str,
+        body: Option<&str>,
+        timeout: Duration,
+    ) -> Result<Option<String>, HypervisorError> {
+        let (status, response_body) = self.send_request(method, path, body, timeout).await?;
         if (200..300).contains(&status) {
             Ok(response_body)
         } else {
@@ -192,7 +214,7 @@ impl CloudHypervisorClient {
         }
     }
 
-    pub fn create_vm(&self, config: &VmConfig) -> Result<(), HypervisorError> {
+    pub async fn create_vm(&self, config: &VmConfig) -> Result<(), HypervisorError> {
         let vm_config = VmCreateConfig {
             cpus: CpuConfig {
                 boot_vcpus: config.vcpu_count,
@@ -231,14 +253,15 @@ impl CloudHypervisorClient {
         let body = serde_json::to_string(&vm_config)
             .map_err(|e| HypervisorError::ApiRequest(e.to_string()))?;
 
-        self.expect_success("PUT", "/vm.create", Some(&body))?;
+        self.expect_success("PUT", "/vm.create", Some(&body)).await?;
         Ok(())
     }
 
     /// Extract console PTY path from vm.info response
-    pub fn get_console_pty_path(&self) -> Result<Option<String>, HypervisorError> {
+    pub async fn get_console_pty_path(&self) -> Result<Option<String>, HypervisorError> {
         let body = self
-            .expect_success("GET", "/vm.info", None)?
+            .expect_success("GET", "/vm.info", None)
+            .await?
             .ok_or_else(|| {
                 HypervisorError::ApiRequest("vm.info returned no body".to_string())
             })?;
@@ -258,43 +281,100 @@ impl CloudHypervisorClient {
         Ok(None)
     }
 
-    pub fn boot_vm(&self) -> Result<(), HypervisorError> {
-        self.expect_success("PUT", "/vm.boot", None)?;
+    pub async fn boot_vm(&self) -> Result<(), HypervisorError> {
+        self.expect_success("PUT", "/vm.boot", None).await?;
+        Ok(())
+    }
+
+    pub async fn pause_vm(&self) -> Result<(), HypervisorError> {
+        self.expect_success("PUT", "/vm.pause", None).await?;
+        Ok(())
+    }
+
+    pub async fn resume_vm(&self) -> Result<(), HypervisorError> {
+        self.expect_success("PUT", "/vm.resume", None).await?;
         Ok(())
     }
 
-    pub fn pause_vm(&self) -> Result<(), HypervisorError> {
-        self.expect_success("PUT", "/vm.pause", None)?;
+    pub async fn shutdown_vm(&self) -> Result<(), HypervisorError> {
+        self.expect_success("PUT", "/vm.shutdown", None).await?;
+        Ok(())
+    }
+
+    /// Reset the guest in place. CH keeps the console PTY across the
+    /// reboot, so the console proxy carries on.
+    pub async fn reboot_vm(&self) -> Result<(), HypervisorError> {
+        self.expect_success("PUT", "/vm.reboot", None).await?;
+        Ok(())
+    }
+
+    pub async fn power_button(&self) -> Result<(), HypervisorError> {
+        self.expect_success("PUT", "/vm.power-button", None).await?;
+        Ok(())
+    }
+
+    /// Write the config, memory and device state of a paused VM into `dir`.
+    pub async fn snapshot_vm(&self, dir: &Path) -> Result<(), HypervisorError> {
+        let body = serde_json::json!({
+            "destination_url": format!("file://{}", dir.display()),
+        })
+        .to_string();
+        self.expect_success("PUT", "/vm.snapshot", Some(&body)).await?;
+        Ok(())
+    }
+
+    /// Recreate the VM from a snapshot in `dir`. Only valid before
+    /// `vm.create`; the restored VM is paused.
+    pub async fn restore_vm(&self, dir: &Path) -> Result<(), HypervisorError> {
+        let body = serde_json::json!({
+            "source_url": format!("file://{}", dir.display()),
+        })
+        .to_string();
+        self.expect_success("PUT", "/vm.restore", Some(&body)).await?;
         Ok(())
     }
 
-    pub fn resume_vm(&self) -> Result<(), HypervisorError> {
-        self.expect_success("PUT", "/vm.resume", None)?;
+    /// Live-migrate the VM to a CH receiving on `destination_url`. Blocks
+    /// until the transfer is complete; the local VM is gone afterwards.
+    pub async fn send_migration(&self, destination_url: &str) -> Result<(), HypervisorError> {
+        let body = serde_json::json!({
+            "destination_url": destination_url,
+        })
+        .to_string();
+        self.expect_success_within("PUT", "/vm.send-migration", Some(&body), MIGRATION_TIMEOUT)
+            .await?;
         Ok(())
     }
 
-    pub fn shutdown_vm(&self) -> Result<(), HypervisorError> {
-        self.expect_success("PUT", "/vm.shutdown", None)?;
+    /// Listen on `receiver_url` for a migrating VM. Only valid before
+    /// `vm.create`. Blocks until the VM has been received and resumed.
+    pub async fn receive_migration(&self, receiver_url: &str) -> Result<(), HypervisorError> {
+        let body = serde_json::json!({
+            "receiver_url": receiver_url,
+        })
+        .to_string();
+        self.expect_success_within("PUT", "/vm.receive-migration", Some(&body), MIGRATION_TIMEOUT)
+            .await?;
         Ok(())
     }
 
-    pub fn add_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+    pub async fn add_device(&self, device_path: &str) -> Result<(), HypervisorError> {
         let body = serde_json::json!({
             "path": device_path,
             "iommu": false,
             "id": vfio_device_id(device_path),
         })
         .to_string();
-        self.expect_success("PUT", "/vm.add-device", Some(&body))?;
+        self.expect_success("PUT", "/vm.add-device", Some(&body)).await?;
         Ok(())
     }
 
-    pub fn remove_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+    pub async fn remove_device(&self, device_path: &str) -> Result<(), HypervisorError> {
         let body = serde_json::json!({
             "id": vfio_device_id(device_path),
         })
         .to_string();
-        self.expect_success("PUT", "/vm.remove-device", Some(&body))?;
+        self.expect_success("PUT", "/vm.remove-device", Some(&body)).await?;
         Ok(())
     }
 }
@@ -303,67 +383,61 @@ impl CloudHypervisorClient {
 /// e.g. "/sys/bus/pci/devices/0000:41:00.0" -> "_vfio_0000_41_00_0"
 fn vfio_device_id(path: &str) -> String {
     let bdf = path.rsplit('/').next().unwrap_or(path);
-    format!("_vfio_{}", bdf.replace(':', "_").replace('.', "_"))
+    format!("_vfio_{}", bdf.replace([':', '.'], "_"))
 }
 
 /// Manages a running Cloud-Hypervisor process
 pub struct CloudHypervisorProcessHandle {
-    child: Mutex<Option<Child>>,
+    child: Mutex<Option<HypervisorChild>>,
     socket_path: String,
     console_socket_path: String,
     log_path: String,
-    running: Arc<AtomicBool>,
-    console_thread: Mutex<Option<thread::JoinHandle<()>>>,
+    log_policy: LogPolicy,
+    running: AtomicBool,
+    console: Mutex<Option<ConsoleProxy>>,
 }
 
 impl CloudHypervisorProcessHandle {
-    pub fn spawn(
+    pub async fn spawn(
         socket_path: &str,
         console_socket_path: &str,
         log_path: &str,
+        log_policy: LogPolicy,
     ) -> Result<Self, HypervisorError> {
         // Remove existing sockets if present
         let _ = std::fs::remove_file(socket_path);
         let _ = std::fs::remove_file(console_socket_path);
 
-        // Create/truncate log file
-        let _log_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(log_path)?;
-
-        // Spawn cloud-hypervisor with API socket
+        // Spawn cloud-hypervisor with API socket, in its own process group
+        // so it outlives us
         let child = Command::new("cloud-hypervisor")
             .arg("--api-socket")
             .arg(socket_path)
             .stdin(Stdio::null())
             .stdout(Stdio::null())
             .stderr(Stdio::null())
+            .process_group(0)
             .spawn()?;
 
-        let running = Arc::new(AtomicBool::new(true));
-
         // Wait for API socket to be available
         for _ in 0..50 {
             if std::path::Path::new(socket_path).exists() {
                 return Ok(Self {
-                    child: Mutex::new(Some(child)),
+                    child: Mutex::new(Some(HypervisorChild::Spawned(child))),
                     socket_path: socket_path.to_string(),
                     console_socket_path: console_socket_path.to_string(),
                     log_path: log_path.to_string(),
-                    running,
-                    console_thread: Mutex::new(None),
+                    log_policy,
+                    running: AtomicBool::new(true),
+                    console: Mutex::new(None),
                 });
             }
-            std::thread::sleep(Duration::from_millis(100));
+            tokio::time::sleep(Duration::from_millis(100)).await;
         }
 
         // Cleanup on timeout
-        running.store(false, Ordering::SeqCst);
         let mut child = child;
-        let _ = child.kill();
-        let _ = child.wait();
+        let _ = child.kill().await;
         let _ = std::fs::remove_file(socket_path);
 
         Err(HypervisorError::Timeout(
@@ -371,13 +445,29 @@ impl CloudHypervisorProcessHandle {
         ))
     }
 
-    /// Start the console proxy thread that bridges the PTY to a Unix socket
-    pub fn start_console_proxy(&self, pty_path: &str) -> Result<(), HypervisorError> {
-        // Remove existing console socket if present
-        let _ = std::fs::remove_file(&self.console_socket_path);
+    /// Handle for a cloud-hypervisor an earlier control plane spawned.
+    pub fn adopt(
+        id: ProcessId,
+        socket_path: &str,
+        console_socket_path: &str,
+        log_path: &str,
+        log_policy: LogPolicy,
+    ) -> Self {
+        Self {
+            child: Mutex::new(Some(HypervisorChild::Adopted(id))),
+            socket_path: socket_path.to_string(),
+            console_socket_path: console_socket_path.to_string(),
+            log_path: log_path.to_string(),
+            log_policy,
+            running: AtomicBool::new(true),
+            console: Mutex::new(None),
+        }
+    }
 
+    /// Start the console proxy that bridges the PTY to a Unix socket
+    pub fn start_console_proxy(&self, pty_path: &str) -> Result<(), HypervisorError> {
         // Open the PTY
-        let pty_fd = OpenOptions::new()
+        let pty = OpenOptions::new()
             .read(true)
             .write(true)
             .open(pty_path)
@@ -385,143 +475,56 @@ impl CloudHypervisorProcessHandle {
                 HypervisorError::SocketConnection(format!("Failed to open PTY {}: {}", pty_path, e))
             })?;
 
-        // Create Unix socket for console connections
-        let console_listener = UnixListener::bind(&self.console_socket_path).map_err(|e| {
-            HypervisorError::SocketConnection(format!("Failed to create console socket: {}", e))
-        })?;
-        console_listener.set_nonblocking(true).map_err(|e| {
-            HypervisorError::SocketConnection(format!("Failed to set non-blocking: {}", e))
-        })?;
-
-        // Open log file for writing
-        let log_file = OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.log_path)?;
-
-        let running_clone = self.running.clone();
-        let log_path_clone = self.log_path.clone();
-
-        // Spawn thread to handle console I/O
-        let console_thread = thread::spawn(move || {
-            Self::console_proxy_loop(pty_fd, console_listener, log_file, &log_path_clone, running_clone);
-        });
-
-        *self.console_thread.lock().unwrap() = Some(console_thread);
+        let console = Serial::pty(pty)
+            .and_then(|serial| {
+                ConsoleProxy::spawn(
+                    serial,
+                    &self.console_socket_path,
+                    &self.log_path,
+                    self.log_policy,
+                )
+            })
+            .map_err(|e| {
+                HypervisorError::SocketConnection(format!("Failed to start console proxy: {}", e))
+            })?;
+        *self.console.lock().unwrap() = Some(console);
         Ok(())
     }
-
-    fn console_proxy_loop(
-        pty_file: File,
-        listener: UnixListener,
-        mut log_file: File,
-        log_path: &str,
-        running: Arc<AtomicBool>,
-    ) {
-        let pty_raw = pty_file.as_raw_fd();
-        let mut clients: Vec<UnixStream> = Vec::new();
-        let mut buf = [0u8; 4096];
-        // PTY reads EOF once cloud-hypervisor's virtio console closes. We
-        // don't tear down the listener in that case — clients should still
-        // be able to connect and replay the captured log to diagnose why
-        // the guest died.
-        let mut pty_alive = true;
-
-        // Set PTY to non-blocking
-        unsafe {
-            let flags = libc::fcntl(pty_raw, libc::F_GETFL);
-            libc::fcntl(pty_raw, libc::F_SETFL, flags | libc::O_NONBLOCK);
-        }
-
-        while running.load(Ordering::SeqCst) {
-            // Accept new client connections
-            if let Ok((stream, _)) = listener.accept() {
-                stream.set_nonblocking(true).ok();
-                // Send existing log content to new client
-                if let Ok(mut existing_log) = File::open(log_path) {
-                    let mut log_content = Vec::new();
-                    if existing_log.read_to_end(&mut log_content).is_ok() && !log_content.is_empty()
-                    {
-                        let mut s = stream.try_clone().unwrap();
-                        let _ = s.write_all(&log_content);
-                    }
-                }
-                clients.push(stream);
-            }
-
-            if pty_alive {
-                // Read from PTY and broadcast to clients + log file
-                let mut pty_reader = unsafe { File::from_raw_fd(libc::dup(pty_raw)) };
-                match pty_reader.read(&mut buf) {
-                    Ok(0) => pty_alive = false,
-                    Ok(n) => {
-                        let data = &buf[..n];
-
-                        let _ = log_file.write_all(data);
-                        let _ = log_file.flush();
-
-                        clients.retain_mut(|client| client.write_all(data).is_ok());
-                    }
-                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-                    Err(_) => pty_alive = false,
-                }
-
-                // Read from clients and write to PTY
-                for client in &mut clients {
-                    match client.read(&mut buf) {
-                        Ok(0) => {}
-                        Ok(n) => {
-                            let mut pty_writer =
-                                unsafe { File::from_raw_fd(libc::dup(pty_raw)) };
-                            let _ = pty_writer.write_all(&buf[..n]);
-                            let _ = pty_writer.flush();
-                        }
-                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-                        Err(_) => {}
-                    }
-                }
-            }
-
-            thread::sleep(Duration::from_millis(10));
-        }
-    }
 }
 
 /// Cloud-Hypervisor instance that implements HypervisorProcess
 pub struct CloudHypervisorInstance {
     process: CloudHypervisorProcessHandle,
     client: CloudHypervisorClient,
+    /// The long-running `vm.receive-migration` call of an incoming migration.
+    incoming: Mutex<Option<JoinHandle<Result<(), HypervisorError>>>>,
 }
 
 impl CloudHypervisorInstance {
     pub fn new(process: CloudHypervisorProcessHandle) -> Self {
         let client = CloudHypervisorClient::new(&process.socket_path);
-        Self { process, client }
-    }
-}
-
-impl HypervisorProcess for CloudHypervisorInstance {
-    fn configure(&self, config: &VmConfig) -> Result<(), HypervisorError> {
-        self.client.create_vm(config)?;
-        Ok(())
+        Self {
+            process,
+            client,
+            incoming: Mutex::new(None),
+        }
     }
 
-    fn start(&self) -> Result<(), HypervisorError> {
-        self.client.boot_vm()?;
-
-        // The console PTY is allocated during vm.boot (device creation),
-        // not during vm.create. Poll for the PTY path to become available.
+    /// Start the console proxy once CH has allocated the console PTY,
+    /// which happens when devices are created (vm.boot or vm.restore),
+    /// not during vm.create. Polls for the PTY path to become available.
+    async fn attach_console(&self) -> Result<(), HypervisorError> {
         for _ in 0..30 {
-            match self.client.get_console_pty_path() {
+            match self.client.get_console_pty_path().await {
                 Ok(Some(pty_path)) => {
                     self.process.start_console_proxy(&pty_path)?;
                     return Ok(());
                 }
                 Ok(None) => {
-                    std::thread::sleep(Duration::from_millis(100));
+                    tokio::time::sleep(Duration::from_millis(100)).await;
                 }
                 Err(_) => {
-                    std::thread::sleep(Duration::from_millis(100));
+                    tokio::time::sleep(Duration::from_millis(100)).await;
                 }
             }
         }
@@ -530,47 +533,147 @@ impl HypervisorProcess for CloudHypervisorInstance {
             "Console PTY path not available after boot".to_string(),
         ))
     }
+}
+
+#[async_trait]
+impl HypervisorProcess for CloudHypervisorInstance {
+    async fn configure(&self, config: &VmConfig) -> Result<(), HypervisorError> {
+        self.client.create_vm(config).await?;
+        Ok(())
+    }
+
+    async fn start(&self) -> Result<(), HypervisorError> {
+        self.client.boot_vm().await?;
+        self.attach_console().await
+    }
 
-    fn pause(&self) -> Result<(), HypervisorError> {
-        self.client.pause_vm()
+    async fn pause(&self) -> Result<(), HypervisorError> {
+        self.client.pause_vm().await
     }
 
-    fn resume(&self) -> Result<(), HypervisorError> {
-        self.client.resume_vm()
+    async fn resume(&self) -> Result<(), HypervisorError> {
+        self.client.resume_vm().await
     }
 
-    fn kill(&self) -> Result<(), HypervisorError> {
+    async fn kill(&self) -> Result<(), HypervisorError> {
         self.process.running.store(false, Ordering::SeqCst);
 
         // Try graceful shutdown first
-        let _ = self.client.shutdown_vm();
+        let _ = self.client.shutdown_vm().await;
 
         // Then force kill if needed
-        if let Some(mut child) = self.process.child.lock().unwrap().take() {
-            let _ = child.kill();
-            let _ = child.wait();
+        let child = self.process.child.lock().unwrap().take();
+        if let Some(mut child) = child {
+            child.kill().await;
         }
 
-        // Wait for console thread to finish
-        if let Some(handle) = self.process.console_thread.lock().unwrap().take() {
-            let _ = handle.join();
-        }
+        stop_console(&self.process.console).await;
 
         let _ = std::fs::remove_file(&self.process.socket_path);
         let _ = std::fs::remove_file(&self.process.console_socket_path);
         Ok(())
     }
 
-    fn add_device(&self, device_path: &str) -> Result<(), HypervisorError> {
-        self.client.add_device(device_path)
+    async fn power_button(&self) -> Result<(), HypervisorError> {
+        self.client.power_button().await
+    }
+
+    // No `reboot`: CH emulates no keyboard, so there is nothing to send a
+    // Ctrl-Alt-Del to, and the ACPI power button only powers off.
+
+    async fn reset(&self) -> Result<(), HypervisorError> {
+        self.client.reboot_vm().await
+    }
+
+    async fn snapshot(&self, dir: &Path) -> Result<(), HypervisorError> {
+        self.client.snapshot_vm(dir).await
+    }
+
+    async fn restore(&self, _config: &VmConfig, dir: &Path) -> Result<(), HypervisorError> {
+        // The snapshot carries its own copy of the VM config.
+        self.client.restore_vm(dir).await?;
+        self.attach_console().await
+    }
+
+    async fn send_migration(&self, uri: &str) -> Result<(), HypervisorError> {
+        self.client.send_migration(uri).await
+    }
+
+    async fn receive_migration(
+        &self,
+        _config: &VmConfig,
+        uri: &str,
+    ) -> Result<(), HypervisorError> {
+        // The VM config travels with the migration stream. The API call
+        // only returns once the VM has arrived, so it runs as a task of
+        // its own; the listening socket appearing means we are ready.
+        let client = CloudHypervisorClient::new(&self.process.socket_path);
+        let url = uri.to_string();
+        let receiver = tokio::spawn(async move { client.receive_migration(&url).await });
+
+        let listen_path = uri.strip_prefix("unix:").unwrap_or(uri).to_string();
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !Path::new(&listen_path).exists() {
+            if receiver.is_finished() {
+                return match receiver.await {
+                    Ok(Err(e)) => Err(e),
+                    _ => Err(HypervisorError::ApiRequest(
+                        "vm.receive-migration returned before a sender connected".to_string(),
+                    )),
+                };
+            }
+            if Instant::now() >= deadline {
+                return Err(HypervisorError::Timeout(format!(
+                    "migration socket {} not available after timeout",
+                    listen_path
+                )));
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+
+        *self.incoming.lock().unwrap() = Some(receiver);
+        Ok(())
+    }
+
+    async fn finish_migration(&self, timeout: Duration) -> Result<(), HypervisorError> {
+        let receiver = self.incoming.lock().unwrap().take().ok_or_else(|| {
+            HypervisorError::InvalidConfig("no incoming migration in progress".to_string())
+        })?;
+
+        tokio::time::timeout(timeout, receiver)
+            .await
+            .map_err(|_| {
+                HypervisorError::Timeout(format!(
+                    "incoming migration did not complete within {:?}",
+                    timeout
+                ))
+            })?
+            .map_err(|_| {
+                HypervisorError::ApiRequest("vm.receive-migration task panicked".to_string())
+            })??;
+
+        // CH resumes the guest itself; its console PTY is new.
+        self.attach_console().await
+    }
+
+    async fn add_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+        self.client.add_device(device_path).await
+    }
+
+    async fn remove_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+        self.client.remove_device(device_path).await
+    }
+
+    fn try_wait(&self) -> Option<VmExit> {
+        self.process.child.lock().unwrap().as_mut()?.try_wait()
     }
 
-    fn remove_device(&self, device_path: &str) -> Result<(), HypervisorError> {
-        self.client.remove_device(device_path)
+    fn process_id(&self) -> Option<ProcessId> {
+        self.process.child.lock().unwrap().as_ref()?.process_id()
     }
 
     fn is_running(&self) -> bool {
-        self.process.running.load(Ordering::SeqCst)
+        self.process.running.load(Ordering::SeqCst) && self.try_wait().is_none()
     }
 
     fn socket_path(&self) -> &str {
@@ -589,24 +692,54 @@ impl HypervisorProcess for CloudHypervisorInstance {
 /// Cloud-Hypervisor backend factory
 pub struct CloudHypervisorBackend;
 
+#[async_trait]
 impl Hypervisor for CloudHypervisorBackend {
-    fn spawn(
+    async fn spawn(
         &self,
+        config: &VmConfig,
         socket_path: &str,
         console_socket_path: &str,
         log_path: &str,
     ) -> Result<Box<dyn HypervisorProcess>, HypervisorError> {
-        let process =
-            CloudHypervisorProcessHandle::spawn(socket_path, console_socket_path, log_path)?;
+        let process = CloudHypervisorProcessHandle::spawn(
+            socket_path,
+            console_socket_path,
+            log_path,
+            config.log_policy,
+        )
+        .await?;
         Ok(Box::new(CloudHypervisorInstance::new(process)))
     }
 
+    async fn attach(
+        &self,
+        id: ProcessId,
+        config: &VmConfig,
+        socket_path: &str,
+        console_socket_path: &str,
+        log_path: &str,
+    ) -> Result<Box<dyn HypervisorProcess>, HypervisorError> {
+        let process = CloudHypervisorProcessHandle::adopt(
+            id,
+            socket_path,
+            console_socket_path,
+            log_path,
+            config.log_policy,
+        );
+        let instance = CloudHypervisorInstance::new(process);
+        let pty_path = instance.client.get_console_pty_path().await?.ok_or_else(|| {
+            HypervisorError::ApiRequest("cloud-hypervisor has no console PTY".to_string())
+        })?;
+        instance.process.start_console_proxy(&pty_path)?;
+        Ok(Box::new(instance))
+    }
+
     fn hypervisor_type(&self) -> HypervisorType {
         HypervisorType::CloudHypervisor
     }
 
     fn is_available(&self) -> bool {
-        Command::new("cloud-hypervisor")
+        std::process::Command::new("cloud-hypervisor")
             .arg("--version")
             .output()
             .is_ok()
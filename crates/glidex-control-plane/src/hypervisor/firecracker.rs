@@ -1,18 +1,24 @@
-use super::{Hypervisor, HypervisorError, HypervisorProcess, HypervisorType};
-use crate::models::VmConfig;
-use nix::pty::{openpty, OpenptyResult};
-use nix::unistd::setsid;
+use super::{
+    stop_console, Hypervisor, HypervisorChild, HypervisorError, HypervisorProcess,
+    HypervisorType, ProcessId,
+};
+use crate::console::{ConsoleProxy, Serial};
+use crate::models::{LogPolicy, VmConfig, VmExit};
+use async_trait::async_trait;
+use nix::sys::stat::Mode;
+use nix::unistd::{mkfifo, setsid};
 use serde::Serialize;
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Read, Write};
-use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
-use std::os::unix::net::{UnixListener, UnixStream};
-use std::os::unix::process::CommandExt;
-use std::process::{Child, Command, Stdio};
+use std::fs::OpenOptions;
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::sync::Mutex;
 use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+use tokio::process::Command;
+use tokio::time::Instant;
 
 #[derive(Debug, Serialize)]
 struct BootSource {
@@ -39,6 +45,33 @@ struct InstanceAction {
     action_type: String,
 }
 
+#[derive(Debug, Serialize)]
+struct SnapshotCreateParams {
+    snapshot_type: String,
+    snapshot_path: String,
+    mem_file_path: String,
+}
+
+#[derive(Debug, Serialize)]
+struct MemBackend {
+    backend_type: String,
+    backend_path: String,
+}
+
+#[derive(Debug, Serialize)]
+struct SnapshotLoadParams {
+    snapshot_path: String,
+    mem_backend: MemBackend,
+    resume_vm: bool,
+}
+
+/// How long to wait for a response from the firecracker API.
+const API_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// File names inside a snapshot directory.
+const SNAPSHOT_STATE_FILE: &str = "vmstate";
+const SNAPSHOT_MEMORY_FILE: &str = "memory";
+
 /// HTTP client for communicating with Firecracker API over Unix socket
 pub struct FirecrackerClient {
     socket_path: String,
@@ -51,23 +84,18 @@ impl FirecrackerClient {
         }
     }
 
-    fn send_request(
+    async fn send_request(
         &self,
         method: &str,
         path: &str,
         body: Option<&str>,
     ) -> Result<String, HypervisorError> {
         let stream = UnixStream::connect(&self.socket_path)
+            .await
             .map_err(|e| HypervisorError::SocketConnection(e.to_string()))?;
 
-        stream
-            .set_read_timeout(Some(Duration::from_secs(30)))
-            .map_err(HypervisorError::ProcessStart)?;
-
-        let mut writer = stream
-            .try_clone()
-            .map_err(HypervisorError::ProcessStart)?;
-        let mut reader = BufReader::new(stream);
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
 
         let body_str = body.unwrap_or("");
         let content_length = body_str.len();
@@ -79,42 +107,50 @@ impl FirecrackerClient {
 
         writer
             .write_all(request.as_bytes())
+            .await
             .map_err(HypervisorError::ProcessStart)?;
-        writer.flush().map_err(HypervisorError::ProcessStart)?;
+        writer.flush().await.map_err(HypervisorError::ProcessStart)?;
 
-        let mut response = String::new();
-        let mut content_length: usize = 0;
+        let read_response = async {
+            let mut response = String::new();
+            let mut content_length: usize = 0;
 
-        loop {
-            let mut line = String::new();
-            reader
-                .read_line(&mut line)
-                .map_err(HypervisorError::ProcessStart)?;
-            response.push_str(&line);
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).await? == 0 {
+                    break;
+                }
+                response.push_str(&line);
+
+                if line.to_lowercase().starts_with("content-length:") {
+                    if let Some(len_str) = line.split(':').nth(1) {
+                        content_length = len_str.trim().parse().unwrap_or(0);
+                    }
+                }
 
-            if line.to_lowercase().starts_with("content-length:") {
-                if let Some(len_str) = line.split(':').nth(1) {
-                    content_length = len_str.trim().parse().unwrap_or(0);
+                if line == "\r\n" || line == "\n" {
+                    break;
                 }
             }
 
-            if line == "\r\n" || line == "\n" {
-                break;
+            if content_length > 0 {
+                let mut body_buf = vec![0u8; content_length];
+                reader.read_exact(&mut body_buf).await?;
+                response.push_str(&String::from_utf8_lossy(&body_buf));
             }
-        }
 
-        if content_length > 0 {
-            let mut body_buf = vec![0u8; content_length];
-            reader
-                .read_exact(&mut body_buf)
-                .map_err(HypervisorError::ProcessStart)?;
-            response.push_str(&String::from_utf8_lossy(&body_buf));
-        }
+            Ok::<_, std::io::Error>(response)
+        };
 
-        Ok(response)
+        tokio::time::timeout(API_TIMEOUT, read_response)
+            .await
+            .map_err(|_| {
+                HypervisorError::Timeout(format!("{} {} got no response", method, path))
+            })?
+            .map_err(HypervisorError::ProcessStart)
     }
 
-    pub fn configure_machine(&self, config: &VmConfig) -> Result<(), HypervisorError> {
+    pub async fn configure_machine(&self, config: &VmConfig) -> Result<(), HypervisorError> {
         let machine_config = MachineConfig {
             vcpu_count: config.vcpu_count,
             mem_size_mib: config.mem_size_mib,
@@ -123,7 +159,7 @@ impl FirecrackerClient {
         let body = serde_json::to_string(&machine_config)
             .map_err(|e| HypervisorError::ApiRequest(e.to_string()))?;
 
-        let response = self.send_request("PUT", "/machine-config", Some(&body))?;
+        let response = self.send_request("PUT", "/machine-config", Some(&body)).await?;
 
         if !response.contains("HTTP/1.1 204") && !response.contains("HTTP/1.1 200") {
             return Err(HypervisorError::ApiRequest(format!(
@@ -135,7 +171,7 @@ impl FirecrackerClient {
         Ok(())
     }
 
-    pub fn set_boot_source(&self, config: &VmConfig) -> Result<(), HypervisorError> {
+    pub async fn set_boot_source(&self, config: &VmConfig) -> Result<(), HypervisorError> {
         let boot_source = BootSource {
             kernel_image_path: config.kernel_image_path.clone(),
             boot_args: config.kernel_args.clone(),
@@ -144,7 +180,7 @@ impl FirecrackerClient {
         let body = serde_json::to_string(&boot_source)
             .map_err(|e| HypervisorError::ApiRequest(e.to_string()))?;
 
-        let response = self.send_request("PUT", "/boot-source", Some(&body))?;
+        let response = self.send_request("PUT", "/boot-source", Some(&body)).await?;
 
         if !response.contains("HTTP/1.1 204") && !response.contains("HTTP/1.1 200") {
             return Err(HypervisorError::ApiRequest(format!(
@@ -156,7 +192,7 @@ impl FirecrackerClient {
         Ok(())
     }
 
-    pub fn add_root_drive(&self, rootfs_path: &str) -> Result<(), HypervisorError> {
+    pub async fn add_root_drive(&self, rootfs_path: &str) -> Result<(), HypervisorError> {
         let drive = Drive {
             drive_id: "rootfs".to_string(),
             path_on_host: rootfs_path.to_string(),
@@ -167,7 +203,7 @@ impl FirecrackerClient {
         let body = serde_json::to_string(&drive)
             .map_err(|e| HypervisorError::ApiRequest(e.to_string()))?;
 
-        let response = self.send_request("PUT", "/drives/rootfs", Some(&body))?;
+        let response = self.send_request("PUT", "/drives/rootfs", Some(&body)).await?;
 
         if !response.contains("HTTP/1.1 204") && !response.contains("HTTP/1.1 200") {
             return Err(HypervisorError::ApiRequest(format!(
@@ -179,7 +215,7 @@ impl FirecrackerClient {
         Ok(())
     }
 
-    pub fn start_instance(&self) -> Result<(), HypervisorError> {
+    pub async fn start_instance(&self) -> Result<(), HypervisorError> {
         let action = InstanceAction {
             action_type: "InstanceStart".to_string(),
         };
@@ -187,7 +223,7 @@ impl FirecrackerClient {
         let body = serde_json::to_string(&action)
             .map_err(|e| HypervisorError::ApiRequest(e.to_string()))?;
 
-        let response = self.send_request("PUT", "/actions", Some(&body))?;
+        let response = self.send_request("PUT", "/actions", Some(&body)).await?;
 
         if !response.contains("HTTP/1.1 204") && !response.contains("HTTP/1.1 200") {
             return Err(HypervisorError::ApiRequest(format!(
@@ -199,9 +235,32 @@ impl FirecrackerClient {
         Ok(())
     }
 
-    pub fn pause_instance(&self) -> Result<(), HypervisorError> {
+    /// Firecracker has no ACPI; the closest thing to a power button is an
+    /// i8042 Ctrl-Alt-Del, which a `reboot=k` guest turns into a reset that
+    /// makes Firecracker exit.
+    pub async fn send_ctrl_alt_del(&self) -> Result<(), HypervisorError> {
+        let action = InstanceAction {
+            action_type: "SendCtrlAltDel".to_string(),
+        };
+
+        let body = serde_json::to_string(&action)
+            .map_err(|e| HypervisorError::ApiRequest(e.to_string()))?;
+
+        let response = self.send_request("PUT", "/actions", Some(&body)).await?;
+
+        if !response.contains("HTTP/1.1 204") && !response.contains("HTTP/1.1 200") {
+            return Err(HypervisorError::ApiRequest(format!(
+                "Failed to send Ctrl-Alt-Del: {}",
+                response
+            )));
+        }
+
+        Ok(())
+    }
+
+    pub async fn pause_instance(&self) -> Result<(), HypervisorError> {
         let body = r#"{"state": "Paused"}"#;
-        let response = self.send_request("PATCH", "/vm", Some(body))?;
+        let response = self.send_request("PATCH", "/vm", Some(body)).await?;
 
         if !response.contains("HTTP/1.1 204") && !response.contains("HTTP/1.1 200") {
             return Err(HypervisorError::ApiRequest(format!(
@@ -213,9 +272,9 @@ impl FirecrackerClient {
         Ok(())
     }
 
-    pub fn resume_instance(&self) -> Result<(), HypervisorError> {
+    pub async fn resume_instance(&self) -> Result<(), HypervisorError> {
         let body = r#"{"state": "Resumed"}"#;
-        let response = self.send_request("PATCH", "/vm", Some(body))?;
+        let response = self.send_request("PATCH", "/vm", Some(body)).await?;
 
         if !response.contains("HTTP/1.1 204") && !response.contains("HTTP/1.1 200") {
             return Err(HypervisorError::ApiRequest(format!(
@@ -226,50 +285,169 @@ impl FirecrackerClient {
 
         Ok(())
     }
+
+    /// Write a full snapshot of a paused microVM: device state to
+    /// `snapshot_path`, guest memory to `mem_file_path`.
+    pub async fn create_snapshot(
+        &self,
+        snapshot_path: &Path,
+        mem_file_path: &Path,
+    ) -> Result<(), HypervisorError> {
+        let params = SnapshotCreateParams {
+            snapshot_type: "Full".to_string(),
+            snapshot_path: snapshot_path.to_string_lossy().into_owned(),
+            mem_file_path: mem_file_path.to_string_lossy().into_owned(),
+        };
+
+        let body = serde_json::to_string(&params)
+            .map_err(|e| HypervisorError::ApiRequest(e.to_string()))?;
+
+        let response = self.send_request("PUT", "/snapshot/create", Some(&body)).await?;
+
+        if !response.contains("HTTP/1.1 204") && !response.contains("HTTP/1.1 200") {
+            return Err(HypervisorError::ApiRequest(format!(
+                "Failed to create snapshot: {}",
+                response
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Load a snapshot into a firecracker that has not been configured
+    /// yet. The microVM is left paused.
+    pub async fn load_snapshot(
+        &self,
+        snapshot_path: &Path,
+        mem_file_path: &Path,
+    ) -> Result<(), HypervisorError> {
+        let params = SnapshotLoadParams {
+            snapshot_path: snapshot_path.to_string_lossy().into_owned(),
+            mem_backend: MemBackend {
+                backend_type: "File".to_string(),
+                backend_path: mem_file_path.to_string_lossy().into_owned(),
+            },
+            resume_vm: false,
+        };
+
+        let body = serde_json::to_string(&params)
+            .map_err(|e| HypervisorError::ApiRequest(e.to_string()))?;
+
+        let response = self.send_request("PUT", "/snapshot/load", Some(&body)).await?;
+
+        if !response.contains("HTTP/1.1 204") && !response.contains("HTTP/1.1 200") {
+            return Err(HypervisorError::ApiRequest(format!(
+                "Failed to load snapshot: {}",
+                response
+            )));
+        }
+
+        Ok(())
+    }
 }
 
 /// Manages a running Firecracker process
+///
+/// Firecracker's serial console is its stdin/stdout. These are named FIFOs
+/// next to the API socket rather than a PTY, so that a restarted control
+/// plane can open them again.
 pub struct FirecrackerProcessHandle {
-    child: Mutex<Option<Child>>,
+    child: Mutex<Option<HypervisorChild>>,
     socket_path: String,
     console_socket_path: String,
     log_path: String,
-    running: Arc<AtomicBool>,
-    console_thread: Mutex<Option<thread::JoinHandle<()>>>,
+    serial_in: PathBuf,
+    serial_out: PathBuf,
+    log_policy: LogPolicy,
+    running: AtomicBool,
+    console: Mutex<Option<ConsoleProxy>>,
 }
 
 impl FirecrackerProcessHandle {
-    pub fn spawn(
+    fn new(
+        socket_path: &str,
+        console_socket_path: &str,
+        log_path: &str,
+        log_policy: LogPolicy,
+    ) -> Self {
+        Self {
+            child: Mutex::new(None),
+            socket_path: socket_path.to_string(),
+            console_socket_path: console_socket_path.to_string(),
+            log_path: log_path.to_string(),
+            serial_in: Path::new(socket_path).with_extension("serial-in"),
+            serial_out: Path::new(socket_path).with_extension("serial-out"),
+            log_policy,
+            running: AtomicBool::new(false),
+            console: Mutex::new(None),
+        }
+    }
+
+    pub async fn spawn(
         socket_path: &str,
         console_socket_path: &str,
         log_path: &str,
+        log_policy: LogPolicy,
     ) -> Result<Self, HypervisorError> {
-        // Remove existing sockets if present
-        let _ = std::fs::remove_file(socket_path);
-        let _ = std::fs::remove_file(console_socket_path);
+        let handle = Self::new(socket_path, console_socket_path, log_path, log_policy);
+        handle.launch().await?;
+        Ok(handle)
+    }
 
-        // Create/truncate log file
-        let log_file = OpenOptions::new()
-            .create(true)
-            .write(true)
-            .truncate(true)
-            .open(log_path)?;
+    /// Handle for a firecracker an earlier control plane spawned, with its
+    /// console proxy restarted.
+    pub fn attach(
+        id: ProcessId,
+        socket_path: &str,
+        console_socket_path: &str,
+        log_path: &str,
+        log_policy: LogPolicy,
+    ) -> Result<Self, HypervisorError> {
+        let handle = Self::new(socket_path, console_socket_path, log_path, log_policy);
+        *handle.child.lock().unwrap() = Some(HypervisorChild::Adopted(id));
+        handle.start_console_proxy()?;
+        Ok(handle)
+    }
 
-        // Create a PTY pair for interactive console
-        let OpenptyResult { master, slave } = openpty(None, None)
-            .map_err(|e| HypervisorError::SocketConnection(format!("Failed to create PTY: {}", e)))?;
+    /// Start firecracker and its console proxy, which appends to the log.
+    async fn launch(&self) -> Result<(), HypervisorError> {
+        // Remove existing sockets if present
+        let _ = std::fs::remove_file(&self.socket_path);
+        let _ = std::fs::remove_file(&self.console_socket_path);
+
+        // Create the serial FIFOs
+        for fifo in [&self.serial_in, &self.serial_out] {
+            let _ = std::fs::remove_file(fifo);
+            mkfifo(fifo, Mode::S_IRUSR | Mode::S_IWUSR).map_err(|e| {
+                HypervisorError::SocketConnection(format!(
+                    "Failed to create FIFO {}: {}",
+                    fifo.display(),
+                    e
+                ))
+            })?;
+        }
 
-        // Convert slave fd to Stdio for the child process
-        let slave_raw = slave.as_raw_fd();
-        let stdin_fd = unsafe { File::from_raw_fd(libc::dup(slave_raw)) };
-        let stdout_fd = unsafe { File::from_raw_fd(libc::dup(slave_raw)) };
-        let stderr_fd = unsafe { File::from_raw_fd(libc::dup(slave_raw)) };
+        // Firecracker opens both ends of its FIFOs itself so neither sees EOF
+        // or SIGPIPE while no control plane is attached. Output written
+        // meanwhile is dropped once the FIFO is full instead of stalling
+        // the guest.
+        let stdin_fd = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&self.serial_in)?;
+        let stdout_fd = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NONBLOCK)
+            .open(&self.serial_out)?;
+        let stderr_fd = stdout_fd.try_clone()?;
 
-        // Spawn firecracker with the PTY as stdin/stdout/stderr
+        // Spawn firecracker with the FIFOs as stdin/stdout/stderr. setsid
+        // keeps it out of our session, so it outlives us.
         let child = unsafe {
             Command::new("firecracker")
                 .arg("--api-sock")
-                .arg(socket_path)
+                .arg(&self.socket_path)
                 .stdin(Stdio::from(stdin_fd))
                 .stdout(Stdio::from(stdout_fd))
                 .stderr(Stdio::from(stderr_fd))
@@ -279,129 +457,77 @@ impl FirecrackerProcessHandle {
                 })
                 .spawn()?
         };
+        *self.child.lock().unwrap() = Some(HypervisorChild::Spawned(child));
 
-        // Drop slave fd - we only need the master
-        drop(slave);
-
-        // Create Unix socket for console connections
-        let console_listener = UnixListener::bind(console_socket_path).map_err(|e| {
-            HypervisorError::SocketConnection(format!("Failed to create console socket: {}", e))
-        })?;
-        console_listener.set_nonblocking(true).map_err(|e| {
-            HypervisorError::SocketConnection(format!("Failed to set non-blocking: {}", e))
-        })?;
-
-        let running = Arc::new(AtomicBool::new(true));
-        let running_clone = running.clone();
-        let log_path_clone = log_path.to_string();
-
-        // Spawn thread to handle console I/O
-        let console_thread = thread::spawn(move || {
-            Self::console_proxy_loop(master, console_listener, log_file, &log_path_clone, running_clone);
-        });
+        if let Err(e) = self.start_console_proxy() {
+            self.terminate().await;
+            return Err(e);
+        }
 
         // Wait for API socket to be available
         for _ in 0..50 {
-            if std::path::Path::new(socket_path).exists() {
-                return Ok(Self {
-                    child: Mutex::new(Some(child)),
-                    socket_path: socket_path.to_string(),
-                    console_socket_path: console_socket_path.to_string(),
-                    log_path: log_path.to_string(),
-                    running,
-                    console_thread: Mutex::new(Some(console_thread)),
-                });
+            if std::path::Path::new(&self.socket_path).exists() {
+                return Ok(());
             }
-            std::thread::sleep(Duration::from_millis(100));
+            tokio::time::sleep(Duration::from_millis(100)).await;
         }
 
         // Cleanup on timeout
-        running.store(false, Ordering::SeqCst);
-        let _ = console_thread.join();
-        let mut child = child;
-        let _ = child.kill();
-        let _ = child.wait();
-        let _ = std::fs::remove_file(socket_path);
-        let _ = std::fs::remove_file(console_socket_path);
+        self.terminate().await;
 
         Err(HypervisorError::Timeout(
             "Socket not available after timeout".to_string(),
         ))
     }
 
-    fn console_proxy_loop(
-        master: OwnedFd,
-        listener: UnixListener,
-        mut log_file: File,
-        log_path: &str,
-        running: Arc<AtomicBool>,
-    ) {
-        let master_raw = master.as_raw_fd();
-        let mut clients: Vec<UnixStream> = Vec::new();
-        let mut buf = [0u8; 4096];
-        // PTY reads EOF once firecracker exits. We don't tear down the
-        // listener in that case — clients should still be able to connect
-        // and replay the captured log to diagnose why the guest died.
-        let mut pty_alive = true;
-
-        // Set master to non-blocking
-        unsafe {
-            let flags = libc::fcntl(master_raw, libc::F_GETFL);
-            libc::fcntl(master_raw, libc::F_SETFL, flags | libc::O_NONBLOCK);
-        }
-
-        while running.load(Ordering::SeqCst) {
-            // Accept new client connections
-            if let Ok((stream, _)) = listener.accept() {
-                stream.set_nonblocking(true).ok();
-                // Send existing log content to new client
-                if let Ok(mut existing_log) = File::open(log_path) {
-                    let mut log_content = Vec::new();
-                    if existing_log.read_to_end(&mut log_content).is_ok() && !log_content.is_empty()
-                    {
-                        let mut s = stream.try_clone().unwrap();
-                        let _ = s.write_all(&log_content);
-                    }
-                }
-                clients.push(stream);
-            }
-
-            if pty_alive {
-                // Read from PTY master and broadcast to clients + log file
-                let master_file = unsafe { File::from_raw_fd(libc::dup(master_raw)) };
-                let mut master_reader = master_file;
-                match master_reader.read(&mut buf) {
-                    Ok(0) => pty_alive = false,
-                    Ok(n) => {
-                        let data = &buf[..n];
+    /// Open the serial FIFOs and start the proxy bridging them to the
+    /// console socket and the log.
+    fn start_console_proxy(&self) -> Result<(), HypervisorError> {
+        let open_fifo = |path: &Path, options: &mut OpenOptions| {
+            options
+                .custom_flags(libc::O_NONBLOCK)
+                .open(path)
+                .map_err(|e| {
+                    HypervisorError::SocketConnection(format!(
+                        "Failed to open FIFO {}: {}",
+                        path.display(),
+                        e
+                    ))
+                })
+        };
+        let serial_out = open_fifo(&self.serial_out, OpenOptions::new().read(true))?;
+        let serial_in = open_fifo(&self.serial_in, OpenOptions::new().write(true))?;
+
+        let console = ConsoleProxy::spawn(
+            Serial::split(serial_out, serial_in),
+            &self.console_socket_path,
+            &self.log_path,
+            self.log_policy,
+        )
+        .map_err(|e| {
+            HypervisorError::SocketConnection(format!("Failed to start console proxy: {}", e))
+        })?;
+        *self.console.lock().unwrap() = Some(console);
+        self.running.store(true, Ordering::SeqCst);
+        Ok(())
+    }
 
-                        let _ = log_file.write_all(data);
-                        let _ = log_file.flush();
+    /// Kill firecracker, stop the console proxy and remove the sockets.
+    /// The handle can be `launch`ed again afterwards.
+    async fn terminate(&self) {
+        self.running.store(false, Ordering::SeqCst);
 
-                        clients.retain_mut(|client| client.write_all(data).is_ok());
-                    }
-                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-                    Err(_) => pty_alive = false,
-                }
+        let child = self.child.lock().unwrap().take();
+        if let Some(mut child) = child {
+            child.kill().await;
+        }
 
-                // Read from clients and write to PTY master
-                for client in &mut clients {
-                    match client.read(&mut buf) {
-                        Ok(0) => {}
-                        Ok(n) => {
-                            let mut master_writer =
-                                unsafe { File::from_raw_fd(libc::dup(master_raw)) };
-                            let _ = master_writer.write_all(&buf[..n]);
-                            let _ = master_writer.flush();
-                        }
-                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-                        Err(_) => {}
-                    }
-                }
-            }
+        stop_console(&self.console).await;
 
-            thread::sleep(Duration::from_millis(10));
-        }
+        let _ = std::fs::remove_file(&self.socket_path);
+        let _ = std::fs::remove_file(&self.console_socket_path);
+        let _ = std::fs::remove_file(&self.serial_in);
+        let _ = std::fs::remove_file(&self.serial_out);
     }
 }
 
@@ -409,54 +535,127 @@ impl FirecrackerProcessHandle {
 pub struct FirecrackerInstance {
     process: FirecrackerProcessHandle,
     client: FirecrackerClient,
+    /// Kept from `configure` so `respawn` can boot the same VM again.
+    config: Mutex<Option<VmConfig>>,
 }
 
 impl FirecrackerInstance {
     pub fn new(process: FirecrackerProcessHandle) -> Self {
         let client = FirecrackerClient::new(&process.socket_path);
-        Self { process, client }
+        Self {
+            process,
+            client,
+            config: Mutex::new(None),
+        }
+    }
+
+    /// Replace the firecracker process with a fresh one booting the same
+    /// VM. Firecracker cannot reset a guest in place and exits when the
+    /// guest reboots, so this is how reset and reboot are done. The console
+    /// log carries on; this is not a new boot as far as it is concerned.
+    async fn respawn(&self) -> Result<(), HypervisorError> {
+        let config = self.config.lock().unwrap().clone().ok_or_else(|| {
+            HypervisorError::InvalidConfig("VM has not been configured".to_string())
+        })?;
+
+        self.process.terminate().await;
+        self.process.launch().await?;
+        let booted = match self.configure(&config).await {
+            Ok(()) => self.start().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = booted {
+            self.process.terminate().await;
+            return Err(e);
+        }
+        Ok(())
     }
 }
 
+#[async_trait]
 impl HypervisorProcess for FirecrackerInstance {
-    fn configure(&self, config: &VmConfig) -> Result<(), HypervisorError> {
-        self.client.configure_machine(config)?;
-        self.client.set_boot_source(config)?;
-        self.client.add_root_drive(&config.rootfs_path)?;
+    async fn configure(&self, config: &VmConfig) -> Result<(), HypervisorError> {
+        self.client.configure_machine(config).await?;
+        self.client.set_boot_source(config).await?;
+        self.client.add_root_drive(&config.rootfs_path).await?;
+        *self.config.lock().unwrap() = Some(config.clone());
         Ok(())
     }
 
-    fn start(&self) -> Result<(), HypervisorError> {
-        self.client.start_instance()
+    async fn start(&self) -> Result<(), HypervisorError> {
+        self.client.start_instance().await
     }
 
-    fn pause(&self) -> Result<(), HypervisorError> {
-        self.client.pause_instance()
+    async fn pause(&self) -> Result<(), HypervisorError> {
+        self.client.pause_instance().await
     }
 
-    fn resume(&self) -> Result<(), HypervisorError> {
-        self.client.resume_instance()
+    async fn resume(&self) -> Result<(), HypervisorError> {
+        self.client.resume_instance().await
     }
 
-    fn kill(&self) -> Result<(), HypervisorError> {
-        self.process.running.store(false, Ordering::SeqCst);
-
-        if let Some(mut child) = self.process.child.lock().unwrap().take() {
-            let _ = child.kill();
-            let _ = child.wait();
+    async fn reboot(&self, timeout: Duration) -> Result<(), HypervisorError> {
+        self.client.send_ctrl_alt_del().await?;
+
+        // A `reboot=k` guest resets through the i8042, which makes
+        // firecracker exit; boot it again once it has.
+        let deadline = Instant::now() + timeout;
+        while self.try_wait().is_none() {
+            if Instant::now() >= deadline {
+                return Err(HypervisorError::Timeout(format!(
+                    "guest did not reboot within {:?}",
+                    timeout
+                )));
+            }
+            tokio::time::sleep(super::EXIT_POLL_INTERVAL).await;
         }
+        self.respawn().await
+    }
 
-        if let Some(handle) = self.process.console_thread.lock().unwrap().take() {
-            let _ = handle.join();
-        }
+    async fn reset(&self) -> Result<(), HypervisorError> {
+        self.respawn().await
+    }
 
-        let _ = std::fs::remove_file(&self.process.socket_path);
-        let _ = std::fs::remove_file(&self.process.console_socket_path);
+    async fn snapshot(&self, dir: &Path) -> Result<(), HypervisorError> {
+        self.client
+            .create_snapshot(
+                &dir.join(SNAPSHOT_STATE_FILE),
+                &dir.join(SNAPSHOT_MEMORY_FILE),
+            )
+            .await
+    }
+
+    async fn restore(&self, config: &VmConfig, dir: &Path) -> Result<(), HypervisorError> {
+        self.client
+            .load_snapshot(
+                &dir.join(SNAPSHOT_STATE_FILE),
+                &dir.join(SNAPSHOT_MEMORY_FILE),
+            )
+            .await?;
+        // A later reset cold-boots the same configuration.
+        *self.config.lock().unwrap() = Some(config.clone());
+        Ok(())
+    }
+
+    async fn kill(&self) -> Result<(), HypervisorError> {
+        self.process.terminate().await;
         Ok(())
     }
 
+    async fn power_button(&self) -> Result<(), HypervisorError> {
+        self.client.send_ctrl_alt_del().await
+    }
+
+    fn try_wait(&self) -> Option<VmExit> {
+        self.process.child.lock().unwrap().as_mut()?.try_wait()
+    }
+
+    fn process_id(&self) -> Option<ProcessId> {
+        self.process.child.lock().unwrap().as_ref()?.process_id()
+    }
+
     fn is_running(&self) -> bool {
-        self.process.running.load(Ordering::SeqCst)
+        self.process.running.load(Ordering::SeqCst) && self.try_wait().is_none()
     }
 
     fn socket_path(&self) -> &str {
@@ -475,23 +674,52 @@ impl HypervisorProcess for FirecrackerInstance {
 /// Firecracker backend factory
 pub struct FirecrackerBackend;
 
+#[async_trait]
 impl Hypervisor for FirecrackerBackend {
-    fn spawn(
+    async fn spawn(
         &self,
+        config: &VmConfig,
         socket_path: &str,
         console_socket_path: &str,
         log_path: &str,
     ) -> Result<Box<dyn HypervisorProcess>, HypervisorError> {
-        let process = FirecrackerProcessHandle::spawn(socket_path, console_socket_path, log_path)?;
+        let process = FirecrackerProcessHandle::spawn(
+            socket_path,
+            console_socket_path,
+            log_path,
+            config.log_policy,
+        )
+        .await?;
         Ok(Box::new(FirecrackerInstance::new(process)))
     }
 
+    async fn attach(
+        &self,
+        id: ProcessId,
+        config: &VmConfig,
+        socket_path: &str,
+        console_socket_path: &str,
+        log_path: &str,
+    ) -> Result<Box<dyn HypervisorProcess>, HypervisorError> {
+        let process = FirecrackerProcessHandle::attach(
+            id,
+            socket_path,
+            console_socket_path,
+            log_path,
+            config.log_policy,
+        )?;
+        let instance = FirecrackerInstance::new(process);
+        // A later reset cold-boots the same configuration.
+        *instance.config.lock().unwrap() = Some(config.clone());
+        Ok(Box::new(instance))
+    }
+
     fn hypervisor_type(&self) -> HypervisorType {
         HypervisorType::Firecracker
     }
 
     fn is_available(&self) -> bool {
-        Command::new("firecracker")
+        std::process::Command::new("firecracker")
             .arg("--version")
             .output()
             .is_ok()
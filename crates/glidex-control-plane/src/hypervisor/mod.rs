@@ -2,59 +2,25 @@ pub mod cloud_hypervisor;
 pub mod firecracker;
 pub mod qemu;
 
-use crate::models::VmConfig;
+use crate::console::ConsoleProxy;
+use crate::models::{VmConfig, VmExit};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::fmt;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
 use thiserror::Error;
+use tokio::process::Child;
+use tokio::time::Instant;
 
-/// Supported hypervisor types
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default)]
-#[serde(rename_all = "lowercase")]
-pub enum HypervisorType {
-    Firecracker,
-    CloudHypervisor,
-    #[default]
-    Qemu,
-}
-
-impl HypervisorType {
-    /// Get the binary name for this hypervisor
-    pub fn binary_name(&self) -> &'static str {
-        match self {
-            HypervisorType::Firecracker => "firecracker",
-            HypervisorType::CloudHypervisor => "cloud-hypervisor",
-            HypervisorType::Qemu => "qemu-system-x86_64",
-        }
-    }
+pub use glidex_client::models::{HypervisorType, ProcessUsage};
 
-    /// Get the socket path prefix for this hypervisor
-    pub fn socket_prefix(&self) -> &'static str {
-        match self {
-            HypervisorType::Firecracker => "firecracker",
-            HypervisorType::CloudHypervisor => "cloud-hypervisor",
-            HypervisorType::Qemu => "qemu",
-        }
-    }
+/// How often to check whether a hypervisor process has exited while
+/// waiting for it (`shutdown`, Firecracker reboot).
+const EXIT_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-    /// Get the default kernel boot arguments for this hypervisor
-    pub fn default_kernel_args(&self) -> &'static str {
-        match self {
-            HypervisorType::Firecracker => "console=ttyS0 reboot=k panic=1 pci=off",
-            HypervisorType::CloudHypervisor => "console=hvc0 root=/dev/vda reboot=k panic=1",
-            HypervisorType::Qemu => "console=ttyS0 root=/dev/vda reboot=k panic=1",
-        }
-    }
-}
-
-impl fmt::Display for HypervisorType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            HypervisorType::Firecracker => write!(f, "firecracker"),
-            HypervisorType::CloudHypervisor => write!(f, "cloudhypervisor"),
-            HypervisorType::Qemu => write!(f, "qemu"),
-        }
-    }
-}
+/// How long a snapshot, restore or live migration may take.
+pub const MIGRATION_TIMEOUT: Duration = Duration::from_secs(300);
 
 /// Errors that can occur during hypervisor operations
 #[derive(Error, Debug)]
@@ -78,11 +44,124 @@ pub enum HypervisorError {
     Timeout(String),
 }
 
+/// Identifies a hypervisor process across control-plane restarts. The
+/// start time guards against the pid having been recycled in between.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProcessId {
+    pub pid: u32,
+    /// Field 22 of `/proc/<pid>/stat`: clock ticks since boot.
+    pub start_time: u64,
+}
+
+impl ProcessId {
+    /// The identity of the live process `pid`, if there is one.
+    pub fn of(pid: u32) -> Option<Self> {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+        // The command name in field 2 may contain spaces and parentheses,
+        // so count fields from the last ')'.
+        let rest = &stat[stat.rfind(')')? + 1..];
+        let start_time = rest.split_whitespace().nth(19)?.parse().ok()?;
+        Some(Self { pid, start_time })
+    }
+
+    /// Whether this exact process still exists.
+    pub fn is_alive(&self) -> bool {
+        Self::of(self.pid).as_ref() == Some(self)
+    }
+
+    /// The CPU time and resident memory of the process, if it is still
+    /// this one.
+    pub fn usage(&self) -> Option<ProcessUsage> {
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", self.pid)).ok()?;
+        let fields: Vec<&str> = stat[stat.rfind(')')? + 1..].split_whitespace().collect();
+        // `proc(5)` numbers the fields from 1; the first after ')' is 3.
+        let field = |n: usize| -> Option<u64> { fields.get(n - 3)?.parse().ok() };
+        if field(22)? != self.start_time {
+            return None;
+        }
+        let ticks_per_sec = unsafe { libc::sysconf(libc::_SC_CLK_TCK) }.max(1) as u64;
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) }.max(1) as u64;
+        Some(ProcessUsage {
+            pid: self.pid,
+            cpu_time_ms: (field(14)? + field(15)?) * 1000 / ticks_per_sec,
+            rss_bytes: field(24)? * page_size,
+        })
+    }
+
+    /// SIGKILL the process if it is still this one, and wait briefly for
+    /// whoever reaps it to do so.
+    pub async fn kill(&self) {
+        if !self.is_alive() {
+            return;
+        }
+        unsafe {
+            libc::kill(self.pid as libc::pid_t, libc::SIGKILL);
+        }
+        let deadline = Instant::now() + Duration::from_secs(1);
+        while self.is_alive() && Instant::now() < deadline {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    }
+}
+
+/// A backend's hypervisor process: either spawned by this control plane,
+/// or adopted after a restart from the control plane that spawned it.
+pub enum HypervisorChild {
+    Spawned(Child),
+    /// Not our child, so it cannot be waited for and its exit status is
+    /// lost; all we can tell is that it is gone.
+    Adopted(ProcessId),
+}
+
+impl HypervisorChild {
+    pub fn process_id(&self) -> Option<ProcessId> {
+        match self {
+            HypervisorChild::Spawned(child) => ProcessId::of(child.id()?),
+            HypervisorChild::Adopted(id) => Some(*id),
+        }
+    }
+
+    /// Non-blocking exit check.
+    pub fn try_wait(&mut self) -> Option<VmExit> {
+        match self {
+            HypervisorChild::Spawned(child) => child.try_wait().ok().flatten().map(VmExit::from),
+            HypervisorChild::Adopted(id) => (!id.is_alive()).then(VmExit::default),
+        }
+    }
+
+    /// Kill the process and wait for it to be gone.
+    pub async fn kill(&mut self) {
+        match self {
+            HypervisorChild::Spawned(child) => {
+                let _ = child.kill().await;
+            }
+            HypervisorChild::Adopted(id) => id.kill().await,
+        }
+    }
+}
+
 /// Trait for hypervisor backends that can spawn VM processes
+#[async_trait]
 pub trait Hypervisor: Send + Sync {
-    /// Spawn a new hypervisor process
-    fn spawn(
+    /// Spawn a new hypervisor process for a VM with `config`. Only the
+    /// console log policy is taken from it here; the guest itself is set
+    /// up by `configure`, `restore` or `receive_migration` afterwards.
+    async fn spawn(
+        &self,
+        config: &VmConfig,
+        socket_path: &str,
+        console_socket_path: &str,
+        log_path: &str,
+    ) -> Result<Box<dyn HypervisorProcess>, HypervisorError>;
+
+    /// Take over a hypervisor process with a booted guest that an earlier
+    /// control plane spawned for `config` with these paths. Reconnects to
+    /// its API socket and restarts the console proxy, appending to the
+    /// existing log.
+    async fn attach(
         &self,
+        id: ProcessId,
+        config: &VmConfig,
         socket_path: &str,
         console_socket_path: &str,
         log_path: &str,
@@ -96,24 +175,134 @@ pub trait Hypervisor: Send + Sync {
 }
 
 /// Trait for a running hypervisor process instance
+///
+/// Operations are async so that a slow hypervisor only holds up callers
+/// waiting on that VM. The accessors and `try_wait` must not block.
+#[async_trait]
 pub trait HypervisorProcess: Send + Sync {
     /// Configure the VM with the given configuration
-    fn configure(&self, config: &VmConfig) -> Result<(), HypervisorError>;
+    async fn configure(&self, config: &VmConfig) -> Result<(), HypervisorError>;
 
     /// Start/boot the VM instance
-    fn start(&self) -> Result<(), HypervisorError>;
+    async fn start(&self) -> Result<(), HypervisorError>;
 
     /// Pause the VM
-    fn pause(&self) -> Result<(), HypervisorError>;
+    async fn pause(&self) -> Result<(), HypervisorError>;
 
     /// Resume a paused VM
-    fn resume(&self) -> Result<(), HypervisorError>;
+    async fn resume(&self) -> Result<(), HypervisorError>;
 
     /// Kill the hypervisor process
-    fn kill(&self) -> Result<(), HypervisorError>;
+    async fn kill(&self) -> Result<(), HypervisorError>;
+
+    /// Ask the guest to power off, as if its power button was pressed
+    /// (ACPI power button, or Ctrl-Alt-Del where there is no ACPI).
+    /// Returns as soon as the request is delivered; the guest may ignore it.
+    async fn power_button(&self) -> Result<(), HypervisorError> {
+        Err(HypervisorError::Unsupported(
+            "power_button not supported by this hypervisor".to_string(),
+        ))
+    }
+
+    /// Shut the guest down gracefully: press the power button, wait up to
+    /// `timeout` for the hypervisor process to exit, then `kill()`. `kill()`
+    /// runs either way so the console proxy and sockets are torn down.
+    async fn shutdown(&self, timeout: Duration) -> Result<(), HypervisorError> {
+        match self.power_button().await {
+            Ok(()) => {
+                // `timeout` comes from the caller and may be too far off to
+                // add to `Instant::now()`; `tokio::time::timeout` caps it.
+                let exited = tokio::time::timeout(timeout, async {
+                    while self.try_wait().is_none() {
+                        tokio::time::sleep(EXIT_POLL_INTERVAL).await;
+                    }
+                })
+                .await;
+                if exited.is_err() {
+                    tracing::warn!(
+                        socket = self.socket_path(),
+                        "Guest did not power off within {:?}, killing hypervisor",
+                        timeout
+                    );
+                }
+            }
+            Err(e) => {
+                tracing::warn!(
+                    socket = self.socket_path(),
+                    "Graceful shutdown unavailable ({}), killing hypervisor",
+                    e
+                );
+            }
+        }
+        self.kill().await
+    }
+
+    /// Ask the guest to reboot itself (Ctrl-Alt-Del) and wait up to
+    /// `timeout` for it to come back through reset. The hypervisor process
+    /// and console log survive where the backend allows it.
+    async fn reboot(&self, _timeout: Duration) -> Result<(), HypervisorError> {
+        Err(HypervisorError::Unsupported(
+            "reboot not supported by this hypervisor".to_string(),
+        ))
+    }
+
+    /// Hard-reset the guest, like pressing the reset button. The guest gets
+    /// no chance to shut down cleanly.
+    async fn reset(&self) -> Result<(), HypervisorError> {
+        Err(HypervisorError::Unsupported(
+            "reset not supported by this hypervisor".to_string(),
+        ))
+    }
+
+    /// Write the paused guest's memory and device state into `dir`, which
+    /// already exists. The guest stays paused. Disks are not copied.
+    async fn snapshot(&self, _dir: &Path) -> Result<(), HypervisorError> {
+        Err(HypervisorError::Unsupported(
+            "snapshot not supported by this hypervisor".to_string(),
+        ))
+    }
+
+    /// Bring a freshly spawned hypervisor up from a snapshot in `dir`
+    /// instead of `configure` + `start`. `config` must be the configuration
+    /// the snapshot was taken with. The guest is left paused.
+    async fn restore(&self, _config: &VmConfig, _dir: &Path) -> Result<(), HypervisorError> {
+        Err(HypervisorError::Unsupported(
+            "restore not supported by this hypervisor".to_string(),
+        ))
+    }
+
+    /// Live-migrate the running guest to a hypervisor listening on `uri`
+    /// (`unix:<path>`). Returns once the target holds the complete state;
+    /// the local guest must not be resumed after that.
+    async fn send_migration(&self, _uri: &str) -> Result<(), HypervisorError> {
+        Err(HypervisorError::Unsupported(
+            "live migration not supported by this hypervisor".to_string(),
+        ))
+    }
+
+    /// Put a freshly spawned hypervisor into incoming-migration mode on
+    /// `uri` instead of `configure` + `start`. `config` is the sender's.
+    /// Returns once the sender can connect.
+    async fn receive_migration(
+        &self,
+        _config: &VmConfig,
+        _uri: &str,
+    ) -> Result<(), HypervisorError> {
+        Err(HypervisorError::Unsupported(
+            "live migration not supported by this hypervisor".to_string(),
+        ))
+    }
+
+    /// Wait up to `timeout` for the migration started by
+    /// `receive_migration` to complete, then get the guest running.
+    async fn finish_migration(&self, _timeout: Duration) -> Result<(), HypervisorError> {
+        Err(HypervisorError::Unsupported(
+            "live migration not supported by this hypervisor".to_string(),
+        ))
+    }
 
     /// Hot-add a VFIO device to a running VM
-    fn add_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+    async fn add_device(&self, device_path: &str) -> Result<(), HypervisorError> {
         Err(HypervisorError::Unsupported(format!(
             "add_device not supported by this hypervisor (device: {})",
             device_path
@@ -121,13 +310,27 @@ pub trait HypervisorProcess: Send + Sync {
     }
 
     /// Hot-remove a VFIO device from a running VM
-    fn remove_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+    async fn remove_device(&self, device_path: &str) -> Result<(), HypervisorError> {
         Err(HypervisorError::Unsupported(format!(
             "remove_device not supported by this hypervisor (device: {})",
             device_path
         )))
     }
 
+    /// Return how the hypervisor ended if the process has terminated.
+    /// Must not block. Returns `None` while the process is alive, and for
+    /// backends that have not launched a process yet. Adopted processes
+    /// report an exit with neither code nor signal.
+    fn try_wait(&self) -> Option<VmExit> {
+        None
+    }
+
+    /// The hypervisor process, once launched, so that a restarted control
+    /// plane can `attach` to it again.
+    fn process_id(&self) -> Option<ProcessId> {
+        None
+    }
+
     /// Check if the process is still running
     fn is_running(&self) -> bool;
 
@@ -141,6 +344,14 @@ pub trait HypervisorProcess: Send + Sync {
     fn log_path(&self) -> &str;
 }
 
+/// Stop a process's console proxy, if it has one.
+async fn stop_console(console: &Mutex<Option<ConsoleProxy>>) {
+    let console = console.lock().unwrap().take();
+    if let Some(console) = console {
+        console.stop().await;
+    }
+}
+
 /// Create a hypervisor backend for the given type
 pub fn create_backend(hypervisor_type: HypervisorType) -> Box<dyn Hypervisor> {
     match hypervisor_type {
@@ -196,20 +407,21 @@ mod tests {
         running: std::sync::atomic::AtomicBool,
     }
 
+    #[async_trait]
     impl HypervisorProcess for StubProcess {
-        fn configure(&self, _config: &VmConfig) -> Result<(), HypervisorError> {
+        async fn configure(&self, _config: &VmConfig) -> Result<(), HypervisorError> {
             Ok(())
         }
-        fn start(&self) -> Result<(), HypervisorError> {
+        async fn start(&self) -> Result<(), HypervisorError> {
             Ok(())
         }
-        fn pause(&self) -> Result<(), HypervisorError> {
+        async fn pause(&self) -> Result<(), HypervisorError> {
             Ok(())
         }
-        fn resume(&self) -> Result<(), HypervisorError> {
+        async fn resume(&self) -> Result<(), HypervisorError> {
             Ok(())
         }
-        fn kill(&self) -> Result<(), HypervisorError> {
+        async fn kill(&self) -> Result<(), HypervisorError> {
             self.running
                 .store(false, std::sync::atomic::Ordering::SeqCst);
             Ok(())
@@ -228,8 +440,8 @@ mod tests {
         }
     }
 
-    #[test]
-    fn hypervisor_process_accessors_round_trip() {
+    #[tokio::test]
+    async fn hypervisor_process_accessors_round_trip() {
         let proc: Box<dyn HypervisorProcess> = Box::new(StubProcess {
             socket_path: "/tmp/sock".to_string(),
             console_socket_path: "/tmp/console".to_string(),
@@ -242,17 +454,68 @@ mod tests {
         assert_eq!(proc.log_path(), "/tmp/log");
         assert!(proc.is_running());
 
-        // Default add_device / remove_device should report Unsupported.
+        // A handle without a child process never reports an exit.
+        assert!(proc.try_wait().is_none());
+
+        // Default reboot / reset / snapshot / add_device / remove_device
+        // should report Unsupported.
         assert!(matches!(
-            proc.add_device("0000:00:1f.0"),
+            proc.reboot(Duration::from_secs(1)).await,
             Err(HypervisorError::Unsupported(_))
         ));
+        assert!(matches!(proc.reset().await, Err(HypervisorError::Unsupported(_))));
         assert!(matches!(
-            proc.remove_device("0000:00:1f.0"),
+            proc.snapshot(Path::new("/tmp/snap")).await,
+            Err(HypervisorError::Unsupported(_))
+        ));
+        assert!(matches!(
+            proc.add_device("0000:00:1f.0").await,
+            Err(HypervisorError::Unsupported(_))
+        ));
+        assert!(matches!(
+            proc.remove_device("0000:00:1f.0").await,
             Err(HypervisorError::Unsupported(_))
         ));
 
-        proc.kill().unwrap();
+        proc.kill().await.unwrap();
         assert!(!proc.is_running());
     }
+
+    #[test]
+    fn process_id_detects_exit_and_pid_reuse() {
+        let me = ProcessId::of(std::process::id()).unwrap();
+        assert!(me.is_alive());
+        let usage = me.usage().unwrap();
+        assert_eq!(usage.pid, me.pid);
+        assert!(usage.rss_bytes > 0);
+
+        // Same pid, different process: neither alive nor measured.
+        let recycled = ProcessId {
+            start_time: me.start_time + 1,
+            ..me
+        };
+        assert!(!recycled.is_alive());
+        assert_eq!(recycled.usage(), None);
+    }
+
+    #[tokio::test]
+    async fn adopted_child_can_be_killed() {
+        let mut sleeper = std::process::Command::new("sleep")
+            .arg("60")
+            .spawn()
+            .unwrap();
+        let id = ProcessId::of(sleeper.id()).unwrap();
+
+        // Reap it from another thread, as init would for a real orphan.
+        let reaper = std::thread::spawn(move || sleeper.wait());
+
+        let mut adopted = HypervisorChild::Adopted(id);
+        assert_eq!(adopted.process_id(), Some(id));
+        assert!(adopted.try_wait().is_none());
+
+        adopted.kill().await;
+        reaper.join().unwrap().unwrap();
+        let exit = adopted.try_wait().unwrap();
+        assert_eq!((exit.code, exit.signal), (None, None));
+    }
 }
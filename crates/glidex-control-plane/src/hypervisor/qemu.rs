@@ -1,17 +1,44 @@
-use super::{Hypervisor, HypervisorError, HypervisorProcess, HypervisorType};
-use crate::models::VmConfig;
-use nix::pty::{openpty, OpenptyResult};
+use super::{
+    stop_console, Hypervisor, HypervisorChild, HypervisorError, HypervisorProcess,
+    HypervisorType, ProcessId, MIGRATION_TIMEOUT,
+};
+use crate::console::{ConsoleProxy, Serial};
+use crate::models::{LogPolicy, ReadinessProbe, VmConfig, VmExit};
+use async_trait::async_trait;
 use nix::unistd::setsid;
-use std::fs::{File, OpenOptions};
-use std::io::{BufRead, BufReader, Read, Write};
-use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
-use std::os::unix::net::{UnixListener, UnixStream};
-use std::os::unix::process::CommandExt;
-use std::process::{Child, Command, Stdio};
+use std::fs::OpenOptions;
+use std::future::Future;
+use std::path::Path;
+use std::process::Stdio;
 use std::sync::atomic::{AtomicBool, Ordering};
-use std::sync::{Arc, Mutex};
-use std::thread;
+use std::sync::Mutex;
 use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+use tokio::process::Command;
+use tokio::time::Instant;
+
+/// File name of the migration stream inside a snapshot directory.
+const SNAPSHOT_STATE_FILE: &str = "state";
+
+/// How often `query-migrate` is polled while a migration runs.
+const MIGRATION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long to wait for any single line from QMP.
+const QMP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The socket QEMU serves the guest agent's virtio-serial port on, next
+/// to the QMP socket. Only there for VMs with a `guest_agent` readiness
+/// probe.
+pub fn guest_agent_socket_path(socket_path: &str) -> String {
+    format!("{}.qga", socket_path)
+}
+
+/// Quote `s` for `sh -c`, as used by `exec:` migration URIs.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
 
 /// Client for communicating with QEMU over the QEMU Machine Protocol (QMP)
 /// on a Unix socket. Each command opens a fresh connection, performs the
@@ -20,6 +47,30 @@ pub struct QmpClient {
     socket_path: String,
 }
 
+type QmpReader = BufReader<OwnedReadHalf>;
+
+/// Read one line from QMP within `timeout`. Returns an empty string once
+/// QEMU has closed the connection.
+async fn read_line(reader: &mut QmpReader, timeout: Duration) -> Result<String, HypervisorError> {
+    let mut line = String::new();
+    match tokio::time::timeout(timeout, reader.read_line(&mut line)).await {
+        Ok(Ok(_)) => Ok(line),
+        Ok(Err(e)) => Err(HypervisorError::ProcessStart(e)),
+        Err(_) => Err(HypervisorError::Timeout(format!(
+            "no reply from QMP within {:?}",
+            timeout
+        ))),
+    }
+}
+
+/// Send one command line to QMP.
+async fn send_line(writer: &mut OwnedWriteHalf, command: &str) -> Result<(), HypervisorError> {
+    writer
+        .write_all(format!("{}\r\n", command).as_bytes())
+        .await
+        .map_err(HypervisorError::ProcessStart)
+}
+
 impl QmpClient {
     pub fn new(socket_path: &str) -> Self {
         Self {
@@ -28,37 +79,22 @@ impl QmpClient {
     }
 
     /// Open a QMP connection and complete the qmp_capabilities handshake.
-    fn connect(&self) -> Result<(UnixStream, BufReader<UnixStream>), HypervisorError> {
+    async fn connect(&self) -> Result<(OwnedWriteHalf, QmpReader), HypervisorError> {
         let stream = UnixStream::connect(&self.socket_path)
+            .await
             .map_err(|e| HypervisorError::SocketConnection(e.to_string()))?;
-        stream
-            .set_read_timeout(Some(Duration::from_secs(30)))
-            .map_err(HypervisorError::ProcessStart)?;
-
-        let reader_stream = stream
-            .try_clone()
-            .map_err(HypervisorError::ProcessStart)?;
-        let mut reader = BufReader::new(reader_stream);
+        let (read_half, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(read_half);
 
         // QEMU sends a greeting line on connect.
-        let mut greeting = String::new();
-        reader
-            .read_line(&mut greeting)
-            .map_err(HypervisorError::ProcessStart)?;
+        read_line(&mut reader, QMP_TIMEOUT).await?;
 
-        let mut writer = stream.try_clone().map_err(HypervisorError::ProcessStart)?;
-        writer
-            .write_all(b"{\"execute\":\"qmp_capabilities\"}\r\n")
-            .map_err(HypervisorError::ProcessStart)?;
-        writer.flush().map_err(HypervisorError::ProcessStart)?;
+        send_line(&mut writer, r#"{"execute":"qmp_capabilities"}"#).await?;
 
         // Drain lines until the capabilities reply arrives.
         loop {
-            let mut line = String::new();
-            let n = reader
-                .read_line(&mut line)
-                .map_err(HypervisorError::ProcessStart)?;
-            if n == 0 {
+            let line = read_line(&mut reader, QMP_TIMEOUT).await?;
+            if line.is_empty() {
                 return Err(HypervisorError::ApiRequest(
                     "QMP connection closed during handshake".to_string(),
                 ));
@@ -74,27 +110,21 @@ impl QmpClient {
             }
         }
 
-        Ok((stream, reader))
+        Ok((writer, reader))
     }
 
-    fn execute(&self, command: &str) -> Result<(), HypervisorError> {
-        let (stream, mut reader) = self.connect()?;
-        let mut writer = stream.try_clone().map_err(HypervisorError::ProcessStart)?;
+    async fn execute(&self, command: &str) -> Result<(), HypervisorError> {
+        self.query(command).await.map(|_| ())
+    }
 
-        writer
-            .write_all(command.as_bytes())
-            .map_err(HypervisorError::ProcessStart)?;
-        writer
-            .write_all(b"\r\n")
-            .map_err(HypervisorError::ProcessStart)?;
-        writer.flush().map_err(HypervisorError::ProcessStart)?;
+    /// Like `execute`, but return the `"return"` value of the reply.
+    async fn query(&self, command: &str) -> Result<serde_json::Value, HypervisorError> {
+        let (mut writer, mut reader) = self.connect().await?;
+        send_line(&mut writer, command).await?;
 
         loop {
-            let mut line = String::new();
-            let n = reader
-                .read_line(&mut line)
-                .map_err(HypervisorError::ProcessStart)?;
-            if n == 0 {
+            let line = read_line(&mut reader, QMP_TIMEOUT).await?;
+            if line.is_empty() {
                 return Err(HypervisorError::ApiRequest(
                     "QMP connection closed before reply".to_string(),
                 ));
@@ -103,59 +133,205 @@ impl QmpClient {
                 return Err(HypervisorError::ApiRequest(line.trim().to_string()));
             }
             if line.contains("\"return\"") {
-                return Ok(());
+                let reply: serde_json::Value = serde_json::from_str(&line)
+                    .map_err(|e| HypervisorError::ApiRequest(e.to_string()))?;
+                return Ok(reply["return"].clone());
             }
             // Otherwise it's an asynchronous event — keep reading for the reply.
         }
     }
 
-    pub fn cont(&self) -> Result<(), HypervisorError> {
-        self.execute(r#"{"execute":"cont"}"#)
+    /// Send `command` like `execute`, then keep reading on the same
+    /// connection until QEMU emits `event` or `timeout` passes. QMP only
+    /// delivers events to connected clients, hence the single connection.
+    async fn execute_and_wait_for_event(
+        &self,
+        command: &str,
+        event: &str,
+        timeout: Duration,
+    ) -> Result<(), HypervisorError> {
+        let deadline = Instant::now() + timeout;
+        let (mut writer, mut reader) = self.connect().await?;
+        send_line(&mut writer, command).await?;
+
+        let event_marker = format!("\"event\": \"{}\"", event);
+        let mut replied = false;
+        let mut seen = false;
+        while !(replied && seen) {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+
+            let line = match read_line(&mut reader, remaining).await {
+                Ok(line) if line.is_empty() => {
+                    return Err(HypervisorError::ApiRequest(format!(
+                        "QMP connection closed while waiting for {}",
+                        event
+                    )));
+                }
+                Ok(line) => line,
+                Err(HypervisorError::Timeout(_)) => break,
+                Err(e) => return Err(e),
+            };
+
+            if line.contains(&event_marker) {
+                seen = true;
+            } else if line.contains("\"event\"") {
+                // Some other asynchronous event.
+            } else if line.contains("\"error\"") {
+                return Err(HypervisorError::ApiRequest(line.trim().to_string()));
+            } else if line.contains("\"return\"") {
+                replied = true;
+            }
+        }
+
+        if replied && seen {
+            Ok(())
+        } else {
+            Err(HypervisorError::Timeout(format!(
+                "no {} event from QEMU within {:?}",
+                event, timeout
+            )))
+        }
     }
 
-    pub fn stop(&self) -> Result<(), HypervisorError> {
-        self.execute(r#"{"execute":"stop"}"#)
+    pub async fn cont(&self) -> Result<(), HypervisorError> {
+        self.execute(r#"{"execute":"cont"}"#).await
     }
 
-    pub fn quit(&self) -> Result<(), HypervisorError> {
-        self.execute(r#"{"execute":"quit"}"#)
+    pub async fn stop(&self) -> Result<(), HypervisorError> {
+        self.execute(r#"{"execute":"stop"}"#).await
     }
 
-    pub fn add_vfio_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+    /// Inject an ACPI power-button press.
+    pub async fn system_powerdown(&self) -> Result<(), HypervisorError> {
+        self.execute(r#"{"execute":"system_powerdown"}"#).await
+    }
+
+    pub async fn system_reset(&self) -> Result<(), HypervisorError> {
+        self.execute(r#"{"execute":"system_reset"}"#).await
+    }
+
+    /// Press Ctrl-Alt-Del on the guest keyboard and wait for the guest to
+    /// reset in response.
+    pub async fn ctrl_alt_del_and_wait_for_reset(
+        &self,
+        timeout: Duration,
+    ) -> Result<(), HypervisorError> {
+        self.execute_and_wait_for_event(
+            r#"{"execute":"send-key","arguments":{"keys":[{"type":"qcode","data":"ctrl"},{"type":"qcode","data":"alt"},{"type":"qcode","data":"delete"}]}}"#,
+            "RESET",
+            timeout,
+        )
+        .await
+    }
+
+    /// Choose what a guest reset request does: `"reset"` the guest or
+    /// `"shutdown"` QEMU (what `-no-reboot` selects). Needs QEMU 6.0+.
+    pub async fn set_reboot_action(&self, action: &str) -> Result<(), HypervisorError> {
+        let cmd = format!(
+            r#"{{"execute":"set-action","arguments":{{"reboot":"{}"}}}}"#,
+            action
+        );
+        self.execute(&cmd).await
+    }
+
+    /// Start migrating the VM state out to `uri`.
+    pub async fn migrate(&self, uri: &str) -> Result<(), HypervisorError> {
+        let cmd = serde_json::json!({
+            "execute": "migrate",
+            "arguments": { "uri": uri },
+        });
+        self.execute(&cmd.to_string()).await
+    }
+
+    /// Start reading VM state from `uri`. QEMU must have been launched
+    /// with `-incoming defer`.
+    pub async fn migrate_incoming(&self, uri: &str) -> Result<(), HypervisorError> {
+        let cmd = serde_json::json!({
+            "execute": "migrate-incoming",
+            "arguments": { "uri": uri },
+        });
+        self.execute(&cmd.to_string()).await
+    }
+
+    /// Poll `query-migrate` until the running migration, in either
+    /// direction, completes or fails.
+    pub async fn wait_for_migration(&self, timeout: Duration) -> Result<(), HypervisorError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let info = self.query(r#"{"execute":"query-migrate"}"#).await?;
+            match info["status"].as_str() {
+                Some("completed") => return Ok(()),
+                Some(status @ ("failed" | "cancelled")) => {
+                    return Err(HypervisorError::ApiRequest(format!(
+                        "migration {}: {}",
+                        status,
+                        info["error-desc"].as_str().unwrap_or("no details")
+                    )));
+                }
+                _ => {}
+            }
+            if Instant::now() >= deadline {
+                return Err(HypervisorError::Timeout(format!(
+                    "migration did not complete within {:?}",
+                    timeout
+                )));
+            }
+            tokio::time::sleep(MIGRATION_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Path of the PTY QEMU allocated for `-serial pty`.
+    pub async fn serial_pty_path(&self) -> Result<String, HypervisorError> {
+        let chardevs = self.query(r#"{"execute":"query-chardev"}"#).await?;
+        chardevs
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|dev| dev["label"] == "serial0")
+            .find_map(|dev| dev["filename"].as_str()?.strip_prefix("pty:"))
+            .map(str::to_string)
+            .ok_or_else(|| HypervisorError::ApiRequest("QEMU has no serial PTY".to_string()))
+    }
+
+    pub async fn quit(&self) -> Result<(), HypervisorError> {
+        self.execute(r#"{"execute":"quit"}"#).await
+    }
+
+    pub async fn add_vfio_device(&self, device_path: &str) -> Result<(), HypervisorError> {
         let bdf = vfio_bdf(device_path);
         let id = vfio_device_id(device_path);
         let cmd = format!(
             r#"{{"execute":"device_add","arguments":{{"driver":"vfio-pci","host":"{}","id":"{}"}}}}"#,
             bdf, id
         );
-        self.execute(&cmd)
+        self.execute(&cmd).await
     }
 
-    pub fn remove_vfio_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+    pub async fn remove_vfio_device(&self, device_path: &str) -> Result<(), HypervisorError> {
         let id = vfio_device_id(device_path);
         let cmd = format!(
             r#"{{"execute":"device_del","arguments":{{"id":"{}"}}}}"#,
             id
         );
-        self.execute(&cmd)
+        self.execute(&cmd).await
     }
 }
 
 /// Try to open the QMP socket and read the greeting line. Returns true if
 /// QEMU responded, false if the socket exists but is dead / not yet ready.
-fn probe_qmp(socket_path: &str) -> bool {
-    let Ok(stream) = UnixStream::connect(socket_path) else {
+async fn probe_qmp(socket_path: &str) -> bool {
+    let Ok(stream) = UnixStream::connect(socket_path).await else {
         return false;
     };
-    if stream
-        .set_read_timeout(Some(Duration::from_millis(500)))
-        .is_err()
-    {
-        return false;
-    }
-    let mut reader = BufReader::new(stream);
-    let mut line = String::new();
-    matches!(reader.read_line(&mut line), Ok(n) if n > 0 && line.contains("QMP"))
+    let (read_half, _writer) = stream.into_split();
+    let mut reader = BufReader::new(read_half);
+    matches!(
+        read_line(&mut reader, Duration::from_millis(500)).await,
+        Ok(line) if line.contains("QMP")
+    )
 }
 
 /// Extract the BDF (e.g. "0000:41:00.0") from a sysfs device path.
@@ -170,7 +346,7 @@ fn vfio_bdf(path: &str) -> String {
 /// e.g. "/sys/bus/pci/devices/0000:41:00.0" -> "_vfio_0000_41_00_0"
 fn vfio_device_id(path: &str) -> String {
     let bdf = vfio_bdf(path);
-    format!("_vfio_{}", bdf.replace(':', "_").replace('.', "_"))
+    format!("_vfio_{}", bdf.replace([':', '.'], "_"))
 }
 
 /// QEMU VM instance implementing HypervisorProcess.
@@ -183,54 +359,88 @@ pub struct QemuInstance {
     socket_path: String,
     console_socket_path: String,
     log_path: String,
-    child: Mutex<Option<Child>>,
-    console_thread: Mutex<Option<thread::JoinHandle<()>>>,
-    running: Arc<AtomicBool>,
+    log_policy: LogPolicy,
+    child: Mutex<Option<HypervisorChild>>,
+    console: Mutex<Option<ConsoleProxy>>,
+    running: AtomicBool,
+    /// Whether QEMU was launched with `-no-reboot`.
+    no_reboot: AtomicBool,
     client: QmpClient,
 }
 
 impl QemuInstance {
-    pub fn new(socket_path: &str, console_socket_path: &str, log_path: &str) -> Self {
+    pub fn new(
+        socket_path: &str,
+        console_socket_path: &str,
+        log_path: &str,
+        log_policy: LogPolicy,
+    ) -> Self {
         let client = QmpClient::new(socket_path);
         Self {
             socket_path: socket_path.to_string(),
             console_socket_path: console_socket_path.to_string(),
             log_path: log_path.to_string(),
+            log_policy,
             child: Mutex::new(None),
-            console_thread: Mutex::new(None),
-            running: Arc::new(AtomicBool::new(true)),
+            console: Mutex::new(None),
+            running: AtomicBool::new(true),
+            no_reboot: AtomicBool::new(false),
             client,
         }
     }
 
-    fn launch(&self, config: &VmConfig) -> Result<(), HypervisorError> {
+    /// Take over a QEMU that an earlier control plane launched for
+    /// `config` and whose guest has booted.
+    pub async fn attach(
+        id: ProcessId,
+        config: &VmConfig,
+        socket_path: &str,
+        console_socket_path: &str,
+        log_path: &str,
+    ) -> Result<Self, HypervisorError> {
+        let instance = Self::new(socket_path, console_socket_path, log_path, config.log_policy);
+        *instance.child.lock().unwrap() = Some(HypervisorChild::Adopted(id));
+        instance.no_reboot.store(config.no_reboot, Ordering::SeqCst);
+
+        if !probe_qmp(socket_path).await {
+            return Err(HypervisorError::SocketConnection(format!(
+                "QMP socket {} is not answering",
+                socket_path
+            )));
+        }
+        instance.start_console_proxy().await?;
+        Ok(instance)
+    }
+
+    /// Start qemu-system-x86_64 for `config`, held stopped by `-S`. With
+    /// `incoming` it also waits for a `migrate-incoming` instead of booting.
+    async fn launch(&self, config: &VmConfig, incoming: bool) -> Result<(), HypervisorError> {
         let _ = std::fs::remove_file(&self.socket_path);
         let _ = std::fs::remove_file(&self.console_socket_path);
+        let _ = std::fs::remove_file(guest_agent_socket_path(&self.socket_path));
 
-        let log_file = OpenOptions::new()
+        // QEMU's own messages go straight to the log; the guest serial
+        // is appended by the console proxy. QEMU keeps writing to the
+        // file it was given even after the proxy rotates it.
+        let stderr_log = OpenOptions::new()
             .create(true)
-            .write(true)
-            .truncate(true)
+            .append(true)
             .open(&self.log_path)?;
-
-        let OpenptyResult { master, slave } = openpty(None, None).map_err(|e| {
-            HypervisorError::SocketConnection(format!("Failed to create PTY: {}", e))
-        })?;
-
-        let slave_raw = slave.as_raw_fd();
-        let stdin_fd = unsafe { File::from_raw_fd(libc::dup(slave_raw)) };
-        let stdout_fd = unsafe { File::from_raw_fd(libc::dup(slave_raw)) };
-        let stderr_fd = unsafe { File::from_raw_fd(libc::dup(slave_raw)) };
+        let stdout_log = stderr_log.try_clone()?;
 
         // Note: we deliberately avoid `-nographic` (which forces
         // `-serial mon:stdio` and conflicts with our explicit serial), and
         // avoid `-cpu host` (which fails on hosts where the feature set
         // isn't expressible). `server,nowait` is accepted by both old and
-        // new QEMU, unlike `server=on,wait=off`.
+        // new QEMU, unlike `server=on,wait=off`. The serial port is a PTY
+        // QEMU owns, so a restarted control plane can open it again.
         let mut cmd = Command::new("qemu-system-x86_64");
-        cmd.arg("-enable-kvm")
-            .arg("-no-reboot")
-            .arg("-machine")
+        cmd.arg("-enable-kvm");
+        if config.no_reboot {
+            cmd.arg("-no-reboot");
+        }
+        self.no_reboot.store(config.no_reboot, Ordering::SeqCst);
+        cmd.arg("-machine")
             .arg("q35")
             .arg("-m")
             .arg(format!("{}M", config.mem_size_mib))
@@ -248,10 +458,24 @@ impl QemuInstance {
             .arg("-qmp")
             .arg(format!("unix:{},server,nowait", self.socket_path))
             .arg("-serial")
-            .arg("stdio")
+            .arg("pty")
             .arg("-display")
             .arg("none")
             .arg("-S");
+        if incoming {
+            cmd.arg("-incoming").arg("defer");
+        }
+        if config.readiness == Some(ReadinessProbe::GuestAgent) {
+            cmd.arg("-chardev")
+                .arg(format!(
+                    "socket,id=qga0,path={},server,nowait",
+                    guest_agent_socket_path(&self.socket_path)
+                ))
+                .arg("-device")
+                .arg("virtio-serial")
+                .arg("-device")
+                .arg("virtserialport,chardev=qga0,name=org.qemu.guest_agent.0");
+        }
 
         for device in &config.vfio_devices {
             let bdf = vfio_bdf(device);
@@ -260,10 +484,11 @@ impl QemuInstance {
                 .arg(format!("vfio-pci,host={},id={}", bdf, id));
         }
 
+        // setsid keeps QEMU out of our session, so it outlives us.
         let child = unsafe {
-            cmd.stdin(Stdio::from(stdin_fd))
-                .stdout(Stdio::from(stdout_fd))
-                .stderr(Stdio::from(stderr_fd))
+            cmd.stdin(Stdio::null())
+                .stdout(Stdio::from(stdout_log))
+                .stderr(Stdio::from(stderr_log))
                 .pre_exec(|| {
                     setsid().ok();
                     Ok(())
@@ -271,202 +496,224 @@ impl QemuInstance {
                 .spawn()?
         };
 
-        drop(slave);
-
-        let console_listener = UnixListener::bind(&self.console_socket_path).map_err(|e| {
-            HypervisorError::SocketConnection(format!("Failed to create console socket: {}", e))
-        })?;
-        console_listener.set_nonblocking(true).map_err(|e| {
-            HypervisorError::SocketConnection(format!("Failed to set non-blocking: {}", e))
-        })?;
-
-        let running = self.running.clone();
-        let log_path_clone = self.log_path.clone();
-
-        let console_thread = thread::spawn(move || {
-            Self::console_proxy_loop(
-                master,
-                console_listener,
-                log_file,
-                &log_path_clone,
-                running,
-            );
-        });
-
-        *self.child.lock().unwrap() = Some(child);
-        *self.console_thread.lock().unwrap() = Some(console_thread);
+        *self.child.lock().unwrap() = Some(HypervisorChild::Spawned(child));
 
         // Wait for the QMP socket to become usable. The file existing is
         // not sufficient: if QEMU crashes it leaves an orphaned socket
         // that accepts but immediately resets. Probe the greeting to
         // confirm the process is alive and listening.
         for _ in 0..50 {
-            if let Some(exit_status) = self.child_exit_status() {
+            if let Some(exit) = self.try_wait() {
                 let log = std::fs::read_to_string(&self.log_path).unwrap_or_default();
-                self.cleanup_partial();
-                return Err(HypervisorError::ProcessStart(std::io::Error::new(
-                    std::io::ErrorKind::Other,
+                self.cleanup_partial().await;
+                return Err(HypervisorError::ProcessStart(std::io::Error::other(
                     format!(
-                        "qemu-system-x86_64 exited with {} before QMP was ready.\n--- qemu output ---\n{}",
-                        exit_status,
+                        "qemu-system-x86_64 {} before QMP was ready.\n--- qemu output ---\n{}",
+                        exit,
                         log.trim()
                     ),
                 )));
             }
 
-            if std::path::Path::new(&self.socket_path).exists() && probe_qmp(&self.socket_path) {
+            if Path::new(&self.socket_path).exists() && probe_qmp(&self.socket_path).await {
+                if let Err(e) = self.start_console_proxy().await {
+                    self.cleanup_partial().await;
+                    return Err(e);
+                }
                 return Ok(());
             }
-            std::thread::sleep(Duration::from_millis(100));
+            tokio::time::sleep(Duration::from_millis(100)).await;
         }
 
         let log = std::fs::read_to_string(&self.log_path).unwrap_or_default();
-        self.cleanup_partial();
+        self.cleanup_partial().await;
         Err(HypervisorError::Timeout(format!(
             "QMP socket not ready after timeout.\n--- qemu output ---\n{}",
             log.trim()
         )))
     }
 
-    /// Return the child's exit status if it has already terminated.
-    fn child_exit_status(&self) -> Option<std::process::ExitStatus> {
-        let mut guard = self.child.lock().unwrap();
-        match guard.as_mut()?.try_wait() {
-            Ok(Some(status)) => Some(status),
-            _ => None,
+    /// Run `op` with guest resets actually resetting the guest. Under
+    /// `-no-reboot` QEMU turns every reset request, including QMP
+    /// `system_reset`, into a shutdown, so the reboot action is flipped to
+    /// `reset` for the duration and restored afterwards.
+    async fn with_reboot_action_reset(
+        &self,
+        op: impl Future<Output = Result<(), HypervisorError>>,
+    ) -> Result<(), HypervisorError> {
+        if !self.no_reboot.load(Ordering::SeqCst) {
+            return op.await;
+        }
+
+        self.client.set_reboot_action("reset").await?;
+        let result = op.await;
+        if let Err(e) = self.client.set_reboot_action("shutdown").await {
+            tracing::warn!(
+                socket = %self.socket_path,
+                "Failed to restore -no-reboot behaviour: {}",
+                e
+            );
         }
+        result
+    }
+
+    /// Open the guest's serial PTY and proxy it to the console socket and
+    /// the log.
+    async fn start_console_proxy(&self) -> Result<(), HypervisorError> {
+        let pty_path = self.client.serial_pty_path().await?;
+
+        let pty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&pty_path)
+            .map_err(|e| {
+                HypervisorError::SocketConnection(format!("Failed to open PTY {}: {}", pty_path, e))
+            })?;
+
+        let console = Serial::pty(pty)
+            .and_then(|serial| {
+                ConsoleProxy::spawn(
+                    serial,
+                    &self.console_socket_path,
+                    &self.log_path,
+                    self.log_policy,
+                )
+            })
+            .map_err(|e| {
+                HypervisorError::SocketConnection(format!("Failed to start console proxy: {}", e))
+            })?;
+        *self.console.lock().unwrap() = Some(console);
+        Ok(())
     }
 
-    /// Kill the child and join the console thread. Used on failed launches.
-    fn cleanup_partial(&self) {
+    /// Kill the child and stop the console proxy. Used on failed launches.
+    async fn cleanup_partial(&self) {
         self.running.store(false, Ordering::SeqCst);
-        if let Some(mut child) = self.child.lock().unwrap().take() {
-            let _ = child.kill();
-            let _ = child.wait();
-        }
-        if let Some(handle) = self.console_thread.lock().unwrap().take() {
-            let _ = handle.join();
+        let child = self.child.lock().unwrap().take();
+        if let Some(mut child) = child {
+            child.kill().await;
         }
+        stop_console(&self.console).await;
         let _ = std::fs::remove_file(&self.socket_path);
         let _ = std::fs::remove_file(&self.console_socket_path);
+        let _ = std::fs::remove_file(guest_agent_socket_path(&self.socket_path));
     }
+}
 
-    fn console_proxy_loop(
-        master: OwnedFd,
-        listener: UnixListener,
-        mut log_file: File,
-        log_path: &str,
-        running: Arc<AtomicBool>,
-    ) {
-        let master_raw = master.as_raw_fd();
-        let mut clients: Vec<UnixStream> = Vec::new();
-        let mut buf = [0u8; 4096];
-        // PTY reads EOF once QEMU exits. We don't tear down the listener in
-        // that case — clients should still be able to connect and read the
-        // captured log to see *why* the guest died.
-        let mut pty_alive = true;
-
-        unsafe {
-            let flags = libc::fcntl(master_raw, libc::F_GETFL);
-            libc::fcntl(master_raw, libc::F_SETFL, flags | libc::O_NONBLOCK);
-        }
+#[async_trait]
+impl HypervisorProcess for QemuInstance {
+    async fn configure(&self, config: &VmConfig) -> Result<(), HypervisorError> {
+        self.launch(config, false).await
+    }
 
-        while running.load(Ordering::SeqCst) {
-            if let Ok((stream, _)) = listener.accept() {
-                stream.set_nonblocking(true).ok();
-                if let Ok(mut existing_log) = File::open(log_path) {
-                    let mut log_content = Vec::new();
-                    if existing_log.read_to_end(&mut log_content).is_ok()
-                        && !log_content.is_empty()
-                    {
-                        let mut s = stream.try_clone().unwrap();
-                        let _ = s.write_all(&log_content);
-                    }
-                }
-                clients.push(stream);
-            }
+    async fn start(&self) -> Result<(), HypervisorError> {
+        self.client.cont().await
+    }
 
-            if pty_alive {
-                let master_file = unsafe { File::from_raw_fd(libc::dup(master_raw)) };
-                let mut master_reader = master_file;
-                match master_reader.read(&mut buf) {
-                    Ok(0) => pty_alive = false,
-                    Ok(n) => {
-                        let data = &buf[..n];
-                        let _ = log_file.write_all(data);
-                        let _ = log_file.flush();
-                        clients.retain_mut(|client| client.write_all(data).is_ok());
-                    }
-                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-                    Err(_) => pty_alive = false,
-                }
+    async fn pause(&self) -> Result<(), HypervisorError> {
+        self.client.stop().await
+    }
 
-                for client in &mut clients {
-                    match client.read(&mut buf) {
-                        Ok(0) => {}
-                        Ok(n) => {
-                            let mut master_writer =
-                                unsafe { File::from_raw_fd(libc::dup(master_raw)) };
-                            let _ = master_writer.write_all(&buf[..n]);
-                            let _ = master_writer.flush();
-                        }
-                        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {}
-                        Err(_) => {}
-                    }
-                }
-            }
+    async fn resume(&self) -> Result<(), HypervisorError> {
+        self.client.cont().await
+    }
 
-            thread::sleep(Duration::from_millis(10));
-        }
+    async fn reboot(&self, timeout: Duration) -> Result<(), HypervisorError> {
+        self.with_reboot_action_reset(self.client.ctrl_alt_del_and_wait_for_reset(timeout))
+            .await
     }
-}
 
-impl HypervisorProcess for QemuInstance {
-    fn configure(&self, config: &VmConfig) -> Result<(), HypervisorError> {
-        self.launch(config)
+    async fn reset(&self) -> Result<(), HypervisorError> {
+        self.with_reboot_action_reset(self.client.system_reset())
+            .await
+    }
+
+    async fn snapshot(&self, dir: &Path) -> Result<(), HypervisorError> {
+        let path = dir.join(SNAPSHOT_STATE_FILE);
+        self.client
+            .migrate(&format!(
+                "exec:cat > {}",
+                shell_quote(&path.to_string_lossy())
+            ))
+            .await?;
+        self.client.wait_for_migration(MIGRATION_TIMEOUT).await
+    }
+
+    async fn restore(&self, config: &VmConfig, dir: &Path) -> Result<(), HypervisorError> {
+        // QEMU needs the same command line the snapshot was taken with.
+        self.launch(config, true).await?;
+        let path = dir.join(SNAPSHOT_STATE_FILE);
+        self.client
+            .migrate_incoming(&format!(
+                "exec:cat {}",
+                shell_quote(&path.to_string_lossy())
+            ))
+            .await?;
+        // `-S` keeps the guest paused once the state is loaded.
+        self.client.wait_for_migration(MIGRATION_TIMEOUT).await
     }
 
-    fn start(&self) -> Result<(), HypervisorError> {
-        self.client.cont()
+    async fn send_migration(&self, uri: &str) -> Result<(), HypervisorError> {
+        self.client.migrate(uri).await?;
+        self.client.wait_for_migration(MIGRATION_TIMEOUT).await
     }
 
-    fn pause(&self) -> Result<(), HypervisorError> {
-        self.client.stop()
+    async fn receive_migration(
+        &self,
+        config: &VmConfig,
+        uri: &str,
+    ) -> Result<(), HypervisorError> {
+        self.launch(config, true).await?;
+        // QEMU is listening on `uri` once this returns.
+        self.client.migrate_incoming(uri).await
     }
 
-    fn resume(&self) -> Result<(), HypervisorError> {
-        self.client.cont()
+    async fn finish_migration(&self, timeout: Duration) -> Result<(), HypervisorError> {
+        self.client.wait_for_migration(timeout).await?;
+        // `-S` holds the guest after the last page arrives.
+        self.client.cont().await
     }
 
-    fn kill(&self) -> Result<(), HypervisorError> {
+    async fn kill(&self) -> Result<(), HypervisorError> {
         self.running.store(false, Ordering::SeqCst);
 
-        let _ = self.client.quit();
+        let _ = self.client.quit().await;
 
-        if let Some(mut child) = self.child.lock().unwrap().take() {
-            let _ = child.kill();
-            let _ = child.wait();
+        let child = self.child.lock().unwrap().take();
+        if let Some(mut child) = child {
+            child.kill().await;
         }
 
-        if let Some(handle) = self.console_thread.lock().unwrap().take() {
-            let _ = handle.join();
-        }
+        stop_console(&self.console).await;
 
         let _ = std::fs::remove_file(&self.socket_path);
         let _ = std::fs::remove_file(&self.console_socket_path);
+        let _ = std::fs::remove_file(guest_agent_socket_path(&self.socket_path));
         Ok(())
     }
 
-    fn add_device(&self, device_path: &str) -> Result<(), HypervisorError> {
-        self.client.add_vfio_device(device_path)
+    async fn power_button(&self) -> Result<(), HypervisorError> {
+        self.client.system_powerdown().await
+    }
+
+    async fn add_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+        self.client.add_vfio_device(device_path).await
+    }
+
+    async fn remove_device(&self, device_path: &str) -> Result<(), HypervisorError> {
+        self.client.remove_vfio_device(device_path).await
+    }
+
+    fn try_wait(&self) -> Option<VmExit> {
+        self.child.lock().unwrap().as_mut()?.try_wait()
     }
 
-    fn remove_device(&self, device_path: &str) -> Result<(), HypervisorError> {
-        self.client.remove_vfio_device(device_path)
+    fn process_id(&self) -> Option<ProcessId> {
+        self.child.lock().unwrap().as_ref()?.process_id()
     }
 
     fn is_running(&self) -> bool {
-        self.running.load(Ordering::SeqCst)
+        self.running.load(Ordering::SeqCst) && self.try_wait().is_none()
     }
 
     fn socket_path(&self) -> &str {
@@ -485,9 +732,11 @@ impl HypervisorProcess for QemuInstance {
 /// QEMU backend factory.
 pub struct QemuBackend;
 
+#[async_trait]
 impl Hypervisor for QemuBackend {
-    fn spawn(
+    async fn spawn(
         &self,
+        config: &VmConfig,
         socket_path: &str,
         console_socket_path: &str,
         log_path: &str,
@@ -498,15 +747,29 @@ impl Hypervisor for QemuBackend {
             socket_path,
             console_socket_path,
             log_path,
+            config.log_policy,
         )))
     }
 
+    async fn attach(
+        &self,
+        id: ProcessId,
+        config: &VmConfig,
+        socket_path: &str,
+        console_socket_path: &str,
+        log_path: &str,
+    ) -> Result<Box<dyn HypervisorProcess>, HypervisorError> {
+        Ok(Box::new(
+            QemuInstance::attach(id, config, socket_path, console_socket_path, log_path).await?,
+        ))
+    }
+
     fn hypervisor_type(&self) -> HypervisorType {
         HypervisorType::Qemu
     }
 
     fn is_available(&self) -> bool {
-        Command::new("qemu-system-x86_64")
+        std::process::Command::new("qemu-system-x86_64")
             .arg("--version")
             .output()
             .is_ok()
@@ -1,6 +1,11 @@
 pub mod api;
+pub mod console;
 pub mod hypervisor;
+pub mod logs;
+pub mod migration;
 pub mod models;
 pub mod pci;
 pub mod persistence;
+pub mod readiness;
+pub mod recording;
 pub mod state;
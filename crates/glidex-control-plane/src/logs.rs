@@ -0,0 +1,492 @@
+//! Console log files on disk.
+//!
+//! Each start of a VM begins a new *boot*. The current boot's log is
+//! `log_path`; the console proxy rotates it into numbered segments
+//! (`<log_path>.1` is the newest) once it reaches the VM's
+//! [`LogPolicy::max_bytes`]. Previous boots are kept next to it as
+//! `<stem>.boot-1.log` (the last one), `<stem>.boot-2.log`, … with the
+//! same segment layout. Next to each segment, `<segment>.idx` records
+//! when its lines arrived. See `spec/console.md`.
+
+use crate::models::LogPolicy;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Path of the live file of `boot`: 0 is the current boot, 1 the one
+/// before it, and so on.
+pub fn boot_path(log_path: &Path, boot: u32) -> PathBuf {
+    if boot == 0 {
+        return log_path.to_path_buf();
+    }
+    let log = log_path.to_string_lossy();
+    match log.strip_suffix(".log") {
+        Some(stem) => PathBuf::from(format!("{}.boot-{}.log", stem, boot)),
+        None => PathBuf::from(format!("{}.boot-{}", log, boot)),
+    }
+}
+
+/// Path of rotated segment `n` of a boot's log, 0 being the live file.
+fn segment_path(boot_path: &Path, n: u32) -> PathBuf {
+    if n == 0 {
+        return boot_path.to_path_buf();
+    }
+    let mut path = boot_path.as_os_str().to_owned();
+    path.push(format!(".{}", n));
+    PathBuf::from(path)
+}
+
+/// Path of the timestamp index of a log segment. Each line of it is
+/// `<offset> <unix millis>`: the first line that starts in a chunk of
+/// output, and when the chunk arrived. Lines without an entry of their
+/// own arrived with the line before them.
+fn index_path(segment: &Path) -> PathBuf {
+    let mut path = segment.as_os_str().to_owned();
+    path.push(".idx");
+    PathBuf::from(path)
+}
+
+/// Rename a segment together with its index.
+fn move_segment(from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)?;
+    match fs::rename(index_path(from), index_path(to)) {
+        // Logs written before timestamps were recorded have no index.
+        Err(e) if e.kind() == io::ErrorKind::NotFound => remove_index(to),
+        result => result,
+    }
+}
+
+fn remove_segment(segment: &Path) -> io::Result<()> {
+    fs::remove_file(segment)?;
+    remove_index(segment)
+}
+
+fn remove_index(segment: &Path) -> io::Result<()> {
+    match fs::remove_file(index_path(segment)) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// The segments of a boot's log that exist, with their numbers, newest
+/// first.
+fn segments(boot_path: &Path) -> Vec<(u32, PathBuf)> {
+    let mut segments = Vec::new();
+    if boot_path.exists() {
+        segments.push((0, boot_path.to_path_buf()));
+    }
+    for n in 1.. {
+        let segment = segment_path(boot_path, n);
+        if !segment.exists() {
+            break;
+        }
+        segments.push((n, segment));
+    }
+    segments
+}
+
+/// Begin a new boot: archive the current boot's log as the previous one,
+/// shift older boots back and drop those beyond `policy.keep_boots`.
+pub fn start_boot(log_path: &Path, policy: &LogPolicy) -> io::Result<()> {
+    for boot in (0..=policy.keep_boots).rev() {
+        let from = boot_path(log_path, boot);
+        for (n, segment) in segments(&from) {
+            if boot == policy.keep_boots {
+                remove_segment(&segment)?;
+            } else {
+                move_segment(&segment, &segment_path(&boot_path(log_path, boot + 1), n))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Remove every log file of a VM, including previous boots.
+pub fn remove_all(log_path: &Path) {
+    for boot in 0.. {
+        let path = boot_path(log_path, boot);
+        let segments = segments(&path);
+        // Boot 0 may be missing while older boots exist, e.g. if the VM
+        // was deleted between archiving and its hypervisor starting.
+        if segments.is_empty() && boot > 0 {
+            break;
+        }
+        for (_, segment) in segments {
+            let _ = remove_segment(&segment);
+        }
+    }
+}
+
+/// The whole log of one boot, with when each line arrived.
+pub struct BootLog {
+    pub data: Vec<u8>,
+    /// `(offset into data, unix millis)` in the order of the indexes.
+    stamps: Vec<(usize, u64)>,
+    path: PathBuf,
+    /// The live file, positioned at the end of `data`.
+    live: File,
+}
+
+/// The whole log of `boot`, oldest segment first.
+pub fn read_boot(log_path: &Path, boot: u32) -> io::Result<BootLog> {
+    let path = boot_path(log_path, boot);
+    // Open the live file first: if it is rotated while the older segments
+    // are read, it shows up as `.1` and must not be read twice.
+    let mut live = File::open(&path)?;
+    let live_ino = live.metadata()?.ino();
+
+    let mut log = BootLog {
+        data: Vec::new(),
+        stamps: Vec::new(),
+        path: path.clone(),
+        live: live.try_clone()?,
+    };
+    for (n, segment) in segments(&path).iter().rev() {
+        if *n == 0 {
+            continue;
+        }
+        let mut file = File::open(segment)?;
+        if file.metadata()?.ino() != live_ino {
+            log.append(&mut file, segment)?;
+        }
+    }
+    log.append(&mut live, &path)?;
+    Ok(log)
+}
+
+impl BootLog {
+    fn append(&mut self, file: &mut File, segment: &Path) -> io::Result<()> {
+        let start = self.data.len();
+        file.read_to_end(&mut self.data)?;
+        let index = match fs::read_to_string(index_path(segment)) {
+            Ok(index) => index,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        for entry in index.lines() {
+            let Some((offset, millis)) = entry.split_once(' ') else {
+                continue;
+            };
+            let (Ok(offset), Ok(millis)) = (offset.parse::<usize>(), millis.parse()) else {
+                continue;
+            };
+            // The index is written ahead of the output it describes.
+            if start + offset < self.data.len() {
+                self.stamps.push((start + offset, millis));
+            }
+        }
+        Ok(())
+    }
+
+    /// The suffix of the log holding the lines that arrived at or after
+    /// `since` (unix millis), and of those only the last `tail` lines. A
+    /// trailing line without a newline counts as a line. Lines from before
+    /// timestamps were recorded count as older than any `since`.
+    pub fn select(&self, since: Option<u64>, tail: Option<usize>) -> &[u8] {
+        let mut start = 0;
+        if let Some(since) = since {
+            start = self
+                .stamps
+                .iter()
+                .find(|(_, millis)| *millis >= since)
+                .map_or(self.data.len(), |(offset, _)| *offset);
+        }
+        if let Some(tail) = tail {
+            start = start.max(tail_start(&self.data, tail));
+        }
+        &self.data[start..]
+    }
+
+    /// Follow the log from the end of `data` on.
+    pub fn follow(self) -> LogFollower {
+        LogFollower {
+            path: self.path,
+            file: self.live,
+        }
+    }
+}
+
+/// Offset of the first of the last `lines` lines of `data`.
+fn tail_start(data: &[u8], lines: usize) -> usize {
+    if lines == 0 {
+        return data.len();
+    }
+    let body = data.strip_suffix(b"\n").unwrap_or(data);
+    body.iter()
+        .enumerate()
+        .rev()
+        .filter(|(_, byte)| **byte == b'\n')
+        .nth(lines - 1)
+        .map_or(0, |(newline, _)| newline + 1)
+}
+
+/// Reads what is appended to a boot's live file, carrying on with the new
+/// file when the console proxy rotates it.
+pub struct LogFollower {
+    path: PathBuf,
+    file: File,
+}
+
+impl LogFollower {
+    /// Output appended since the last call, possibly nothing.
+    pub fn read_new(&mut self) -> io::Result<Vec<u8>> {
+        let mut new = Vec::new();
+        self.file.read_to_end(&mut new)?;
+        if !new.is_empty() {
+            return Ok(new);
+        }
+        // Rotated: the proxy is done with a file once a newer one exists,
+        // so drain it one last time and carry on with the next newer one.
+        // Skipping ahead to the live file would lose output when it was
+        // rotated more than once since the last call.
+        let ino = self.file.metadata()?.ino();
+        let segments = segments(&self.path);
+        let ours = segments
+            .iter()
+            .position(|(_, segment)| fs::metadata(segment).is_ok_and(|m| m.ino() == ino));
+        let next = match ours {
+            Some(0) => return Ok(new),
+            Some(ours) => &segments[ours - 1].1,
+            // Dropped by the rotation already.
+            None => &self.path,
+        };
+        match File::open(next) {
+            Ok(file) => {
+                self.file.read_to_end(&mut new)?;
+                self.file = file;
+                Ok(new)
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(new),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The last `max_bytes` of the current boot's log, which may span a
+/// rotation.
+pub fn read_tail(log_path: &Path, max_bytes: u64) -> io::Result<Vec<u8>> {
+    let mut parts = Vec::new();
+    let mut remaining = max_bytes;
+    for (_, segment) in segments(log_path) {
+        if remaining == 0 {
+            break;
+        }
+        let mut file = File::open(&segment)?;
+        let len = file.seek(SeekFrom::End(0))?;
+        let start = len.saturating_sub(remaining);
+        file.seek(SeekFrom::Start(start))?;
+        let mut part = Vec::new();
+        file.read_to_end(&mut part)?;
+        remaining -= part.len() as u64;
+        parts.push(part);
+    }
+    parts.reverse();
+    Ok(parts.concat())
+}
+
+/// Appends console output to the current boot's log and its timestamp
+/// index, rotating both per the VM's [`LogPolicy`]. Clients are replayed
+/// from memory, so rotating never disturbs them.
+pub struct LogWriter {
+    path: PathBuf,
+    policy: LogPolicy,
+    file: File,
+    index: File,
+    at_line_start: bool,
+}
+
+impl LogWriter {
+    pub fn open(path: &Path, policy: LogPolicy) -> io::Result<Self> {
+        let (file, index) = open_segment(path)?;
+        let len = file.metadata()?.len();
+        let mut last = [0];
+        let at_line_start = len == 0 || (file.read_at(&mut last, len - 1)? == 1 && last[0] == b'\n');
+        Ok(Self {
+            path: path.to_path_buf(),
+            policy,
+            file,
+            index,
+            at_line_start,
+        })
+    }
+
+    pub fn write(&mut self, data: &[u8]) -> io::Result<()> {
+        // Not tracked in memory: QEMU appends its own stderr to the file.
+        let mut len = self.file.metadata()?.len();
+        let max = self.policy.max_bytes;
+        if max > 0 && len > 0 && len + data.len() as u64 > max {
+            self.rotate()?;
+            len = 0;
+        }
+
+        let first_line = if self.at_line_start {
+            Some(0)
+        } else {
+            data.iter()
+                .position(|byte| *byte == b'\n')
+                .map(|newline| newline + 1)
+                .filter(|start| *start < data.len())
+        };
+        if let Some(first_line) = first_line {
+            let millis = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis();
+            writeln!(self.index, "{} {}", len + first_line as u64, millis)?;
+        }
+        self.file.write_all(data)?;
+        if let Some(last) = data.last() {
+            self.at_line_start = *last == b'\n';
+        }
+        Ok(())
+    }
+
+    /// Start a new live file, keeping at most `policy.max_files` rotated
+    /// segments.
+    fn rotate(&mut self) -> io::Result<()> {
+        let max_files = self.policy.max_files;
+        for n in (0..=max_files).rev() {
+            let segment = segment_path(&self.path, n);
+            if !segment.exists() {
+                continue;
+            }
+            if n == max_files {
+                remove_segment(&segment)?;
+            } else {
+                move_segment(&segment, &segment_path(&self.path, n + 1))?;
+            }
+        }
+        (self.file, self.index) = open_segment(&self.path)?;
+        Ok(())
+    }
+}
+
+fn open_segment(path: &Path) -> io::Result<(File, File)> {
+    let file = OpenOptions::new()
+        .create(true)
+        .read(true)
+        .append(true)
+        .open(path)?;
+    let index = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(index_path(path))?;
+    Ok((file, index))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn policy(max_bytes: u64, max_files: u32, keep_boots: u32) -> LogPolicy {
+        LogPolicy {
+            max_bytes,
+            max_files,
+            keep_boots,
+            ..LogPolicy::default()
+        }
+    }
+
+    #[test]
+    fn boot_paths_keep_log_extension() {
+        let log = Path::new("/tmp/qemu-abc.log");
+        assert_eq!(boot_path(log, 0), log);
+        assert_eq!(boot_path(log, 2), Path::new("/tmp/qemu-abc.boot-2.log"));
+        assert_eq!(
+            segment_path(&boot_path(log, 1), 3),
+            Path::new("/tmp/qemu-abc.boot-1.log.3")
+        );
+        assert_eq!(boot_path(Path::new("/tmp/console"), 1), Path::new("/tmp/console.boot-1"));
+    }
+
+    #[test]
+    fn writer_rotates_and_caps_segments() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("vm.log");
+        let mut writer = LogWriter::open(&log, policy(10, 2, 0)).unwrap();
+        for line in ["aaaaaaa\n", "bbbbbbb\n", "ccccccc\n", "ddddddd\n"] {
+            writer.write(line.as_bytes()).unwrap();
+        }
+
+        assert_eq!(fs::read(&log).unwrap(), b"ddddddd\n");
+        assert_eq!(fs::read(segment_path(&log, 1)).unwrap(), b"ccccccc\n");
+        assert_eq!(fs::read(segment_path(&log, 2)).unwrap(), b"bbbbbbb\n");
+        assert!(!segment_path(&log, 3).exists());
+        assert_eq!(read_boot(&log, 0).unwrap().data, b"bbbbbbb\nccccccc\nddddddd\n");
+        assert_eq!(read_tail(&log, 12).unwrap(), b"ccc\nddddddd\n");
+    }
+
+    #[test]
+    fn start_boot_archives_previous_boots() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("vm.log");
+        let policy = policy(10, 1, 2);
+
+        for boot in ["first", "second", "third"] {
+            start_boot(&log, &policy).unwrap();
+            let mut writer = LogWriter::open(&log, policy).unwrap();
+            writer.write(format!("{} boot\n", boot).as_bytes()).unwrap();
+            writer.write(b"more\n").unwrap();
+        }
+
+        assert_eq!(read_boot(&log, 0).unwrap().data, b"third boot\nmore\n");
+        assert_eq!(read_boot(&log, 1).unwrap().data, b"second boot\nmore\n");
+        assert_eq!(read_boot(&log, 2).unwrap().data, b"first boot\nmore\n");
+
+        start_boot(&log, &policy).unwrap();
+        assert!(!log.exists());
+        assert_eq!(read_boot(&log, 2).unwrap().data, b"second boot\nmore\n");
+        assert!(matches!(
+            read_boot(&log, 3),
+            Err(e) if e.kind() == io::ErrorKind::NotFound
+        ));
+
+        remove_all(&log);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn select_filters_by_arrival_and_line_count() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("vm.log");
+        let mut writer = LogWriter::open(&log, LogPolicy::default()).unwrap();
+        writer.write(b"old 1\nold 2\npar").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        let since = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis() as u64;
+        writer.write(b"tial\nnew 1\n").unwrap();
+        writer.write(b"new 2\nprompt$ ").unwrap();
+
+        let boot = read_boot(&log, 0).unwrap();
+        assert_eq!(boot.select(None, None), boot.data);
+        // The partial line started before `since`, so it is not included.
+        assert_eq!(boot.select(Some(since), None), b"new 1\nnew 2\nprompt$ ");
+        assert_eq!(boot.select(None, Some(2)), b"new 2\nprompt$ ");
+        assert_eq!(boot.select(Some(since), Some(10)), b"new 1\nnew 2\nprompt$ ");
+        assert_eq!(boot.select(None, Some(0)), b"");
+        assert_eq!(boot.select(Some(u64::MAX), None), b"");
+        assert_eq!(tail_start(b"a\nb\n", 1), 2);
+    }
+
+    #[test]
+    fn follower_continues_across_rotation() {
+        let dir = TempDir::new().unwrap();
+        let log = dir.path().join("vm.log");
+        let mut writer = LogWriter::open(&log, policy(8, 2, 0)).unwrap();
+        writer.write(b"first\n").unwrap();
+
+        let mut follower = read_boot(&log, 0).unwrap().follow();
+        assert_eq!(follower.read_new().unwrap(), b"");
+        writer.write(b"second\n").unwrap();
+        writer.write(b"third\n").unwrap();
+
+        // Rotated twice in between: the follower moves one file per call.
+        let mut followed = Vec::new();
+        for _ in 0..5 {
+            followed.extend(follower.read_new().unwrap());
+        }
+        assert_eq!(followed, b"second\nthird\n");
+    }
+}
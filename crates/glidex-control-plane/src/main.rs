@@ -1,13 +1,19 @@
 mod api;
+mod console;
 mod hypervisor;
+mod logs;
+mod migration;
 mod models;
 mod pci;
 mod persistence;
+mod readiness;
+mod recording;
 mod state;
 
+use clap::Parser;
 use std::io::{self, Write};
 use std::net::SocketAddr;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::signal;
 use tower_http::trace::TraceLayer;
@@ -17,17 +23,25 @@ use crate::state::VmManager;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+#[derive(Parser)]
+#[command(name = "glidex-control-plane")]
+#[command(about = "Glidex VM control plane")]
+struct Args {
+    /// Port to listen on
+    #[arg(short, long, default_value_t = 8080)]
+    port: u16,
+
+    /// Database path. Must differ between control planes on one host.
+    #[arg(long, default_value_os_t = state::VmManager::default_db_path())]
+    db: PathBuf,
+}
+
 fn print_status(msg: &str) {
     print!("  {}... ", msg);
     let _ = io::stdout().flush();
 }
 
-fn print_banner() {
-    let db_path = dirs::home_dir()
-        .unwrap_or_else(|| std::path::PathBuf::from("."))
-        .join(".glidex")
-        .join("glidex.db");
-
+fn print_banner(db_path: &Path) {
     println!();
     println!("  ╔═══════════════════════════════════════════╗");
     println!("  ║            GlideX Control Plane           ║");
@@ -72,8 +86,10 @@ fn check_kvm_access() -> Result<(), String> {
 
 #[tokio::main]
 async fn main() {
+    let args = Args::parse();
+
     // Print startup banner
-    print_banner();
+    print_banner(&args.db);
 
     // Initialize tracing
     tracing_subscriber::registry()
@@ -95,7 +111,7 @@ async fn main() {
 
     // Create VM manager with persistence
     print_status("Opening database");
-    let vm_manager = match state::VmManager::new() {
+    let vm_manager = match state::VmManager::with_db_path(args.db) {
         Ok(manager) => manager,
         Err(e) => {
             println!("FAILED");
@@ -115,6 +131,10 @@ async fn main() {
     let vm_count = vm_manager.list_vms().await.len();
     println!("OK ({} VMs)", vm_count);
 
+    // Watch hypervisor processes so guests that die on their own don't stay
+    // "running" forever
+    vm_manager.spawn_supervisor();
+
     // Clone vm_manager for the shutdown handler before passing to router
     let vm_manager_shutdown = Arc::clone(&vm_manager);
 
@@ -122,7 +142,7 @@ async fn main() {
     let app = api::create_router(vm_manager).layer(TraceLayer::new_for_http());
 
     // Start server
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
+    let addr = SocketAddr::from(([0, 0, 0, 0], args.port));
     println!();
     println!("  Listening on http://{}", addr);
     println!("  Press Ctrl+C to shutdown");
@@ -160,9 +180,9 @@ async fn shutdown_signal(vm_manager: Arc<VmManager>) {
     }
 
     println!();
-    tracing::info!("Shutdown signal received, stopping VMs...");
+    tracing::info!("Shutdown signal received, detaching from VMs...");
 
-    // Stop all running hypervisor processes
+    // Running guests are left alone and reattached on the next start
     vm_manager.shutdown().await;
 
     tracing::info!("Shutdown complete");
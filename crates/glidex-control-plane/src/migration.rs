@@ -0,0 +1,83 @@
+//! Client side of the control-plane to control-plane migration protocol.
+//!
+//! The source `VmManager` drives a live migration by calling these
+//! endpoints on the target control plane:
+//!
+//! 1. `POST /v1/migrations/incoming` — create the VM record and start a
+//!    hypervisor waiting for the migration stream; returns the URI to
+//!    send it to.
+//! 2. `POST /v1/migrations/incoming/{id}/complete` — after the source
+//!    hypervisor has sent everything, wait for the target to take over and
+//!    mark the VM running.
+//! 3. `DELETE /v1/migrations/incoming/{id}` — give up; the target kills its
+//!    hypervisor and forgets the VM.
+
+use crate::models::{IncomingMigrationRequest, VmConfig};
+use glidex_client::{Client, Error};
+use std::time::Duration;
+
+/// Generous enough for `complete`, which waits for the target hypervisor.
+const PEER_TIMEOUT: Duration = Duration::from_secs(90);
+
+pub struct PeerClient {
+    client: Client,
+}
+
+impl PeerClient {
+    pub fn new(base_url: &str) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(PEER_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            client: Client::with_http_client(base_url, http),
+        }
+    }
+
+    pub fn base_url(&self) -> &str {
+        self.client.base_url()
+    }
+
+    /// Ask the target to get ready for `id`. Returns the hypervisor
+    /// migration URI to send to.
+    pub async fn prepare(&self, id: &str, name: &str, config: &VmConfig) -> Result<String, String> {
+        let request = IncomingMigrationRequest {
+            id: id.to_string(),
+            name: name.to_string(),
+            config: config.clone(),
+        };
+        self.client
+            .prepare_incoming_migration(&request)
+            .await
+            .map(|response| response.uri)
+            .map_err(|e| self.describe(e))
+    }
+
+    /// Tell the target the stream has been sent and wait for it to resume
+    /// the guest.
+    pub async fn complete(&self, id: &str) -> Result<(), String> {
+        self.client
+            .complete_incoming_migration(id)
+            .await
+            .map(drop)
+            .map_err(|e| self.describe(e))
+    }
+
+    /// Cancel an incoming migration on the target. Best effort.
+    pub async fn abort(&self, id: &str) {
+        if let Err(e) = self.client.abort_incoming_migration(id).await {
+            tracing::warn!(vm_id = %id, "Failed to abort migration: {}", self.describe(e));
+        }
+    }
+
+    /// The target's error message, or why it could not be asked.
+    fn describe(&self, error: Error) -> String {
+        match error {
+            Error::Api { status, error } => {
+                format!("target returned {}: {}", status, error.message)
+            }
+            Error::Decode(e) => format!("invalid response from {}: {}", self.base_url(), e),
+            e => format!("{}: {}", self.base_url(), e),
+        }
+    }
+}
@@ -1,7 +1,19 @@
-use crate::hypervisor::HypervisorType;
+//! The control plane's records of VMs and snapshots. The API's request
+//! and response bodies are shared with clients and live in
+//! `glidex_client::models`; they are re-exported here.
+
+use crate::hypervisor::{HypervisorType, ProcessId};
 use serde::{Deserialize, Serialize};
 use uuid::Uuid;
 
+use glidex_client::models::default_no_reboot;
+pub use glidex_client::models::{
+    ApiError, ConsoleInfo, ConsoleSession, CreateSnapshotRequest, CreateVmRequest, DeviceRequest,
+    ExpectRequest, ExpectResponse, IncomingMigrationRequest, IncomingMigrationResponse, LogPolicy,
+    MigrateVmRequest, MigrateVmResponse, ReadinessProbe, RestoreVmRequest, SnapshotResponse,
+    StartQuery, StartWait, StopVmRequest, UpdateVmRequest, VmConfig, VmExit, VmResponse, VmState,
+};
+
 /// Expand a leading `~` or `~/` to the user's home directory. Hypervisors
 /// don't do shell-style expansion themselves, so paths like
 /// `~/.glidex/rootfs.ext4` need to be resolved before being passed to
@@ -20,28 +32,6 @@ fn expand_tilde(path: String) -> String {
     path
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
-#[serde(rename_all = "lowercase")]
-pub enum VmState {
-    Created,
-    Running,
-    Paused,
-    Stopped,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct VmConfig {
-    pub vcpu_count: u8,
-    pub mem_size_mib: u32,
-    pub kernel_image_path: String,
-    pub rootfs_path: String,
-    pub kernel_args: String,
-    #[serde(default)]
-    pub hypervisor: HypervisorType,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub vfio_devices: Vec<String>,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vm {
     pub id: String,
@@ -52,16 +42,49 @@ pub struct Vm {
     pub console_socket_path: String,
     pub log_path: String,
     pub hypervisor: HypervisorType,
+    /// Set when the hypervisor exited on its own; cleared on the next start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_exit: Option<VmExit>,
+    /// Set when a start attempt failed; cleared on the next start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_error: Option<String>,
+    /// The hypervisor process while there is one, so a restarted control
+    /// plane can reattach to it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hypervisor_pid: Option<ProcessId>,
+    /// The current boot's guest passed its readiness probe. Only
+    /// meaningful while the VM is running or paused; see `is_ready`.
+    #[serde(default)]
+    pub ready: bool,
+    /// When the current (or last) boot began, in milliseconds since the
+    /// Unix epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_started_at: Option<u64>,
+    /// How long that boot took to pass the readiness probe.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub boot_duration_ms: Option<u64>,
 }
 
 impl Vm {
     pub fn new(name: String, config: VmConfig) -> Self {
         let id = Uuid::new_v4().to_string();
+        Self::with_paths(id.clone(), name, config, &id)
+    }
+
+    /// The record for a VM arriving by live migration. It keeps its id, but
+    /// gets fresh runtime paths: the source's hypervisor still owns the old
+    /// ones until the migration completes, possibly on this same host.
+    pub fn incoming(id: String, name: String, config: VmConfig) -> Self {
+        let stem = Uuid::new_v4().to_string();
+        Self::with_paths(id, name, config, &stem)
+    }
+
+    fn with_paths(id: String, name: String, config: VmConfig, stem: &str) -> Self {
         let hypervisor = config.hypervisor;
         let prefix = hypervisor.socket_prefix();
-        let socket_path = format!("/tmp/{}-{}.sock", prefix, id);
-        let console_socket_path = format!("/tmp/{}-{}.console.sock", prefix, id);
-        let log_path = format!("/tmp/{}-{}.log", prefix, id);
+        let socket_path = format!("/tmp/{}-{}.sock", prefix, stem);
+        let console_socket_path = format!("/tmp/{}-{}.console.sock", prefix, stem);
+        let log_path = format!("/tmp/{}-{}.log", prefix, stem);
         Self {
             id,
             name,
@@ -71,54 +94,77 @@ impl Vm {
             console_socket_path,
             log_path,
             hypervisor,
+            last_exit: None,
+            last_error: None,
+            hypervisor_pid: None,
+            ready: false,
+            boot_started_at: None,
+            boot_duration_ms: None,
         }
     }
-}
 
-#[derive(Debug, Deserialize)]
-pub struct CreateVmRequest {
-    pub name: String,
-    pub vcpu_count: u8,
-    pub mem_size_mib: u32,
-    pub kernel_image_path: String,
-    pub rootfs_path: String,
-    #[serde(default)]
-    pub kernel_args: Option<String>,
-    #[serde(default)]
-    pub hypervisor: Option<HypervisorType>,
-    #[serde(default)]
-    pub vfio_devices: Option<Vec<String>>,
-}
+    /// Whether the guest is up and has passed its readiness probe.
+    pub fn is_ready(&self) -> bool {
+        self.ready && matches!(self.state, VmState::Running | VmState::Paused)
+    }
 
-impl From<CreateVmRequest> for VmConfig {
-    fn from(req: CreateVmRequest) -> Self {
-        let hypervisor = req.hypervisor.unwrap_or_default();
-        VmConfig {
-            vcpu_count: req.vcpu_count,
-            mem_size_mib: req.mem_size_mib,
-            kernel_image_path: expand_tilde(req.kernel_image_path),
-            rootfs_path: expand_tilde(req.rootfs_path),
-            kernel_args: req
-                .kernel_args
-                .unwrap_or_else(|| hypervisor.default_kernel_args().to_string()),
-            hypervisor,
-            vfio_devices: req.vfio_devices.unwrap_or_default(),
+    /// How long the guest has been up, while it is running or paused.
+    pub fn uptime_secs(&self) -> Option<u64> {
+        if !matches!(self.state, VmState::Running | VmState::Paused) {
+            return None;
         }
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .ok()?
+            .as_millis() as u64;
+        Some(now.saturating_sub(self.boot_started_at?) / 1000)
     }
 }
 
-#[derive(Debug, Serialize)]
-pub struct VmResponse {
-    pub id: String,
-    pub name: String,
-    pub state: VmState,
-    pub vcpu_count: u8,
-    pub mem_size_mib: u32,
-    pub console_socket_path: String,
-    pub log_path: String,
-    pub hypervisor: HypervisorType,
-    #[serde(default, skip_serializing_if = "Vec::is_empty")]
-    pub vfio_devices: Vec<String>,
+/// The config of a VM created by `POST /vms`, with the server's
+/// defaults filled in and `~` expanded on this host.
+pub fn new_config(req: CreateVmRequest) -> VmConfig {
+    let hypervisor = req.hypervisor.unwrap_or_default();
+    VmConfig {
+        vcpu_count: req.vcpu_count,
+        mem_size_mib: req.mem_size_mib,
+        kernel_image_path: expand_tilde(req.kernel_image_path),
+        rootfs_path: expand_tilde(req.rootfs_path),
+        kernel_args: req
+            .kernel_args
+            .unwrap_or_else(|| hypervisor.default_kernel_args().to_string()),
+        hypervisor,
+        vfio_devices: req.vfio_devices.unwrap_or_default(),
+        no_reboot: req.no_reboot.unwrap_or_else(default_no_reboot),
+        log_policy: req.log_policy.unwrap_or_default(),
+        readiness: req.readiness,
+    }
+}
+
+/// Apply a `PATCH /vms/{id}` to `config`: change what `update` sets, keep
+/// the rest.
+pub fn update_config(config: &mut VmConfig, update: UpdateVmRequest) {
+    if let Some(vcpu_count) = update.vcpu_count {
+        config.vcpu_count = vcpu_count;
+    }
+    if let Some(mem_size_mib) = update.mem_size_mib {
+        config.mem_size_mib = mem_size_mib;
+    }
+    if let Some(path) = update.kernel_image_path {
+        config.kernel_image_path = expand_tilde(path);
+    }
+    if let Some(path) = update.rootfs_path {
+        config.rootfs_path = expand_tilde(path);
+    }
+    if let Some(kernel_args) = update.kernel_args {
+        config.kernel_args = kernel_args;
+    }
+    if let Some(vfio_devices) = update.vfio_devices {
+        config.vfio_devices = vfio_devices;
+    }
+    if let Some(no_reboot) = update.no_reboot {
+        config.no_reboot = no_reboot;
+    }
 }
 
 impl From<&Vm> for VmResponse {
@@ -129,30 +175,73 @@ impl From<&Vm> for VmResponse {
             state: vm.state.clone(),
             vcpu_count: vm.config.vcpu_count,
             mem_size_mib: vm.config.mem_size_mib,
+            kernel_image_path: vm.config.kernel_image_path.clone(),
+            rootfs_path: vm.config.rootfs_path.clone(),
+            kernel_args: vm.config.kernel_args.clone(),
+            no_reboot: vm.config.no_reboot,
             console_socket_path: vm.console_socket_path.clone(),
             log_path: vm.log_path.clone(),
             hypervisor: vm.hypervisor,
             vfio_devices: vm.config.vfio_devices.clone(),
+            log_policy: vm.config.log_policy,
+            readiness: vm.config.readiness.clone(),
+            ready: vm.is_ready(),
+            boot_duration_ms: vm.boot_duration_ms,
+            uptime_secs: vm.uptime_secs(),
+            process: vm.hypervisor_pid.and_then(|id| id.usage()),
+            last_exit: vm.last_exit.clone(),
+            last_error: vm.last_error.clone(),
         }
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct DeviceRequest {
-    pub device_path: String,
+/// A checkpoint of a paused VM's memory and device state, stored as a
+/// directory of hypervisor-specific files. Disks are not part of it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub id: String,
+    pub vm_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// Seconds since the Unix epoch.
+    pub created_at: u64,
+    pub path: String,
+    pub hypervisor: HypervisorType,
+    /// The VM config at snapshot time; a restore must launch the
+    /// hypervisor with exactly this.
+    pub config: VmConfig,
 }
 
-#[derive(Debug, Serialize)]
-pub struct ApiError {
-    pub error: String,
-    pub message: String,
+impl Snapshot {
+    /// A new snapshot of `vm`, stored in its own directory under `root`.
+    pub fn new(vm: &Vm, name: Option<String>, root: &std::path::Path) -> Self {
+        let id = Uuid::new_v4().to_string();
+        let path = root.join(&id).to_string_lossy().into_owned();
+        let created_at = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Self {
+            id,
+            vm_id: vm.id.clone(),
+            name,
+            created_at,
+            path,
+            hypervisor: vm.hypervisor,
+            config: vm.config.clone(),
+        }
+    }
 }
 
-impl ApiError {
-    pub fn new(error: impl Into<String>, message: impl Into<String>) -> Self {
-        Self {
-            error: error.into(),
-            message: message.into(),
+impl From<&Snapshot> for SnapshotResponse {
+    fn from(snapshot: &Snapshot) -> Self {
+        SnapshotResponse {
+            id: snapshot.id.clone(),
+            vm_id: snapshot.vm_id.clone(),
+            name: snapshot.name.clone(),
+            created_at: snapshot.created_at,
+            path: snapshot.path.clone(),
+            hypervisor: snapshot.hypervisor,
         }
     }
 }
@@ -1,19 +1,9 @@
-use serde::Serialize;
 use std::fs;
 use std::path::Path;
 
-const PCI_DEVICES_PATH: &str = "/sys/bus/pci/devices";
+pub use glidex_client::models::PciDeviceInfo;
 
-#[derive(Debug, Clone, Serialize)]
-pub struct PciDeviceInfo {
-    pub address: String,
-    pub vendor_id: String,
-    pub device_id: String,
-    pub class_id: String,
-    pub driver: Option<String>,
-    pub iommu_group: Option<String>,
-    pub sysfs_path: String,
-}
+const PCI_DEVICES_PATH: &str = "/sys/bus/pci/devices";
 
 /// Read a sysfs attribute file, returning trimmed contents.
 fn read_sysfs_attr(device_path: &Path, attr: &str) -> Option<String> {
@@ -1,9 +1,10 @@
-use crate::models::{Vm, VmState};
+use crate::models::{Snapshot, Vm, VmState};
 use redb::{Database, ReadableDatabase, ReadableTable, TableDefinition};
 use std::path::Path;
 use thiserror::Error;
 
 const VMS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("vms");
+const SNAPSHOTS_TABLE: TableDefinition<&str, &[u8]> = TableDefinition::new("snapshots");
 
 #[derive(Error, Debug)]
 pub enum PersistenceError {
@@ -30,6 +31,9 @@ pub enum PersistenceError {
 
     #[error("VM not found: {0}")]
     VmNotFound(String),
+
+    #[error("Snapshot not found: {0}")]
+    SnapshotNotFound(String),
 }
 
 pub struct VmStore {
@@ -46,10 +50,11 @@ impl VmStore {
 
         let db = Database::create(path)?;
 
-        // Initialize table on first run
+        // Initialize tables on first run
         let write_txn = db.begin_write()?;
         {
             let _ = write_txn.open_table(VMS_TABLE)?;
+            let _ = write_txn.open_table(SNAPSHOTS_TABLE)?;
         }
         write_txn.commit()?;
 
@@ -119,4 +124,59 @@ impl VmStore {
 
         Ok(())
     }
+
+    /// Save or update a snapshot record
+    pub fn save_snapshot(&self, snapshot: &Snapshot) -> Result<(), PersistenceError> {
+        let serialized = serde_json::to_vec(snapshot)?;
+
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(SNAPSHOTS_TABLE)?;
+            table.insert(snapshot.id.as_str(), serialized.as_slice())?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
+
+    /// Look up a snapshot by ID
+    pub fn get_snapshot(&self, snapshot_id: &str) -> Result<Snapshot, PersistenceError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(SNAPSHOTS_TABLE)?;
+
+        let value = table
+            .get(snapshot_id)?
+            .ok_or_else(|| PersistenceError::SnapshotNotFound(snapshot_id.to_string()))?;
+        Ok(serde_json::from_slice(value.value())?)
+    }
+
+    /// Load all snapshots taken of a VM, oldest first
+    pub fn load_snapshots(&self, vm_id: &str) -> Result<Vec<Snapshot>, PersistenceError> {
+        let read_txn = self.db.begin_read()?;
+        let table = read_txn.open_table(SNAPSHOTS_TABLE)?;
+
+        let mut snapshots = Vec::new();
+        for result in table.iter()? {
+            let (_, value): (_, redb::AccessGuard<'_, &[u8]>) = result?;
+            let snapshot: Snapshot = serde_json::from_slice(value.value())?;
+            if snapshot.vm_id == vm_id {
+                snapshots.push(snapshot);
+            }
+        }
+        snapshots.sort_by_key(|s| s.created_at);
+
+        Ok(snapshots)
+    }
+
+    /// Delete a snapshot record by ID
+    pub fn delete_snapshot(&self, snapshot_id: &str) -> Result<(), PersistenceError> {
+        let write_txn = self.db.begin_write()?;
+        {
+            let mut table = write_txn.open_table(SNAPSHOTS_TABLE)?;
+            table.remove(snapshot_id)?;
+        }
+        write_txn.commit()?;
+
+        Ok(())
+    }
 }
@@ -0,0 +1,274 @@
+//! Readiness probes: telling a guest that has booted apart from one whose
+//! hypervisor is merely running.
+//!
+//! `VmManager` starts a probe whenever a guest boots or reboots and
+//! records when it passes. A probe retries until then; it only gives up
+//! once the guest's console closes, i.e. the hypervisor is gone.
+
+use crate::console::{self, ExpectError, Replay};
+use crate::hypervisor::qemu::guest_agent_socket_path;
+use crate::hypervisor::HypervisorType;
+use crate::models::{ReadinessProbe, Vm};
+use regex_automata::meta::Regex;
+use std::future::Future;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpStream, UnixStream};
+
+/// How often TCP and guest-agent probes are retried.
+const PROBE_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long one TCP connect or guest-agent exchange may take.
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Check that `probe` can be evaluated for a VM on `hypervisor`.
+pub fn validate(probe: &ReadinessProbe, hypervisor: HypervisorType) -> Result<(), String> {
+    match probe {
+        ReadinessProbe::Console { pattern } => Regex::new(pattern)
+            .map(drop)
+            .map_err(|e| format!("invalid readiness pattern '{}': {}", pattern, e)),
+        ReadinessProbe::Tcp { host, port } if host.is_empty() || *port == 0 => {
+            Err("tcp readiness probe needs a host and a non-zero port".to_string())
+        }
+        ReadinessProbe::GuestAgent if hypervisor != HypervisorType::Qemu => Err(format!(
+            "guest_agent readiness probe is only supported on qemu, not {}",
+            hypervisor
+        )),
+        ReadinessProbe::Tcp { .. } | ReadinessProbe::GuestAgent => Ok(()),
+    }
+}
+
+/// Wait until the guest of `vm` passes `probe`. `replay` is how much of
+/// the console scrollback a console probe searches. Fails if the
+/// guest's console closes first.
+pub async fn wait_until_ready(
+    probe: &ReadinessProbe,
+    vm: &Vm,
+    replay: Replay,
+) -> Result<(), String> {
+    let console_socket = Path::new(&vm.console_socket_path);
+    match probe {
+        ReadinessProbe::Console { pattern } => {
+            let pattern = Regex::new(pattern).map_err(|e| e.to_string())?;
+            match console::expect(console_socket, b"", &pattern, Duration::MAX, replay).await {
+                Ok(_) => Ok(()),
+                Err(ExpectError::Io(e)) => Err(format!("console connection failed: {}", e)),
+                Err(ExpectError::TimedOut { .. } | ExpectError::GuestExited { .. }) => {
+                    Err("guest exited before it was ready".to_string())
+                }
+            }
+        }
+        ReadinessProbe::Tcp { host, port } => {
+            retry_until_exit(console_socket, || connect_tcp(host, *port)).await
+        }
+        ReadinessProbe::GuestAgent => {
+            let socket_path = guest_agent_socket_path(&vm.socket_path);
+            retry_until_exit(console_socket, || ping_guest_agent(&socket_path)).await
+        }
+    }
+}
+
+/// Run `attempt` every `PROBE_INTERVAL` until it succeeds, unless the
+/// console at `console_socket` closes first.
+async fn retry_until_exit<F, Fut>(console_socket: &Path, mut attempt: F) -> Result<(), String>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = bool>,
+{
+    let retrying = async {
+        while !attempt().await {
+            tokio::time::sleep(PROBE_INTERVAL).await;
+        }
+    };
+    tokio::select! {
+        () = retrying => Ok(()),
+        exited = console::wait_for_exit(console_socket) => Err(match exited {
+            Ok(()) => "guest exited before it was ready".to_string(),
+            Err(e) => format!("console connection failed: {}", e),
+        }),
+    }
+}
+
+async fn connect_tcp(host: &str, port: u16) -> bool {
+    matches!(
+        tokio::time::timeout(ATTEMPT_TIMEOUT, TcpStream::connect((host, port))).await,
+        Ok(Ok(_))
+    )
+}
+
+/// Ping the QEMU guest agent behind `socket_path`. The channel buffers
+/// whatever was sent while no agent was listening, and the agent may
+/// answer it late, so the ping is preceded by a `guest-sync-delimited`
+/// with a fresh id: replies before its answer are stale.
+async fn ping_guest_agent(socket_path: &str) -> bool {
+    let id = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.subsec_nanos())
+        .unwrap_or_default();
+    let exchange = async {
+        let stream = UnixStream::connect(socket_path).await.ok()?;
+        let (read_half, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(read_half);
+
+        // A leading 0xFF resets the agent's parser after a partial
+        // request; the sync reply is prefixed with one in turn.
+        let sync = serde_json::json!({
+            "execute": "guest-sync-delimited",
+            "arguments": { "id": id },
+        });
+        let mut request = vec![0xff];
+        request.extend(format!("{}\n{{\"execute\":\"guest-ping\"}}\n", sync).bytes());
+        writer.write_all(&request).await.ok()?;
+
+        let mut synced = false;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line).await.ok()? == 0 {
+                return None;
+            }
+            let start = line.iter().rposition(|&b| b == 0xff).map_or(0, |i| i + 1);
+            let Ok(reply) = serde_json::from_slice::<serde_json::Value>(&line[start..]) else {
+                continue;
+            };
+            if synced {
+                return Some(reply.get("return").is_some());
+            }
+            synced = reply["return"] == id;
+        }
+    };
+    tokio::time::timeout(ATTEMPT_TIMEOUT, exchange)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::VmConfig;
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpListener, UnixListener};
+
+    fn test_vm(dir: &TempDir) -> Vm {
+        let config = VmConfig {
+            vcpu_count: 1,
+            mem_size_mib: 256,
+            kernel_image_path: "/path/to/kernel".to_string(),
+            rootfs_path: "/path/to/rootfs.ext4".to_string(),
+            kernel_args: String::new(),
+            hypervisor: HypervisorType::Qemu,
+            vfio_devices: Vec::new(),
+            no_reboot: true,
+            log_policy: Default::default(),
+            readiness: Some(ReadinessProbe::GuestAgent),
+        };
+        let mut vm = Vm::new("probed".to_string(), config);
+        vm.socket_path = dir.path().join("vm.sock").to_string_lossy().into_owned();
+        vm.console_socket_path = dir.path().join("console.sock").to_string_lossy().into_owned();
+        vm
+    }
+
+    #[test]
+    fn validate_rejects_unusable_probes() {
+        let console = |pattern: &str| ReadinessProbe::Console {
+            pattern: pattern.to_string(),
+        };
+        assert!(validate(&console("login: $"), HypervisorType::Firecracker).is_ok());
+        assert!(validate(&console("login: ("), HypervisorType::Qemu).is_err());
+
+        let tcp = ReadinessProbe::Tcp {
+            host: "10.0.0.2".to_string(),
+            port: 0,
+        };
+        assert!(validate(&tcp, HypervisorType::Qemu).is_err());
+
+        assert!(validate(&ReadinessProbe::GuestAgent, HypervisorType::Qemu).is_ok());
+        assert!(validate(&ReadinessProbe::GuestAgent, HypervisorType::CloudHypervisor).is_err());
+    }
+
+    #[tokio::test]
+    async fn tcp_probe_retries_until_the_port_opens_and_stops_with_the_console() {
+        let dir = TempDir::new().unwrap();
+        let vm = test_vm(&dir);
+        let console = UnixListener::bind(&vm.console_socket_path).unwrap();
+
+        // Find a free port, then leave it closed for a while.
+        let port = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let probe = ReadinessProbe::Tcp {
+            host: "127.0.0.1".to_string(),
+            port,
+        };
+        let opening = async {
+            let (held, _) = console.accept().await.unwrap();
+            tokio::time::sleep(PROBE_INTERVAL * 2).await;
+            (held, TcpListener::bind(("127.0.0.1", port)).await.unwrap())
+        };
+        let (ready, _open) = tokio::join!(wait_until_ready(&probe, &vm, Replay::None), opening);
+        assert_eq!(ready, Ok(()));
+
+        // The hypervisor going away ends the probe.
+        let probe = ReadinessProbe::Tcp {
+            host: "127.0.0.1".to_string(),
+            port: 1,
+        };
+        let closing = async {
+            drop(console.accept().await.unwrap());
+        };
+        let (ready, ()) = tokio::join!(wait_until_ready(&probe, &vm, Replay::None), closing);
+        assert!(ready.unwrap_err().contains("exited"));
+    }
+
+    #[tokio::test]
+    async fn guest_agent_ping_skips_stale_replies() {
+        let dir = TempDir::new().unwrap();
+        let vm = test_vm(&dir);
+        let agent_path = guest_agent_socket_path(&vm.socket_path);
+        let agent = UnixListener::bind(&agent_path).unwrap();
+
+        let answering = async {
+            let (mut stream, _) = agent.accept().await.unwrap();
+            let mut request = Vec::new();
+            while request.iter().filter(|&&b| b == b'\n').count() < 2 {
+                let mut buf = [0u8; 256];
+                let n = stream.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            assert_eq!(request[0], 0xff);
+            let sync: serde_json::Value = serde_json::from_slice(
+                request[1..].split(|&b| b == b'\n').next().unwrap(),
+            )
+            .unwrap();
+            let id = &sync["arguments"]["id"];
+            let mut reply = b"{\"return\": {}}\n".to_vec();
+            reply.push(0xff);
+            reply.extend(format!("{{\"return\": {}}}\n{{\"return\": {{}}}}\n", id).bytes());
+            stream.write_all(&reply).await.unwrap();
+        };
+        let (alive, ()) = tokio::join!(
+            ping_guest_agent(&agent_path),
+            answering
+        );
+        assert!(alive);
+
+        // Nobody answering is a failed attempt, not a hang.
+        let silent = async {
+            let (stream, _) = agent.accept().await.unwrap();
+            tokio::time::sleep(ATTEMPT_TIMEOUT * 2).await;
+            drop(stream);
+        };
+        let (alive, ()) = tokio::join!(
+            ping_guest_agent(&agent_path),
+            silent
+        );
+        assert!(!alive);
+    }
+}
@@ -0,0 +1,331 @@
+//! Console session recordings, in asciicast v2 format.
+//!
+//! The console proxy records every client session that does not opt out
+//! in its handshake: what the client was sent (`o` events, including the
+//! replay), what it typed (`i`), its terminal resizes (`r`) and, as
+//! markers (`m`), breaks and the guest exiting. Each session is one
+//! `<id>.cast` file in a directory next to the VM's log; the oldest are
+//! deleted beyond the log policy's `keep_sessions`.
+
+use crate::models::{ConsoleSession, LogPolicy};
+use serde_json::{json, Value};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Terminal size in a recording's header. Clients report theirs, if at
+/// all, with a resize after connecting.
+const DEFAULT_COLS: u16 = 80;
+const DEFAULT_ROWS: u16 = 24;
+
+/// How much of the end of a recording is read to find its duration.
+const TAIL_BYTES: u64 = 256 * 1024;
+
+/// The recorded sessions of one VM.
+#[derive(Debug, Clone)]
+pub struct Recordings {
+    dir: PathBuf,
+    keep: u32,
+}
+
+impl Recordings {
+    /// The recordings kept next to `log_path` under `policy`.
+    pub fn new(log_path: &Path, policy: &LogPolicy) -> Self {
+        Self {
+            dir: log_path.with_extension("sessions"),
+            keep: policy.keep_sessions,
+        }
+    }
+
+    /// Start recording a session of `client`, first deleting the oldest
+    /// recordings so that `keep` remain including the new one. Returns
+    /// `None` if recording is disabled or the file cannot be created.
+    pub fn start(&self, client: Option<&str>) -> Option<Recorder> {
+        if self.keep == 0 {
+            return None;
+        }
+        match Recorder::create(&self.dir, self.keep - 1, client) {
+            Ok(recorder) => Some(recorder),
+            Err(e) => {
+                tracing::warn!(
+                    dir = %self.dir.display(),
+                    "Failed to start console recording: {}",
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// The recorded sessions, oldest first.
+    pub fn list(&self) -> io::Result<Vec<ConsoleSession>> {
+        let mut sessions = Vec::new();
+        for path in self.files()? {
+            match describe(&path) {
+                Ok(session) => sessions.push(session),
+                // Deleted while listing, or not a recording of ours.
+                Err(e) => tracing::debug!(path = %path.display(), "Skipping recording: {}", e),
+            }
+        }
+        Ok(sessions)
+    }
+
+    /// The asciicast file of session `id`.
+    pub fn read(&self, id: &str) -> io::Result<Vec<u8>> {
+        if !valid_id(id) {
+            return Err(io::ErrorKind::NotFound.into());
+        }
+        fs::read(self.dir.join(format!("{}.cast", id)))
+    }
+
+    /// Remove every recording, e.g. together with the VM's logs.
+    pub fn remove_all(&self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+
+    /// The recording files, oldest first. Their names start with the
+    /// time they were started, so that is their name order.
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e),
+        };
+        let mut files: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "cast"))
+            .collect();
+        files.sort();
+        Ok(files)
+    }
+}
+
+/// Session ids are generated by [`Recorder::create`]; anything else,
+/// in particular a path, names no session.
+fn valid_id(id: &str) -> bool {
+    !id.is_empty() && id.bytes().all(|b| b.is_ascii_hexdigit() || b == b'-')
+}
+
+/// Read the header and the time of the last event of the recording at
+/// `path`.
+fn describe(path: &Path) -> io::Result<ConsoleSession> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+    let mut file = File::open(path)?;
+    let size_bytes = file.metadata()?.len();
+
+    let mut header = String::new();
+    BufReader::new(&mut file).read_line(&mut header)?;
+    let header: Value = serde_json::from_str(&header).map_err(|_| invalid("bad header"))?;
+    if header["version"] != 2 {
+        return Err(invalid("not an asciicast v2 file"));
+    }
+
+    let start = size_bytes.saturating_sub(TAIL_BYTES);
+    file.seek(SeekFrom::Start(start))?;
+    let mut tail = Vec::new();
+    file.take(TAIL_BYTES).read_to_end(&mut tail)?;
+    let duration_secs = tail
+        .split(|&b| b == b'\n')
+        .rev()
+        .find_map(|line| serde_json::from_slice::<(f64, Value, Value)>(line).ok())
+        .map_or(0.0, |(time, _, _)| time);
+
+    Ok(ConsoleSession {
+        id: path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        started_at: header["timestamp"].as_u64().unwrap_or_default(),
+        duration_secs,
+        size_bytes,
+        client: header["title"].as_str().map(str::to_string),
+    })
+}
+
+/// Writes one session's recording as it happens.
+pub struct Recorder {
+    file: File,
+    path: PathBuf,
+    started: Instant,
+    output: Utf8Decoder,
+    input: Utf8Decoder,
+    /// Set after a failed write; the rest of the session is not recorded.
+    failed: bool,
+}
+
+impl Recorder {
+    /// Create a recording in `dir`, deleting the oldest ones beyond
+    /// `keep` first.
+    fn create(dir: &Path, keep: u32, client: Option<&str>) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        let recordings = Recordings {
+            dir: dir.to_path_buf(),
+            keep,
+        };
+        let files = recordings.files()?;
+        for old in &files[..files.len().saturating_sub(keep as usize)] {
+            let _ = fs::remove_file(old);
+        }
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let id = format!("{:013}-{}", now.as_millis(), &Uuid::new_v4().simple().to_string()[..8]);
+        let path = dir.join(format!("{}.cast", id));
+        let mut file = OpenOptions::new().create_new(true).append(true).open(&path)?;
+        let mut header = json!({
+            "version": 2,
+            "width": DEFAULT_COLS,
+            "height": DEFAULT_ROWS,
+            "timestamp": now.as_secs(),
+        });
+        if let Some(client) = client {
+            header["title"] = json!(client);
+        }
+        writeln!(file, "{}", header)?;
+
+        Ok(Self {
+            file,
+            path,
+            started: Instant::now(),
+            output: Utf8Decoder::default(),
+            input: Utf8Decoder::default(),
+            failed: false,
+        })
+    }
+
+    /// Record output sent to the client.
+    pub fn output(&mut self, data: &[u8]) {
+        let text = self.output.decode(data);
+        self.event("o", &text);
+    }
+
+    /// Record what the client typed.
+    pub fn input(&mut self, data: &[u8]) {
+        let text = self.input.decode(data);
+        self.event("i", &text);
+    }
+
+    pub fn resize(&mut self, cols: u16, rows: u16) {
+        self.event("r", &format!("{}x{}", cols, rows));
+    }
+
+    pub fn marker(&mut self, label: &str) {
+        self.event("m", label);
+    }
+
+    fn event(&mut self, code: &str, data: &str) {
+        if self.failed || (data.is_empty() && code != "m") {
+            return;
+        }
+        let time = self.started.elapsed().as_micros() as f64 / 1e6;
+        if let Err(e) = writeln!(self.file, "{}", json!([time, code, data])) {
+            tracing::warn!(path = %self.path.display(), "Failed to record console session: {}", e);
+            self.failed = true;
+        }
+    }
+}
+
+/// Turns a byte stream into text, holding back a UTF-8 sequence that is
+/// split across chunks. Invalid bytes become U+FFFD.
+#[derive(Default)]
+struct Utf8Decoder {
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    fn decode(&mut self, data: &[u8]) -> String {
+        self.pending.extend_from_slice(data);
+        let mut text = String::new();
+        let mut rest = &self.pending[..];
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(valid) => {
+                    text.push_str(valid);
+                    rest = &[];
+                    break;
+                }
+                Err(e) => {
+                    let (valid, after) = rest.split_at(e.valid_up_to());
+                    text.push_str(std::str::from_utf8(valid).unwrap_or_default());
+                    match e.error_len() {
+                        Some(len) => {
+                            text.push(char::REPLACEMENT_CHARACTER);
+                            rest = &after[len..];
+                        }
+                        // Incomplete: the rest of it is in the next chunk.
+                        None => {
+                            rest = after;
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+        self.pending = rest.to_vec();
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn in_dir(dir: &TempDir, keep: u32) -> Recordings {
+        let policy = LogPolicy {
+            keep_sessions: keep,
+            ..LogPolicy::default()
+        };
+        Recordings::new(&dir.path().join("vm.log"), &policy)
+    }
+
+    #[test]
+    fn utf8_split_across_chunks_is_kept_whole() {
+        let mut decoder = Utf8Decoder::default();
+        let snowman = "☃".as_bytes();
+        assert_eq!(decoder.decode(&[b'a', snowman[0]]), "a");
+        assert_eq!(decoder.decode(&snowman[1..]), "☃");
+        assert_eq!(decoder.decode(&[0xff, b'b']), "\u{fffd}b");
+    }
+
+    #[test]
+    fn sessions_are_listed_read_and_pruned() {
+        let dir = TempDir::new().unwrap();
+        let recordings = in_dir(&dir, 2);
+        assert!(recordings.list().unwrap().is_empty());
+
+        let mut recorder = recordings.start(Some("gxctl")).unwrap();
+        recorder.output(b"login: ");
+        recorder.input(b"root\r");
+        recorder.resize(120, 40);
+        drop(recorder);
+
+        let sessions = recordings.list().unwrap();
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].client.as_deref(), Some("gxctl"));
+        let cast = String::from_utf8(recordings.read(&sessions[0].id).unwrap()).unwrap();
+        let events: Vec<(f64, String, String)> = cast
+            .lines()
+            .skip(1)
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        let events: Vec<_> = events.iter().map(|(_, c, d)| (c.as_str(), d.as_str())).collect();
+        assert_eq!(events, [("o", "login: "), ("i", "root\r"), ("r", "120x40")]);
+        assert!(recordings.read("../vm").is_err());
+
+        // Starting a third session drops the first.
+        let first = sessions[0].id.clone();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        drop(recordings.start(None));
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        drop(recordings.start(None));
+        let ids: Vec<_> = recordings.list().unwrap().into_iter().map(|s| s.id).collect();
+        assert_eq!(ids.len(), 2);
+        assert!(!ids.contains(&first));
+
+        assert!(in_dir(&dir, 0).start(None).is_none());
+    }
+}
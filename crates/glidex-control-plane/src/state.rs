@@ -1,19 +1,192 @@
-use crate::hypervisor::{create_backend, Hypervisor, HypervisorError, HypervisorProcess, HypervisorType};
-use crate::models::{Vm, VmConfig, VmState};
+use crate::hypervisor::{
+    create_backend, Hypervisor, HypervisorError, HypervisorProcess, HypervisorType, ProcessId,
+};
+use crate::console::{self, ExpectError, Expected, Replay};
+use crate::logs;
+use crate::migration::PeerClient;
+use crate::models::{
+    update_config, ConsoleSession, Snapshot, UpdateVmRequest, Vm, VmConfig, VmState,
+};
 use crate::persistence::{PersistenceError, VmStore};
+use crate::readiness;
+use crate::recording::Recordings;
+use regex_automata::meta::Regex;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::sync::{Mutex, OwnedMutexGuard, RwLock};
+use tokio::task::JoinHandle;
+
+/// How often the supervisor polls hypervisor processes for unexpected exits.
+const SUPERVISOR_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long `stop_vm` waits for the guest to power off when the caller
+/// does not say.
+pub const DEFAULT_STOP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long `expect_console` waits for its pattern when the caller does
+/// not say.
+pub const DEFAULT_EXPECT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// How long `wait_ready` waits for the guest to pass its readiness probe
+/// when the caller does not say.
+pub const DEFAULT_READY_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// How often `wait_ready` checks whether the guest has become ready.
+const READY_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// How long `reboot_vm` waits for the guest to come back through reset.
+const REBOOT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// How long the target of a live migration waits, once the source has
+/// sent everything, for its hypervisor to take over the guest.
+const MIGRATION_FINISH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Number of console lines kept with a crash record.
+const CONSOLE_TAIL_LINES: usize = 20;
+
+/// Reject `current -> next` unless the `VmState` transition table allows it.
+fn check_transition(
+    current: &VmState,
+    next: &VmState,
+    operation: &str,
+) -> Result<(), VmManagerError> {
+    if current.can_transition_to(next) {
+        Ok(())
+    } else {
+        Err(VmManagerError::InvalidState {
+            current: current.clone(),
+            operation: operation.to_string(),
+        })
+    }
+}
+
+/// Reject obviously broken configurations before persisting them.
+fn validate_config(config: &VmConfig) -> Result<(), VmManagerError> {
+    if config.vcpu_count == 0 {
+        return Err(HypervisorError::InvalidConfig(
+            "vcpu_count must be greater than 0".to_string(),
+        )
+        .into());
+    }
+    if config.mem_size_mib == 0 {
+        return Err(HypervisorError::InvalidConfig(
+            "mem_size_mib must be greater than 0".to_string(),
+        )
+        .into());
+    }
+    if let Some(probe) = &config.readiness {
+        readiness::validate(probe, config.hypervisor).map_err(HypervisorError::InvalidConfig)?;
+    }
+    Ok(())
+}
+
+/// How `restart_guest` brings a guest back.
+#[derive(Debug, Clone, Copy)]
+enum GuestRestart {
+    /// Ask the guest to reboot itself.
+    Reboot,
+    /// Reset it without warning.
+    Reset,
+}
+
+impl GuestRestart {
+    fn operation(self) -> &'static str {
+        match self {
+            GuestRestart::Reboot => "reboot",
+            GuestRestart::Reset => "reset",
+        }
+    }
+}
+
+/// Where the target hypervisor of a live migration listens for the
+/// migration stream.
+fn migration_socket_path(vm: &Vm) -> PathBuf {
+    Path::new(&vm.socket_path).with_extension("migrate.sock")
+}
+
+/// Begin a new boot in a VM's console log, archiving the previous one.
+/// Failing to do so is not worth failing the start over; the new boot's
+/// output is then appended to the previous one's.
+fn start_log_boot(vm: &Vm) {
+    if let Err(e) = logs::start_boot(Path::new(&vm.log_path), &vm.config.log_policy) {
+        tracing::warn!(vm_id = %vm.id, "Failed to archive the previous console log: {}", e);
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|t| t.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Record that `vm`'s guest began booting at `started_at`. It is not
+/// ready until its readiness probe passes, or right away without one.
+fn begin_boot(vm: &mut Vm, started_at: u64) {
+    vm.ready = vm.config.readiness.is_none();
+    vm.boot_started_at = Some(started_at);
+    vm.boot_duration_ms = None;
+}
+
+/// Read the last `lines` lines of a console log. Only the final 16 KiB are
+/// read so a long-running VM's log doesn't have to be loaded in full.
+fn read_console_tail(log_path: &str, lines: usize) -> Vec<String> {
+    const MAX_TAIL_BYTES: u64 = 16 * 1024;
+
+    let Ok(buf) = logs::read_tail(Path::new(log_path), MAX_TAIL_BYTES) else {
+        return Vec::new();
+    };
+
+    let text = String::from_utf8_lossy(&buf);
+    let all: Vec<&str> = text
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    all[all.len().saturating_sub(lines)..]
+        .iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// The last few lines of console output, for an error message.
+fn describe_output(output: &[u8]) -> String {
+    let text = String::from_utf8_lossy(output);
+    let lines: Vec<&str> = text
+        .lines()
+        .map(|line| line.trim_end_matches('\r'))
+        .filter(|line| !line.trim().is_empty())
+        .collect();
+    match lines.len() {
+        0 => "; no output".to_string(),
+        n => format!("; last output: {}", lines[n.saturating_sub(5)..].join(" | ")),
+    }
+}
 
 #[derive(Debug)]
 pub enum VmManagerError {
     VmNotFound(String),
     VmAlreadyExists(String),
+    SnapshotNotFound(String),
+    /// No console log for the requested boot of a VM.
+    LogNotFound(String),
     InvalidState { current: VmState, operation: String },
     HypervisorError(HypervisorError),
     PersistenceError(String),
     HypervisorNotAvailable(HypervisorType),
+    /// Talking to the peer control plane of a live migration failed.
+    MigrationFailed(String),
+    /// The console never printed what `expect_console` waited for.
+    ExpectFailed(String),
+    /// The guest exited while `expect_console` was waiting.
+    GuestExited(String),
+    /// The VM's console proxy could not be reached, or broke off.
+    ConsoleFailed(String),
+    /// The guest did not pass its readiness probe in time.
+    NotReady(String),
 }
 
 impl std::fmt::Display for VmManagerError {
@@ -21,6 +194,8 @@ impl std::fmt::Display for VmManagerError {
         match self {
             VmManagerError::VmNotFound(id) => write!(f, "VM not found: {}", id),
             VmManagerError::VmAlreadyExists(name) => write!(f, "VM already exists: {}", name),
+            VmManagerError::SnapshotNotFound(id) => write!(f, "Snapshot not found: {}", id),
+            VmManagerError::LogNotFound(what) => write!(f, "Log not found: {}", what),
             VmManagerError::InvalidState { current, operation } => {
                 write!(f, "Invalid state {:?} for operation: {}", current, operation)
             }
@@ -29,6 +204,11 @@ impl std::fmt::Display for VmManagerError {
             VmManagerError::HypervisorNotAvailable(h) => {
                 write!(f, "Hypervisor not available: {:?}", h)
             }
+            VmManagerError::MigrationFailed(e) => write!(f, "Migration failed: {}", e),
+            VmManagerError::ExpectFailed(e) => write!(f, "Expect failed: {}", e),
+            VmManagerError::GuestExited(e) => write!(f, "Guest exited: {}", e),
+            VmManagerError::ConsoleFailed(e) => write!(f, "Console failed: {}", e),
+            VmManagerError::NotReady(e) => write!(f, "VM not ready: {}", e),
         }
     }
 }
@@ -48,23 +228,99 @@ impl From<PersistenceError> for VmManagerError {
 struct VmEntry {
     vm: Vm,
     process: Option<Box<dyn HypervisorProcess>>,
+    /// This VM is the target of a live migration that has not completed.
+    incoming: bool,
+    /// The VM was deleted or migrated away; whoever was waiting for the
+    /// entry must treat it as gone.
+    removed: bool,
+    /// The copy of `vm` readers see, shared with the `VmSlot`.
+    published: Arc<std::sync::RwLock<Vm>>,
+    /// The readiness probe of the current boot, replaced when the guest
+    /// boots again. It ends by itself once the hypervisor is gone.
+    probe: Option<JoinHandle<()>>,
+}
+
+impl VmEntry {
+    fn new(vm: Vm, process: Option<Box<dyn HypervisorProcess>>) -> Self {
+        Self {
+            published: Arc::new(std::sync::RwLock::new(vm.clone())),
+            vm,
+            process,
+            incoming: false,
+            removed: false,
+            probe: None,
+        }
+    }
+
+    /// Make the current `vm` visible to readers before the operation
+    /// holding the entry finishes, e.g. a transitional state.
+    fn publish(&self) {
+        *self.published.write().unwrap() = self.vm.clone();
+    }
+}
+
+/// One VM in the manager. An operation locks `entry` for its whole
+/// duration, hypervisor I/O included, so only operations on the same VM
+/// wait for each other. `record` is the last published copy of the VM;
+/// `get_vm` and `list_vms` read it without waiting for the entry.
+struct VmSlot {
+    record: Arc<std::sync::RwLock<Vm>>,
+    entry: Arc<Mutex<VmEntry>>,
+}
+
+impl VmSlot {
+    fn new(entry: VmEntry) -> Arc<Self> {
+        Arc::new(Self {
+            record: Arc::clone(&entry.published),
+            entry: Arc::new(Mutex::new(entry)),
+        })
+    }
+
+    fn record(&self) -> Vm {
+        self.record.read().unwrap().clone()
+    }
+}
+
+/// A locked `VmEntry`. Whatever the operation left in `vm` is published
+/// when the guard is dropped.
+struct VmGuard(OwnedMutexGuard<VmEntry>);
+
+impl Deref for VmGuard {
+    type Target = VmEntry;
+
+    fn deref(&self) -> &VmEntry {
+        &self.0
+    }
+}
+
+impl DerefMut for VmGuard {
+    fn deref_mut(&mut self) -> &mut VmEntry {
+        &mut self.0
+    }
+}
+
+impl Drop for VmGuard {
+    fn drop(&mut self) {
+        self.0.publish();
+    }
 }
 
 pub struct VmManager {
-    vms: RwLock<HashMap<String, VmEntry>>,
+    vms: RwLock<HashMap<String, Arc<VmSlot>>>,
     store: VmStore,
     backends: HashMap<HypervisorType, Box<dyn Hypervisor>>,
+    /// Snapshot directories live here, one per snapshot, next to the db.
+    snapshot_dir: PathBuf,
 }
 
 impl VmManager {
-    /// Create a new VmManager with persistence at the default location
-    pub fn new() -> Result<Arc<Self>, VmManagerError> {
-        Self::with_db_path(Self::default_db_path())
-    }
-
     /// Create a new VmManager with persistence at a custom path
     pub fn with_db_path(db_path: PathBuf) -> Result<Arc<Self>, VmManagerError> {
         let store = VmStore::open(&db_path)?;
+        let snapshot_dir = db_path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join("snapshots");
 
         // Initialize hypervisor backends and probe whether each binary is on PATH.
         let mut backends: HashMap<HypervisorType, Box<dyn Hypervisor>> = HashMap::new();
@@ -88,6 +344,7 @@ impl VmManager {
             vms: RwLock::new(HashMap::new()),
             store,
             backends,
+            snapshot_dir,
         }))
     }
 
@@ -99,8 +356,34 @@ impl VmManager {
             .ok_or(VmManagerError::HypervisorNotAvailable(hypervisor))
     }
 
+    /// Lock one VM for an operation. Neither other VMs nor readers of this
+    /// one are held up while the guard is alive.
+    async fn lock_vm(&self, vm_id: &str) -> Result<VmGuard, VmManagerError> {
+        let slot = self
+            .vms
+            .read()
+            .await
+            .get(vm_id)
+            .cloned()
+            .ok_or_else(|| VmManagerError::VmNotFound(vm_id.to_string()))?;
+
+        let entry = VmGuard(slot.entry.clone().lock_owned().await);
+        // The VM may have been deleted while we were waiting for it.
+        if entry.removed {
+            return Err(VmManagerError::VmNotFound(vm_id.to_string()));
+        }
+        Ok(entry)
+    }
+
+    /// Forget a locked VM. Operations already waiting for it fail with
+    /// `VmNotFound`.
+    async fn remove_vm(&self, entry: &mut VmEntry) {
+        entry.removed = true;
+        self.vms.write().await.remove(&entry.vm.id);
+    }
+
     /// Get the default database path (~/.glidex/glidex.db)
-    fn default_db_path() -> PathBuf {
+    pub fn default_db_path() -> PathBuf {
         dirs::home_dir()
             .unwrap_or_else(|| PathBuf::from("."))
             .join(".glidex")
@@ -108,45 +391,93 @@ impl VmManager {
     }
 
     /// Initialize VmManager by loading persisted VMs and reconciling state
-    pub async fn initialize(&self) -> Result<(), VmManagerError> {
+    pub async fn initialize(self: &Arc<Self>) -> Result<(), VmManagerError> {
         let persisted_vms = self.store.load_all()?;
-        let mut vms = self.vms.write().await;
 
+        let mut attached = 0;
         for mut vm in persisted_vms {
-            // Reconcile state: VMs that were Running/Paused are now orphaned
-            let reconciled_state = self.reconcile_vm_state(&vm);
+            let before = (vm.state.clone(), vm.hypervisor_pid);
+            let process = self.reconcile_vm(&mut vm).await;
+            attached += usize::from(process.is_some());
+
+            // A guest taken over before it passed its probe is probed
+            // again, against what the scrollback still holds of its boot.
+            let unready = process.is_some() && !vm.ready;
+            if unready {
+                let started_at = vm.boot_started_at.unwrap_or_else(unix_millis);
+                begin_boot(&mut vm, started_at);
+            }
 
-            if vm.state != reconciled_state {
-                vm.state = reconciled_state;
+            if (vm.state.clone(), vm.hypervisor_pid) != before {
                 // Update DB with reconciled state
                 self.store.save(&vm)?;
             }
 
-            vms.insert(
-                vm.id.clone(),
-                VmEntry {
-                    vm,
-                    process: None, // Process handles cannot be restored
-                },
-            );
+            let mut entry = VmEntry::new(vm, process);
+            if unready {
+                self.watch_readiness(&mut entry, Replay::All);
+            }
+            self.vms
+                .write()
+                .await
+                .insert(entry.vm.id.clone(), VmSlot::new(entry));
         }
 
+        if attached > 0 {
+            tracing::info!("Reattached to {} running VM(s)", attached);
+        }
         Ok(())
     }
 
-    /// Reconcile VM state after restart
-    fn reconcile_vm_state(&self, vm: &Vm) -> VmState {
+    /// Reconcile a persisted VM with what is actually running after a
+    /// restart. Running and paused guests whose hypervisor survived are
+    /// taken over; anything caught mid-transition is killed.
+    async fn reconcile_vm(&self, vm: &mut Vm) -> Option<Box<dyn HypervisorProcess>> {
         match vm.state {
             VmState::Running | VmState::Paused => {
-                // Check if the hypervisor process is still alive
-                if self.is_hypervisor_alive(&vm.socket_path) {
-                    // Process exists but we lost the handle - clean up and mark as stopped
-                    self.cleanup_orphaned_vm(vm);
+                if let Some(id) = vm.hypervisor_pid.filter(ProcessId::is_alive) {
+                    let attached = match self.get_backend(vm.hypervisor) {
+                        Ok(backend) => backend
+                            .attach(
+                                id,
+                                &vm.config,
+                                &vm.socket_path,
+                                &vm.console_socket_path,
+                                &vm.log_path,
+                            )
+                            .await
+                            .map_err(VmManagerError::from),
+                        Err(e) => Err(e),
+                    };
+                    match attached {
+                        Ok(process) => {
+                            tracing::info!(vm_id = %vm.id, pid = id.pid, "Reattached to hypervisor");
+                            return Some(process);
+                        }
+                        Err(e) => tracing::warn!(
+                            vm_id = %vm.id,
+                            "Could not reattach to hypervisor {}: {}",
+                            id.pid,
+                            e
+                        ),
+                    }
                 }
-                VmState::Stopped
+                vm.state = VmState::Stopped;
+            }
+            VmState::Stopping => vm.state = VmState::Stopped,
+            VmState::Starting => {
+                vm.state = VmState::Failed;
+                vm.last_error =
+                    Some("control plane restarted while the VM was starting".to_string());
+            }
+            VmState::Created | VmState::Stopped | VmState::Crashed | VmState::Failed => {
+                return None;
             }
-            VmState::Created | VmState::Stopped => vm.state.clone(),
         }
+
+        self.cleanup_orphaned_vm(vm).await;
+        vm.hypervisor_pid = None;
+        None
     }
 
     /// Check if a hypervisor process is still alive by probing its socket
@@ -154,8 +485,16 @@ impl VmManager {
         std::path::Path::new(socket_path).exists()
     }
 
-    /// Clean up resources from an orphaned VM
-    fn cleanup_orphaned_vm(&self, vm: &Vm) {
+    /// Kill a hypervisor we could not take over and clean up its resources
+    async fn cleanup_orphaned_vm(&self, vm: &Vm) {
+        let alive = vm.hypervisor_pid.filter(ProcessId::is_alive);
+        if alive.is_none() && !self.is_hypervisor_alive(&vm.socket_path) {
+            return;
+        }
+        if let Some(id) = alive {
+            id.kill().await;
+        }
+
         // Remove socket files
         let _ = std::fs::remove_file(&vm.socket_path);
         let _ = std::fs::remove_file(&vm.console_socket_path);
@@ -168,24 +507,12 @@ impl VmManager {
     }
 
     pub async fn create_vm(&self, name: String, config: VmConfig) -> Result<Vm, VmManagerError> {
-        // Reject obviously broken configurations before persisting them.
-        if config.vcpu_count == 0 {
-            return Err(HypervisorError::InvalidConfig(
-                "vcpu_count must be greater than 0".to_string(),
-            )
-            .into());
-        }
-        if config.mem_size_mib == 0 {
-            return Err(HypervisorError::InvalidConfig(
-                "mem_size_mib must be greater than 0".to_string(),
-            )
-            .into());
-        }
+        validate_config(&config)?;
 
         let mut vms = self.vms.write().await;
 
         // Check if VM with same name exists
-        if vms.values().any(|entry| entry.vm.name == name) {
+        if vms.values().any(|slot| slot.record().name == name) {
             return Err(VmManagerError::VmAlreadyExists(name));
         }
 
@@ -196,149 +523,300 @@ impl VmManager {
 
         let vm_clone = vm.clone();
 
-        vms.insert(
-            vm.id.clone(),
-            VmEntry {
-                vm,
-                process: None,
-            },
-        );
+        vms.insert(vm.id.clone(), VmSlot::new(VmEntry::new(vm, None)));
 
         Ok(vm_clone)
     }
 
-    pub async fn start_vm(&self, vm_id: &str) -> Result<Vm, VmManagerError> {
-        let mut vms = self.vms.write().await;
-
-        let entry = vms
-            .get_mut(vm_id)
-            .ok_or_else(|| VmManagerError::VmNotFound(vm_id.to_string()))?;
+    pub async fn start_vm(self: &Arc<Self>, vm_id: &str) -> Result<Vm, VmManagerError> {
+        let mut guard = self.lock_vm(vm_id).await?;
+        let entry = &mut *guard;
 
-        match entry.vm.state {
-            VmState::Created | VmState::Stopped => {
-                // Get the appropriate backend for this VM's hypervisor
-                let backend = self.get_backend(entry.vm.hypervisor)?;
-
-                // Spawn hypervisor process with console socket and log file
-                let process = backend.spawn(
-                    &entry.vm.socket_path,
-                    &entry.vm.console_socket_path,
-                    &entry.vm.log_path,
-                )?;
-
-                // Configure the VM, cleanup process on failure
-                if let Err(e) = process.configure(&entry.vm.config) {
-                    let _ = process.kill();
-                    return Err(e.into());
-                }
+        if entry.vm.state == VmState::Paused {
+            return self.resume_entry(vm_id, entry).await;
+        }
 
-                // Start the VM, cleanup process on failure
-                if let Err(e) = process.start() {
-                    let _ = process.kill();
-                    return Err(e.into());
-                }
+        check_transition(&entry.vm.state, &VmState::Starting, "start")?;
 
-                // Persist state change BEFORE updating in-memory state
-                // If persist fails, kill the process to maintain consistency
-                if let Err(e) = self.store.update_state(vm_id, VmState::Running) {
-                    let _ = process.kill();
-                    return Err(e.into());
-                }
+        // Get the appropriate backend for this VM's hypervisor
+        let backend = self.get_backend(entry.vm.hypervisor)?;
 
-                tracing::info!(
-                    vm_id = %entry.vm.id,
-                    running = process.is_running(),
-                    socket = process.socket_path(),
-                    console = process.console_socket_path(),
-                    log = process.log_path(),
-                    "VM started"
-                );
+        // Record the transitional state first so a control-plane crash
+        // mid-start is visible to reconciliation.
+        self.store.update_state(vm_id, VmState::Starting)?;
+        entry.vm.state = VmState::Starting;
+        entry.publish();
 
-                entry.process = Some(process);
-                entry.vm.state = VmState::Running;
+        let started_at = unix_millis();
+        let process = match Self::launch(backend, &entry.vm).await {
+            Ok(process) => process,
+            Err(e) => {
+                let message = e.to_string();
+                tracing::error!(vm_id = %vm_id, "VM failed to start: {}", message);
 
-                Ok(entry.vm.clone())
-            }
-            VmState::Paused => {
-                // Resume paused VM
-                if let Some(ref process) = entry.process {
-                    process.resume()?;
-                } else {
-                    return Err(VmManagerError::InvalidState {
-                        current: VmState::Paused,
-                        operation: "start (no process handle)".to_string(),
-                    });
+                entry.vm.state = VmState::Failed;
+                entry.vm.last_error = Some(message);
+                if let Err(persist_err) = self.store.save(&entry.vm) {
+                    tracing::error!(
+                        "Failed to persist VM {} state change to Failed: {}. State will be reconciled on restart.",
+                        vm_id, persist_err
+                    );
                 }
+                return Err(e.into());
+            }
+        };
 
-                // Persist state change BEFORE updating in-memory state
-                // If persist fails, pause again to maintain consistency
-                if let Err(e) = self.store.update_state(vm_id, VmState::Running) {
-                    if let Some(ref process) = entry.process {
-                        let _ = process.pause();
-                    }
-                    return Err(e.into());
-                }
+        // Persist state change BEFORE updating in-memory state
+        // If persist fails, kill the process to maintain consistency
+        let mut updated = entry.vm.clone();
+        updated.state = VmState::Running;
+        updated.last_exit = None;
+        updated.last_error = None;
+        updated.hypervisor_pid = process.process_id();
+        begin_boot(&mut updated, started_at);
+        if let Err(e) = self.store.save(&updated) {
+            let _ = process.kill().await;
+            entry.vm.state = VmState::Failed;
+            entry.vm.last_error = Some(e.to_string());
+            return Err(e.into());
+        }
 
-                entry.vm.state = VmState::Running;
+        tracing::info!(
+            vm_id = %entry.vm.id,
+            running = process.is_running(),
+            socket = process.socket_path(),
+            console = process.console_socket_path(),
+            log = process.log_path(),
+            "VM started"
+        );
 
-                Ok(entry.vm.clone())
-            }
-            VmState::Running => Err(VmManagerError::InvalidState {
-                current: VmState::Running,
-                operation: "start".to_string(),
-            }),
-        }
-    }
+        entry.process = Some(process);
+        entry.vm = updated;
+        // The log was just started for this boot, so all of the
+        // scrollback belongs to it.
+        self.watch_readiness(entry, Replay::All);
 
-    pub async fn stop_vm(&self, vm_id: &str) -> Result<Vm, VmManagerError> {
-        let mut vms = self.vms.write().await;
+        Ok(entry.vm.clone())
+    }
 
-        let entry = vms
-            .get_mut(vm_id)
-            .ok_or_else(|| VmManagerError::VmNotFound(vm_id.to_string()))?;
+    /// Evaluate the readiness probe of the boot `entry` just began, in
+    /// the background. `replay` is how much of the console scrollback
+    /// belongs to that boot.
+    fn watch_readiness(self: &Arc<Self>, entry: &mut VmEntry, replay: Replay) {
+        if let Some(probe) = entry.probe.take() {
+            probe.abort();
+        }
+        let (Some(probe), Some(started_at)) =
+            (entry.vm.config.readiness.clone(), entry.vm.boot_started_at)
+        else {
+            return;
+        };
+        if entry.vm.ready {
+            return;
+        }
 
-        match entry.vm.state {
-            VmState::Running | VmState::Paused => {
-                // Kill the hypervisor process (cannot be undone)
-                if let Some(ref process) = entry.process {
-                    let _ = process.kill();
-                }
-                entry.process = None;
-                entry.vm.state = VmState::Stopped;
+        let manager = Arc::downgrade(self);
+        let vm = entry.vm.clone();
+        entry.probe = Some(tokio::spawn(async move {
+            let result = readiness::wait_until_ready(&probe, &vm, replay).await;
+            if let Some(manager) = manager.upgrade() {
+                manager.finish_probe(&vm.id, started_at, result).await;
+            }
+        }));
+    }
 
-                // Persist state change - log warning if fails since operation already happened
-                if let Err(e) = self.store.update_state(vm_id, VmState::Stopped) {
+    /// Record the outcome of the readiness probe of the boot that began
+    /// at `started_at`, unless the guest has booted again since.
+    async fn finish_probe(&self, vm_id: &str, started_at: u64, result: Result<(), String>) {
+        let Ok(mut entry) = self.lock_vm(vm_id).await else {
+            return;
+        };
+        if entry.vm.boot_started_at != Some(started_at) {
+            return;
+        }
+        match result {
+            Ok(()) => {
+                let duration = unix_millis().saturating_sub(started_at);
+                entry.vm.ready = true;
+                entry.vm.boot_duration_ms = Some(duration);
+                tracing::info!(vm_id = %vm_id, boot_duration_ms = duration, "VM ready");
+                if let Err(e) = self.store.save(&entry.vm) {
                     tracing::error!(
-                        "Failed to persist VM {} state change to Stopped: {}. State will be reconciled on restart.",
+                        "Failed to persist readiness of VM {}: {}. It will be probed again after a restart.",
                         vm_id, e
                     );
                 }
+            }
+            Err(reason) => {
+                tracing::warn!(vm_id = %vm_id, "Readiness probe gave up: {}", reason);
+            }
+        }
+    }
 
-                Ok(entry.vm.clone())
+    /// Wait up to `timeout` for a VM's guest to pass its readiness probe.
+    /// Fails early if the VM stops running in the meantime.
+    pub async fn wait_ready(&self, vm_id: &str, timeout: Duration) -> Result<Vm, VmManagerError> {
+        // No deadline when `timeout` is too far off to represent.
+        let deadline = tokio::time::Instant::now().checked_add(timeout);
+        loop {
+            let vm = self.get_vm(vm_id).await?;
+            if vm.is_ready() {
+                return Ok(vm);
             }
-            _ => Err(VmManagerError::InvalidState {
-                current: entry.vm.state.clone(),
-                operation: "stop".to_string(),
-            }),
+            if !matches!(
+                vm.state,
+                VmState::Starting | VmState::Running | VmState::Paused
+            ) {
+                let exit = vm
+                    .last_exit
+                    .map(|exit| format!(" (hypervisor {})", exit))
+                    .unwrap_or_default();
+                return Err(VmManagerError::NotReady(format!(
+                    "VM is {:?}{}",
+                    vm.state, exit
+                )));
+            }
+            if deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline) {
+                return Err(VmManagerError::NotReady(format!(
+                    "readiness probe did not pass within {}s",
+                    timeout.as_secs()
+                )));
+            }
+            tokio::time::sleep(READY_POLL_INTERVAL).await;
         }
     }
 
-    pub async fn pause_vm(&self, vm_id: &str) -> Result<Vm, VmManagerError> {
-        let mut vms = self.vms.write().await;
+    /// Spawn, configure and boot a hypervisor for `vm`. Any partially
+    /// started process is killed before an error is returned.
+    async fn launch(
+        backend: &dyn Hypervisor,
+        vm: &Vm,
+    ) -> Result<Box<dyn HypervisorProcess>, HypervisorError> {
+        start_log_boot(vm);
+
+        // Spawn hypervisor process with console socket and log file
+        let process = backend
+            .spawn(&vm.config, &vm.socket_path, &vm.console_socket_path, &vm.log_path)
+            .await?;
+
+        // Configure the VM, cleanup process on failure
+        if let Err(e) = process.configure(&vm.config).await {
+            let _ = process.kill().await;
+            return Err(e);
+        }
 
-        let entry = vms
-            .get_mut(vm_id)
-            .ok_or_else(|| VmManagerError::VmNotFound(vm_id.to_string()))?;
+        // Start the VM, cleanup process on failure
+        if let Err(e) = process.start().await {
+            let _ = process.kill().await;
+            return Err(e);
+        }
 
-        if entry.vm.state != VmState::Running {
+        Ok(process)
+    }
+
+    /// Resume a paused VM (`start` on a Paused VM).
+    async fn resume_entry(&self, vm_id: &str, entry: &mut VmEntry) -> Result<Vm, VmManagerError> {
+        check_transition(&entry.vm.state, &VmState::Running, "start")?;
+
+        if let Some(ref process) = entry.process {
+            process.resume().await?;
+        } else {
             return Err(VmManagerError::InvalidState {
-                current: entry.vm.state.clone(),
-                operation: "pause".to_string(),
+                current: VmState::Paused,
+                operation: "start (no process handle)".to_string(),
             });
         }
 
+        // Persist state change BEFORE updating in-memory state
+        // If persist fails, pause again to maintain consistency
+        if let Err(e) = self.store.update_state(vm_id, VmState::Running) {
+            if let Some(ref process) = entry.process {
+                let _ = process.pause().await;
+            }
+            return Err(e.into());
+        }
+
+        entry.vm.state = VmState::Running;
+
+        Ok(entry.vm.clone())
+    }
+
+    /// Stop a VM. Unless `force` is set, the guest is asked to power off
+    /// and given `timeout` to do so before the hypervisor is killed. The VM
+    /// reads as `Stopping` in the meantime; the VM is not locked while
+    /// waiting for the guest.
+    pub async fn stop_vm(
+        self: &Arc<Self>,
+        vm_id: &str,
+        force: bool,
+        timeout: Duration,
+    ) -> Result<Vm, VmManagerError> {
+        let (process, was_paused) = {
+            let mut entry = self.lock_vm(vm_id).await?;
+
+            check_transition(&entry.vm.state, &VmState::Stopping, "stop")?;
+
+            self.store.update_state(vm_id, VmState::Stopping)?;
+            let was_paused = entry.vm.state == VmState::Paused;
+            entry.vm.state = VmState::Stopping;
+
+            // Taking the handle also keeps the supervisor from reaping the
+            // guest's own power-off as an unexpected exit.
+            (entry.process.take(), was_paused)
+        };
+
+        // Finish in a separate task so the VM is not stranded in Stopping
+        // if the caller goes away halfway through the grace period.
+        let manager = Arc::clone(self);
+        let vm_id = vm_id.to_string();
+        let task = tokio::spawn(async move {
+            if let Some(process) = process {
+                let result = if force {
+                    process.kill().await
+                } else {
+                    // A paused guest cannot react to the power button.
+                    if was_paused {
+                        let _ = process.resume().await;
+                    }
+                    process.shutdown(timeout).await
+                };
+                if let Err(e) = result {
+                    tracing::warn!(vm_id = %vm_id, "Error while stopping hypervisor: {}", e);
+                }
+            }
+            manager.finish_stop(&vm_id).await
+        });
+
+        task.await
+            .unwrap_or_else(|e| std::panic::resume_unwind(e.into_panic()))
+    }
+
+    /// Record that a `Stopping` VM's hypervisor is gone.
+    async fn finish_stop(&self, vm_id: &str) -> Result<Vm, VmManagerError> {
+        // The VM may have been deleted while it was shutting down.
+        let mut entry = self.lock_vm(vm_id).await?;
+        entry.vm.state = VmState::Stopped;
+        entry.vm.hypervisor_pid = None;
+
+        // Persist state change - log warning if fails since operation already happened
+        if let Err(e) = self.store.save(&entry.vm) {
+            tracing::error!(
+                "Failed to persist VM {} state change to Stopped: {}. State will be reconciled on restart.",
+                vm_id, e
+            );
+        }
+
+        Ok(entry.vm.clone())
+    }
+
+    pub async fn pause_vm(&self, vm_id: &str) -> Result<Vm, VmManagerError> {
+        let mut guard = self.lock_vm(vm_id).await?;
+        let entry = &mut *guard;
+
+        check_transition(&entry.vm.state, &VmState::Paused, "pause")?;
+
         if let Some(ref process) = entry.process {
-            process.pause()?;
+            process.pause().await?;
         } else {
             return Err(VmManagerError::InvalidState {
                 current: entry.vm.state.clone(),
@@ -350,7 +828,7 @@ impl VmManager {
         // If persist fails, resume the VM to maintain consistency
         if let Err(e) = self.store.update_state(vm_id, VmState::Paused) {
             if let Some(ref process) = entry.process {
-                let _ = process.resume();
+                let _ = process.resume().await;
             }
             return Err(e.into());
         }
@@ -360,80 +838,431 @@ impl VmManager {
         Ok(entry.vm.clone())
     }
 
-    pub async fn get_vm(&self, vm_id: &str) -> Result<Vm, VmManagerError> {
-        let vms = self.vms.read().await;
-        vms.get(vm_id)
-            .map(|entry| entry.vm.clone())
-            .ok_or_else(|| VmManagerError::VmNotFound(vm_id.to_string()))
+    /// Ask the guest to reboot itself. Returns once it has come back
+    /// through reset; the VM stays `Running` throughout.
+    pub async fn reboot_vm(self: &Arc<Self>, vm_id: &str) -> Result<Vm, VmManagerError> {
+        self.restart_guest(vm_id, GuestRestart::Reboot).await
     }
 
-    pub async fn list_vms(&self) -> Vec<Vm> {
-        let vms = self.vms.read().await;
-        vms.values().map(|entry| entry.vm.clone()).collect()
+    /// Hard-reset the guest without giving it a chance to shut down.
+    pub async fn reset_vm(self: &Arc<Self>, vm_id: &str) -> Result<Vm, VmManagerError> {
+        self.restart_guest(vm_id, GuestRestart::Reset).await
     }
 
-    pub async fn attach_device(&self, vm_id: &str, device_path: String) -> Result<Vm, VmManagerError> {
-        let mut vms = self.vms.write().await;
-
-        let entry = vms
-            .get_mut(vm_id)
-            .ok_or_else(|| VmManagerError::VmNotFound(vm_id.to_string()))?;
+    /// Shared body of `reboot_vm` / `reset_vm`. If the operation fails and
+    /// takes the hypervisor down with it (a Firecracker respawn that could
+    /// not boot), the VM moves to `Failed`.
+    async fn restart_guest(
+        self: &Arc<Self>,
+        vm_id: &str,
+        restart: GuestRestart,
+    ) -> Result<Vm, VmManagerError> {
+        let operation = restart.operation();
+        let mut guard = self.lock_vm(vm_id).await?;
+        let entry = &mut *guard;
 
-        // Reject if device is already attached
-        if entry.vm.config.vfio_devices.contains(&device_path) {
+        if entry.vm.state != VmState::Running {
             return Err(VmManagerError::InvalidState {
                 current: entry.vm.state.clone(),
-                operation: format!("attach_device: {} is already attached", device_path),
+                operation: operation.to_string(),
             });
         }
-
-        match entry.vm.state {
-            VmState::Running => {
-                // Hot-plug: call hypervisor API, then update config
-                if let Some(ref process) = entry.process {
-                    process.add_device(&device_path)?;
-                } else {
-                    return Err(VmManagerError::InvalidState {
-                        current: entry.vm.state.clone(),
-                        operation: "attach_device (no process handle)".to_string(),
-                    });
-                }
-
-                entry.vm.config.vfio_devices.push(device_path);
-
-                // Persist updated config. On failure, rollback the hot-plug.
+        let Some(process) = entry.process.as_deref() else {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: format!("{} (no process handle)", operation),
+            });
+        };
+
+        let started_at = unix_millis();
+        let result = match restart {
+            GuestRestart::Reboot => process.reboot(REBOOT_TIMEOUT).await,
+            GuestRestart::Reset => process.reset().await,
+        };
+        let e = match result {
+            Ok(()) => {
+                tracing::info!(vm_id = %vm_id, "VM {}", operation);
+                // Firecracker restarts the guest in a new process.
+                entry.vm.hypervisor_pid = process.process_id();
+                begin_boot(&mut entry.vm, started_at);
                 if let Err(e) = self.store.save(&entry.vm) {
-                    let removed = entry.vm.config.vfio_devices.pop();
-                    if let (Some(ref process), Some(path)) = (&entry.process, removed) {
-                        let _ = process.remove_device(&path);
-                    }
-                    return Err(e.into());
+                    tracing::error!(
+                        "Failed to persist {} of VM {}: {}. It will not be reattached after a restart.",
+                        operation, vm_id, e
+                    );
                 }
-
-                Ok(entry.vm.clone())
-            }
-            VmState::Created | VmState::Stopped => {
-                // Config-only: device will be included at next VM start
-                entry.vm.config.vfio_devices.push(device_path);
-                self.store.save(&entry.vm)?;
-                Ok(entry.vm.clone())
+                // The log still holds the previous boot, so only output
+                // from here on counts.
+                self.watch_readiness(entry, Replay::None);
+                return Ok(entry.vm.clone());
             }
-            VmState::Paused => Err(VmManagerError::InvalidState {
-                current: VmState::Paused,
-                operation: "attach_device".to_string(),
-            }),
+            Err(e) if process.is_running() => return Err(e.into()),
+            Err(e) => e,
+        };
+
+        let message = format!("{} failed: {}", operation, e);
+        tracing::error!(vm_id = %vm_id, "{}", message);
+        if let Some(process) = entry.process.take() {
+            let _ = process.kill().await;
         }
+        entry.vm.state = VmState::Failed;
+        entry.vm.hypervisor_pid = None;
+        entry.vm.last_error = Some(message);
+        if let Err(persist_err) = self.store.save(&entry.vm) {
+            tracing::error!(
+                "Failed to persist VM {} state change to Failed: {}. State will be reconciled on restart.",
+                vm_id, persist_err
+            );
+        }
+        Err(e.into())
     }
 
-    pub async fn detach_device(&self, vm_id: &str, device_path: &str) -> Result<Vm, VmManagerError> {
-        let mut vms = self.vms.write().await;
+    /// Checkpoint a paused VM's memory and device state to disk. The VM
+    /// stays paused. Disks are not copied, so a restore is only sound while
+    /// the rootfs is unchanged since the snapshot.
+    pub async fn snapshot_vm(
+        &self,
+        vm_id: &str,
+        name: Option<String>,
+    ) -> Result<Snapshot, VmManagerError> {
+        let entry = self.lock_vm(vm_id).await?;
+
+        if entry.vm.state != VmState::Paused {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: "snapshot".to_string(),
+            });
+        }
+        // Passed-through devices have state the hypervisor cannot save.
+        if !entry.vm.config.vfio_devices.is_empty() {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: "snapshot (VFIO devices attached)".to_string(),
+            });
+        }
+        let Some(process) = entry.process.as_deref() else {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: "snapshot (no process handle)".to_string(),
+            });
+        };
+
+        let snapshot = Snapshot::new(&entry.vm, name, &self.snapshot_dir);
+        let dir = Path::new(&snapshot.path);
+        std::fs::create_dir_all(dir).map_err(PersistenceError::from)?;
+
+        let result = process
+            .snapshot(dir)
+            .await
+            .map_err(VmManagerError::from)
+            .and_then(|()| self.store.save_snapshot(&snapshot).map_err(Into::into));
+        if let Err(e) = result {
+            let _ = std::fs::remove_dir_all(dir);
+            return Err(e);
+        }
 
-        let entry = vms
-            .get_mut(vm_id)
-            .ok_or_else(|| VmManagerError::VmNotFound(vm_id.to_string()))?;
+        tracing::info!(vm_id = %vm_id, snapshot_id = %snapshot.id, "VM snapshotted");
+        Ok(snapshot)
+    }
 
-        // Check that the device is actually attached
-        let pos = entry
+    /// Look up one of `vm_id`'s snapshots.
+    fn find_snapshot(&self, vm_id: &str, snapshot_id: &str) -> Result<Snapshot, VmManagerError> {
+        match self.store.get_snapshot(snapshot_id) {
+            Ok(snapshot) if snapshot.vm_id == vm_id => Ok(snapshot),
+            Ok(_) | Err(PersistenceError::SnapshotNotFound(_)) => {
+                Err(VmManagerError::SnapshotNotFound(snapshot_id.to_string()))
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn list_snapshots(&self, vm_id: &str) -> Result<Vec<Snapshot>, VmManagerError> {
+        let vms = self.vms.read().await;
+        if !vms.contains_key(vm_id) {
+            return Err(VmManagerError::VmNotFound(vm_id.to_string()));
+        }
+        Ok(self.store.load_snapshots(vm_id)?)
+    }
+
+    pub async fn delete_snapshot(&self, vm_id: &str, snapshot_id: &str) -> Result<(), VmManagerError> {
+        // Held so a restore from this snapshot cannot be in progress.
+        let _entry = self.lock_vm(vm_id).await?;
+
+        let snapshot = self.find_snapshot(vm_id, snapshot_id)?;
+        self.store.delete_snapshot(snapshot_id)?;
+        if let Err(e) = std::fs::remove_dir_all(&snapshot.path) {
+            tracing::warn!("Failed to remove snapshot directory {}: {}", snapshot.path, e);
+        }
+        Ok(())
+    }
+
+    /// Boot a stopped VM from one of its snapshots instead of from the
+    /// kernel. The VM comes up `Paused`, with the config it had when the
+    /// snapshot was taken; `start` resumes it.
+    pub async fn restore_vm(
+        self: &Arc<Self>,
+        vm_id: &str,
+        snapshot_id: &str,
+    ) -> Result<Vm, VmManagerError> {
+        let mut guard = self.lock_vm(vm_id).await?;
+        let entry = &mut *guard;
+
+        let snapshot = self.find_snapshot(vm_id, snapshot_id)?;
+        check_transition(&entry.vm.state, &VmState::Starting, "restore")?;
+
+        let backend = self.get_backend(snapshot.hypervisor)?;
+
+        self.store.update_state(vm_id, VmState::Starting)?;
+        entry.vm.state = VmState::Starting;
+        entry.publish();
+
+        let started_at = unix_millis();
+        let process = match Self::launch_from_snapshot(backend, &entry.vm, &snapshot).await {
+            Ok(process) => process,
+            Err(e) => {
+                let message = format!("restore failed: {}", e);
+                tracing::error!(vm_id = %vm_id, "{}", message);
+
+                entry.vm.state = VmState::Failed;
+                entry.vm.last_error = Some(message);
+                if let Err(persist_err) = self.store.save(&entry.vm) {
+                    tracing::error!(
+                        "Failed to persist VM {} state change to Failed: {}. State will be reconciled on restart.",
+                        vm_id, persist_err
+                    );
+                }
+                return Err(e.into());
+            }
+        };
+
+        let mut updated = entry.vm.clone();
+        updated.state = VmState::Paused;
+        updated.config = snapshot.config.clone();
+        updated.last_exit = None;
+        updated.last_error = None;
+        updated.hypervisor_pid = process.process_id();
+        begin_boot(&mut updated, started_at);
+        if let Err(e) = self.store.save(&updated) {
+            let _ = process.kill().await;
+            entry.vm.state = VmState::Failed;
+            entry.vm.last_error = Some(e.to_string());
+            return Err(e.into());
+        }
+
+        tracing::info!(vm_id = %vm_id, snapshot_id = %snapshot_id, "VM restored");
+
+        entry.process = Some(process);
+        entry.vm = updated;
+        self.watch_readiness(entry, Replay::All);
+
+        Ok(entry.vm.clone())
+    }
+
+    /// Spawn a hypervisor and load `snapshot` into it. Any partially
+    /// started process is killed before an error is returned.
+    async fn launch_from_snapshot(
+        backend: &dyn Hypervisor,
+        vm: &Vm,
+        snapshot: &Snapshot,
+    ) -> Result<Box<dyn HypervisorProcess>, HypervisorError> {
+        start_log_boot(vm);
+
+        let process = backend
+            .spawn(&vm.config, &vm.socket_path, &vm.console_socket_path, &vm.log_path)
+            .await?;
+
+        if let Err(e) = process
+            .restore(&snapshot.config, Path::new(&snapshot.path))
+            .await
+        {
+            let _ = process.kill().await;
+            return Err(e);
+        }
+
+        Ok(process)
+    }
+
+    /// Read a VM without waiting for any operation in progress on it. A
+    /// VM in the middle of one reads as its transitional state.
+    pub async fn get_vm(&self, vm_id: &str) -> Result<Vm, VmManagerError> {
+        let vms = self.vms.read().await;
+        vms.get(vm_id)
+            .map(|slot| slot.record())
+            .ok_or_else(|| VmManagerError::VmNotFound(vm_id.to_string()))
+    }
+
+    /// The console log of one boot of a VM: 0 is the current (or last)
+    /// boot, -1 the one before it, and so on, as far back as its log
+    /// policy keeps them.
+    pub async fn read_log(&self, vm_id: &str, boot: i64) -> Result<logs::BootLog, VmManagerError> {
+        let vm = self.get_vm(vm_id).await?;
+        let not_found = || VmManagerError::LogNotFound(format!("boot {} of VM {}", boot, vm_id));
+        let index = boot
+            .checked_neg()
+            .and_then(|index| u32::try_from(index).ok())
+            .ok_or_else(not_found)?;
+
+        let log_path = PathBuf::from(vm.log_path);
+        match tokio::task::spawn_blocking(move || logs::read_boot(&log_path, index)).await {
+            Ok(Ok(log)) => Ok(log),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => Err(not_found()),
+            Ok(Err(e)) => Err(VmManagerError::PersistenceError(format!(
+                "Failed to read console log: {}",
+                e
+            ))),
+            Err(e) => Err(VmManagerError::PersistenceError(e.to_string())),
+        }
+    }
+
+    /// The clients attached to a VM's console. A VM whose console proxy
+    /// is not running has none.
+    pub async fn console_sessions(&self, vm_id: &str) -> Result<console::Attached, VmManagerError> {
+        let vm = self.get_vm(vm_id).await?;
+        match console::attached(Path::new(&vm.console_socket_path)).await {
+            Ok(attached) => Ok(attached),
+            Err(e) => {
+                tracing::debug!(vm_id = %vm_id, "No console sessions to list: {}", e);
+                Ok(console::Attached::default())
+            }
+        }
+    }
+
+    /// The recorded console sessions of a VM, oldest first.
+    pub async fn list_console_sessions(
+        &self,
+        vm_id: &str,
+    ) -> Result<Vec<ConsoleSession>, VmManagerError> {
+        let vm = self.get_vm(vm_id).await?;
+        let recordings = Recordings::new(Path::new(&vm.log_path), &vm.config.log_policy);
+        match tokio::task::spawn_blocking(move || recordings.list()).await {
+            Ok(Ok(sessions)) => Ok(sessions),
+            Ok(Err(e)) => Err(VmManagerError::PersistenceError(format!(
+                "Failed to list console sessions: {}",
+                e
+            ))),
+            Err(e) => Err(VmManagerError::PersistenceError(e.to_string())),
+        }
+    }
+
+    /// The asciicast recording of one console session of a VM.
+    pub async fn read_console_session(
+        &self,
+        vm_id: &str,
+        session_id: &str,
+    ) -> Result<Vec<u8>, VmManagerError> {
+        let vm = self.get_vm(vm_id).await?;
+        let recordings = Recordings::new(Path::new(&vm.log_path), &vm.config.log_policy);
+        let id = session_id.to_string();
+        match tokio::task::spawn_blocking(move || recordings.read(&id)).await {
+            Ok(Ok(cast)) => Ok(cast),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::NotFound => Err(
+                VmManagerError::LogNotFound(format!("console session {} of VM {}", session_id, vm_id)),
+            ),
+            Ok(Err(e)) => Err(VmManagerError::PersistenceError(format!(
+                "Failed to read console session: {}",
+                e
+            ))),
+            Err(e) => Err(VmManagerError::PersistenceError(e.to_string())),
+        }
+    }
+
+    /// Type `input` into a VM's console and wait up to `timeout` for
+    /// `pattern` to appear in the output that follows.
+    pub async fn expect_console(
+        &self,
+        vm_id: &str,
+        input: &[u8],
+        pattern: &Regex,
+        timeout: Duration,
+    ) -> Result<Expected, VmManagerError> {
+        let vm = self.get_vm(vm_id).await?;
+        if !matches!(vm.state, VmState::Running | VmState::Paused) {
+            return Err(VmManagerError::InvalidState {
+                current: vm.state,
+                operation: "expect".to_string(),
+            });
+        }
+
+        let socket_path = Path::new(&vm.console_socket_path);
+        console::expect(socket_path, input, pattern, timeout, Replay::None)
+            .await
+            .map_err(|e| match e {
+                ExpectError::Io(e) => VmManagerError::ConsoleFailed(e.to_string()),
+                ExpectError::TimedOut { output } => VmManagerError::ExpectFailed(format!(
+                    "pattern did not appear within {}s{}",
+                    timeout.as_secs(),
+                    describe_output(&output)
+                )),
+                ExpectError::GuestExited { output } => VmManagerError::GuestExited(format!(
+                    "before the pattern appeared{}",
+                    describe_output(&output)
+                )),
+            })
+    }
+
+    pub async fn list_vms(&self) -> Vec<Vm> {
+        let vms = self.vms.read().await;
+        vms.values().map(|slot| slot.record()).collect()
+    }
+
+    pub async fn attach_device(&self, vm_id: &str, device_path: String) -> Result<Vm, VmManagerError> {
+        let mut guard = self.lock_vm(vm_id).await?;
+        let entry = &mut *guard;
+
+        // Reject if device is already attached
+        if entry.vm.config.vfio_devices.contains(&device_path) {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: format!("attach_device: {} is already attached", device_path),
+            });
+        }
+
+        match entry.vm.state {
+            VmState::Running => {
+                // Hot-plug: call hypervisor API, then update config
+                if let Some(ref process) = entry.process {
+                    process.add_device(&device_path).await?;
+                } else {
+                    return Err(VmManagerError::InvalidState {
+                        current: entry.vm.state.clone(),
+                        operation: "attach_device (no process handle)".to_string(),
+                    });
+                }
+
+                entry.vm.config.vfio_devices.push(device_path);
+
+                // Persist updated config. On failure, rollback the hot-plug.
+                if let Err(e) = self.store.save(&entry.vm) {
+                    let removed = entry.vm.config.vfio_devices.pop();
+                    if let (Some(ref process), Some(path)) = (&entry.process, removed) {
+                        let _ = process.remove_device(&path).await;
+                    }
+                    return Err(e.into());
+                }
+
+                Ok(entry.vm.clone())
+            }
+            VmState::Created | VmState::Stopped | VmState::Crashed | VmState::Failed => {
+                // Config-only: device will be included at next VM start
+                entry.vm.config.vfio_devices.push(device_path);
+                self.store.save(&entry.vm)?;
+                Ok(entry.vm.clone())
+            }
+            VmState::Starting | VmState::Paused | VmState::Stopping => {
+                Err(VmManagerError::InvalidState {
+                    current: entry.vm.state.clone(),
+                    operation: "attach_device".to_string(),
+                })
+            }
+        }
+    }
+
+    pub async fn detach_device(&self, vm_id: &str, device_path: &str) -> Result<Vm, VmManagerError> {
+        let mut guard = self.lock_vm(vm_id).await?;
+        let entry = &mut *guard;
+
+        // Check that the device is actually attached
+        let pos = entry
             .vm
             .config
             .vfio_devices
@@ -448,7 +1277,7 @@ impl VmManager {
             VmState::Running => {
                 // Hot-unplug: call hypervisor API, then update config
                 if let Some(ref process) = entry.process {
-                    process.remove_device(device_path)?;
+                    process.remove_device(device_path).await?;
                 } else {
                     return Err(VmManagerError::InvalidState {
                         current: entry.vm.state.clone(),
@@ -463,71 +1292,1332 @@ impl VmManager {
                     // Re-insert at original position
                     entry.vm.config.vfio_devices.insert(pos, removed.clone());
                     if let Some(ref process) = entry.process {
-                        let _ = process.add_device(&removed);
+                        let _ = process.add_device(&removed).await;
                     }
                     return Err(e.into());
                 }
 
                 Ok(entry.vm.clone())
             }
-            VmState::Created | VmState::Stopped => {
+            VmState::Created | VmState::Stopped | VmState::Crashed | VmState::Failed => {
                 // Config-only: just remove from the device list
                 entry.vm.config.vfio_devices.remove(pos);
                 self.store.save(&entry.vm)?;
                 Ok(entry.vm.clone())
             }
-            VmState::Paused => Err(VmManagerError::InvalidState {
-                current: VmState::Paused,
-                operation: "detach_device".to_string(),
-            }),
+            VmState::Starting | VmState::Paused | VmState::Stopping => {
+                Err(VmManagerError::InvalidState {
+                    current: entry.vm.state.clone(),
+                    operation: "detach_device".to_string(),
+                })
+            }
         }
     }
 
-    pub async fn delete_vm(&self, vm_id: &str) -> Result<(), VmManagerError> {
-        let mut vms = self.vms.write().await;
+    /// Change the settings of a VM without a hypervisor. They take
+    /// effect on its next start.
+    pub async fn update_vm(&self, vm_id: &str, update: UpdateVmRequest) -> Result<Vm, VmManagerError> {
+        let mut guard = self.lock_vm(vm_id).await?;
+        let entry = &mut *guard;
 
-        let entry = vms
-            .get_mut(vm_id)
-            .ok_or_else(|| VmManagerError::VmNotFound(vm_id.to_string()))?;
+        match entry.vm.state {
+            VmState::Created | VmState::Stopped | VmState::Crashed | VmState::Failed => {}
+            VmState::Starting | VmState::Running | VmState::Paused | VmState::Stopping => {
+                return Err(VmManagerError::InvalidState {
+                    current: entry.vm.state.clone(),
+                    operation: "update".to_string(),
+                });
+            }
+        }
+
+        let mut config = entry.vm.config.clone();
+        update_config(&mut config, update);
+        validate_config(&config)?;
+
+        let previous = std::mem::replace(&mut entry.vm.config, config);
+        if let Err(e) = self.store.save(&entry.vm) {
+            entry.vm.config = previous;
+            return Err(e.into());
+        }
+        Ok(entry.vm.clone())
+    }
+
+    pub async fn delete_vm(&self, vm_id: &str) -> Result<(), VmManagerError> {
+        let mut entry = self.lock_vm(vm_id).await?;
 
         // Stop the VM if running
         if let Some(ref process) = entry.process {
-            let _ = process.kill();
+            let _ = process.kill().await;
         }
 
         // Delete from database BEFORE removing from memory
         self.store.delete(vm_id)?;
 
-        vms.remove(vm_id);
+        self.remove_vm(&mut entry).await;
+
+        self.purge_snapshots(vm_id);
+        logs::remove_all(Path::new(&entry.vm.log_path));
+        Recordings::new(Path::new(&entry.vm.log_path), &entry.vm.config.log_policy).remove_all();
         Ok(())
     }
 
-    /// Shutdown all running VMs. Called during control-plane termination.
-    pub async fn shutdown(&self) {
-        let mut vms = self.vms.write().await;
-        let mut stopped_count = 0;
+    /// Remove the snapshots of a VM that no longer exists here. They are
+    /// useless without it; failing to clean them up only leaks disk space.
+    fn purge_snapshots(&self, vm_id: &str) {
+        match self.store.load_snapshots(vm_id) {
+            Ok(snapshots) => {
+                for snapshot in snapshots {
+                    let _ = std::fs::remove_dir_all(&snapshot.path);
+                    if let Err(e) = self.store.delete_snapshot(&snapshot.id) {
+                        tracing::warn!("Failed to delete snapshot {}: {}", snapshot.id, e);
+                    }
+                }
+            }
+            Err(e) => tracing::warn!("Failed to load snapshots of removed VM {}: {}", vm_id, e),
+        }
+    }
 
-        for (vm_id, entry) in vms.iter_mut() {
-            if let Some(ref process) = entry.process {
-                tracing::info!("Stopping VM {} ({})...", entry.vm.name, vm_id);
-                let _ = process.kill();
-                stopped_count += 1;
-
-                // Update state in DB - log warning if fails
-                if let Err(e) = self.store.update_state(vm_id, VmState::Stopped) {
-                    tracing::warn!(
-                        "Failed to persist VM {} state change to Stopped: {}",
-                        vm_id,
-                        e
+    /// Live-migrate a running VM to the control plane at `target`. The
+    /// guest keeps running throughout; on success the VM exists only on
+    /// the target, under the same id and name. Snapshots are not moved.
+    pub async fn migrate_vm(&self, vm_id: &str, target: &str) -> Result<(), VmManagerError> {
+        let peer = PeerClient::new(target);
+        let mut guard = self.lock_vm(vm_id).await?;
+        let entry = &mut *guard;
+
+        if entry.vm.state != VmState::Running {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: "migrate".to_string(),
+            });
+        }
+        if !entry.vm.hypervisor.supports_live_migration() {
+            return Err(HypervisorError::Unsupported(format!(
+                "{} does not support live migration",
+                entry.vm.hypervisor
+            ))
+            .into());
+        }
+        if !entry.vm.config.vfio_devices.is_empty() {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: "migrate (VFIO devices attached)".to_string(),
+            });
+        }
+        let Some(process) = entry.process.as_deref() else {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: "migrate (no process handle)".to_string(),
+            });
+        };
+
+        let uri = peer
+            .prepare(&entry.vm.id, &entry.vm.name, &entry.vm.config)
+            .await
+            .map_err(VmManagerError::MigrationFailed)?;
+
+        tracing::info!(vm_id = %vm_id, target = %peer.base_url(), uri = %uri, "Migrating VM");
+
+        let handover = match process.send_migration(&uri).await {
+            Ok(()) => peer.complete(vm_id).await,
+            Err(e) => Err(e.to_string()),
+        };
+        if let Err(e) = handover {
+            peer.abort(vm_id).await;
+            // A failed QEMU migration leaves the source running, or paused
+            // if it got as far as the final copy. Cloud-Hypervisor may have
+            // torn the guest down already, in which case it is lost.
+            if let Err(resume_err) = process.resume().await {
+                if let Some(process) = entry.process.take() {
+                    let _ = process.kill().await;
+                }
+                entry.vm.state = VmState::Failed;
+                entry.vm.hypervisor_pid = None;
+                entry.vm.last_error = Some(format!(
+                    "migration failed: {}; guest could not be resumed: {}",
+                    e, resume_err
+                ));
+                if let Err(persist_err) = self.store.save(&entry.vm) {
+                    tracing::error!(
+                        "Failed to persist VM {} state change to Failed: {}. State will be reconciled on restart.",
+                        vm_id, persist_err
                     );
                 }
             }
-            entry.process = None;
-            entry.vm.state = VmState::Stopped;
+            return Err(VmManagerError::MigrationFailed(e));
+        }
+
+        // The guest now runs on the target; what is left here is a paused
+        // (QEMU) or empty (Cloud-Hypervisor) hypervisor.
+        if let Some(process) = entry.process.take() {
+            let _ = process.kill().await;
+        }
+        if let Err(e) = self.store.delete(vm_id) {
+            tracing::error!(
+                "Failed to delete migrated VM {} from the database: {}. It will show as stopped after a restart.",
+                vm_id,
+                e
+            );
+        }
+        self.remove_vm(entry).await;
+        self.purge_snapshots(vm_id);
+        logs::remove_all(Path::new(&entry.vm.log_path));
+        Recordings::new(Path::new(&entry.vm.log_path), &entry.vm.config.log_policy).remove_all();
+
+        tracing::info!(vm_id = %vm_id, target = %peer.base_url(), "VM migrated");
+        Ok(())
+    }
+
+    /// Target side of a live migration: record a VM arriving from another
+    /// control plane and start a hypervisor waiting for its state. Returns
+    /// the record and the URI the source hypervisor should send to.
+    pub async fn prepare_incoming(
+        &self,
+        id: String,
+        name: String,
+        config: VmConfig,
+    ) -> Result<(Vm, String), VmManagerError> {
+        if !config.hypervisor.supports_live_migration() {
+            return Err(HypervisorError::Unsupported(format!(
+                "{} does not support live migration",
+                config.hypervisor
+            ))
+            .into());
+        }
+        if !config.vfio_devices.is_empty() {
+            return Err(HypervisorError::InvalidConfig(
+                "VMs with VFIO devices cannot be migrated".to_string(),
+            )
+            .into());
+        }
+
+        let backend = self.get_backend(config.hypervisor)?;
+
+        let mut vm = Vm::incoming(id, name, config);
+        vm.state = VmState::Starting;
+        let uri = format!("unix:{}", migration_socket_path(&vm).display());
+
+        // Claim the id and name, locked, while the hypervisor starts.
+        let mut entry = {
+            let mut vms = self.vms.write().await;
+
+            let name_taken = vms.values().any(|slot| slot.record().name == vm.name);
+            if vms.contains_key(&vm.id) || name_taken {
+                return Err(VmManagerError::VmAlreadyExists(vm.name));
+            }
+
+            self.store.save(&vm)?;
+
+            let mut entry = VmEntry::new(vm, None);
+            entry.incoming = true;
+            let slot = VmSlot::new(entry);
+            let entry = VmGuard(slot.entry.clone().lock_owned().await);
+            vms.insert(entry.vm.id.clone(), slot);
+            entry
+        };
+
+        let process = match Self::launch_incoming(backend, &entry.vm, &uri).await {
+            Ok(process) => process,
+            Err(e) => {
+                if let Err(persist_err) = self.store.delete(&entry.vm.id) {
+                    tracing::error!(
+                        "Failed to remove incoming VM {} after a failed prepare: {}",
+                        entry.vm.id,
+                        persist_err
+                    );
+                }
+                self.remove_vm(&mut entry).await;
+                return Err(e.into());
+            }
+        };
+
+        // Recorded so a restart can kill the waiting hypervisor.
+        entry.vm.hypervisor_pid = process.process_id();
+        if let Err(e) = self.store.save(&entry.vm) {
+            tracing::error!(
+                "Failed to persist hypervisor pid of incoming VM {}: {}",
+                entry.vm.id,
+                e
+            );
+        }
+        entry.process = Some(process);
+
+        tracing::info!(vm_id = %entry.vm.id, uri = %uri, "Waiting for incoming migration");
+
+        Ok((entry.vm.clone(), uri))
+    }
+
+    /// Spawn a hypervisor that waits for a migration stream on `uri`. Any
+    /// partially started process is killed before an error is returned.
+    async fn launch_incoming(
+        backend: &dyn Hypervisor,
+        vm: &Vm,
+        uri: &str,
+    ) -> Result<Box<dyn HypervisorProcess>, HypervisorError> {
+        let process = backend
+            .spawn(&vm.config, &vm.socket_path, &vm.console_socket_path, &vm.log_path)
+            .await?;
+
+        if let Err(e) = process.receive_migration(&vm.config, uri).await {
+            let _ = process.kill().await;
+            return Err(e);
+        }
+
+        Ok(process)
+    }
+
+    /// Target side of a live migration: the source has sent everything;
+    /// wait for the guest to resume here and mark the VM running. On
+    /// failure the VM is left `Failed` for the source to abort.
+    pub async fn complete_incoming(self: &Arc<Self>, vm_id: &str) -> Result<Vm, VmManagerError> {
+        let mut guard = self.lock_vm(vm_id).await?;
+        let entry = &mut *guard;
+
+        if !entry.incoming || entry.vm.state != VmState::Starting {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: "complete migration".to_string(),
+            });
+        }
+        let Some(process) = entry.process.as_deref() else {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: "complete migration (no process handle)".to_string(),
+            });
+        };
+
+        let result = process.finish_migration(MIGRATION_FINISH_TIMEOUT).await;
+        let _ = std::fs::remove_file(migration_socket_path(&entry.vm));
+
+        if let Err(e) = result {
+            let message = format!("incoming migration failed: {}", e);
+            tracing::error!(vm_id = %vm_id, "{}", message);
+
+            if let Some(process) = entry.process.take() {
+                let _ = process.kill().await;
+            }
+            entry.vm.state = VmState::Failed;
+            entry.vm.hypervisor_pid = None;
+            entry.vm.last_error = Some(message);
+            if let Err(persist_err) = self.store.save(&entry.vm) {
+                tracing::error!(
+                    "Failed to persist VM {} state change to Failed: {}. State will be reconciled on restart.",
+                    vm_id, persist_err
+                );
+            }
+            return Err(e.into());
+        }
+
+        let mut updated = entry.vm.clone();
+        updated.state = VmState::Running;
+        begin_boot(&mut updated, unix_millis());
+        if let Err(e) = self.store.save(&updated) {
+            // The source still holds its record and will resume or fail
+            // its copy when we report the error.
+            if let Some(process) = entry.process.take() {
+                let _ = process.kill().await;
+            }
+            entry.vm.state = VmState::Failed;
+            entry.vm.last_error = Some(e.to_string());
+            return Err(e.into());
+        }
+
+        entry.vm = updated;
+        entry.incoming = false;
+        self.watch_readiness(entry, Replay::All);
+
+        tracing::info!(vm_id = %vm_id, "Incoming migration complete");
+        Ok(entry.vm.clone())
+    }
+
+    /// Target side of a live migration: the source gave up. Kill the
+    /// waiting hypervisor and forget the VM.
+    pub async fn abort_incoming(&self, vm_id: &str) -> Result<(), VmManagerError> {
+        let mut entry = self.lock_vm(vm_id).await?;
+
+        if !entry.incoming {
+            return Err(VmManagerError::InvalidState {
+                current: entry.vm.state.clone(),
+                operation: "abort migration".to_string(),
+            });
+        }
+
+        if let Some(process) = entry.process.take() {
+            let _ = process.kill().await;
+        }
+        let _ = std::fs::remove_file(migration_socket_path(&entry.vm));
+
+        self.store.delete(vm_id)?;
+        self.remove_vm(&mut entry).await;
+
+        tracing::info!(vm_id = %vm_id, "Incoming migration aborted");
+        Ok(())
+    }
+
+    /// Spawn the background supervisor that notices hypervisor processes
+    /// exiting on their own (guest panic with `-no-reboot`, crash, external
+    /// `kill`). The task holds only a weak reference, so it ends once the
+    /// manager is dropped.
+    pub fn spawn_supervisor(self: &Arc<Self>) -> JoinHandle<()> {
+        let manager = Arc::downgrade(self);
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SUPERVISOR_POLL_INTERVAL);
+            loop {
+                interval.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    break;
+                };
+                manager.reap_exited_vms().await;
+            }
+        })
+    }
+
+    /// Move every VM whose hypervisor process has exited out of
+    /// Running/Paused, recording the exit status in the store.
+    async fn reap_exited_vms(&self) {
+        let slots: Vec<(String, Arc<VmSlot>)> = {
+            let vms = self.vms.read().await;
+            vms.iter()
+                .map(|(vm_id, slot)| (vm_id.clone(), Arc::clone(slot)))
+                .collect()
+        };
+
+        for (vm_id, slot) in slots {
+            // A VM in the middle of an operation is skipped rather than
+            // waited for; the next pass picks up an exit the operation
+            // itself did not notice.
+            let Ok(entry) = slot.entry.clone().try_lock_owned() else {
+                continue;
+            };
+            let mut guard = VmGuard(entry);
+            let entry = &mut *guard;
+            if entry.removed {
+                continue;
+            }
+            let Some(mut exit) = entry.process.as_ref().and_then(|p| p.try_wait()) else {
+                continue;
+            };
+
+            // The child is already gone; kill() just tears down the console
+            // proxy thread and unlinks the sockets.
+            if let Some(process) = entry.process.take() {
+                let _ = process.kill().await;
+            }
+
+            exit.console_tail = read_console_tail(&entry.vm.log_path, CONSOLE_TAIL_LINES);
+            let next = if exit.is_crash() {
+                VmState::Crashed
+            } else {
+                VmState::Stopped
+            };
+            if !entry.vm.state.can_transition_to(&next) {
+                tracing::error!(
+                    vm_id = %vm_id,
+                    "Unexpected hypervisor exit in state {:?}",
+                    entry.vm.state
+                );
+            }
+            tracing::warn!(
+                vm_id = %vm_id,
+                name = %entry.vm.name,
+                "Hypervisor process {} while VM was {:?}, now {:?}",
+                exit,
+                entry.vm.state,
+                next
+            );
+
+            entry.vm.state = next;
+            entry.vm.last_exit = Some(exit);
+            entry.vm.hypervisor_pid = None;
+
+            // The process is already gone, so there is nothing to roll back;
+            // reconciliation converges on restart if this fails.
+            if let Err(e) = self.store.save(&entry.vm) {
+                tracing::error!(
+                    "Failed to persist exit of VM {}: {}. State will be reconciled on restart.",
+                    vm_id,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Let go of all hypervisors. Called during control-plane termination.
+    /// Guests keep running, and `initialize` takes them over again on the
+    /// next start; use `stop_vm` to actually stop one.
+    pub async fn shutdown(&self) {
+        let slots: Vec<Arc<VmSlot>> = self.vms.read().await.values().cloned().collect();
+        let mut detached_count = 0;
+
+        // Dropping a handle stops nothing: hypervisors run in their own
+        // session and the console proxies die with us.
+        for slot in slots {
+            let mut entry = slot.entry.clone().lock_owned().await;
+            if entry.process.take().is_some() {
+                tracing::info!("Leaving VM {} ({}) running", entry.vm.name, entry.vm.id);
+                detached_count += 1;
+            }
+        }
+
+        if detached_count > 0 {
+            tracing::info!("Detached from {} running VM(s)", detached_count);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{ReadinessProbe, VmExit};
+    use async_trait::async_trait;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tempfile::TempDir;
+    use tokio::io::AsyncReadExt;
+    use tokio::sync::Notify;
+
+    /// What a `MockProcess` was asked to do.
+    #[derive(Default)]
+    struct MockLog {
+        pressed: AtomicBool,
+        resumed: AtomicBool,
+        killed: AtomicBool,
+    }
+
+    /// Stand-in for a hypervisor process, shared by the tests below. It
+    /// records calls in `log`; the other fields pick how it behaves.
+    #[derive(Default)]
+    struct MockProcess {
+        log: Arc<MockLog>,
+        /// The "child" has already exited with this status.
+        exited: Option<ExitStatus>,
+        /// The guest stays up when the power button is pressed, instead of
+        /// powering off.
+        ignores_power_button: bool,
+        /// `reset` fails and leaves no hypervisor behind, like a
+        /// Firecracker respawn that cannot boot.
+        reset_breaks_it: bool,
+        /// `snapshot` writes a marker file, standing in for the backend's
+        /// state files. Unsupported otherwise.
+        snapshots: bool,
+        /// `pause` notifies the first and then hangs until the second is
+        /// notified, like a QMP command that gets no answer.
+        hang_pause: Option<(Arc<Notify>, Arc<Notify>)>,
+    }
+
+    impl MockProcess {
+        fn logging(log: &Arc<MockLog>) -> Self {
+            Self {
+                log: log.clone(),
+                ..Default::default()
+            }
+        }
+
+        fn exited(status: ExitStatus) -> Self {
+            Self {
+                exited: Some(status),
+                ..Default::default()
+            }
+        }
+
+        fn snapshotting() -> Self {
+            Self {
+                snapshots: true,
+                ..Default::default()
+            }
+        }
+    }
+
+    #[async_trait]
+    impl HypervisorProcess for MockProcess {
+        async fn configure(&self, _config: &VmConfig) -> Result<(), HypervisorError> {
+            Ok(())
+        }
+        async fn start(&self) -> Result<(), HypervisorError> {
+            Ok(())
+        }
+        async fn pause(&self) -> Result<(), HypervisorError> {
+            if let Some((entered, release)) = &self.hang_pause {
+                entered.notify_one();
+                release.notified().await;
+            }
+            Ok(())
+        }
+        async fn resume(&self) -> Result<(), HypervisorError> {
+            self.log.resumed.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+        async fn kill(&self) -> Result<(), HypervisorError> {
+            self.log.killed.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+        async fn power_button(&self) -> Result<(), HypervisorError> {
+            self.log.pressed.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+        async fn reset(&self) -> Result<(), HypervisorError> {
+            if self.reset_breaks_it {
+                self.log.killed.store(true, Ordering::SeqCst);
+                return Err(HypervisorError::Timeout("no API socket".to_string()));
+            }
+            Ok(())
+        }
+        async fn snapshot(&self, dir: &Path) -> Result<(), HypervisorError> {
+            if !self.snapshots {
+                return Err(HypervisorError::Unsupported("no snapshots".to_string()));
+            }
+            std::fs::write(dir.join("state"), b"guest state")?;
+            Ok(())
         }
+        fn try_wait(&self) -> Option<VmExit> {
+            if let Some(status) = self.exited {
+                return Some(VmExit::from(status));
+            }
+            let powered_off =
+                self.log.pressed.load(Ordering::SeqCst) && !self.ignores_power_button;
+            powered_off.then(|| VmExit::from(ExitStatus::from_raw(0)))
+        }
+        fn is_running(&self) -> bool {
+            self.try_wait().is_none() && !self.log.killed.load(Ordering::SeqCst)
+        }
+        fn socket_path(&self) -> &str {
+            "/tmp/test.sock"
+        }
+        fn console_socket_path(&self) -> &str {
+            "/tmp/test.console.sock"
+        }
+        fn log_path(&self) -> &str {
+            "/tmp/test.log"
+        }
+    }
+
+    fn test_config() -> VmConfig {
+        VmConfig {
+            vcpu_count: 1,
+            mem_size_mib: 256,
+            kernel_image_path: "/path/to/kernel".to_string(),
+            rootfs_path: "/path/to/rootfs.ext4".to_string(),
+            kernel_args: String::new(),
+            hypervisor: HypervisorType::Qemu,
+            vfio_devices: Vec::new(),
+            no_reboot: true,
+            log_policy: Default::default(),
+            readiness: None,
+        }
+    }
+
+    /// Create a VM and pretend it is running with the given process handle.
+    async fn insert_running_vm(
+        manager: &VmManager,
+        name: &str,
+        process: Box<dyn HypervisorProcess>,
+    ) -> String {
+        let vm = manager
+            .create_vm(name.to_string(), test_config())
+            .await
+            .unwrap();
+        let mut entry = manager.lock_vm(&vm.id).await.unwrap();
+        entry.vm.state = VmState::Running;
+        entry.process = Some(process);
+        vm.id
+    }
+
+    #[tokio::test]
+    async fn reaper_records_exit_code_and_marks_crash() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let manager = VmManager::with_db_path(db_path.clone()).unwrap();
+
+        let log = Arc::new(MockLog::default());
+        let vm_id = insert_running_vm(
+            &manager,
+            "crashy",
+            Box::new(MockProcess {
+                exited: Some(ExitStatus::from_raw(1 << 8)),
+                ..MockProcess::logging(&log)
+            }),
+        )
+        .await;
+
+        manager.reap_exited_vms().await;
+
+        let vm = manager.get_vm(&vm_id).await.unwrap();
+        assert_eq!(vm.state, VmState::Crashed);
+        let exit = vm.last_exit.unwrap();
+        assert_eq!(exit.code, Some(1));
+        assert_eq!(exit.signal, None);
+        assert!(log.killed.load(Ordering::SeqCst), "console proxy not torn down");
+
+        // The exit reason must survive a control-plane restart.
+        drop(manager);
+        let store = VmStore::open(&db_path).unwrap();
+        let persisted = store.load_all().unwrap();
+        assert_eq!(persisted[0].state, VmState::Crashed);
+        assert_eq!(persisted[0].last_exit.as_ref().unwrap().code, Some(1));
+    }
+
+    #[tokio::test]
+    async fn reaper_distinguishes_poweroff_from_kernel_panic() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let clean_id = insert_running_vm(
+            &manager,
+            "poweroff",
+            Box::new(MockProcess::exited(ExitStatus::from_raw(0))),
+        )
+        .await;
+        let panic_id = insert_running_vm(
+            &manager,
+            "panicked",
+            Box::new(MockProcess::exited(ExitStatus::from_raw(0))),
+        )
+        .await;
+
+        // With `-no-reboot panic=1` a panic also exits 0; only the console
+        // tells the two apart.
+        let log_path = temp_dir.path().join("panic.log");
+        std::fs::write(
+            &log_path,
+            "[    1.0] VFS: Unable to mount root fs\r\n[    1.1] Kernel panic - not syncing: VFS\r\n",
+        )
+        .unwrap();
+        manager.lock_vm(&panic_id).await.unwrap().vm.log_path =
+            log_path.to_string_lossy().into_owned();
+
+        manager.reap_exited_vms().await;
+
+        let clean = manager.get_vm(&clean_id).await.unwrap();
+        assert_eq!(clean.state, VmState::Stopped);
+        assert_eq!(clean.last_exit.unwrap().code, Some(0));
+
+        let panicked = manager.get_vm(&panic_id).await.unwrap();
+        assert_eq!(panicked.state, VmState::Crashed);
+        assert_eq!(
+            panicked.last_exit.unwrap().console_tail,
+            vec![
+                "[    1.0] VFS: Unable to mount root fs".to_string(),
+                "[    1.1] Kernel panic - not syncing: VFS".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn transition_table_matches_lifecycle() {
+        use VmState::*;
+
+        for from in [Created, Stopped, Crashed, Failed] {
+            assert!(from.can_transition_to(&Starting), "{:?} -> Starting", from);
+            assert!(!from.can_transition_to(&Running), "{:?} -> Running", from);
+            assert!(!from.can_transition_to(&Stopping), "{:?} -> Stopping", from);
+        }
+        assert!(Starting.can_transition_to(&Running));
+        assert!(Starting.can_transition_to(&Failed));
+        assert!(!Starting.can_transition_to(&Stopping));
+        assert!(Running.can_transition_to(&Paused));
+        assert!(Paused.can_transition_to(&Running));
+        assert!(!Paused.can_transition_to(&Paused));
+        assert!(Running.can_transition_to(&Crashed));
+        assert!(Running.can_transition_to(&Failed));
+        assert!(Stopping.can_transition_to(&Stopped));
+        assert!(!Stopping.can_transition_to(&Running));
+        assert!(!Running.can_transition_to(&Starting));
+    }
+
+    #[tokio::test]
+    async fn reaper_records_terminating_signal() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let vm_id = insert_running_vm(
+            &manager,
+            "sigkilled",
+            Box::new(MockProcess::exited(ExitStatus::from_raw(libc::SIGKILL))),
+        )
+        .await;
+
+        manager.reap_exited_vms().await;
+
+        let vm = manager.get_vm(&vm_id).await.unwrap();
+        assert_eq!(vm.state, VmState::Crashed);
+        let exit = vm.last_exit.unwrap();
+        assert_eq!(exit.signal, Some(libc::SIGKILL));
+        assert_eq!(exit.to_string(), "killed by signal 9");
+    }
+
+    #[tokio::test]
+    async fn stop_presses_power_button_before_killing() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let log = Arc::new(MockLog::default());
+        let vm_id =
+            insert_running_vm(&manager, "graceful", Box::new(MockProcess::logging(&log))).await;
+
+        let vm = manager
+            .stop_vm(&vm_id, false, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(vm.state, VmState::Stopped);
+        assert!(log.pressed.load(Ordering::SeqCst));
+        // kill() still runs to tear down the console proxy and sockets.
+        assert!(log.killed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn stop_with_unbounded_timeout_still_stops() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let log = Arc::new(MockLog::default());
+        let vm_id =
+            insert_running_vm(&manager, "patient", Box::new(MockProcess::logging(&log))).await;
+
+        // Too far off to be added to an Instant.
+        let vm = manager.stop_vm(&vm_id, false, Duration::MAX).await.unwrap();
+
+        assert_eq!(vm.state, VmState::Stopped);
+        assert!(log.pressed.load(Ordering::SeqCst));
+        assert!(log.killed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn stop_escalates_when_guest_ignores_power_button() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let log = Arc::new(MockLog::default());
+        let process = MockProcess {
+            ignores_power_button: true,
+            ..MockProcess::logging(&log)
+        };
+        let vm_id = insert_running_vm(&manager, "stubborn", Box::new(process)).await;
+
+        let started = std::time::Instant::now();
+        let vm = manager
+            .stop_vm(&vm_id, false, Duration::from_millis(300))
+            .await
+            .unwrap();
+
+        assert_eq!(vm.state, VmState::Stopped);
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert!(log.pressed.load(Ordering::SeqCst));
+        assert!(log.killed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn force_stop_skips_power_button() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let log = Arc::new(MockLog::default());
+        let vm_id =
+            insert_running_vm(&manager, "forced", Box::new(MockProcess::logging(&log))).await;
+
+        let vm = manager
+            .stop_vm(&vm_id, true, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert_eq!(vm.state, VmState::Stopped);
+        assert!(!log.pressed.load(Ordering::SeqCst));
+        assert!(log.killed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn stop_resumes_paused_guest_before_power_button() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let log = Arc::new(MockLog::default());
+        let vm_id =
+            insert_running_vm(&manager, "paused", Box::new(MockProcess::logging(&log))).await;
+        manager.pause_vm(&vm_id).await.unwrap();
+
+        manager
+            .stop_vm(&vm_id, false, Duration::from_secs(5))
+            .await
+            .unwrap();
+
+        assert!(log.resumed.load(Ordering::SeqCst));
+        assert!(log.pressed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn reset_keeps_vm_running() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let vm_id = insert_running_vm(
+            &manager,
+            "resettable",
+            Box::new(MockProcess::default()),
+        )
+        .await;
+
+        let vm = manager.reset_vm(&vm_id).await.unwrap();
+        assert_eq!(vm.state, VmState::Running);
+
+        // The stub has no reboot(), so the trait default reports it.
+        assert!(matches!(
+            manager.reboot_vm(&vm_id).await,
+            Err(VmManagerError::HypervisorError(HypervisorError::Unsupported(_)))
+        ));
+        assert_eq!(manager.get_vm(&vm_id).await.unwrap().state, VmState::Running);
+    }
 
-        if stopped_count > 0 {
-            tracing::info!("Stopped {} running VM(s)", stopped_count);
+    #[tokio::test]
+    async fn readiness_probe_gates_wait_ready_and_reruns_after_reset() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let manager = VmManager::with_db_path(db_path.clone()).unwrap();
+
+        let vm_id = insert_running_vm(
+            &manager,
+            "probed",
+            Box::new(MockProcess::default()),
+        )
+        .await;
+
+        // Without a probe the guest is ready as soon as it (re)boots.
+        manager.reset_vm(&vm_id).await.unwrap();
+        assert!(manager.wait_ready(&vm_id, Duration::ZERO).await.unwrap().is_ready());
+        assert!(manager.wait_ready(&vm_id, Duration::MAX).await.unwrap().is_ready());
+
+        // A console nobody closes, and a port nobody listens on yet.
+        let console_path = temp_dir.path().join("console.sock");
+        let _console = tokio::net::UnixListener::bind(&console_path).unwrap();
+        let port = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        {
+            let mut entry = manager.lock_vm(&vm_id).await.unwrap();
+            entry.vm.console_socket_path = console_path.to_string_lossy().into_owned();
+            entry.vm.config.readiness = Some(ReadinessProbe::Tcp {
+                host: "127.0.0.1".to_string(),
+                port,
+            });
         }
+
+        let vm = manager.reset_vm(&vm_id).await.unwrap();
+        assert!(!vm.is_ready());
+        assert!(matches!(
+            manager.wait_ready(&vm_id, Duration::from_millis(200)).await,
+            Err(VmManagerError::NotReady(_))
+        ));
+
+        let _guest = tokio::net::TcpListener::bind(("127.0.0.1", port)).await.unwrap();
+        let vm = manager.wait_ready(&vm_id, Duration::from_secs(5)).await.unwrap();
+        assert!(vm.boot_duration_ms.unwrap() >= 200);
+        assert!(vm.uptime_secs().is_some());
+
+        // A stopped VM is not ready, and waiting for it fails at once.
+        manager.lock_vm(&vm_id).await.unwrap().vm.state = VmState::Stopped;
+        let vm = manager.get_vm(&vm_id).await.unwrap();
+        assert!(!vm.is_ready());
+        assert_eq!(vm.uptime_secs(), None);
+        assert!(matches!(
+            manager.wait_ready(&vm_id, Duration::MAX).await,
+            Err(VmManagerError::NotReady(message)) if message.contains("Stopped")
+        ));
+    }
+
+    #[tokio::test]
+    async fn expect_tells_timeout_exit_and_broken_console_apart() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+        let vm_id = insert_running_vm(&manager, "typed", Box::new(MockProcess::default())).await;
+        let pattern = Regex::new("login:").unwrap();
+        let expect = |console_path: &Path| {
+            let manager = Arc::clone(&manager);
+            let vm_id = vm_id.clone();
+            let pattern = pattern.clone();
+            let console_path = console_path.to_string_lossy().into_owned();
+            async move {
+                manager.lock_vm(&vm_id).await.unwrap().vm.console_socket_path = console_path;
+                let timeout = Duration::from_millis(200);
+                manager.expect_console(&vm_id, b"", &pattern, timeout).await
+            }
+        };
+
+        // No proxy listening
+        let missing = temp_dir.path().join("missing.sock");
+        assert!(matches!(
+            expect(&missing).await,
+            Err(VmManagerError::ConsoleFailed(_))
+        ));
+
+        // A console that stays quiet
+        let quiet = temp_dir.path().join("quiet.sock");
+        let _quiet = tokio::net::UnixListener::bind(&quiet).unwrap();
+        assert!(matches!(
+            expect(&quiet).await,
+            Err(VmManagerError::ExpectFailed(_))
+        ));
+
+        // A console that closes, as the proxy does when the guest exits
+        let closing = temp_dir.path().join("closing.sock");
+        let listener = tokio::net::UnixListener::bind(&closing).unwrap();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let _ = stream.read(&mut [0u8; 256]).await;
+        });
+        assert!(matches!(
+            expect(&closing).await,
+            Err(VmManagerError::GuestExited(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn reset_that_loses_the_hypervisor_marks_vm_failed() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let vm_id = insert_running_vm(
+            &manager,
+            "broken",
+            Box::new(MockProcess {
+                reset_breaks_it: true,
+                ..Default::default()
+            }),
+        )
+        .await;
+
+        assert!(manager.reset_vm(&vm_id).await.is_err());
+
+        let vm = manager.get_vm(&vm_id).await.unwrap();
+        assert_eq!(vm.state, VmState::Failed);
+        assert!(vm.last_error.unwrap().starts_with("reset failed"));
+        assert!(manager.lock_vm(&vm_id).await.unwrap().process.is_none());
+    }
+
+    #[tokio::test]
+    async fn snapshot_requires_paused_vm() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let vm_id =
+            insert_running_vm(&manager, "warm", Box::new(MockProcess::snapshotting())).await;
+
+        assert!(matches!(
+            manager.snapshot_vm(&vm_id, None).await,
+            Err(VmManagerError::InvalidState { .. })
+        ));
+
+        manager.pause_vm(&vm_id).await.unwrap();
+        let snapshot = manager
+            .snapshot_vm(&vm_id, Some("booted".to_string()))
+            .await
+            .unwrap();
+
+        assert_eq!(snapshot.vm_id, vm_id);
+        assert_eq!(snapshot.name.as_deref(), Some("booted"));
+        assert!(Path::new(&snapshot.path).starts_with(temp_dir.path().join("snapshots")));
+        assert!(Path::new(&snapshot.path).join("state").exists());
+        assert_eq!(manager.get_vm(&vm_id).await.unwrap().state, VmState::Paused);
+
+        let listed = manager.list_snapshots(&vm_id).await.unwrap();
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].id, snapshot.id);
+
+        // Restoring needs the VM to be stopped first.
+        assert!(matches!(
+            manager.restore_vm(&vm_id, &snapshot.id).await,
+            Err(VmManagerError::InvalidState { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn snapshot_of_unsupported_backend_leaves_nothing_behind() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let vm_id = insert_running_vm(
+            &manager,
+            "nosnap",
+            Box::new(MockProcess::default()),
+        )
+        .await;
+        manager.pause_vm(&vm_id).await.unwrap();
+
+        assert!(matches!(
+            manager.snapshot_vm(&vm_id, None).await,
+            Err(VmManagerError::HypervisorError(HypervisorError::Unsupported(_)))
+        ));
+        assert!(manager.list_snapshots(&vm_id).await.unwrap().is_empty());
+        let leftovers = std::fs::read_dir(temp_dir.path().join("snapshots"))
+            .unwrap()
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+
+    #[tokio::test]
+    async fn snapshots_belong_to_their_vm() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let vm_id =
+            insert_running_vm(&manager, "source", Box::new(MockProcess::snapshotting())).await;
+        manager.pause_vm(&vm_id).await.unwrap();
+        let snapshot = manager.snapshot_vm(&vm_id, None).await.unwrap();
+
+        let other = manager
+            .create_vm("other".to_string(), test_config())
+            .await
+            .unwrap();
+        assert!(matches!(
+            manager.restore_vm(&other.id, &snapshot.id).await,
+            Err(VmManagerError::SnapshotNotFound(_))
+        ));
+        assert!(matches!(
+            manager.delete_snapshot(&other.id, &snapshot.id).await,
+            Err(VmManagerError::SnapshotNotFound(_))
+        ));
+
+        // Deleting the VM takes its snapshots with it.
+        manager.delete_vm(&vm_id).await.unwrap();
+        assert!(!Path::new(&snapshot.path).exists());
+        assert!(matches!(
+            manager.store.get_snapshot(&snapshot.id),
+            Err(PersistenceError::SnapshotNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn migrate_requires_running_vm_and_capable_backend() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let created = manager
+            .create_vm("idle".to_string(), test_config())
+            .await
+            .unwrap();
+        assert!(matches!(
+            manager.migrate_vm(&created.id, "http://127.0.0.1:1").await,
+            Err(VmManagerError::InvalidState { .. })
+        ));
+
+        let vm_id =
+            insert_running_vm(&manager, "firecracker", Box::new(MockProcess::default())).await;
+        manager.lock_vm(&vm_id).await.unwrap().vm.hypervisor =
+            HypervisorType::Firecracker;
+        assert!(matches!(
+            manager.migrate_vm(&vm_id, "http://127.0.0.1:1").await,
+            Err(VmManagerError::HypervisorError(HypervisorError::Unsupported(_)))
+        ));
+    }
+
+    #[tokio::test]
+    async fn unreachable_target_leaves_vm_running() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let vm_id = insert_running_vm(&manager, "stay", Box::new(MockProcess::default())).await;
+
+        assert!(matches!(
+            manager.migrate_vm(&vm_id, "http://127.0.0.1:1").await,
+            Err(VmManagerError::MigrationFailed(_))
+        ));
+        let vm = manager.get_vm(&vm_id).await.unwrap();
+        assert_eq!(vm.state, VmState::Running);
+        assert_eq!(manager.store.load_all().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn incoming_migration_rejects_clashes_and_firecracker() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let existing = manager
+            .create_vm("taken".to_string(), test_config())
+            .await
+            .unwrap();
+
+        // Same id under another name, and same name under another id.
+        assert!(matches!(
+            manager
+                .prepare_incoming(existing.id.clone(), "fresh".to_string(), test_config())
+                .await,
+            Err(VmManagerError::VmAlreadyExists(_))
+        ));
+        assert!(matches!(
+            manager
+                .prepare_incoming("other-id".to_string(), "taken".to_string(), test_config())
+                .await,
+            Err(VmManagerError::VmAlreadyExists(_))
+        ));
+
+        let mut config = test_config();
+        config.hypervisor = HypervisorType::Firecracker;
+        assert!(matches!(
+            manager
+                .prepare_incoming("fc-id".to_string(), "fc".to_string(), config)
+                .await,
+            Err(VmManagerError::HypervisorError(HypervisorError::Unsupported(_)))
+        ));
+        assert_eq!(manager.list_vms().await.len(), 1);
+
+        // A VM that was created here is not an incoming migration.
+        assert!(matches!(
+            manager.complete_incoming(&existing.id).await,
+            Err(VmManagerError::InvalidState { .. })
+        ));
+        assert!(matches!(
+            manager.abort_incoming(&existing.id).await,
+            Err(VmManagerError::InvalidState { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn restart_kills_hypervisors_it_cannot_take_over() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+
+        // A live process with no QMP socket: QEMU cannot attach to it.
+        let mut sleeper = std::process::Command::new("sleep")
+            .arg("60")
+            .spawn()
+            .unwrap();
+        let live = ProcessId::of(sleeper.id()).unwrap();
+        let reaper = std::thread::spawn(move || sleeper.wait());
+        // Same pid, different start time: a recycled pid, not our process.
+        let recycled = ProcessId {
+            start_time: live.start_time + 1,
+            ..live
+        };
+
+        {
+            let store = VmStore::open(&db_path).unwrap();
+            for (name, state, pid) in [
+                ("unattachable", VmState::Running, Some(live)),
+                ("gone", VmState::Paused, Some(recycled)),
+                ("booting", VmState::Starting, None),
+            ] {
+                let mut vm = Vm::new(name.to_string(), test_config());
+                vm.state = state;
+                vm.hypervisor_pid = pid;
+                store.save(&vm).unwrap();
+            }
+        }
+
+        let manager = VmManager::with_db_path(db_path).unwrap();
+        manager.initialize().await.unwrap();
+
+        let vms = manager.list_vms().await;
+        let state_of = |name: &str| {
+            let vm = vms.iter().find(|vm| vm.name == name).unwrap();
+            assert_eq!(vm.hypervisor_pid, None);
+            vm.state.clone()
+        };
+        assert_eq!(state_of("unattachable"), VmState::Stopped);
+        assert_eq!(state_of("gone"), VmState::Stopped);
+        assert_eq!(state_of("booting"), VmState::Failed);
+
+        let status = reaper.join().unwrap().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+    }
+
+    #[tokio::test]
+    async fn shutdown_leaves_guests_running() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let log = Arc::new(MockLog::default());
+        let vm_id =
+            insert_running_vm(&manager, "survivor", Box::new(MockProcess::logging(&log))).await;
+
+        manager.shutdown().await;
+
+        assert_eq!(manager.get_vm(&vm_id).await.unwrap().state, VmState::Running);
+        assert!(!log.pressed.load(Ordering::SeqCst));
+        assert!(!log.killed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn hung_hypervisor_holds_up_only_its_own_vm() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+
+        let entered = Arc::new(Notify::new());
+        let release = Arc::new(Notify::new());
+        let hung_id = insert_running_vm(
+            &manager,
+            "hung",
+            Box::new(MockProcess {
+                hang_pause: Some((entered.clone(), release.clone())),
+                ..Default::default()
+            }),
+        )
+        .await;
+        let other_id = insert_running_vm(&manager, "other", Box::new(MockProcess::default())).await;
+
+        let pausing = tokio::spawn({
+            let manager = Arc::clone(&manager);
+            let vm_id = hung_id.clone();
+            async move { manager.pause_vm(&vm_id).await }
+        });
+        entered.notified().await;
+
+        let quick = Duration::from_secs(1);
+        let vms = tokio::time::timeout(quick, manager.list_vms())
+            .await
+            .expect("list_vms waited for the hung VM");
+        assert_eq!(vms.len(), 2);
+        let hung = tokio::time::timeout(quick, manager.get_vm(&hung_id))
+            .await
+            .expect("get_vm waited for the hung VM")
+            .unwrap();
+        assert_eq!(hung.state, VmState::Running);
+        let other = tokio::time::timeout(quick, manager.pause_vm(&other_id))
+            .await
+            .expect("pausing another VM waited for the hung VM")
+            .unwrap();
+        assert_eq!(other.state, VmState::Paused);
+
+        release.notify_one();
+        let hung = pausing.await.unwrap().unwrap();
+        assert_eq!(hung.state, VmState::Paused);
+        assert_eq!(manager.get_vm(&hung_id).await.unwrap().state, VmState::Paused);
+    }
+
+    #[tokio::test]
+    async fn previous_boot_logs_are_readable_until_vm_is_deleted() {
+        let temp_dir = TempDir::new().unwrap();
+        let manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+        let vm = manager
+            .create_vm("logged".to_string(), test_config())
+            .await
+            .unwrap();
+        let log_path = temp_dir.path().join("logged.log");
+        manager.lock_vm(&vm.id).await.unwrap().vm.log_path =
+            log_path.to_string_lossy().into_owned();
+
+        assert!(matches!(
+            manager.read_log(&vm.id, 0).await,
+            Err(VmManagerError::LogNotFound(_))
+        ));
+
+        let vm = manager.get_vm(&vm.id).await.unwrap();
+        for boot in ["first boot\n", "second boot\n"] {
+            start_log_boot(&vm);
+            std::fs::write(&log_path, boot).unwrap();
+        }
+        assert_eq!(manager.read_log(&vm.id, 0).await.unwrap().data, b"second boot\n");
+        assert_eq!(manager.read_log(&vm.id, -1).await.unwrap().data, b"first boot\n");
+        for boot in [-2, 1] {
+            assert!(matches!(
+                manager.read_log(&vm.id, boot).await,
+                Err(VmManagerError::LogNotFound(_))
+            ));
+        }
+
+        manager.delete_vm(&vm.id).await.unwrap();
+        assert!(!log_path.exists());
+        assert!(!logs::boot_path(&log_path, 1).exists());
     }
 }
@@ -8,6 +8,9 @@ use tempfile::TempDir;
 use tower::ServiceExt;
 
 use glidex_control_plane::api::create_router;
+use glidex_control_plane::logs::LogWriter;
+use glidex_control_plane::models::LogPolicy;
+use glidex_control_plane::recording::Recordings;
 use glidex_control_plane::state::VmManager;
 
 /// Helper to create a test app instance with a temporary database
@@ -149,6 +152,67 @@ async fn test_create_vm_with_optional_fields() {
     assert_eq!(body["mem_size_mib"], 1024);
 }
 
+#[tokio::test]
+async fn test_create_vm_with_readiness_probe() {
+    let (app, _temp_dir) = create_test_app();
+
+    let create = |name: &str, hypervisor: &str, readiness: Value| {
+        let request = json!({
+            "name": name,
+            "vcpu_count": 1,
+            "mem_size_mib": 256,
+            "kernel_image_path": "/path/to/kernel",
+            "rootfs_path": "/path/to/rootfs.ext4",
+            "hypervisor": hypervisor,
+            "readiness": readiness
+        });
+        Request::builder()
+            .method("POST")
+            .uri("/vms")
+            .header("content-type", "application/json")
+            .body(Body::from(request.to_string()))
+            .unwrap()
+    };
+
+    let probe = json!({ "type": "console", "pattern": "login: $" });
+    let response = app
+        .clone()
+        .oneshot(create("probed", "qemu", probe.clone()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["readiness"], probe);
+    assert_eq!(body["ready"], false);
+    assert!(body.get("boot_duration_ms").is_none());
+    let vm_id = body["id"].as_str().unwrap().to_string();
+
+    // Probes that could never pass are refused up front.
+    for (name, hypervisor, readiness) in [
+        ("bad-regex", "qemu", json!({ "type": "console", "pattern": "(" })),
+        ("no-agent", "firecracker", json!({ "type": "guest_agent" })),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(create(name, hypervisor, readiness))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR, "{}", name);
+    }
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/vms/{}/start?wait=booted", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn test_create_vm_duplicate_name() {
     let (app, _temp_dir) = create_test_app();
@@ -311,6 +375,79 @@ async fn test_get_vm_not_found() {
     assert_eq!(body["error"], "not_found");
 }
 
+// ============================================================================
+// VM Update Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_update_vm() {
+    let (app, _temp_dir) = create_test_app();
+
+    let create_request = json!({
+        "name": "update-test-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
+    assert_eq!(created_vm["rootfs_path"], "/path/to/rootfs.ext4");
+    assert_eq!(created_vm["no_reboot"], true);
+
+    let patch = |body: Value, uri: String| {
+        Request::builder()
+            .method("PATCH")
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap()
+    };
+
+    // Only the given settings change.
+    let response = app
+        .clone()
+        .oneshot(patch(
+            json!({"vcpu_count": 2, "kernel_args": "console=ttyS0"}),
+            format!("/vms/{}", vm_id),
+        ))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["vcpu_count"], 2);
+    assert_eq!(body["mem_size_mib"], 256);
+    assert_eq!(body["kernel_args"], "console=ttyS0");
+
+    let response = app
+        .clone()
+        .oneshot(patch(json!({"mem_size_mib": 0}), format!("/vms/{}", vm_id)))
+        .await
+        .unwrap();
+    // Rejected like the same setting on create.
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["error"], "hypervisor_error");
+
+    let response = app
+        .oneshot(patch(json!({"vcpu_count": 2}), "/vms/nonexistent".to_string()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
 // ============================================================================
 // VM Delete Tests
 // ============================================================================
@@ -462,6 +599,69 @@ async fn test_start_vm_not_found() {
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
 }
 
+#[tokio::test]
+async fn test_start_vm_rejects_oversized_timeout() {
+    let (app, _temp_dir) = create_test_app();
+
+    let create_request = json!({
+        "name": "start-timeout-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!(
+                    "/vms/{}/start?wait=ready&timeout_secs={}",
+                    vm_id,
+                    u64::MAX
+                ))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["error"], "invalid_request");
+
+    // Rejected before the VM was started
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/vms/{}", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let vm = body_to_json(response.into_body()).await;
+    assert_eq!(vm["state"], "created");
+}
+
 #[tokio::test]
 async fn test_stop_vm_not_found() {
     let (app, _temp_dir) = create_test_app();
@@ -481,30 +681,48 @@ async fn test_stop_vm_not_found() {
 }
 
 #[tokio::test]
-async fn test_pause_vm_not_found() {
+async fn test_stop_vm_accepts_options_body() {
     let (app, _temp_dir) = create_test_app();
 
     let response = app
+        .clone()
         .oneshot(
             Request::builder()
                 .method("POST")
-                .uri("/vms/nonexistent-id/pause")
-                .body(Body::empty())
+                .uri("/vms/nonexistent-id/stop")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({ "force": true, "timeout_secs": 5 }).to_string(),
+                ))
                 .unwrap(),
         )
         .await
         .unwrap();
 
+    // The body parsed; the VM lookup is what failed.
     assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms/nonexistent-id/stop")
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "timeout_secs": "soon" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
 }
 
 #[tokio::test]
-async fn test_stop_vm_invalid_state() {
+async fn test_stop_vm_rejects_oversized_timeout() {
     let (app, _temp_dir) = create_test_app();
 
-    // Create a VM (state: created)
     let create_request = json!({
-        "name": "stop-test-vm",
+        "name": "stop-timeout-vm",
         "vcpu_count": 1,
         "mem_size_mib": 256,
         "kernel_image_path": "/path/to/kernel",
@@ -527,31 +745,46 @@ async fn test_stop_vm_invalid_state() {
     let created_vm = body_to_json(response.into_body()).await;
     let vm_id = created_vm["id"].as_str().unwrap();
 
-    // Try to stop a VM that is not running (should fail)
     let response = app
+        .clone()
         .oneshot(
             Request::builder()
                 .method("POST")
                 .uri(format!("/vms/{}/stop", vm_id))
-                .body(Body::empty())
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({ "timeout_secs": u64::MAX }).to_string(),
+                ))
                 .unwrap(),
         )
         .await
         .unwrap();
 
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
-
     let body = body_to_json(response.into_body()).await;
-    assert_eq!(body["error"], "invalid_state");
+    assert_eq!(body["error"], "invalid_request");
+
+    // Rejected before the VM was touched
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/vms/{}", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let vm = body_to_json(response.into_body()).await;
+    assert_eq!(vm["state"], "created");
 }
 
 #[tokio::test]
-async fn test_pause_vm_invalid_state() {
+async fn test_console_expect_rejects_oversized_timeout() {
     let (app, _temp_dir) = create_test_app();
 
-    // Create a VM (state: created)
     let create_request = json!({
-        "name": "pause-test-vm",
+        "name": "expect-timeout-vm",
         "vcpu_count": 1,
         "mem_size_mib": 256,
         "kernel_image_path": "/path/to/kernel",
@@ -574,36 +807,35 @@ async fn test_pause_vm_invalid_state() {
     let created_vm = body_to_json(response.into_body()).await;
     let vm_id = created_vm["id"].as_str().unwrap();
 
-    // Try to pause a VM that is not running (should fail)
+    // Refused before the VM's state is looked at
     let response = app
         .oneshot(
             Request::builder()
                 .method("POST")
-                .uri(format!("/vms/{}/pause", vm_id))
-                .body(Body::empty())
+                .uri(format!("/vms/{}/console/expect", vm_id))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({ "pattern": "login:", "timeout_secs": u64::MAX }).to_string(),
+                ))
                 .unwrap(),
         )
         .await
         .unwrap();
 
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
-
     let body = body_to_json(response.into_body()).await;
-    assert_eq!(body["error"], "invalid_state");
+    assert_eq!(body["error"], "invalid_request");
 }
 
-// ============================================================================
-// Console Endpoint Tests
-// ============================================================================
-
 #[tokio::test]
-async fn test_get_console_info_not_found() {
+async fn test_pause_vm_not_found() {
     let (app, _temp_dir) = create_test_app();
 
     let response = app
         .oneshot(
             Request::builder()
-                .uri("/vms/nonexistent-id/console")
+                .method("POST")
+                .uri("/vms/nonexistent-id/pause")
                 .body(Body::empty())
                 .unwrap(),
         )
@@ -614,12 +846,12 @@ async fn test_get_console_info_not_found() {
 }
 
 #[tokio::test]
-async fn test_get_console_info_vm_not_running() {
+async fn test_stop_vm_invalid_state() {
     let (app, _temp_dir) = create_test_app();
 
-    // Create a VM
+    // Create a VM (state: created)
     let create_request = json!({
-        "name": "console-test-vm",
+        "name": "stop-test-vm",
         "vcpu_count": 1,
         "mem_size_mib": 256,
         "kernel_image_path": "/path/to/kernel",
@@ -642,69 +874,674 @@ async fn test_get_console_info_vm_not_running() {
     let created_vm = body_to_json(response.into_body()).await;
     let vm_id = created_vm["id"].as_str().unwrap();
 
-    // Get console info (VM not running)
+    // Try to stop a VM that is not running (should fail)
     let response = app
         .oneshot(
             Request::builder()
-                .uri(format!("/vms/{}/console", vm_id))
+                .method("POST")
+                .uri(format!("/vms/{}/stop", vm_id))
                 .body(Body::empty())
                 .unwrap(),
         )
         .await
         .unwrap();
 
-    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
     let body = body_to_json(response.into_body()).await;
-    assert_eq!(body["vm_id"], vm_id);
-    assert_eq!(body["available"], false);
-    // console_socket_path and log_path should be present
-    assert!(body["console_socket_path"].is_string());
-    assert!(body["log_path"].is_string());
+    assert_eq!(body["error"], "invalid_state");
 }
 
-// ============================================================================
-// Persistence Tests
-// ============================================================================
-
 #[tokio::test]
-async fn test_vm_persists_across_restart() {
-    let temp_dir = TempDir::new().unwrap();
-    let db_path = db_path_from_temp_dir(&temp_dir);
+async fn test_reboot_and_reset_require_running_vm() {
+    let (app, _temp_dir) = create_test_app();
 
-    let vm_id: String;
+    let create_request = json!({
+        "name": "reboot-test-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
 
-    // Phase 1: Create a VM with the first manager instance
-    {
-        let vm_manager = VmManager::with_db_path(db_path.clone()).unwrap();
-        vm_manager.initialize().await.unwrap();
-        let app = create_router(vm_manager);
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
 
-        let create_request = json!({
-            "name": "persistent-vm",
-            "vcpu_count": 2,
-            "mem_size_mib": 512,
-            "kernel_image_path": "/path/to/kernel",
-            "rootfs_path": "/path/to/rootfs.ext4"
-        });
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
 
+    for operation in ["reboot", "reset"] {
         let response = app
+            .clone()
             .oneshot(
                 Request::builder()
                     .method("POST")
-                    .uri("/vms")
-                    .header("content-type", "application/json")
-                    .body(Body::from(create_request.to_string()))
+                    .uri(format!("/vms/{}/{}", vm_id, operation))
+                    .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", operation);
         let body = body_to_json(response.into_body()).await;
-        vm_id = body["id"].as_str().unwrap().to_string();
-    }
-    // First manager is dropped here (simulates restart)
+        assert_eq!(body["error"], "invalid_state");
+
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/vms/nonexistent-id/{}", operation))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", operation);
+    }
+}
+
+#[tokio::test]
+async fn test_snapshot_and_restore_endpoints() {
+    let (app, _temp_dir) = create_test_app();
+
+    let create_request = json!({
+        "name": "snapshot-test-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
+
+    // A new VM has no snapshots.
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .uri(format!("/vms/{}/snapshots", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body.as_array().unwrap().len(), 0);
+
+    // Only a paused VM can be snapshotted.
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/vms/{}/snapshots", vm_id))
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "name": "booted" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["error"], "invalid_state");
+
+    // Restoring from a snapshot that doesn't exist
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/vms/{}/restore", vm_id))
+                .header("content-type", "application/json")
+                .body(Body::from(json!({ "snapshot_id": "nonexistent" }).to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["error"], "not_found");
+
+    // Restore needs a snapshot_id
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/vms/{}/restore", vm_id))
+                .header("content-type", "application/json")
+                .body(Body::from("{}"))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_pause_vm_invalid_state() {
+    let (app, _temp_dir) = create_test_app();
+
+    // Create a VM (state: created)
+    let create_request = json!({
+        "name": "pause-test-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
+
+    // Try to pause a VM that is not running (should fail)
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/vms/{}/pause", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["error"], "invalid_state");
+}
+
+#[tokio::test]
+async fn test_start_vm_failure_marks_vm_failed() {
+    let (app, _temp_dir) = create_test_app();
+
+    // A kernel that doesn't exist can't boot on any host, whether or not
+    // qemu-system-x86_64 is installed.
+    let create_request = json!({
+        "name": "failing-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/nonexistent/kernel",
+        "rootfs_path": "/nonexistent/rootfs.ext4",
+        "hypervisor": "qemu"
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/vms/{}/start", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/vms/{}", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["state"], "failed");
+    assert!(body["last_error"].is_string());
+}
+
+// ============================================================================
+// Console Endpoint Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_get_console_info_not_found() {
+    let (app, _temp_dir) = create_test_app();
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri("/vms/nonexistent-id/console")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_get_console_info_vm_not_running() {
+    let (app, _temp_dir) = create_test_app();
+
+    // Create a VM
+    let create_request = json!({
+        "name": "console-test-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
+
+    // Get console info (VM not running)
+    let response = app
+        .oneshot(
+            Request::builder()
+                .uri(format!("/vms/{}/console", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["vm_id"], vm_id);
+    assert_eq!(body["available"], false);
+    // console_socket_path and log_path should be present
+    assert!(body["console_socket_path"].is_string());
+    assert!(body["log_path"].is_string());
+    // Nobody can be attached without a console proxy
+    assert_eq!(body["sessions"], json!([]));
+    assert!(body["write_lock"].is_null());
+}
+
+#[tokio::test]
+async fn test_logs_of_vm_that_never_booted() {
+    let (app, _temp_dir) = create_test_app();
+
+    let create_request = json!({
+        "name": "logs-test-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4",
+        "log_policy": { "max_bytes": 1048576, "keep_boots": 5 }
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
+    // Fields left out of the policy keep their defaults
+    assert_eq!(created_vm["log_policy"]["max_bytes"], 1048576);
+    assert_eq!(created_vm["log_policy"]["max_files"], 3);
+    assert_eq!(created_vm["log_policy"]["keep_boots"], 5);
+
+    for uri in [
+        format!("/vms/{}/logs", vm_id),
+        format!("/vms/{}/logs?boot=-1", vm_id),
+        "/vms/nonexistent-id/logs".to_string(),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND, "{}", uri);
+        let body = body_to_json(response.into_body()).await;
+        assert_eq!(body["error"], "not_found");
+    }
+}
+
+#[tokio::test]
+async fn test_logs_tail_since_follow_and_range() {
+    let (app, _temp_dir) = create_test_app();
+
+    let create_request = json!({
+        "name": "logs-query-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap().to_string();
+    let log_path = std::path::PathBuf::from(created_vm["log_path"].as_str().unwrap());
+
+    // Stand in for the console proxy of a boot that has ended
+    let mut writer = LogWriter::open(&log_path, LogPolicy::default()).unwrap();
+    writer.write(b"Linux version 6.1\nbooting\nlogin: ").unwrap();
+
+    let get = |uri: String, range: Option<&'static str>| {
+        let app = app.clone();
+        async move {
+            let mut request = Request::builder().uri(uri);
+            if let Some(range) = range {
+                request = request.header("range", range);
+            }
+            let response = app
+                .oneshot(request.body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let status = response.status();
+            let content_range = response
+                .headers()
+                .get("content-range")
+                .map(|value| value.to_str().unwrap().to_string());
+            let body = response.into_body().collect().await.unwrap().to_bytes();
+            (status, content_range, String::from_utf8_lossy(&body).into_owned())
+        }
+    };
+
+    let (status, _, body) = get(format!("/vms/{}/logs?tail=2", vm_id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "booting\nlogin: ");
+
+    let (_, _, body) = get(format!("/vms/{}/logs?since=1h", vm_id), None).await;
+    assert_eq!(body, "Linux version 6.1\nbooting\nlogin: ");
+    let (_, _, body) = get(format!("/vms/{}/logs?since=99999999999.5", vm_id), None).await;
+    assert_eq!(body, "");
+    let (status, _, _) = get(format!("/vms/{}/logs?since=yesterday", vm_id), None).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+
+    let (status, content_range, body) =
+        get(format!("/vms/{}/logs", vm_id), Some("bytes=0-4")).await;
+    assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+    assert_eq!(content_range.as_deref(), Some("bytes 0-4/33"));
+    assert_eq!(body, "Linux");
+    let (_, content_range, body) =
+        get(format!("/vms/{}/logs?tail=1", vm_id), Some("bytes=-3")).await;
+    assert_eq!(content_range.as_deref(), Some("bytes 4-6/7"));
+    assert_eq!(body, "n: ");
+    let (status, content_range, _) =
+        get(format!("/vms/{}/logs", vm_id), Some("bytes=100-")).await;
+    assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+    assert_eq!(content_range.as_deref(), Some("bytes */33"));
+
+    // The VM is not running, so following ends after what is there
+    let (status, _, body) = get(format!("/vms/{}/logs?follow=true&tail=1", vm_id), None).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, "login: ");
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri(format!("/vms/{}", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert!(!log_path.exists());
+}
+
+#[tokio::test]
+async fn test_console_sessions_list_and_download() {
+    let (app, _temp_dir) = create_test_app();
+
+    let create_request = json!({
+        "name": "sessions-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap().to_string();
+    let log_path = std::path::PathBuf::from(created_vm["log_path"].as_str().unwrap());
+    assert_eq!(created_vm["log_policy"]["keep_sessions"], 20);
+
+    let get = |uri: String| {
+        let app = app.clone();
+        async move {
+            app.oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap()
+        }
+    };
+    let response = get(format!("/vms/{}/console/sessions", vm_id)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(body_to_json(response.into_body()).await, json!([]));
+
+    // Stand in for the console proxy serving a gxctl client
+    let recordings = Recordings::new(&log_path, &LogPolicy::default());
+    let mut recorder = recordings.start(Some("gxctl")).unwrap();
+    recorder.output(b"login: ");
+    recorder.input(b"root\r");
+    drop(recorder);
+
+    let response = get(format!("/vms/{}/console/sessions", vm_id)).await;
+    let sessions = body_to_json(response.into_body()).await;
+    assert_eq!(sessions.as_array().unwrap().len(), 1);
+    assert_eq!(sessions[0]["client"], "gxctl");
+    let session_id = sessions[0]["id"].as_str().unwrap();
+
+    let response = get(format!("/vms/{}/console/sessions/{}", vm_id, session_id)).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"],
+        "application/x-asciicast"
+    );
+    let body = response.into_body().collect().await.unwrap().to_bytes();
+    let cast = String::from_utf8_lossy(&body);
+    let header: Value = serde_json::from_str(cast.lines().next().unwrap()).unwrap();
+    assert_eq!(header["version"], 2);
+    assert_eq!(cast.lines().count(), 3);
+
+    let response = get(format!("/vms/{}/console/sessions/0-missing", vm_id)).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let response = get("/vms/nonexistent/console/sessions".to_string()).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    recordings.remove_all();
+}
+
+#[tokio::test]
+async fn test_console_expect_validation() {
+    let (app, _temp_dir) = create_test_app();
+
+    let create_request = json!({
+        "name": "expect-test-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
+
+    for (body, status, error) in [
+        (json!({ "pattern": "login:(" }), StatusCode::BAD_REQUEST, "invalid_request"),
+        (
+            json!({ "pattern": "login:", "send": "\r", "timeout_secs": 1 }),
+            StatusCode::BAD_REQUEST,
+            "invalid_state",
+        ),
+    ] {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/vms/{}/console/expect", vm_id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), status);
+        let body = body_to_json(response.into_body()).await;
+        assert_eq!(body["error"], error);
+    }
+}
+
+// ============================================================================
+// Persistence Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_vm_persists_across_restart() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = db_path_from_temp_dir(&temp_dir);
+
+    let vm_id: String;
+
+    // Phase 1: Create a VM with the first manager instance
+    {
+        let vm_manager = VmManager::with_db_path(db_path.clone()).unwrap();
+        vm_manager.initialize().await.unwrap();
+        let app = create_router(vm_manager);
+
+        let create_request = json!({
+            "name": "persistent-vm",
+            "vcpu_count": 2,
+            "mem_size_mib": 512,
+            "kernel_image_path": "/path/to/kernel",
+            "rootfs_path": "/path/to/rootfs.ext4"
+        });
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/vms")
+                    .header("content-type", "application/json")
+                    .body(Body::from(create_request.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = body_to_json(response.into_body()).await;
+        vm_id = body["id"].as_str().unwrap().to_string();
+    }
+    // First manager is dropped here (simulates restart)
 
     // Phase 2: Create a new manager and verify VM is recovered
     {
@@ -907,3 +1744,279 @@ async fn test_multiple_vms_persist() {
         assert!(names.contains(&"multi-vm-3"));
     }
 }
+
+// ============================================================================
+// Migration Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_migration_endpoints() {
+    let (app, _temp_dir) = create_test_app();
+
+    // Migrating a VM that doesn't exist
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms/nonexistent/migrate")
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({ "target": "http://127.0.0.1:1" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let create_request = json!({
+        "name": "migration-test-vm",
+        "vcpu_count": 1,
+        "mem_size_mib": 256,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    let created_vm = body_to_json(response.into_body()).await;
+    let vm_id = created_vm["id"].as_str().unwrap();
+
+    // Only a running VM can be migrated
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri(format!("/vms/{}/migrate", vm_id))
+                .header("content-type", "application/json")
+                .body(Body::from(
+                    json!({ "target": "http://127.0.0.1:1" }).to_string(),
+                ))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = body_to_json(response.into_body()).await;
+    assert_eq!(body["error"], "invalid_state");
+
+    // An incoming migration may not reuse a name that is taken here
+    let incoming_request = json!({
+        "id": "incoming-id",
+        "name": "migration-test-vm",
+        "config": {
+            "vcpu_count": 1,
+            "mem_size_mib": 256,
+            "kernel_image_path": "/path/to/kernel",
+            "rootfs_path": "/path/to/rootfs.ext4",
+            "kernel_args": "",
+            "hypervisor": "qemu"
+        }
+    });
+
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/migrations/incoming")
+                .header("content-type", "application/json")
+                .body(Body::from(incoming_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::CONFLICT);
+
+    // Completing or aborting an unknown incoming migration
+    let response = app
+        .clone()
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/migrations/incoming/nonexistent/complete")
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("DELETE")
+                .uri(format!("/migrations/incoming/{}", vm_id))
+                .body(Body::empty())
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+// ============================================================================
+// Versioning and OpenAPI Tests
+// ============================================================================
+
+#[tokio::test]
+async fn test_unversioned_routes_are_deprecated_aliases() {
+    let (app, _temp_dir) = create_test_app();
+
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/v1/vms").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get("deprecation").is_none());
+
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/vms").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()["deprecation"], "true");
+    assert_eq!(
+        response.headers()["link"],
+        "</v1/vms>; rel=\"successor-version\""
+    );
+
+    // Unknown routes are not aliases of anything
+    let response = app
+        .oneshot(Request::builder().uri("/nonexistent").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    assert!(response.headers().get("deprecation").is_none());
+}
+
+#[tokio::test]
+async fn test_openapi_document_matches_routes() {
+    let (app, _temp_dir) = create_test_app();
+
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/openapi.json").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::OK);
+    let doc = body_to_json(response.into_body()).await;
+    assert_eq!(doc["openapi"], "3.1.0");
+    let paths = doc["paths"].as_object().unwrap();
+    assert!(paths.contains_key("/v1/vms/{id}/console/ws"));
+
+    // Only the /v1 routes are documented, and the document says so.
+    assert!(doc["info"]["description"]
+        .as_str()
+        .unwrap()
+        .contains("Only the `/v1` routes are described"));
+
+    // Every documented operation is routed: an unrouted path or method
+    // gets axum's empty 404 or a 405, where the handlers answer a 404
+    // with a body. Its unversioned alias is routed too, as a deprecated
+    // one.
+    for (path, operations) in paths {
+        let alias = path
+            .strip_prefix("/v1")
+            .unwrap_or_else(|| panic!("{} is not versioned", path));
+        for (uri, is_alias) in [(path.as_str(), false), (alias, true)] {
+            let uri = uri
+                .replace("{id}", "nonexistent")
+                .replace("{snapshot_id}", "nonexistent")
+                .replace("{session_id}", "nonexistent");
+            for method in operations.as_object().unwrap().keys() {
+                let response = app
+                    .clone()
+                    .oneshot(
+                        Request::builder()
+                            .method(method.to_uppercase().as_str())
+                            .uri(&uri)
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let status = response.status();
+                let deprecated = response.headers().contains_key("deprecation");
+                let body = response.into_body().collect().await.unwrap().to_bytes();
+                assert_ne!(status, StatusCode::METHOD_NOT_ALLOWED, "{} {}", method, uri);
+                assert!(
+                    !(status == StatusCode::NOT_FOUND && body.is_empty()),
+                    "{} {} is not routed",
+                    method,
+                    uri
+                );
+                assert_eq!(deprecated, is_alias, "{} {}", method, uri);
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_openapi_schema_matches_vm_response() {
+    let (app, _temp_dir) = create_test_app();
+
+    let response = app
+        .clone()
+        .oneshot(Request::builder().uri("/openapi.json").body(Body::empty()).unwrap())
+        .await
+        .unwrap();
+    let doc = body_to_json(response.into_body()).await;
+    let schema = &doc["components"]["schemas"]["VmResponse"];
+    let properties = schema["properties"].as_object().unwrap();
+
+    let create_request = json!({
+        "name": "test-vm",
+        "vcpu_count": 2,
+        "mem_size_mib": 512,
+        "kernel_image_path": "/path/to/kernel",
+        "rootfs_path": "/path/to/rootfs.ext4"
+    });
+    let response = app
+        .oneshot(
+            Request::builder()
+                .method("POST")
+                .uri("/v1/vms")
+                .header("content-type", "application/json")
+                .body(Body::from(create_request.to_string()))
+                .unwrap(),
+        )
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let body = body_to_json(response.into_body()).await;
+    let body = body.as_object().unwrap();
+
+    for field in body.keys() {
+        assert!(properties.contains_key(field), "{} is not documented", field);
+    }
+    for field in schema["required"].as_array().unwrap() {
+        let field = field.as_str().unwrap();
+        assert!(body.contains_key(field), "required {} is missing", field);
+    }
+}
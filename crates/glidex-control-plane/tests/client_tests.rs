@@ -0,0 +1,116 @@
+use tempfile::TempDir;
+
+use glidex_client::models::{
+    CreateVmRequest, HypervisorType, IncomingMigrationRequest, LogPolicy, UpdateVmRequest,
+    VmConfig, VmState,
+};
+use glidex_client::{Client, Error};
+use glidex_control_plane::api::create_router;
+use glidex_control_plane::state::VmManager;
+
+/// Serve the API on a free local port, and a client of it
+async fn serve() -> (Client, TempDir) {
+    let temp_dir = TempDir::new().unwrap();
+    let vm_manager = VmManager::with_db_path(temp_dir.path().join("test.db")).unwrap();
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move {
+        axum::serve(listener, create_router(vm_manager)).await.unwrap();
+    });
+    (Client::new(url), temp_dir)
+}
+
+fn create_request(name: &str) -> CreateVmRequest {
+    CreateVmRequest {
+        name: name.to_string(),
+        vcpu_count: 2,
+        mem_size_mib: 512,
+        kernel_image_path: "/path/to/kernel".to_string(),
+        rootfs_path: "/path/to/rootfs.ext4".to_string(),
+        kernel_args: None,
+        hypervisor: Some(HypervisorType::CloudHypervisor),
+        vfio_devices: None,
+        no_reboot: None,
+        log_policy: None,
+        readiness: None,
+    }
+}
+
+#[tokio::test]
+async fn test_client_manages_vms() {
+    let (client, _temp_dir) = serve().await;
+    client.health().await.unwrap();
+
+    let vm = client.create_vm(&create_request("web-1")).await.unwrap();
+    assert_eq!(vm.state, VmState::Created);
+    assert_eq!(vm.hypervisor, HypervisorType::CloudHypervisor);
+    assert_eq!(client.get_vm(&vm.id).await.unwrap().name, "web-1");
+    assert_eq!(client.list_vms().await.unwrap().len(), 1);
+    assert_eq!(client.resolve_vm("web-1").await.unwrap(), vm.id);
+
+    let update = UpdateVmRequest {
+        mem_size_mib: Some(1024),
+        ..Default::default()
+    };
+    let updated = client.update_vm(&vm.id, &update).await.unwrap();
+    assert_eq!(updated.mem_size_mib, 1024);
+    assert_eq!(updated.vcpu_count, 2);
+
+    client.delete_vm(&vm.id).await.unwrap();
+    assert!(client.list_vms().await.unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_client_reports_api_errors() {
+    let (client, _temp_dir) = serve().await;
+
+    let err = client.get_vm("nonexistent-id").await.unwrap_err();
+    assert_eq!(err.status(), Some(reqwest::StatusCode::NOT_FOUND));
+    let Error::Api { error, .. } = err else {
+        panic!("expected an API error");
+    };
+    assert_eq!(error.error, "not_found");
+
+    assert!(matches!(
+        client.resolve_vm("web-1").await,
+        Err(Error::VmNotFound(name)) if name == "web-1"
+    ));
+
+    client.create_vm(&create_request("web")).await.unwrap();
+    let err = client.create_vm(&create_request("web")).await.unwrap_err();
+    assert_eq!(err.status(), Some(reqwest::StatusCode::CONFLICT));
+    assert_eq!(err.to_string(), "conflict: VM already exists: web");
+}
+
+#[tokio::test]
+async fn test_client_drives_incoming_migrations() {
+    let (client, _temp_dir) = serve().await;
+    let vm = client.create_vm(&create_request("web")).await.unwrap();
+
+    // The target refuses a VM whose name it already has.
+    let request = IncomingMigrationRequest {
+        id: "incoming-id".to_string(),
+        name: "web".to_string(),
+        config: VmConfig {
+            vcpu_count: 1,
+            mem_size_mib: 256,
+            kernel_image_path: "/path/to/kernel".to_string(),
+            rootfs_path: "/path/to/rootfs.ext4".to_string(),
+            kernel_args: String::new(),
+            hypervisor: HypervisorType::Qemu,
+            vfio_devices: Vec::new(),
+            no_reboot: true,
+            log_policy: LogPolicy::default(),
+            readiness: None,
+        },
+    };
+    let err = client.prepare_incoming_migration(&request).await.unwrap_err();
+    assert_eq!(err.status(), Some(reqwest::StatusCode::CONFLICT));
+
+    let err = client.complete_incoming_migration("nonexistent").await.unwrap_err();
+    assert_eq!(err.status(), Some(reqwest::StatusCode::NOT_FOUND));
+
+    // Only VMs still arriving can be aborted.
+    let err = client.abort_incoming_migration(&vm.id).await.unwrap_err();
+    assert_eq!(err.status(), Some(reqwest::StatusCode::BAD_REQUEST));
+}
@@ -566,8 +566,8 @@ fn download_samples(platform: &Platform) -> Result<()> {
         .to_string();
     // e.g. "v1.14.0" -> "v1.14"
     let ci_version = latest_tag
-        .rsplitn(2, '.')
-        .nth(1)
+        .rsplit_once('.')
+        .map(|(head, _)| head)
         .ok_or_else(|| anyhow::anyhow!("Could not derive CI version from tag {}", latest_tag))?
         .to_string();
     println!("Using Firecracker CI version: {}", ci_version);
@@ -1,11 +1,12 @@
 import type {
   CreateVmRequest,
   HealthResponse,
+  SnapshotResponse,
   VmResponse,
   ApiError,
 } from "./types";
 
-const API_BASE = "/api";
+const API_BASE = "/api/v1";
 
 async function handleResponse<T>(resp: Response): Promise<T> {
   if (resp.ok) {
@@ -47,8 +48,25 @@ export async function startVm(id: string): Promise<VmResponse> {
   return handleResponse(resp);
 }
 
-export async function stopVm(id: string): Promise<VmResponse> {
-  const resp = await fetch(`${API_BASE}/vms/${id}/stop`, { method: "POST" });
+export async function stopVm(
+  id: string,
+  force = false,
+): Promise<VmResponse> {
+  const resp = await fetch(`${API_BASE}/vms/${id}/stop`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ force }),
+  });
+  return handleResponse(resp);
+}
+
+export async function rebootVm(id: string): Promise<VmResponse> {
+  const resp = await fetch(`${API_BASE}/vms/${id}/reboot`, { method: "POST" });
+  return handleResponse(resp);
+}
+
+export async function resetVm(id: string): Promise<VmResponse> {
+  const resp = await fetch(`${API_BASE}/vms/${id}/reset`, { method: "POST" });
   return handleResponse(resp);
 }
 
@@ -57,6 +75,47 @@ export async function pauseVm(id: string): Promise<VmResponse> {
   return handleResponse(resp);
 }
 
+export async function listSnapshots(id: string): Promise<SnapshotResponse[]> {
+  const resp = await fetch(`${API_BASE}/vms/${id}/snapshots`);
+  return handleResponse(resp);
+}
+
+export async function createSnapshot(
+  id: string,
+  name?: string,
+): Promise<SnapshotResponse> {
+  const resp = await fetch(`${API_BASE}/vms/${id}/snapshots`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ name }),
+  });
+  return handleResponse(resp);
+}
+
+export async function deleteSnapshot(
+  id: string,
+  snapshotId: string,
+): Promise<void> {
+  const resp = await fetch(`${API_BASE}/vms/${id}/snapshots/${snapshotId}`, {
+    method: "DELETE",
+  });
+  if (resp.ok || resp.status === 204) return;
+  const err: ApiError = await resp.json();
+  throw new Error(`${err.error}: ${err.message}`);
+}
+
+export async function restoreVm(
+  id: string,
+  snapshotId: string,
+): Promise<VmResponse> {
+  const resp = await fetch(`${API_BASE}/vms/${id}/restore`, {
+    method: "POST",
+    headers: { "Content-Type": "application/json" },
+    body: JSON.stringify({ snapshot_id: snapshotId }),
+  });
+  return handleResponse(resp);
+}
+
 export async function deleteVm(id: string): Promise<void> {
   const resp = await fetch(`${API_BASE}/vms/${id}`, { method: "DELETE" });
   if (resp.ok || resp.status === 204) return;
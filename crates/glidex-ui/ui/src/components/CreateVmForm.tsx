@@ -16,6 +16,7 @@ export default function CreateVmForm({ onSubmit, onCancel }: CreateVmFormProps)
   const [rootfsPath, setRootfsPath] = useState("");
   const [kernelArgs, setKernelArgs] = useState("");
   const [vfioDevices, setVfioDevices] = useState("");
+  const [noReboot, setNoReboot] = useState(true);
   const [submitting, setSubmitting] = useState(false);
 
   const handleSubmit = (e: FormEvent) => {
@@ -36,6 +37,7 @@ export default function CreateVmForm({ onSubmit, onCancel }: CreateVmFormProps)
       hypervisor,
       kernel_args: kernelArgs || undefined,
       vfio_devices: devices.length > 0 ? devices : undefined,
+      no_reboot: hypervisor === "qemu" ? noReboot : undefined,
     });
   };
 
@@ -161,6 +163,24 @@ export default function CreateVmForm({ onSubmit, onCancel }: CreateVmFormProps)
         </p>
       </div>
 
+      {hypervisor === "qemu" && (
+        <div>
+          <label className="flex items-center space-x-2 text-sm font-medium text-gray-700">
+            <input
+              type="checkbox"
+              className="rounded border-gray-300 text-sky-600 focus:ring-sky-500"
+              checked={noReboot}
+              onChange={(e) => setNoReboot(e.target.checked)}
+            />
+            <span>Exit when the guest reboots</span>
+          </label>
+          <p className="mt-1 text-xs text-gray-500">
+            QEMU's -no-reboot. Keeps a panicking guest from reboot-looping;
+            untick to let in-guest reboots reset the VM in place
+          </p>
+        </div>
+      )}
+
       <div className="flex justify-end space-x-3 pt-4">
         <button
           type="button"
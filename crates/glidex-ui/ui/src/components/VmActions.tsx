@@ -1,6 +1,13 @@
 import type { VmState } from "../types";
 
-export type VmAction = "start" | "stop" | "pause" | "delete";
+export type VmAction =
+  | "start"
+  | "stop"
+  | "force-stop"
+  | "pause"
+  | "reboot"
+  | "reset"
+  | "delete";
 
 interface VmActionsProps {
   vmId: string;
@@ -16,9 +23,14 @@ export default function VmActions({
   loading = false,
 }: VmActionsProps) {
   const canStart =
-    state === "created" || state === "stopped" || state === "paused";
+    state === "created" ||
+    state === "stopped" ||
+    state === "paused" ||
+    state === "crashed" ||
+    state === "failed";
   const canStop = state === "running" || state === "paused";
   const canPause = state === "running";
+  const canReboot = state === "running";
 
   return (
     <div className="flex items-center space-x-2">
@@ -40,6 +52,25 @@ export default function VmActions({
           Pause
         </button>
       )}
+      {canReboot && (
+        <button
+          className="px-3 py-1.5 text-sm font-medium text-gray-700 bg-gray-200 hover:bg-gray-300 rounded-lg transition-colors disabled:opacity-50"
+          disabled={loading}
+          onClick={() => onAction(vmId, "reboot")}
+        >
+          Reboot
+        </button>
+      )}
+      {canReboot && (
+        <button
+          className="px-3 py-1.5 text-sm font-medium text-red-700 bg-gray-200 hover:bg-gray-300 rounded-lg transition-colors disabled:opacity-50"
+          disabled={loading}
+          title="Hard reset without letting the guest shut down"
+          onClick={() => onAction(vmId, "reset")}
+        >
+          Reset
+        </button>
+      )}
       {canStop && (
         <button
           className="px-3 py-1.5 text-sm font-medium text-gray-700 bg-gray-200 hover:bg-gray-300 rounded-lg transition-colors disabled:opacity-50"
@@ -49,6 +80,16 @@ export default function VmActions({
           Stop
         </button>
       )}
+      {canStop && (
+        <button
+          className="px-3 py-1.5 text-sm font-medium text-red-700 bg-gray-200 hover:bg-gray-300 rounded-lg transition-colors disabled:opacity-50"
+          disabled={loading}
+          title="Kill the hypervisor without waiting for the guest to power off"
+          onClick={() => onAction(vmId, "force-stop")}
+        >
+          Force stop
+        </button>
+      )}
       <button
         className="px-3 py-1.5 text-sm font-medium text-white bg-red-600 hover:bg-red-700 rounded-lg transition-colors disabled:opacity-50"
         disabled={loading}
@@ -40,9 +40,18 @@ export default function Dashboard() {
         case "stop":
           await api.stopVm(vmId);
           break;
+        case "force-stop":
+          await api.stopVm(vmId, true);
+          break;
         case "pause":
           await api.pauseVm(vmId);
           break;
+        case "reboot":
+          await api.rebootVm(vmId);
+          break;
+        case "reset":
+          await api.resetVm(vmId);
+          break;
         case "delete":
           await api.deleteVm(vmId);
           break;
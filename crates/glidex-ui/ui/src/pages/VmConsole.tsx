@@ -1,16 +1,29 @@
 import { useEffect, useRef, useState } from "react";
-import { Link, useParams } from "react-router-dom";
+import { Link, useParams, useSearchParams } from "react-router-dom";
 import { Terminal } from "@xterm/xterm";
 import { FitAddon } from "@xterm/addon-fit";
 import "@xterm/xterm/css/xterm.css";
 
-type Status = "connecting" | "connected" | "closed" | "error";
+type Status = "connecting" | "connected" | "guest exited" | "closed" | "error";
+
+// Framed console protocol: binary messages are console data, text
+// messages JSON control messages. Servers that do not know it send raw
+// bytes only.
+const CONSOLE_PROTOCOL = "glidex.console.v1";
+
+type ControlMessage =
+  | { type: "status"; event: "replay_finished" | "guest_exited" }
+  | { type: "error"; message: string }
+  | { type: "notice"; message: string };
 
 export default function VmConsole() {
   const { id } = useParams<{ id: string }>();
   const containerRef = useRef<HTMLDivElement>(null);
   const [status, setStatus] = useState<Status>("connecting");
   const [error, setError] = useState<string | null>(null);
+  const [notice, setNotice] = useState<string | null>(null);
+  // read-only, shared (default) or exclusive, e.g. `/vms/:id/console?mode=read-only`.
+  const mode = useSearchParams()[0].get("mode");
 
   useEffect(() => {
     if (!id || !containerRef.current) return;
@@ -37,13 +50,25 @@ export default function VmConsole() {
     window.addEventListener("resize", handleResize);
 
     const wsProto = window.location.protocol === "https:" ? "wss:" : "ws:";
-    const wsUrl = `${wsProto}//${window.location.host}/api/vms/${id}/console/ws`;
-    const ws = new WebSocket(wsUrl);
+    const query = mode ? `?mode=${encodeURIComponent(mode)}` : "";
+    const wsUrl = `${wsProto}//${window.location.host}/api/v1/vms/${id}/console/ws${query}`;
+    const ws = new WebSocket(wsUrl, [CONSOLE_PROTOCOL]);
     ws.binaryType = "arraybuffer";
+    const framed = () => ws.protocol === CONSOLE_PROTOCOL;
+
+    const sendResize = (cols: number, rows: number) => {
+      if (ws.readyState === WebSocket.OPEN && framed()) {
+        ws.send(JSON.stringify({ type: "resize", cols, rows }));
+      }
+    };
+    const resizeDisposable = term.onResize(({ cols, rows }) =>
+      sendResize(cols, rows),
+    );
 
     ws.onopen = () => {
       setStatus("connected");
       setError(null);
+      sendResize(term.cols, term.rows);
     };
     ws.onclose = (ev) => {
       setStatus("closed");
@@ -56,7 +81,16 @@ export default function VmConsole() {
       setError("WebSocket connection error");
     };
     ws.onmessage = (ev) => {
-      if (typeof ev.data === "string") {
+      if (typeof ev.data === "string" && framed()) {
+        const message = JSON.parse(ev.data) as ControlMessage;
+        if (message.type === "error") {
+          setError(message.message);
+        } else if (message.type === "notice") {
+          setNotice(message.message);
+        } else if (message.event === "guest_exited") {
+          setStatus("guest exited");
+        }
+      } else if (typeof ev.data === "string") {
         term.write(ev.data);
       } else {
         term.write(new Uint8Array(ev.data as ArrayBuffer));
@@ -73,6 +107,7 @@ export default function VmConsole() {
     return () => {
       window.removeEventListener("resize", handleResize);
       inputDisposable.dispose();
+      resizeDisposable.dispose();
       try {
         ws.close();
       } catch {
@@ -80,14 +115,14 @@ export default function VmConsole() {
       }
       term.dispose();
     };
-  }, [id]);
+  }, [id, mode]);
 
   const statusColor =
     status === "connected"
       ? "text-green-600"
       : status === "error"
         ? "text-red-600"
-        : status === "closed"
+        : status === "closed" || status === "guest exited"
           ? "text-gray-500"
           : "text-sky-600";
 
@@ -118,6 +153,12 @@ export default function VmConsole() {
         </span>
       </div>
 
+      {notice && (
+        <div className="mb-3 p-3 bg-amber-50 border border-amber-200 rounded-lg text-amber-800 text-sm">
+          {notice}
+        </div>
+      )}
+
       {error && (
         <div className="mb-3 p-3 bg-red-50 border border-red-200 rounded-lg text-red-700 text-sm">
           {error}
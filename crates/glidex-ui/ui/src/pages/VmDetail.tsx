@@ -1,8 +1,13 @@
 import { useCallback, useEffect, useState } from "react";
 import { Link, useNavigate, useParams } from "react-router-dom";
 import * as api from "../api";
-import type { VmResponse } from "../types";
-import { stateColor, stateLabel, HYPERVISOR_LABELS } from "../types";
+import type { SnapshotResponse, VmResponse } from "../types";
+import {
+  describeExit,
+  stateColor,
+  stateLabel,
+  HYPERVISOR_LABELS,
+} from "../types";
 import VmActions, { type VmAction } from "../components/VmActions";
 import { Loading } from "../components/Loading";
 
@@ -10,6 +15,7 @@ export default function VmDetail() {
   const { id } = useParams<{ id: string }>();
   const navigate = useNavigate();
   const [vm, setVm] = useState<VmResponse | null>(null);
+  const [snapshots, setSnapshots] = useState<SnapshotResponse[]>([]);
   const [loading, setLoading] = useState(true);
   const [error, setError] = useState<string | null>(null);
   const [actionLoading, setActionLoading] = useState(false);
@@ -17,8 +23,12 @@ export default function VmDetail() {
   const fetchVm = useCallback(async () => {
     if (!id) return;
     try {
-      const data = await api.getVm(id);
+      const [data, snaps] = await Promise.all([
+        api.getVm(id),
+        api.listSnapshots(id),
+      ]);
       setVm(data);
+      setSnapshots(snaps);
       setError(null);
     } catch (e) {
       setError(e instanceof Error ? e.message : "Failed to load VM");
@@ -42,9 +52,18 @@ export default function VmDetail() {
         case "stop":
           await api.stopVm(vmId);
           break;
+        case "force-stop":
+          await api.stopVm(vmId, true);
+          break;
         case "pause":
           await api.pauseVm(vmId);
           break;
+        case "reboot":
+          await api.rebootVm(vmId);
+          break;
+        case "reset":
+          await api.resetVm(vmId);
+          break;
         case "delete":
           await api.deleteVm(vmId);
           navigate("/");
@@ -58,6 +77,25 @@ export default function VmDetail() {
     }
   };
 
+  const handleSnapshot = async (run: () => Promise<unknown>) => {
+    setActionLoading(true);
+    setError(null);
+    try {
+      await run();
+      fetchVm();
+    } catch (e) {
+      setError(e instanceof Error ? e.message : "Snapshot action failed");
+    } finally {
+      setActionLoading(false);
+    }
+  };
+
+  const canRestore =
+    vm?.state === "created" ||
+    vm?.state === "stopped" ||
+    vm?.state === "crashed" ||
+    vm?.state === "failed";
+
   return (
     <div>
       <Link
@@ -134,6 +172,19 @@ export default function VmDetail() {
                   {vm.mem_size_mib} MiB
                 </p>
               </div>
+              {(vm.state === "running" || vm.state === "paused") && (
+                <div>
+                  <h3 className="text-sm font-medium text-gray-500">Ready</h3>
+                  <p className="text-lg font-semibold text-gray-900">
+                    {vm.ready ? "Yes" : "Booting…"}
+                    {vm.ready && vm.boot_duration_ms !== undefined && (
+                      <span className="ml-2 text-sm font-normal text-gray-500">
+                        booted in {(vm.boot_duration_ms / 1000).toFixed(1)}s
+                      </span>
+                    )}
+                  </p>
+                </div>
+              )}
             </div>
             <div className="space-y-4">
               <div>
@@ -153,6 +204,30 @@ export default function VmDetail() {
             </div>
           </div>
 
+          {vm.last_error && (
+            <div className="mb-6 p-4 bg-red-50 border border-red-200 rounded-lg">
+              <h3 className="text-sm font-medium text-red-700 mb-1">
+                Start failed
+              </h3>
+              <pre className="font-mono text-xs text-red-700 whitespace-pre-wrap break-all">
+                {vm.last_error}
+              </pre>
+            </div>
+          )}
+
+          {vm.last_exit && (
+            <div className="mb-6 p-4 bg-gray-50 border border-gray-200 rounded-lg">
+              <h3 className="text-sm font-medium text-gray-700 mb-1">
+                Hypervisor {describeExit(vm.last_exit)}
+              </h3>
+              {(vm.last_exit.console_tail ?? []).length > 0 && (
+                <pre className="font-mono text-xs text-gray-700 whitespace-pre-wrap break-all">
+                  {(vm.last_exit.console_tail ?? []).join("\n")}
+                </pre>
+              )}
+            </div>
+          )}
+
           {(vm.vfio_devices ?? []).length > 0 && (
             <div className="mb-6">
               <h3 className="text-sm font-medium text-gray-500 mb-2">
@@ -168,6 +243,73 @@ export default function VmDetail() {
             </div>
           )}
 
+          <div className="mb-6">
+            <div className="flex items-center justify-between mb-2">
+              <h3 className="text-sm font-medium text-gray-500">Snapshots</h3>
+              {vm.state === "paused" && (
+                <button
+                  className="px-3 py-1.5 text-sm font-medium text-gray-700 bg-gray-200 hover:bg-gray-300 rounded-lg transition-colors disabled:opacity-50"
+                  disabled={actionLoading}
+                  onClick={() =>
+                    handleSnapshot(() => api.createSnapshot(vm.id))
+                  }
+                >
+                  Take snapshot
+                </button>
+              )}
+            </div>
+            {snapshots.length === 0 ? (
+              <p className="text-sm text-gray-400">
+                {vm.state === "paused"
+                  ? "No snapshots yet"
+                  : "No snapshots. Pause the VM to take one."}
+              </p>
+            ) : (
+              <ul className="divide-y divide-gray-100">
+                {snapshots.map((snap) => (
+                  <li
+                    key={snap.id}
+                    className="flex items-center justify-between py-2"
+                  >
+                    <div>
+                      <p className="text-sm text-gray-900">
+                        {snap.name ?? snap.id}
+                      </p>
+                      <p className="text-xs text-gray-500">
+                        {new Date(snap.created_at * 1000).toLocaleString()}
+                      </p>
+                    </div>
+                    <div className="flex items-center space-x-2">
+                      {canRestore && (
+                        <button
+                          className="px-3 py-1.5 text-sm font-medium text-white bg-sky-600 hover:bg-sky-700 rounded-lg transition-colors disabled:opacity-50"
+                          disabled={actionLoading}
+                          title="Boot the VM from this snapshot; it comes up paused"
+                          onClick={() =>
+                            handleSnapshot(() => api.restoreVm(vm.id, snap.id))
+                          }
+                        >
+                          Restore
+                        </button>
+                      )}
+                      <button
+                        className="px-3 py-1.5 text-sm font-medium text-red-700 bg-gray-200 hover:bg-gray-300 rounded-lg transition-colors disabled:opacity-50"
+                        disabled={actionLoading}
+                        onClick={() =>
+                          handleSnapshot(() =>
+                            api.deleteSnapshot(vm.id, snap.id),
+                          )
+                        }
+                      >
+                        Delete
+                      </button>
+                    </div>
+                  </li>
+                ))}
+              </ul>
+            )}
+          </div>
+
           <div className="pt-6 border-t border-gray-100">
             <h3 className="text-sm font-medium text-gray-500 mb-3">Actions</h3>
             <div className="flex items-center gap-3 flex-wrap">
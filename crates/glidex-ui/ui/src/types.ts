@@ -1,4 +1,12 @@
-export type VmState = "created" | "running" | "paused" | "stopped";
+export type VmState =
+  | "created"
+  | "starting"
+  | "running"
+  | "paused"
+  | "stopping"
+  | "stopped"
+  | "crashed"
+  | "failed";
 
 export type HypervisorType = "cloudhypervisor" | "firecracker" | "qemu";
 
@@ -8,6 +16,17 @@ export const HYPERVISOR_LABELS: Record<HypervisorType, string> = {
   qemu: "QEMU",
 };
 
+export interface VmExit {
+  code?: number;
+  signal?: number;
+  console_tail?: string[];
+}
+
+export type ReadinessProbe =
+  | { type: "console"; pattern: string }
+  | { type: "tcp"; host: string; port: number }
+  | { type: "guest_agent" };
+
 export interface VmResponse {
   id: string;
   name: string;
@@ -17,7 +36,31 @@ export interface VmResponse {
   console_socket_path: string;
   log_path: string;
   hypervisor: HypervisorType;
+  kernel_image_path: string;
+  rootfs_path: string;
+  kernel_args: string;
   vfio_devices: string[];
+  no_reboot: boolean;
+  readiness?: ReadinessProbe;
+  /** The guest passed its readiness probe (or has none) and is running. */
+  ready: boolean;
+  boot_duration_ms?: number;
+  /** Seconds since the current boot began, while running or paused. */
+  uptime_secs?: number;
+  /** The hypervisor process's host usage; CPU time is cumulative. */
+  process?: { pid: number; cpu_time_ms: number; rss_bytes: number };
+  last_exit?: VmExit;
+  last_error?: string;
+}
+
+export interface SnapshotResponse {
+  id: string;
+  vm_id: string;
+  name?: string;
+  /** Seconds since the Unix epoch. */
+  created_at: number;
+  path: string;
+  hypervisor: HypervisorType;
 }
 
 export interface CreateVmRequest {
@@ -29,6 +72,7 @@ export interface CreateVmRequest {
   kernel_args?: string;
   hypervisor?: HypervisorType;
   vfio_devices?: string[];
+  no_reboot?: boolean;
 }
 
 export interface ApiError {
@@ -50,9 +94,21 @@ export function stateColor(state: VmState): string {
       return "bg-yellow-500";
     case "created":
       return "bg-blue-500";
+    case "starting":
+    case "stopping":
+      return "bg-sky-400";
+    case "crashed":
+    case "failed":
+      return "bg-red-700";
   }
 }
 
+export function describeExit(exit: VmExit): string {
+  if (exit.code !== undefined) return `exited with code ${exit.code}`;
+  if (exit.signal !== undefined) return `killed by signal ${exit.signal}`;
+  return "exited";
+}
+
 export function stateLabel(state: VmState): string {
   return state.charAt(0).toUpperCase() + state.slice(1);
 }